@@ -4,7 +4,8 @@
 //! the future.  It can be consumed by `Read`, returning a `t`.  It can be
 //! duplicated by `Dup`, and discarded with `Free`.
 use hugr::{
-    builder::{BuildError, Dataflow},
+    builder::{BuildError, ConditionalBuilder, Dataflow},
+    extension::prelude::BOOL_T,
     extension::{
         simple_op::{
             try_from_name, HasConcrete, HasDef, MakeExtensionOp, MakeOpDef, MakeRegisteredOp,
@@ -14,8 +15,12 @@ use hugr::{
         TypeDef,
     },
     ops::{custom::ExtensionOp, NamedOp, OpType},
-    types::{type_param::TypeParam, CustomType, PolyFuncType, Signature, Type, TypeArg, TypeBound},
-    Extension, Wire,
+    type_row,
+    types::{
+        type_param::TypeParam, CustomType, PolyFuncType, Signature, Type, TypeArg, TypeBound,
+        TypeRow,
+    },
+    Extension, Hugr, Wire,
 };
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
@@ -228,6 +233,27 @@ pub trait FutureOpBuilder: Dataflow {
             .outputs_arr())
     }
 
+    /// Read a `Future<bool>` and feed its value forward into a new
+    /// `Conditional`.
+    ///
+    /// The `Conditional` has two cases, taken when the value is `false` and
+    /// `true` respectively, with `other_inputs` as their inputs. Since the
+    /// read value is a dataflow input of the `Conditional`, passes cannot
+    /// reorder the branch before the read.
+    fn add_read_conditional(
+        &mut self,
+        lifted: Wire,
+        other_inputs: impl IntoIterator<Item = (Type, Wire)>,
+        output_types: TypeRow,
+    ) -> Result<ConditionalBuilder<&mut Hugr>, BuildError> {
+        let [value] = self.add_read(lifted, BOOL_T)?;
+        self.conditional_builder(
+            ([type_row![], type_row![]], value),
+            other_inputs,
+            output_types,
+        )
+    }
+
     /// Add a "tket2.futures.Dup" op.
     fn add_dup(&mut self, lifted: Wire, typ: Type) -> Result<[Wire; 2], BuildError> {
         Ok(self
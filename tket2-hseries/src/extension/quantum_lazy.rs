@@ -118,10 +118,11 @@ mod test {
     use std::sync::Arc;
 
     use cool_asserts::assert_matches;
-    use futures::FutureOpBuilder as _;
+    use futures::{FutureOpBuilder as _, FutureOpDef};
     use hugr::{
-        builder::{DataflowHugr, FunctionBuilder},
-        ops::NamedOp,
+        builder::{DFGBuilder, DataflowHugr, DataflowSubContainer, FunctionBuilder, SubContainer},
+        ops::{handle::NodeHandle, NamedOp},
+        type_row, HugrView,
     };
     use strum::IntoEnumIterator as _;
 
@@ -154,4 +155,46 @@ mod test {
         };
         assert_matches!(hugr.validate(&REGISTRY), Ok(_));
     }
+
+    #[test]
+    fn lazy_measure_is_not_unitary() {
+        let hugr = {
+            let mut dfg = DFGBuilder::new(Signature::new_endo(QB_T)).unwrap();
+            let [qb] = dfg.input_wires_arr();
+            let [qb, lazy_b] = dfg.add_lazy_measure(qb).unwrap();
+            dfg.add_free(lazy_b, BOOL_T).unwrap();
+            dfg.finish_hugr_with_outputs([qb], &REGISTRY).unwrap()
+        };
+        assert!(!tket2::Circuit::from(hugr).is_unitary());
+    }
+
+    #[test]
+    fn feedforward_circuit() {
+        let qbs = type_row![QB_T, QB_T];
+        let mut func_builder =
+            FunctionBuilder::new("circuit", Signature::new_endo(qbs.clone())).unwrap();
+        let [q0, q1] = func_builder.input_wires_arr();
+        let [q0, lazy_b] = func_builder.add_lazy_measure(q0).unwrap();
+        let mut cond = func_builder
+            .add_read_conditional(lazy_b, [(QB_T, q1)], type_row![QB_T])
+            .unwrap();
+        for i in 0..2 {
+            let case = cond.case_builder(i).unwrap();
+            let [q1] = case.input_wires_arr();
+            case.finish_with_outputs([q1]).unwrap();
+        }
+        let cond = cond.finish_sub_container().unwrap();
+        let [q1] = cond.outputs_arr();
+        let hugr = func_builder
+            .finish_hugr_with_outputs([q0, q1], &REGISTRY)
+            .unwrap();
+        assert_matches!(hugr.validate(&REGISTRY), Ok(_));
+
+        // The branch depends on the value read from the measurement.
+        let (read, _) = hugr.single_linked_output(cond.node(), 0).unwrap();
+        assert_eq!(
+            FutureOpDef::try_from(hugr.get_optype(read)),
+            Ok(FutureOpDef::Read)
+        );
+    }
 }
@@ -1,6 +1,8 @@
 //! This module defines the Hugr extension used to represent result reporting operations,
 //! with static string tags.
 //!
+use hugr::hugr::hugrmut::HugrMut;
+use hugr::hugr::ValidationError;
 use hugr::types::Signature;
 use hugr::{
     builder::{BuildError, Dataflow},
@@ -20,8 +22,8 @@ use hugr::{
         },
     },
     type_row,
-    types::{type_param::TypeParam, PolyFuncType, Type, TypeArg},
-    Extension, Wire,
+    types::{type_param::TypeParam, EdgeKind, PolyFuncType, Type, TypeArg, TypeEnum},
+    Extension, HugrView, Wire,
 };
 
 use lazy_static::lazy_static;
@@ -72,11 +74,13 @@ result_int<Tag: StringArg, N: BoundedNat>( int<N> ) // N is bitwidth, e.g. i32,
 result_uint<Tag: StringArg, N: BoundedNat>( int<N> ) // unsigned
 result_bool<Tag: StringArg>( Sum((), ()) )
 result_f64<Tag: StringArg>( f64 )
+result_complex<Tag: StringArg>( (f64, f64) )
 
 result_arr_int<Tag: StringArg, N: Nat, M: BoundedNat>( Array<N, int<M> > )
 result_arr_uint<Tag: StringArg, N: Nat, M: BoundedNat>( Array<N, int<M> > )
 result_arr_f64<Tag: StringArg, N: Nat>( Array<N,f64> )
 result_arr_bool<Tag: StringArg, N: Nat>( Array<N, Sum((), ()) > )
+result_arr_complex<Tag: StringArg, N: Nat>( Array<N, (f64, f64)> )
 */
 pub enum ResultOpDef {
     #[strum(serialize = "result_bool")]
@@ -87,6 +91,8 @@ pub enum ResultOpDef {
     UInt,
     #[strum(serialize = "result_f64")]
     F64,
+    #[strum(serialize = "result_complex")]
+    Complex,
     #[strum(serialize = "result_array_bool")]
     ArrBool,
     #[strum(serialize = "result_array_int")]
@@ -95,6 +101,8 @@ pub enum ResultOpDef {
     ArrUInt,
     #[strum(serialize = "result_array_f64")]
     ArrF64,
+    #[strum(serialize = "result_array_complex")]
+    ArrComplex,
 }
 
 impl ResultOpDef {
@@ -103,7 +111,8 @@ impl ResultOpDef {
             Self::Bool => BOOL_T,
             Self::Int | Self::UInt => int_tv(1),
             Self::F64 => FLOAT64_TYPE,
-            Self::ArrBool | Self::ArrF64 => {
+            Self::Complex => complex_type(),
+            Self::ArrBool | Self::ArrF64 | Self::ArrComplex => {
                 let inner_t = self.simple_type_op().arg_type();
                 array_type(inner_t)
             }
@@ -117,6 +126,7 @@ impl ResultOpDef {
             Self::ArrInt => Self::Int,
             Self::ArrUInt => Self::UInt,
             Self::ArrF64 => Self::F64,
+            Self::ArrComplex => Self::Complex,
             _ => *self,
         }
     }
@@ -127,13 +137,14 @@ impl ResultOpDef {
             Self::Int => Self::ArrInt,
             Self::UInt => Self::ArrUInt,
             Self::F64 => Self::ArrF64,
+            Self::Complex => Self::ArrComplex,
             _ => *self,
         }
     }
 
     fn type_params(&self) -> Vec<TypeParam> {
         match self {
-            Self::Bool | Self::F64 => vec![],
+            Self::Bool | Self::F64 | Self::Complex => vec![],
             Self::Int | Self::UInt => vec![LOG_WIDTH_TYPE_PARAM],
             _ => [
                 vec![TypeParam::max_nat()],
@@ -147,7 +158,9 @@ impl ResultOpDef {
         let parsed_args = concrete_result_op_type_args(args)?;
 
         match (parsed_args, self) {
-            ((tag, None, None), Self::Bool | Self::F64) => Ok(ResultOp::_new_basic(tag, *self)),
+            ((tag, None, None), Self::Bool | Self::F64 | Self::Complex) => {
+                Ok(ResultOp::_new_basic(tag, *self))
+            }
             ((tag, Some(width), None), Self::Int | Self::UInt) => {
                 Ok(ResultOp::_new_int(tag, width as u8, *self))
             }
@@ -175,6 +188,12 @@ impl ResultOpDef {
     }
 }
 
+/// The type of complex numbers reported by "tket2.result" operations, a pair of
+/// their real and imaginary parts.
+pub fn complex_type() -> Type {
+    Type::new_tuple(vec![FLOAT64_TYPE, FLOAT64_TYPE])
+}
+
 fn array_type(inner_t: Type) -> Type {
     prelude::array_type(TypeArg::new_var_use(1, TypeParam::max_nat()), inner_t)
 }
@@ -202,10 +221,12 @@ impl MakeOpDef for ResultOpDef {
             Self::Int => "Report a signed integer result.",
             Self::UInt => "Report an unsigned integer result.",
             Self::F64 => "Report a floating-point result.",
+            Self::Complex => "Report a complex result, as a pair of its real and imaginary parts.",
             Self::ArrBool => "Report an array of boolean results.",
             Self::ArrInt => "Report an array of signed integer results.",
             Self::ArrUInt => "Report an array of unsigned integer results.",
             Self::ArrF64 => "Report an array of floating-point results.",
+            Self::ArrComplex => "Report an array of complex results.",
         }
         .to_string()
     }
@@ -257,6 +278,14 @@ impl ResultOp {
         Self::_new_basic(tag, ResultOpDef::F64)
     }
 
+    /// Create a new "tket2.result" operation for a complex result.
+    ///
+    /// The reported value is a tuple of its real and imaginary parts, see
+    /// [`complex_type`].
+    pub fn new_complex(tag: impl Into<String>) -> Self {
+        Self::_new_basic(tag, ResultOpDef::Complex)
+    }
+
     /// Convert this "tket2.result" operation to an array result operation over the same inner type.
     /// The size of the array is set to the given value.
     /// If this operation is already an array result operation, its size is updated.
@@ -284,6 +313,36 @@ impl ResultOp {
     pub fn new_uint(tag: impl Into<String>, int_width: u8) -> Self {
         Self::_new_int(tag, int_width, ResultOpDef::UInt)
     }
+
+    /// The tag of the result.
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// Prepend `prefix` to the tag of this "tket2.result" operation.
+    pub fn with_tag_prefix(mut self, prefix: &str) -> Self {
+        self.tag.insert_str(0, prefix);
+        self
+    }
+}
+
+/// Prepend `prefix` to the tag of every "tket2.result" operation in the HUGR.
+///
+/// This can be used to namespace the results of a subprogram before composing
+/// it with other modules.
+pub fn prefix_result_tags(hugr: &mut impl HugrMut, prefix: &str) {
+    let result_ops = hugr
+        .nodes()
+        .filter_map(|node| Some((node, ResultOp::try_from(hugr.get_optype(node)).ok()?)))
+        .collect::<Vec<_>>();
+    for (node, op) in result_ops {
+        let op = op
+            .with_tag_prefix(prefix)
+            .to_extension_op()
+            .expect("Result operations should be valid extension ops.");
+        hugr.replace_op(node, op)
+            .expect("Replacing a result op with the same signature should not fail.");
+    }
 }
 
 fn concrete_result_op_type_args(
@@ -385,14 +444,60 @@ impl TryFrom<&OpType> for ResultOp {
     }
 }
 
+/// Returns the length of a prelude array type, if the type is a concrete array.
+fn array_len(typ: &Type) -> Option<u64> {
+    let TypeEnum::Extension(custom) = typ.as_type_enum() else {
+        return None;
+    };
+    if custom.extension() != &prelude::PRELUDE_ID || custom.name() != "array" {
+        return None;
+    }
+    match custom.args() {
+        [TypeArg::BoundedNat { n }, _] => Some(*n),
+        _ => None,
+    }
+}
+
 /// An extension trait for [Dataflow] providing methods to add "tket2.result"
 /// operations.
 pub trait ResultOpBuilder: Dataflow {
     /// Add a "tket2.result" op.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ValidationError::IncompatiblePorts`] error if `op` reports
+    /// an array whose size differs from the length of the array in
+    /// `result_wire`. The operation node is left in the builder, as with any
+    /// other wiring error.
     fn add_result(&mut self, result_wire: Wire, op: ResultOp) -> Result<(), BuildError> {
+        let wire_type = self.get_wire_type(result_wire)?;
+        let op_size = match op.args {
+            ResultArgs::Array(_, size) => Some(size),
+            ResultArgs::Simple(_) => None,
+        };
         let handle = self.add_dataflow_op(op, [result_wire])?;
-
         debug_assert_eq!(handle.outputs().len(), 0);
+
+        let Some(op_size) = op_size else {
+            return Ok(());
+        };
+        if array_len(&wire_type).is_some_and(|n| n != op_size) {
+            let node = handle.node();
+            let (in_port, in_type) = self
+                .hugr()
+                .in_value_types(node)
+                .next()
+                .expect("Result operations have a single input.");
+            return Err(ValidationError::IncompatiblePorts {
+                from: result_wire.node(),
+                from_port: result_wire.source().into(),
+                from_kind: EdgeKind::Value(wire_type),
+                to: node,
+                to_port: in_port.into(),
+                to_kind: EdgeKind::Value(in_type),
+            }
+            .into());
+        }
         Ok(())
     }
 }
@@ -408,6 +513,7 @@ pub(crate) mod test {
         extension::prelude::array_type,
         ops::NamedOp,
         std_extensions::arithmetic::int_types::INT_TYPES,
+        HugrView,
     };
     use std::sync::Arc;
     use strum::IntoEnumIterator;
@@ -486,4 +592,89 @@ pub(crate) mod test {
         };
         assert_matches!(hugr.validate(&REGISTRY), Ok(_));
     }
+
+    #[test]
+    fn complex_result() {
+        const ARR_SIZE: u64 = 4;
+        let arr_t = array_type(TypeArg::BoundedNat { n: ARR_SIZE }, complex_type());
+        let mut func_builder = FunctionBuilder::new(
+            "circuit",
+            Signature::new(vec![FLOAT64_TYPE, FLOAT64_TYPE, arr_t], type_row![]),
+        )
+        .unwrap();
+        let [re, im, arr] = func_builder.input_wires_arr();
+        let z = func_builder.make_tuple([re, im]).unwrap();
+        func_builder
+            .add_result(z, ResultOp::new_complex("z"))
+            .unwrap();
+        func_builder
+            .add_result(arr, ResultOp::new_complex("zs").array_op(ARR_SIZE))
+            .unwrap();
+        let hugr = func_builder
+            .finish_hugr_with_outputs([], &REGISTRY)
+            .unwrap();
+        assert_matches!(hugr.validate(&REGISTRY), Ok(_));
+
+        let ops = hugr
+            .nodes()
+            .filter_map(|n| ResultOp::try_from(hugr.get_optype(n)).ok())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ops,
+            [
+                ResultOp::new_complex("z"),
+                ResultOp::new_complex("zs").array_op(ARR_SIZE)
+            ]
+        );
+        assert_eq!(ops[1].result_op, ResultOpDef::ArrComplex);
+    }
+
+    #[test]
+    fn prefix_tags() {
+        let arr_t = array_type(TypeArg::BoundedNat { n: 3 }, INT_TYPES[6].clone());
+        let mut func_builder =
+            FunctionBuilder::new("circuit", Signature::new(vec![BOOL_T, arr_t], type_row![]))
+                .unwrap();
+        let [b, arr] = func_builder.input_wires_arr();
+        func_builder.add_result(b, ResultOp::new_bool("b")).unwrap();
+        func_builder
+            .add_result(arr, ResultOp::new_int("i", 6).array_op(3))
+            .unwrap();
+        let mut hugr = func_builder
+            .finish_hugr_with_outputs([], &REGISTRY)
+            .unwrap();
+
+        prefix_result_tags(&mut hugr, "sub.");
+        assert_matches!(hugr.validate(&REGISTRY), Ok(_));
+
+        let mut tags = hugr
+            .nodes()
+            .filter_map(|n| ResultOp::try_from(hugr.get_optype(n)).ok())
+            .map(|op| op.tag().to_string())
+            .collect::<Vec<_>>();
+        tags.sort();
+        assert_eq!(tags, ["sub.b", "sub.i"]);
+    }
+
+    #[test]
+    fn array_size_mismatch() {
+        let arr_t = array_type(TypeArg::BoundedNat { n: 5 }, BOOL_T);
+        let mut func_builder =
+            FunctionBuilder::new("circuit", Signature::new(arr_t, type_row![])).unwrap();
+        let [arr] = func_builder.input_wires_arr();
+
+        let op = ResultOp::new_bool("b").array_op(4);
+        assert_matches!(
+            func_builder.add_result(arr, op),
+            Err(BuildError::InvalidHUGR(ValidationError::IncompatiblePorts {
+                from_kind,
+                to_kind,
+                ..
+            })) => {
+                let bool_array = |n| EdgeKind::Value(array_type(TypeArg::BoundedNat { n }, BOOL_T));
+                assert_eq!(from_kind, bool_array(5));
+                assert_eq!(to_kind, bool_array(4));
+            }
+        );
+    }
 }
@@ -158,6 +158,18 @@ impl Tk2Circuit {
         self.circ.num_operations()
     }
 
+    /// Returns summary statistics of the circuit, as a dictionary.
+    ///
+    /// Includes the qubit and bit counts, the number of gates and two-qubit
+    /// gates, the depth, the T-count, a histogram of gate names, and the
+    /// global phase.
+    pub fn stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let json = serde_json::to_string(&self.circ.stats()).map_err(|e| {
+            PyErr::new::<PyValueError, _>(format!("Could not serialise circuit statistics: {e}"))
+        })?;
+        PyModule::import_bound(py, "json")?.call_method1("loads", (json,))
+    }
+
     /// Returns a hash of the circuit.
     pub fn hash(&self) -> u64 {
         self.circ.circuit_hash().unwrap()
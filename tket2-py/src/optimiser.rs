@@ -8,7 +8,7 @@ use tket2::optimiser::badger::BadgerOptions;
 use tket2::optimiser::{BadgerLogger, DefaultBadgerOptimiser};
 use tket2::Circuit;
 
-use crate::circuit::update_circ;
+use crate::circuit::{update_circ, with_circ, CircuitType};
 
 /// The module definition
 pub fn module(py: Python<'_>) -> PyResult<Bound<'_, PyModule>> {
@@ -110,6 +110,50 @@ impl PyBadgerOptimiser {
         };
         update_circ(circ, |circ, _| self.optimise(circ, log_progress, options))
     }
+
+    /// Run the optimiser on a batch of circuits, in parallel.
+    ///
+    /// Each circuit is optimised on a single thread with the given options,
+    /// and the results are returned in the same order as the input.
+    ///
+    /// # Parameters
+    ///
+    /// * `circs`: The circuits to optimise.
+    ///
+    /// * `timeout`, `progress_timeout`, `max_circuit_count`, `queue_size`:
+    ///     Per-circuit options, as in [`PyBadgerOptimiser::py_optimise`].
+    #[pyo3(name = "optimise_batch")]
+    pub fn py_optimise_batch<'py>(
+        &self,
+        py: Python<'py>,
+        circs: Vec<Bound<'py, PyAny>>,
+        timeout: Option<u64>,
+        progress_timeout: Option<u64>,
+        max_circuit_count: Option<usize>,
+        queue_size: Option<usize>,
+    ) -> PyResult<Vec<Bound<'py, PyAny>>> {
+        let options = BadgerOptions {
+            timeout,
+            progress_timeout,
+            max_circuit_count,
+            n_threads: NonZeroUsize::new(1).unwrap(),
+            split_circuit: false,
+            queue_size: queue_size.unwrap_or(100),
+        };
+        let (circs, types): (Vec<Circuit>, Vec<CircuitType>) = circs
+            .iter()
+            .map(|circ| with_circ(circ, |circ, typ| (circ, typ)))
+            .collect::<PyResult<Vec<_>>>()?
+            .into_iter()
+            .unzip();
+        let optimised: Vec<Circuit> =
+            py.allow_threads(|| self.0.optimise_batch(circs, options).collect());
+        optimised
+            .into_iter()
+            .zip(types)
+            .map(|(circ, typ)| typ.convert(py, circ))
+            .collect()
+    }
 }
 
 impl PyBadgerOptimiser {
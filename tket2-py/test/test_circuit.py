@@ -31,6 +31,18 @@ def test_cost():
     )
 
 
+def test_stats():
+    circ = Tk2Circuit(Circuit(3).H(0).CX(0, 1).T(2).CX(1, 2))
+    stats = circ.stats()
+
+    assert stats["qubits"] == 3
+    assert stats["gates"] == 4
+    assert stats["two_qubit_gates"] == 2
+    assert stats["depth"] == 3
+    assert stats["t_count"] == 1
+    assert sum(stats["gate_histogram"].values()) == 4
+
+
 def test_hash():
     circA = Tk2Circuit(Circuit(4).CX(0, 1).CX(1, 2).CX(0, 3))
     circB = Tk2Circuit(Circuit(4).CX(1, 2).CX(0, 1).CX(0, 3))
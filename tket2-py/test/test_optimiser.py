@@ -12,3 +12,15 @@ def test_simple_optimiser():
     exp_c = Circuit(3).CX(1, 2)
 
     assert cc == exp_c
+
+
+def test_optimise_batch():
+    """optimising a batch gives the same results as optimising each circuit"""
+    circs = [
+        Circuit(3).CX(0, 1).CX(0, 1).CX(1, 2),
+        Circuit(2).CX(1, 0).CX(1, 0),
+    ]
+    opt = BadgerOptimiser.compile_eccs("test_files/cx_cx_eccs.json")
+
+    batch = opt.optimise_batch(circs, max_circuit_count=10)
+    assert batch == [opt.optimise(c, max_circuit_count=10) for c in circs]
@@ -32,6 +32,13 @@ class Tk2Circuit:
         Nested circuits are traversed to count their operations.
         """
 
+    def stats(self) -> dict[str, Any]:
+        """Summary statistics of the circuit.
+
+        Includes the qubit and bit counts, the number of gates and two-qubit gates,
+        the depth, the T-count, a histogram of gate names, and the global phase.
+        """
+
     def node_op(self, node: Node) -> bytes:
         """If the node corresponds to a custom op, return it. Otherwise, raise an error."""
 
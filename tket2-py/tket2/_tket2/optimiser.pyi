@@ -35,3 +35,23 @@ class BadgerOptimiser:
         :param queue_size: Maximum number of circuits to keep in the queue of candidates.
         :param log_progress: Log progress to a CSV file.
         """
+
+    def optimise_batch(
+        self,
+        circs: list[CircuitClass],
+        timeout: int | None = None,
+        progress_timeout: int | None = None,
+        max_circuit_count: int | None = None,
+        queue_size: int | None = None,
+    ) -> list[CircuitClass]:
+        """Optimise a batch of circuits in parallel.
+
+        Each circuit is optimised on a single thread, and the results are
+        returned in the same order as the input.
+
+        :param circs: The circuits to optimise.
+        :param timeout: Maximum time to spend on each optimisation.
+        :param progress_timeout: Maximum time to wait between new best results.
+        :param max_circuit_count: Maximum number of candidates to process per circuit.
+        :param queue_size: Maximum number of circuits to keep in the queue of candidates.
+        """
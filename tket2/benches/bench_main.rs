@@ -5,6 +5,13 @@ mod benchmarks;
 
 use criterion::criterion_main;
 
+#[cfg(not(feature = "portmatching"))]
 criterion_main! {
     benchmarks::hash::benches,
 }
+
+#[cfg(feature = "portmatching")]
+criterion_main! {
+    benchmarks::hash::benches,
+    benchmarks::matching::benches,
+}
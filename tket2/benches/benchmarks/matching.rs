@@ -0,0 +1,62 @@
+use criterion::{black_box, criterion_group, AxisScale, BenchmarkId, Criterion, PlotConfiguration};
+use hugr::ops::{NamedOp, OpType};
+use tket2::portmatching::{CircuitPattern, PatternMatcher};
+use tket2::{Circuit, Tk2Op};
+
+use super::generators::build_simple_circuit;
+
+/// A circuit with a single CX every `layers` layers of Hadamards.
+fn sparse_cx_circuit(num_qubits: usize, layers: usize) -> Circuit {
+    build_simple_circuit(num_qubits, |circ| {
+        for layer in 0..layers {
+            for q in 0..num_qubits {
+                circ.append(Tk2Op::H, [q])?;
+            }
+            if layer % 10 == 0 {
+                let q = layer % (num_qubits - 1);
+                circ.append(Tk2Op::CX, [q, q + 1])?;
+            }
+        }
+        Ok(())
+    })
+    .unwrap()
+    .into()
+}
+
+fn bench_anchored_matching(c: &mut Criterion) {
+    let mut g = c.benchmark_group("match a pattern anchored on CX");
+    g.plot_config(PlotConfiguration::default().summary_scale(AxisScale::Logarithmic));
+
+    let pattern_circ: Circuit = build_simple_circuit(2, |circ| {
+        circ.append(Tk2Op::H, [0])?;
+        circ.append(Tk2Op::CX, [0, 1])?;
+        Ok(())
+    })
+    .unwrap()
+    .into();
+    let pattern = CircuitPattern::try_from_circuit(&pattern_circ).unwrap();
+    let anchored = pattern
+        .clone()
+        .with_anchor(OpType::from(Tk2Op::CX).name())
+        .unwrap();
+    let unanchored_matcher = PatternMatcher::from_patterns(vec![pattern]);
+    let anchored_matcher = PatternMatcher::from_patterns(vec![anchored]);
+
+    for size in [10, 100, 1_000] {
+        let circ = sparse_cx_circuit(8, size);
+        g.bench_with_input(BenchmarkId::new("unanchored", size), &circ, |b, circ| {
+            b.iter(|| black_box(unanchored_matcher.find_matches(circ)))
+        });
+        g.bench_with_input(BenchmarkId::new("anchored", size), &circ, |b, circ| {
+            b.iter(|| black_box(anchored_matcher.find_matches(circ)))
+        });
+    }
+    g.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default();
+    targets =
+        bench_anchored_matching,
+}
@@ -1,3 +1,5 @@
 pub mod generators;
 
 pub mod hash;
+#[cfg(feature = "portmatching")]
+pub mod matching;
@@ -0,0 +1,47 @@
+//! Share a single [`ECCRewriter`] between threads to optimise several
+//! circuits concurrently.
+//!
+//! Run from the `tket2` directory with
+//! `cargo run --example concurrent_rewriter --features portmatching`.
+
+use std::sync::Arc;
+use std::thread;
+
+use tket2::rewrite::{ECCRewriter, Rewriter};
+use tket2::Circuit;
+
+const ECC_FILE: &str = "../test_files/eccs/small_eccs.json";
+const N_THREADS: u64 = 4;
+
+/// Greedily apply size-reducing rewrites until none are left.
+fn greedy_optimise(rewriter: &ECCRewriter, mut circ: Circuit) -> Circuit {
+    while let Some(rewrite) = rewriter
+        .get_rewrites(&circ)
+        .into_iter()
+        .find(|rw| rw.node_count_delta() < 0)
+    {
+        rewrite.apply(&mut circ).unwrap();
+    }
+    circ
+}
+
+fn main() {
+    let rewriter = Arc::new(ECCRewriter::try_from_eccs_json_file(ECC_FILE).unwrap());
+
+    let handles: Vec<_> = (0..N_THREADS)
+        .map(|seed| {
+            let rewriter = Arc::clone(&rewriter);
+            thread::spawn(move || {
+                let circ = Circuit::random_clifford(3, 20, seed);
+                let before = circ.num_operations();
+                let circ = greedy_optimise(&rewriter, circ);
+                (seed, before, circ.num_operations())
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        let (seed, before, after) = handle.join().unwrap();
+        println!("circuit {seed}: {before} -> {after} operations");
+    }
+}
@@ -0,0 +1,140 @@
+//! Check that the rewrites produced by an [`ECCRewriter`] preserve the unitary
+//! of small random circuits.
+//!
+//! The equivalence classes in the ECC json files do not record the relative
+//! phases of their circuits, so the unitaries are compared up to a global
+//! phase.
+//!
+//! Run with `cargo +nightly fuzz run rewrite_preserves_unitary` from the
+//! `tket2` directory.
+
+#![no_main]
+
+use std::f64::consts::PI;
+use std::sync::OnceLock;
+
+use arbitrary::{Arbitrary, Unstructured};
+use hugr::builder::{DFGBuilder, Dataflow, DataflowHugr};
+use hugr::extension::prelude::QB_T;
+use hugr::std_extensions::arithmetic::float_types::ConstF64;
+use hugr::types::Signature;
+use libfuzzer_sys::fuzz_target;
+use num_complex::Complex64;
+use tket2::extension::REGISTRY;
+use tket2::rewrite::{ECCRewriter, Rewriter};
+use tket2::{Circuit, Tk2Op};
+
+/// Maximum number of qubits in a generated circuit.
+const MAX_QUBITS: usize = 3;
+/// Maximum number of gates in a generated circuit.
+const MAX_GATES: usize = 12;
+
+/// The ECCs used to generate rewrites.
+const ECC_FILE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../../test_files/eccs/small_eccs.json"
+);
+
+/// A gate of a random circuit.
+///
+/// These are the Clifford gates used by [`Circuit::random_clifford`], along
+/// with `T` gates and rotations. Qubit indices are taken modulo the number of
+/// qubits of the circuit.
+#[derive(Debug, Clone, Copy, Arbitrary)]
+enum ArbitraryOp {
+    H(u8),
+    S(u8),
+    X(u8),
+    T(u8),
+    Tdg(u8),
+    CX(u8, u8),
+    /// A `Rz` rotation on a qubit, by a multiple of π/4.
+    Rz(u8, u8),
+    /// A `Rx` rotation on a qubit, by a multiple of π/4.
+    Rx(u8, u8),
+}
+
+impl ArbitraryOp {
+    /// The operation, its qubits and its angle, in a circuit with `n_qubits`
+    /// qubits. Returns `None` for two-qubit gates on a single qubit circuit.
+    fn gate(self, n_qubits: usize) -> Option<(Tk2Op, Vec<usize>, Option<f64>)> {
+        let qb = |q: u8| q as usize % n_qubits;
+        let angle = |k: u8| (k % 8) as f64 * PI / 4.;
+        let gate = match self {
+            Self::H(q) => (Tk2Op::H, vec![qb(q)], None),
+            Self::S(q) => (Tk2Op::S, vec![qb(q)], None),
+            Self::X(q) => (Tk2Op::X, vec![qb(q)], None),
+            Self::T(q) => (Tk2Op::T, vec![qb(q)], None),
+            Self::Tdg(q) => (Tk2Op::Tdg, vec![qb(q)], None),
+            Self::CX(c, t) => {
+                if n_qubits < 2 {
+                    return None;
+                }
+                // Pick a target different from the control.
+                let (c, offset) = (qb(c), 1 + t as usize % (n_qubits - 1));
+                (Tk2Op::CX, vec![c, (c + offset) % n_qubits], None)
+            }
+            Self::Rz(q, k) => (Tk2Op::RzF64, vec![qb(q)], Some(angle(k))),
+            Self::Rx(q, k) => (Tk2Op::RxF64, vec![qb(q)], Some(angle(k))),
+        };
+        Some(gate)
+    }
+}
+
+/// A small random circuit.
+#[derive(Debug, Clone)]
+struct ArbitraryCircuit(Circuit);
+
+impl<'a> Arbitrary<'a> for ArbitraryCircuit {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let n_qubits = u.int_in_range(1..=MAX_QUBITS)?;
+        let n_gates = u.int_in_range(0..=MAX_GATES)?;
+        let ops: Vec<ArbitraryOp> = (0..n_gates)
+            .map(|_| u.arbitrary())
+            .collect::<arbitrary::Result<_>>()?;
+
+        let signature = Signature::new_endo(vec![QB_T; n_qubits]);
+        let mut dfg = DFGBuilder::new(signature).unwrap();
+        let mut wires: Vec<_> = dfg.input_wires().collect();
+        for (op, qubits, angle) in ops.into_iter().filter_map(|op| op.gate(n_qubits)) {
+            let angle = angle.map(|a| dfg.add_load_value(ConstF64::new(a)));
+            let inputs = qubits.iter().map(|&q| wires[q]).chain(angle);
+            let outputs = dfg.add_dataflow_op(op, inputs).unwrap().outputs();
+            for (&q, wire) in qubits.iter().zip(outputs) {
+                wires[q] = wire;
+            }
+        }
+        let hugr = dfg.finish_hugr_with_outputs(wires, &REGISTRY).unwrap();
+        Ok(Self(hugr.into()))
+    }
+}
+
+/// The rewriter shared by all the fuzzing runs.
+fn rewriter() -> &'static ECCRewriter {
+    static REWRITER: OnceLock<ECCRewriter> = OnceLock::new();
+    REWRITER.get_or_init(|| ECCRewriter::try_from_eccs_json_file(ECC_FILE).unwrap())
+}
+
+fuzz_target!(|circ: ArbitraryCircuit| {
+    let circ = circ.0;
+    let expected = circ.unitary().unwrap();
+    let dim = (expected.len() as f64).sqrt();
+    for rewrite in rewriter().get_rewrites(&circ) {
+        let mut rewritten = circ.clone();
+        rewrite.apply(&mut rewritten).unwrap();
+        rewritten.hugr().validate(&REGISTRY).unwrap();
+
+        // Two unitaries are equal up to a global phase if and only if their
+        // Hilbert-Schmidt inner product has maximal norm.
+        let actual = rewritten.unitary().unwrap();
+        let overlap: Complex64 = actual
+            .iter()
+            .zip(&expected)
+            .map(|(a, e)| a.conj() * e)
+            .sum();
+        assert!(
+            (overlap.norm() - dim).abs() < 1e-6,
+            "{actual:?} != {expected:?}"
+        );
+    }
+});
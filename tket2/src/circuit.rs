@@ -1,32 +1,77 @@
 //! Quantum circuit representation and operations.
 
+pub mod backend;
+pub mod builder;
 pub mod command;
+pub mod control;
 pub mod cost;
 mod extract_dfg;
 mod hash;
+pub(crate) mod params;
+pub mod qasm3;
+pub mod qir;
+pub mod quantikz;
+mod random;
+pub mod register;
+pub mod schedule;
+pub mod simulate;
+mod split;
+pub mod stats;
+pub mod stim;
+mod symbols;
+pub mod synth;
 pub mod units;
+pub mod visitor;
 
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap, HashMap};
+use std::f64::consts::{FRAC_PI_2, PI, TAU};
 use std::iter::Sum;
 
 pub use command::{Command, CommandIterator};
-pub use hash::CircuitHash;
+pub use hash::{CircuitHash, CircuitKey, HashError};
 use hugr::hugr::views::{DescendantsGraph, ExtractHugr, HierarchyView};
 use itertools::Either::{Left, Right};
+pub use params::BoundedParam;
+pub use stats::{stats_diff, CircuitStats, CircuitStatsDiff, FtResources};
+pub use symbols::simplify_param;
+pub use visitor::{CircuitVisitor, GateCounter};
 
+use hugr::builder::{DFGBuilder, Dataflow, DataflowHugr};
+use hugr::extension::prelude::QB_T;
 use hugr::hugr::hugrmut::HugrMut;
+use hugr::hugr::rewrite::inline_dfg::InlineDFG;
 use hugr::ops::dataflow::IOTrait;
-use hugr::ops::{Input, NamedOp, OpParent, OpTag, OpTrait, Output};
+use hugr::ops::{Input, NamedOp, OpParent, OpTag, OpTrait, Output, DFG};
+use hugr::std_extensions::arithmetic::float_ops;
+use hugr::std_extensions::arithmetic::float_types::ConstF64;
 use hugr::types::{PolyFuncType, Signature};
-use hugr::{Hugr, PortIndex};
-use hugr::{HugrView, OutgoingPort};
+use hugr::{CircuitUnit, Direction, Hugr, PortIndex};
+use hugr::{HugrView, IncomingPort, OutgoingPort};
+use hugr_core::hugr::internal::HugrMutInternals;
 use itertools::Itertools;
+use petgraph::unionfind::UnionFind;
 use thiserror::Error;
 
 pub use hugr::ops::OpType;
 pub use hugr::types::{EdgeKind, Type, TypeRow};
 pub use hugr::{Node, Port, Wire};
 
-use self::units::{filter, LinearUnit, Units};
+/// Metadata key for the name of a circuit box, see [`Circuit::append_box`].
+pub const METADATA_BOX_NAME: &str = "TKET2.box_name";
+
+/// Metadata key for the error budget of an operation, see
+/// [`Circuit::error_budget`].
+pub const METADATA_ERROR_BUDGET: &str = "TKET2.error_budget";
+
+use self::cost::ErrorModel;
+use self::params::NumericParams;
+use self::units::{filter, DefaultUnitLabeller, LinearUnit, Units};
+use crate::extension::REGISTRY;
+use crate::ops::{match_permutation_op, match_placeholder_op, match_symb_const_op};
+use crate::serialize::pytket::{tensor_register_metadata, METADATA_PHASE};
+use crate::utils::type_is_linear;
+use crate::Tk2Op;
 
 /// A quantum circuit, represented as a function in a HUGR.
 #[derive(Debug, Clone, PartialEq)]
@@ -121,6 +166,39 @@ impl<T: HugrView> Circuit<T> {
         }
     }
 
+    /// Returns the name of a box added with [`Circuit::append_box`], or `None`
+    /// if the node is not a box.
+    ///
+    /// Boxes are nested [`OpType::DFG`] containers labelled with a
+    /// [`METADATA_BOX_NAME`] entry.
+    pub fn box_name(&self, node: Node) -> Option<&str> {
+        if !matches!(self.hugr.get_optype(node), OpType::DFG(_)) {
+            return None;
+        }
+        self.hugr.get_metadata(node, METADATA_BOX_NAME)?.as_str()
+    }
+
+    /// Returns the error budget of a node set with
+    /// [`Circuit::set_error_budget`], or `0` if it has none.
+    ///
+    /// The budget of the circuit's parent node holds the budgets of the
+    /// operations removed by optimisation passes.
+    pub fn error_budget(&self, node: Node) -> f64 {
+        self.hugr
+            .get_metadata(node, METADATA_ERROR_BUDGET)
+            .and_then(|budget| budget.as_f64())
+            .unwrap_or(0.)
+    }
+
+    /// The sum of the error budgets of the circuit and of its commands.
+    pub fn total_error_budget(&self) -> f64 {
+        let ops_budget: f64 = self
+            .commands()
+            .map(|cmd| self.error_budget(cmd.node()))
+            .sum();
+        ops_budget + self.error_budget(self.parent)
+    }
+
     /// Returns the function type of the circuit.
     #[inline]
     pub fn circuit_signature(&self) -> Signature {
@@ -225,6 +303,109 @@ impl<T: HugrView> Circuit<T> {
         self.units().filter_map(filter::filter_qubit)
     }
 
+    /// Returns the qubits that are used as ancillas in the circuit.
+    ///
+    /// A qubit is considered an ancilla if its first operation prepares it in
+    /// the zero state (a [`Tk2Op::Reset`] or [`Tk2Op::QAlloc`]) and its last
+    /// operation either resets it again or discards it ([`Tk2Op::Reset`] or
+    /// [`Tk2Op::QFree`]).
+    ///
+    /// The detection is conservative, qubits whose first or last operation is
+    /// not a [`Tk2Op`] are never reported.
+    pub fn ancilla_qubits(&self) -> Vec<LinearUnit> {
+        // The first and last operation seen on each qubit.
+        let mut boundary_ops: HashMap<LinearUnit, (Option<Tk2Op>, Option<Tk2Op>)> = HashMap::new();
+        for cmd in self.commands() {
+            let op = Tk2Op::try_from(cmd.optype()).ok();
+            let qubits = cmd
+                .input_qubits()
+                .map(|(unit, _, _)| unit)
+                .chain(cmd.output_qubits().map(|(unit, _, _)| unit))
+                .unique();
+            for qb in qubits {
+                boundary_ops
+                    .entry(qb)
+                    .and_modify(|(_, last)| *last = op)
+                    .or_insert((op, op));
+            }
+        }
+
+        boundary_ops
+            .into_iter()
+            .filter(|(_, ops)| {
+                matches!(
+                    ops,
+                    (
+                        Some(Tk2Op::Reset | Tk2Op::QAlloc),
+                        Some(Tk2Op::Reset | Tk2Op::QFree)
+                    )
+                )
+            })
+            .map(|(qb, _)| qb)
+            .sorted()
+            .collect()
+    }
+
+    /// Returns the classical bit each qubit is measured into.
+    ///
+    /// Maps each qubit acted on by a [`Tk2Op::Measure`] to the boolean wire
+    /// holding the measurement result. Qubits measured multiple times are
+    /// mapped to the result of their last measurement. Use
+    /// [`Circuit::try_measurement_map`] to reject them instead.
+    pub fn measurement_map(&self) -> HashMap<LinearUnit, Wire> {
+        self.measurements().collect()
+    }
+
+    /// Returns the classical bit each qubit is measured into, as in
+    /// [`Circuit::measurement_map`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a qubit is measured more than once.
+    pub fn try_measurement_map(&self) -> Result<HashMap<LinearUnit, Wire>, RepeatedMeasurement> {
+        let mut map = HashMap::new();
+        for (qubit, result) in self.measurements() {
+            if let Some(first) = map.insert(qubit, result) {
+                return Err(RepeatedMeasurement {
+                    qubit,
+                    first: first.node(),
+                    second: result.node(),
+                });
+            }
+        }
+        Ok(map)
+    }
+
+    /// The measured qubits and their measurement results, in topological
+    /// order.
+    fn measurements(&self) -> impl Iterator<Item = (LinearUnit, Wire)> + '_ {
+        self.commands()
+            .filter(|cmd| cmd.optype() == &Tk2Op::Measure.into())
+            .filter_map(|cmd| {
+                let (qubit, _, _) = cmd.input_qubits().next()?;
+                Some((qubit, Wire::new(cmd.node(), 1)))
+            })
+    }
+
+    /// The number of [`Tk2Op::Measure`] operations in the circuit.
+    ///
+    /// Only the top-level operations of the circuit are counted.
+    pub fn n_measurements(&self) -> usize {
+        self.measurements().count()
+    }
+
+    /// Whether the circuit is a purely unitary computation.
+    ///
+    /// Returns `false` if the circuit contains measurements, resets, qubit
+    /// allocations or deallocations, or operations with classical
+    /// side-effects such as conditionals and operations on bits. Classical
+    /// computations of angle parameters are allowed. Circuit boxes are
+    /// checked recursively. Operations from other extensions are assumed to
+    /// have side-effects.
+    pub fn is_unitary(&self) -> bool {
+        self.commands().all(|cmd| is_unitary_command(self, &cmd))
+    }
+
     /// Returns all the commands in the circuit, in some topological order.
     ///
     /// Ignores the Input and Output nodes.
@@ -245,7 +426,7 @@ impl<T: HugrView> Circuit<T> {
     ///
     ///   [`Tk2Op`]: crate::Tk2Op
     #[inline]
-    pub fn operations(&self) -> impl Iterator<Item = Command<T>> + '_
+    pub fn operations(&self) -> impl Iterator<Item = Command<'_, T>> + '_
     where
         Self: Sized,
     {
@@ -253,6 +434,179 @@ impl<T: HugrView> Circuit<T> {
         self.commands().filter(|cmd| cmd.optype().is_custom_op())
     }
 
+    /// Returns the commands acting on a linear unit, in the order they are
+    /// applied to it.
+    ///
+    /// Multi-qubit commands are included whenever one of their inputs or
+    /// outputs is the given unit.
+    pub fn commands_on_qubit(&self, unit: LinearUnit) -> impl Iterator<Item = Command<'_, T>> + '_ {
+        self.commands().filter(move |cmd| {
+            cmd.linear_units(Direction::Incoming)
+                .chain(cmd.linear_units(Direction::Outgoing))
+                .any(|(u, _, _)| u == unit)
+        })
+    }
+
+    /// Returns the node currently feeding each linear output of the circuit.
+    ///
+    /// This is the last operation applied to each linear unit, or the input
+    /// node if the unit is not modified. New operations appended to a unit
+    /// are connected after its frontier node.
+    pub fn frontier(&self) -> HashMap<LinearUnit, Node> {
+        let output = self.output_node();
+        Units::new_incoming(self, output, DefaultUnitLabeller)
+            .filter_map(filter::filter_linear)
+            .filter_map(|(unit, port, _)| {
+                let (node, _) = self.hugr.single_linked_output(output, port)?;
+                Some((unit, node))
+            })
+            .collect()
+    }
+
+    /// Returns the implicit permutation of the circuit's linear units.
+    ///
+    /// Qubits may be permuted by the wiring of the circuit alone, for example
+    /// after absorbing explicit permutations with
+    /// [`push_permutations`](crate::passes::push_permutations). Entry `k` of
+    /// the returned vector is the linear unit ending at the `k`-th linear
+    /// output. The identity permutation means every unit ends at the output
+    /// with its own index.
+    pub fn implicit_permutation(&self) -> Vec<usize> {
+        let input = self.input_node();
+        let output = self.output_node();
+        let linear_inputs: HashMap<OutgoingPort, LinearUnit> = self
+            .linear_units()
+            .map(|(unit, port, _)| (port, unit))
+            .collect();
+        let command_outputs: HashMap<(Node, OutgoingPort), LinearUnit> = self
+            .commands()
+            .flat_map(|cmd| {
+                let node = cmd.node();
+                cmd.linear_outputs()
+                    .map(|(unit, port, _)| ((node, port), unit))
+                    .collect_vec()
+            })
+            .collect();
+        Units::new_incoming(self, output, DefaultUnitLabeller)
+            .filter_map(filter::filter_linear)
+            .map(|(unit, port, _)| {
+                let source = self.hugr.single_linked_output(output, port);
+                let source_unit = match source {
+                    Some((node, port)) if node == input => linear_inputs.get(&port),
+                    Some(source) => command_outputs.get(&source),
+                    None => None,
+                };
+                source_unit.unwrap_or(&unit).index()
+            })
+            .collect()
+    }
+
+    /// Returns the groups of linear units linked by the operations of the
+    /// circuit.
+    ///
+    /// Two units are in the same component if an operation acts on both of
+    /// them, or if an operation on one of them reads a classical value
+    /// computed by an operation on the other, either directly or
+    /// transitively. The circuit factors as a product of independent
+    /// subcircuits on each component. Units that no multi-unit operation
+    /// touches form singleton components.
+    ///
+    /// Each component is sorted, and the components are ordered by their
+    /// first unit.
+    pub fn connected_components(&self) -> Vec<Vec<LinearUnit>> {
+        let n_units = self.linear_units().count();
+        let mut components = UnionFind::<usize>::new(n_units);
+        // A unit linked to the classical outputs of each node.
+        let mut node_unit: HashMap<Node, usize> = HashMap::new();
+        for cmd in self.commands() {
+            let linear = cmd
+                .linear_inputs()
+                .map(|(unit, _, _)| unit.index())
+                .chain(cmd.linear_outputs().map(|(unit, _, _)| unit.index()));
+            let classical = cmd
+                .input_wires()
+                .into_iter()
+                .filter(|(unit, _)| matches!(unit, CircuitUnit::Wire(_)))
+                .filter_map(|(_, wire)| node_unit.get(&wire.node()).copied());
+            let units = linear.chain(classical).collect_vec();
+            if let Some(&first) = units.first() {
+                for &unit in &units[1..] {
+                    components.union(first, unit);
+                }
+                node_unit.insert(cmd.node(), first);
+            }
+        }
+
+        (0..n_units)
+            .map(|unit| (components.find(unit), LinearUnit::new(unit)))
+            .into_group_map()
+            .into_values()
+            .sorted()
+            .collect()
+    }
+
+    /// Returns the generations of the circuit's top-level dataflow graph.
+    ///
+    /// Generation 0 contains the nodes without predecessors, including the
+    /// input node. Each following generation contains the nodes whose
+    /// predecessors all lie in earlier generations. The output node is
+    /// included, so the last generation always contains it.
+    ///
+    /// Unlike [`Circuit::commands`], this is purely structural: every child of
+    /// the circuit's parent is returned, including the boundary nodes.
+    ///
+    /// Only the edges between children of the parent are considered. Edges
+    /// from outside the circuit, such as static edges from constants or
+    /// functions defined in an enclosing region, are always satisfied, so a
+    /// node whose predecessors all lie outside the circuit is in generation 0.
+    /// Edges into nested regions are ignored.
+    pub fn topological_generations(&self) -> Vec<Vec<Node>> {
+        let hugr = self.hugr();
+        let is_child = |node: Node| hugr.get_parent(node) == Some(self.parent());
+        let mut in_degree: HashMap<Node, usize> = hugr
+            .children(self.parent())
+            .map(|node| {
+                let preds = hugr.input_neighbours(node).filter(|&pred| is_child(pred));
+                (node, preds.count())
+            })
+            .collect();
+        let mut generation = in_degree
+            .iter()
+            .filter(|(_, &deg)| deg == 0)
+            .map(|(&node, _)| node)
+            .sorted()
+            .collect_vec();
+
+        let mut generations = Vec::new();
+        while !generation.is_empty() {
+            let mut next = Vec::new();
+            for &node in &generation {
+                for succ in hugr.output_neighbours(node) {
+                    let Some(deg) = in_degree.get_mut(&succ) else {
+                        continue;
+                    };
+                    *deg -= 1;
+                    if *deg == 0 {
+                        next.push(succ);
+                    }
+                }
+            }
+            next.sort();
+            generations.push(std::mem::replace(&mut generation, next));
+        }
+        generations
+    }
+
+    /// Returns the unfilled placeholder operations in the circuit, with their
+    /// names.
+    ///
+    /// See [`Circuit::fill_placeholders`].
+    pub fn placeholders(&self) -> Vec<(Node, String)> {
+        self.commands()
+            .filter_map(|cmd| Some((cmd.node(), match_placeholder_op(cmd.optype())?)))
+            .collect()
+    }
+
     /// Compute the cost of the circuit based on a per-operation cost function.
     #[inline]
     pub fn circuit_cost<F, C>(&self, op_cost: F) -> C
@@ -264,6 +618,17 @@ impl<T: HugrView> Circuit<T> {
         self.commands().map(|cmd| op_cost(cmd.optype())).sum()
     }
 
+    /// Estimate the probability of running the circuit without errors.
+    ///
+    /// This is the product of the fidelities assigned by `model` to each
+    /// command in the circuit.
+    #[inline]
+    pub fn estimated_fidelity(&self, model: &impl ErrorModel) -> f64 {
+        self.commands()
+            .map(|cmd| model.op_fidelity(cmd.optype()))
+            .product()
+    }
+
     /// Compute the cost of a group of nodes in a circuit based on a
     /// per-operation cost function.
     #[inline]
@@ -278,6 +643,80 @@ impl<T: HugrView> Circuit<T> {
             .sum()
     }
 
+    /// Check whether two circuits are structurally identical.
+    ///
+    /// This is an exact comparison, not an equivalence check: the circuits
+    /// must have the same signature and global phase, the same sequence of
+    /// commands, each with the same operation acting on the same linear units
+    /// and reading its classical inputs from the same command outputs, and the
+    /// same [implicit permutation](Circuit::implicit_permutation) of their
+    /// linear units at the output.
+    ///
+    /// The command sequence depends on the order in which commuting commands
+    /// were added to the circuit. If `canonicalise` is set, the commands of
+    /// both circuits are first sorted into a canonical topological order, so
+    /// such reorderings are ignored. Circuits with different [`CircuitHash`]es
+    /// are rejected early, but equal hashes are not trusted on their own.
+    pub fn structurally_eq(&self, other: &Circuit<impl HugrView>, canonicalise: bool) -> bool {
+        let phase = self.hugr().get_metadata(self.parent(), METADATA_PHASE);
+        let other_phase = other.hugr().get_metadata(other.parent(), METADATA_PHASE);
+        if self.circuit_signature() != other.circuit_signature() || phase != other_phase {
+            return false;
+        }
+        if canonicalise {
+            if let (Ok(h1), Ok(h2)) = (self.circuit_hash(), other.circuit_hash()) {
+                if h1 != h2 {
+                    return false;
+                }
+            }
+        }
+        if self.implicit_permutation() != other.implicit_permutation() {
+            return false;
+        }
+        let (mut keys, mut other_keys) = (command_keys(self), command_keys(other));
+        if canonicalise {
+            keys = canonical_command_order(keys);
+            other_keys = canonical_command_order(other_keys);
+        }
+        keys.len() == other_keys.len()
+            && keys.iter().zip(&other_keys).all(|(a, b)| {
+                let (op, inputs, outputs, classical) = a;
+                let (other_op, other_inputs, other_outputs, other_classical) = b;
+                (inputs, outputs, classical) == (other_inputs, other_outputs, other_classical)
+                    && ops_eq(op, other_op)
+            })
+    }
+
+    /// Check whether two circuits apply the same gates with approximately
+    /// equal parameters.
+    ///
+    /// This is less strict than [`Circuit::structurally_eq`], as the
+    /// parameters of each gate are compared with [`Command::approx_eq`]
+    /// within `tol` radians, regardless of how they are computed. Numeric
+    /// global phases are compared within `tol` radians too, and symbolic ones
+    /// after simplification. It is stricter than an equivalence check, as the
+    /// circuits must have the same signature, the same sequence of gates, each
+    /// acting on the same linear units, and the same
+    /// [implicit permutation](Circuit::implicit_permutation).
+    ///
+    /// Parameter computations, such as constants and `AngleAdd` operations,
+    /// are not compared themselves.
+    pub fn approx_eq(&self, other: &Circuit<impl HugrView>, tol: f64) -> bool {
+        if self.circuit_signature() != other.circuit_signature()
+            || !phase_approx_eq(self, other, tol)
+            || self.implicit_permutation() != other.implicit_permutation()
+        {
+            return false;
+        }
+        let (gates, other_gates) = (gate_commands(self), gate_commands(other));
+        gates.len() == other_gates.len()
+            && gates.iter().zip(&other_gates).all(|(a, b)| {
+                let units = a.linear_inputs().map(|(unit, _, _)| unit);
+                let other_units = b.linear_inputs().map(|(unit, _, _)| unit);
+                units.eq(other_units) && a.approx_eq(b, tol)
+            })
+    }
+
     /// Return the graphviz representation of the underlying graph and hierarchy side by side.
     ///
     /// For a simpler representation, use the [`Circuit::mermaid_string`] format instead.
@@ -305,6 +744,11 @@ impl<T: HugrView> Circuit<T> {
     /// Regions that are not descendants of the parent node are not included in the new HUGR.
     /// This may invalidate calls to functions defined elsewhere. Make sure to inline any
     /// external functions before calling this method.
+    ///
+    /// When the circuit is a CFG dataflow block with a single successor, its
+    /// branch predicate output is removed. Other blocks keep their branch
+    /// predicate as the first output, and the reason they were not unpacked
+    /// is logged at the debug level.
     pub fn extract_dfg(&self) -> Result<Circuit<Hugr>, CircuitMutError>
     where
         T: ExtractHugr,
@@ -316,9 +760,658 @@ impl<T: HugrView> Circuit<T> {
                 .expect("Circuit parent was not a dataflow container.");
             view.extract_hugr().into()
         };
-        extract_dfg::rewrite_into_dfg(&mut circ)?;
+        // Errors refer to the extracted copy of the circuit, report the
+        // original parent instead.
+        extract_dfg::rewrite_into_dfg(&mut circ).map_err(|e| match e {
+            CircuitMutError::ParentReplacement { optype, source, .. } => {
+                CircuitMutError::ParentReplacement {
+                    parent: self.parent,
+                    optype,
+                    source,
+                }
+            }
+            CircuitMutError::CfgUnpacking { reason, .. } => CircuitMutError::CfgUnpacking {
+                block: self.parent,
+                reason,
+            },
+            e => e,
+        })?;
         Ok(circ)
     }
+
+    /// Returns the inverse of the circuit, as a new DFG-rooted circuit.
+    ///
+    /// The gates are replaced by their [`Tk2Op::inverse`] in reverse order,
+    /// and rotations by the same gate with their [`Tk2Op::inverse_angles`] as
+    /// constants. `ZZMax` is inverted as `ZZPhase(-π/2)`. The global phase is negated, and the implicit permutation
+    /// of the qubits is reversed. Use it with [`Circuit::append_box`] to
+    /// append a daggered box.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the circuit acts on non-qubit units, if it
+    /// contains an operation without a known inverse, or if a rotation angle
+    /// is not a numeric constant.
+    pub fn dagger(&self) -> Result<Circuit<Hugr>, CircuitMutError> {
+        let signature = self.circuit_signature();
+        let qubits: TypeRow = vec![QB_T; signature.input_count()].into();
+        if signature.input() != &qubits || signature.output() != &qubits {
+            return Err(CircuitMutError::SignatureMismatch {
+                expected: qubits,
+                signature,
+            });
+        }
+        let mut params = NumericParams::default();
+        let mut inverses = Vec::new();
+        for cmd in self.commands() {
+            let non_numeric = |_| CircuitMutError::NonNumericAngle(cmd.node());
+            if params.process(&cmd).map_err(non_numeric)? {
+                continue;
+            }
+            let non_invertible = || CircuitMutError::NonInvertibleOp(cmd.optype().clone());
+            let op = Tk2Op::try_from(cmd.optype()).map_err(|_| non_invertible())?;
+            let angles = params.inputs(&cmd).map_err(non_numeric)?;
+            let (op, angles) = match (op.inverse(), op) {
+                (Some(inverse), _) => (inverse, angles),
+                // `ZZMax` is exactly `ZZPhase(π/2)`, without a global phase.
+                (None, Tk2Op::ZZMax) => (Tk2Op::ZZPhase, vec![-FRAC_PI_2]),
+                (None, op) => (op, op.inverse_angles(&angles).ok_or_else(non_invertible)?),
+            };
+            let units = cmd
+                .input_qubits()
+                .map(|(unit, _, _)| unit.index())
+                .collect_vec();
+            inverses.push((op, units, angles));
+        }
+
+        let mut dfg = DFGBuilder::new(Signature::new_endo(qubits)).unwrap();
+        // The `k`-th input of the inverse is the `k`-th output of the circuit.
+        let boundary = dfg.input_wires().collect_vec();
+        let mut qbs = boundary.clone();
+        for (k, unit) in self.implicit_permutation().into_iter().enumerate() {
+            qbs[unit] = boundary[k];
+        }
+        for (op, units, angles) in inverses.into_iter().rev() {
+            let inputs = units
+                .iter()
+                .map(|&q| qbs[q])
+                .chain(angles.iter().map(|&a| dfg.add_load_value(ConstF64::new(a))))
+                .collect_vec();
+            let outputs = dfg
+                .add_dataflow_op(op, inputs)
+                .expect("Inverse gates act on the same qubits.")
+                .outputs();
+            for (&q, wire) in units.iter().zip(outputs) {
+                qbs[q] = wire;
+            }
+        }
+        let mut inverse: Circuit = dfg
+            .finish_hugr_with_outputs(qbs, &REGISTRY)
+            .expect("Inverse circuit should be valid.")
+            .into();
+
+        let phase = self
+            .hugr
+            .get_metadata(self.parent, METADATA_PHASE)
+            .and_then(|p| p.as_str());
+        let phase = match phase.map(|p| (p, p.parse::<f64>())) {
+            None => None,
+            Some((_, Ok(phase))) => Some((-phase).to_string()),
+            Some((expr, Err(_))) => Some(format!("-({expr})")),
+        };
+        if let Some(phase) = phase {
+            let parent = inverse.parent();
+            inverse
+                .hugr_mut()
+                .set_metadata(parent, METADATA_PHASE, phase);
+        }
+        Ok(inverse)
+    }
+
+    /// Compose two circuits in parallel, placing `other` below `self` on
+    /// disjoint units.
+    ///
+    /// The inputs and outputs of the result are those of `self` followed by
+    /// those of `other`, and its global phase is the sum of both phases. Any
+    /// pytket register names of `other` that collide with those of `self`
+    /// are renamed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either circuit cannot be extracted as a DFG, see
+    /// [`Circuit::extract_dfg`].
+    pub fn tensor(
+        &self,
+        other: &Circuit<impl ExtractHugr>,
+    ) -> Result<Circuit<Hugr>, CircuitMutError>
+    where
+        T: ExtractHugr,
+    {
+        let left = self.extract_dfg()?;
+        let right = other.extract_dfg()?;
+        let register_metadata = tensor_register_metadata(&left, &right);
+        let phase = [&left, &right]
+            .into_iter()
+            .filter_map(|c| c.hugr().get_metadata(c.parent(), METADATA_PHASE))
+            .filter_map(|p| p.as_str().map(str::to_string))
+            .reduce(|a, b| match (a.parse::<f64>(), b.parse::<f64>()) {
+                (Ok(a), Ok(b)) => (a + b).to_string(),
+                _ => format!("{a} + {b}"),
+            });
+
+        let (left_sig, right_sig) = (left.circuit_signature(), right.circuit_signature());
+        let signature = Signature::new(
+            left_sig.input().extend(right_sig.input().iter()),
+            left_sig.output().extend(right_sig.output().iter()),
+        )
+        .with_extension_delta(
+            left_sig
+                .extension_reqs
+                .clone()
+                .union(right_sig.extension_reqs.clone()),
+        );
+        let mut hugr = Hugr::new(OpType::DFG(DFG {
+            signature: signature.clone(),
+        }));
+        let root = hugr.root();
+        let input = hugr.add_node_with_parent(root, Input::new(signature.input().clone()));
+        let output = hugr.add_node_with_parent(root, Output::new(signature.output().clone()));
+
+        // Connect each circuit to its slice of the boundary, and inline it.
+        let (mut in_offset, mut out_offset) = (0, 0);
+        for (circ, sig) in [(left, left_sig), (right, right_sig)] {
+            let node = hugr.insert_hugr(root, circ.into_hugr()).new_root;
+            for i in 0..sig.input_count() {
+                hugr.connect(input, in_offset + i, node, i);
+            }
+            for i in 0..sig.output_count() {
+                hugr.connect(node, i, output, out_offset + i);
+            }
+            in_offset += sig.input_count();
+            out_offset += sig.output_count();
+            hugr.apply_rewrite(InlineDFG(node.into()))
+                .expect("Extracted circuits can be inlined.");
+        }
+
+        if let Some(phase) = phase {
+            hugr.set_metadata(root, METADATA_PHASE, phase);
+        }
+        for (key, value) in register_metadata {
+            hugr.set_metadata(root, key, value);
+        }
+        Ok(hugr.into())
+    }
+}
+
+impl<T: HugrMut> Circuit<T> {
+    /// Append a dataflow HUGR fragment at the end of the circuit, as a single
+    /// opaque operation acting on the given qubits.
+    ///
+    /// The fragment is inserted as a [`OpType::DFG`] node. Its signature must
+    /// take and return exactly the types of the selected linear units, in
+    /// order. Since the node is not a custom operation, optimisation passes
+    /// treat it as a barrier.
+    ///
+    /// Returns the inserted node.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the fragment root cannot define a circuit, if a
+    /// unit does not exist or is repeated, or if the fragment signature does
+    /// not match the selected units. The circuit is not modified on failure.
+    pub fn append_opaque(
+        &mut self,
+        fragment: Hugr,
+        qubits: &[LinearUnit],
+    ) -> Result<Node, CircuitMutError> {
+        check_distinct_units(qubits)?;
+        let root = fragment.root();
+        let fragment = Circuit::try_new(fragment, root)?.extract_dfg()?;
+        let fragment_sig = fragment.circuit_signature();
+
+        // The ports of the output node where each linear unit ends.
+        let linear_outputs = self.linear_outputs();
+        let (ports, types): (Vec<_>, Vec<_>) = qubits
+            .iter()
+            .map(|qb| {
+                linear_outputs
+                    .get(qb.index())
+                    .cloned()
+                    .ok_or(CircuitMutError::InvalidPortOffset(qb.index()))
+            })
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .unzip();
+        let types: TypeRow = types.into();
+        if fragment_sig.input() != &types || fragment_sig.output() != &types {
+            return Err(CircuitMutError::SignatureMismatch {
+                expected: types,
+                signature: fragment_sig,
+            });
+        }
+
+        let node = self
+            .hugr
+            .insert_hugr(self.parent, fragment.into_hugr())
+            .new_root;
+        self.connect_before_output(node, ports);
+        Ok(node)
+    }
+
+    /// Append an operation at the end of the circuit, acting on the given
+    /// qubits.
+    ///
+    /// Units past the end of the circuit's boundary are added to it as new
+    /// qubits with [`Circuit::add_qubit`], along with any missing units before
+    /// them, so circuits can be built without declaring their qubits in
+    /// advance. The operation must take and return exactly the types of the
+    /// selected units, in order.
+    ///
+    /// Returns the inserted node.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a unit is repeated, if the operation signature
+    /// does not match the units, where new units are qubits, or if the
+    /// circuit's boundary cannot be extended. All checks are done before the
+    /// circuit is modified, so it is left unchanged on failure.
+    pub fn append(
+        &mut self,
+        op: impl Into<OpType>,
+        qubits: &[LinearUnit],
+    ) -> Result<Node, CircuitMutError> {
+        check_distinct_units(qubits)?;
+        let op = op.into();
+        let linear_outputs = self.linear_outputs();
+        let types: TypeRow = qubits
+            .iter()
+            .map(|qb| {
+                linear_outputs
+                    .get(qb.index())
+                    .map_or(QB_T, |(_, typ)| typ.clone())
+            })
+            .collect_vec()
+            .into();
+        let signature = op.dataflow_signature().unwrap_or_default();
+        if signature.input() != &types || signature.output() != &types {
+            return Err(CircuitMutError::SignatureMismatch {
+                expected: types,
+                signature,
+            });
+        }
+
+        let n_units = qubits.iter().map(|qb| qb.index() + 1).max().unwrap_or(0);
+        if n_units > linear_outputs.len() {
+            self.add_qubits(n_units - linear_outputs.len())?;
+        }
+        let linear_outputs = self.linear_outputs();
+        let ports = qubits
+            .iter()
+            .map(|qb| linear_outputs[qb.index()].0)
+            .collect_vec();
+        let node = self.hugr.add_node_with_parent(self.parent, op);
+        self.connect_before_output(node, ports);
+        Ok(node)
+    }
+
+    /// Set the error budget of a node, bounding the approximation error its
+    /// operation may introduce.
+    ///
+    /// Passes merging or removing operations combine their budgets instead
+    /// of dropping them, see [`Circuit::total_error_budget`].
+    pub fn set_error_budget(&mut self, node: Node, budget: f64) {
+        self.hugr.set_metadata(node, METADATA_ERROR_BUDGET, budget);
+    }
+
+    /// Add a new qubit at the end of the circuit's boundary, as an input wired
+    /// directly to a new output.
+    ///
+    /// Returns the linear unit of the new qubit.
+    pub fn add_qubit(&mut self) -> Result<LinearUnit, CircuitMutError> {
+        let unit = LinearUnit::new(self.linear_units().count());
+        self.add_qubits(1)?;
+        Ok(unit)
+    }
+
+    /// Add `n` new qubits at the end of the circuit's boundary, each as an
+    /// input wired directly to a new output.
+    ///
+    /// The new signature of the parent is computed before the circuit is
+    /// modified, so the circuit is left unchanged on failure.
+    fn add_qubits(&mut self, n: usize) -> Result<(), CircuitMutError> {
+        let parent = self.parent();
+        let [inp, out] = self.io_nodes();
+        let signature = self.circuit_signature();
+        let (in_port, out_port) = (signature.input_count(), signature.output_count());
+        let extend = |types: &TypeRow| -> TypeRow {
+            types
+                .iter()
+                .cloned()
+                .chain(vec![QB_T; n])
+                .collect_vec()
+                .into()
+        };
+        let inp_types = extend(signature.input());
+        let out_types = extend(signature.output());
+        let parent_op = parent_with_signature(
+            self.hugr(),
+            parent,
+            inp_types.clone(),
+            Some(out_types.clone()),
+        )?;
+
+        let hugr = self.hugr_mut();
+        for i in 0..n {
+            insert_value_port(hugr, inp, Direction::Outgoing, in_port + i);
+            insert_value_port(hugr, out, Direction::Incoming, out_port + i);
+        }
+        hugr.replace_op(inp, Input::new(inp_types))?;
+        hugr.replace_op(out, Output::new(out_types))?;
+        for i in 0..n {
+            hugr.connect(inp, in_port + i, out, out_port + i);
+        }
+        hugr.replace_op(parent, parent_op)?;
+        Ok(())
+    }
+
+    /// The ports of the output node where each linear unit ends, with their
+    /// types.
+    fn linear_outputs(&self) -> Vec<(IncomingPort, Type)> {
+        self.hugr
+            .in_value_types(self.output_node())
+            .filter(|(_, typ)| type_is_linear(typ))
+            .collect_vec()
+    }
+
+    /// Connect the `i`-th input and output of `node` between the output node
+    /// port `ports[i]` and the wire currently feeding it.
+    fn connect_before_output(&mut self, node: Node, ports: Vec<IncomingPort>) {
+        let output = self.output_node();
+        for (i, port) in ports.into_iter().enumerate() {
+            let (src, src_port) = self
+                .hugr
+                .single_linked_output(output, port)
+                .expect("Linear circuit outputs must be connected.");
+            self.hugr.disconnect(output, port);
+            self.hugr.connect(src, src_port, node, i);
+            self.hugr.connect(node, i, output, port);
+        }
+    }
+
+    /// Append a named subcircuit at the end of the circuit, as a single box
+    /// acting on the given qubits.
+    ///
+    /// The box is a nested [`OpType::DFG`] node containing a copy of
+    /// `subcircuit`, labelled with `name` in its [`METADATA_BOX_NAME`]
+    /// metadata. As with [`Circuit::append_opaque`], optimisation passes
+    /// treat the box as a barrier until it is inlined with
+    /// [`decompose_boxes`](crate::passes::decompose_boxes). To append a
+    /// daggered box, pass the [`Circuit::dagger`] of the subcircuit.
+    ///
+    /// Returns the inserted node.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the subcircuit cannot be extracted as a DFG, or in
+    /// the same cases as [`Circuit::append_opaque`].
+    pub fn append_box(
+        &mut self,
+        name: impl Into<String>,
+        subcircuit: &Circuit<impl ExtractHugr>,
+        qubits: &[LinearUnit],
+    ) -> Result<Node, CircuitMutError> {
+        let fragment = subcircuit.extract_dfg()?.into_hugr();
+        let node = self.append_opaque(fragment, qubits)?;
+        self.hugr.set_metadata(node, METADATA_BOX_NAME, name.into());
+        Ok(node)
+    }
+
+    /// Insert an operation on the given qubits immediately before the command
+    /// at `target`.
+    ///
+    /// The operation must take and return exactly the types of the selected
+    /// linear units, in order, and the units must be inputs of `target`.
+    ///
+    /// Returns the inserted node.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `target` is not a command of the circuit, if a unit
+    /// is not an input of `target` or is repeated, or if the operation
+    /// signature does not match the units. The circuit is not modified on
+    /// failure.
+    pub fn insert_op_before(
+        &mut self,
+        target: Node,
+        op: impl Into<OpType>,
+        qubits: &[LinearUnit],
+    ) -> Result<Node, CircuitMutError> {
+        self.insert_op(target, op.into(), qubits, Direction::Incoming)
+    }
+
+    /// Insert an operation on the given qubits immediately after the command
+    /// at `target`.
+    ///
+    /// The operation must take and return exactly the types of the selected
+    /// linear units, in order, and the units must be outputs of `target`.
+    ///
+    /// Returns the inserted node.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `target` is not a command of the circuit, if a unit
+    /// is not an output of `target` or is repeated, or if the operation
+    /// signature does not match the units. The circuit is not modified on
+    /// failure.
+    pub fn insert_op_after(
+        &mut self,
+        target: Node,
+        op: impl Into<OpType>,
+        qubits: &[LinearUnit],
+    ) -> Result<Node, CircuitMutError> {
+        self.insert_op(target, op.into(), qubits, Direction::Outgoing)
+    }
+
+    /// Replace the placeholder operations in the circuit with concrete
+    /// operations.
+    ///
+    /// Each placeholder created with [`placeholder_op`] whose name is a key of
+    /// `ops` is replaced by the corresponding operation. Placeholders without
+    /// an entry are left in place, and can be listed with
+    /// [`Circuit::placeholders`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an operation's input or output types differ from
+    /// the ones declared by its placeholder. Placeholders are checked before
+    /// any replacement, so the circuit is not modified on failure.
+    ///
+    ///   [`placeholder_op`]: crate::placeholder_op
+    pub fn fill_placeholders(
+        &mut self,
+        ops: &HashMap<String, OpType>,
+    ) -> Result<(), CircuitMutError> {
+        let mut replacements = Vec::new();
+        for (node, name) in self.placeholders() {
+            let Some(op) = ops.get(&name) else {
+                continue;
+            };
+            let expected = self.hugr.signature(node).unwrap();
+            let matches = op.dataflow_signature().is_some_and(|sig| {
+                sig.input() == expected.input() && sig.output() == expected.output()
+            });
+            if !matches {
+                return Err(CircuitMutError::PlaceholderMismatch {
+                    name,
+                    signature: expected,
+                    op: op.clone(),
+                });
+            }
+            replacements.push((node, op.clone()));
+        }
+        for (node, op) in replacements {
+            self.hugr.replace_op(node, op)?;
+        }
+        Ok(())
+    }
+
+    /// Splice `op` into the wires of `qubits` at the `direction` side of
+    /// `target`.
+    fn insert_op(
+        &mut self,
+        target: Node,
+        op: OpType,
+        qubits: &[LinearUnit],
+        direction: Direction,
+    ) -> Result<Node, CircuitMutError> {
+        check_distinct_units(qubits)?;
+        let (ports, types): (Vec<Port>, Vec<Type>) = {
+            let cmd = self
+                .commands()
+                .find(|cmd| cmd.node() == target)
+                .ok_or(CircuitMutError::NotACommand(target))?;
+            qubits
+                .iter()
+                .map(|&unit| {
+                    cmd.linear_units(direction)
+                        .find(|(u, _, _)| *u == unit)
+                        .map(|(_, port, typ)| (port, typ))
+                        .ok_or(CircuitMutError::UnitNotInCommand { node: target, unit })
+                })
+                .collect::<Result<Vec<_>, _>>()?
+                .into_iter()
+                .unzip()
+        };
+        let types: TypeRow = types.into();
+        let signature = op.dataflow_signature().unwrap_or_default();
+        if signature.input() != &types || signature.output() != &types {
+            return Err(CircuitMutError::SignatureMismatch {
+                expected: types,
+                signature,
+            });
+        }
+
+        let node = self.hugr.add_node_with_parent(self.parent, op);
+        for (i, port) in ports.into_iter().enumerate() {
+            let (other, other_port) = self
+                .hugr
+                .linked_ports(target, port)
+                .exactly_one()
+                .ok()
+                .expect("Linear ports must have exactly one link.");
+            self.hugr.disconnect(target, port);
+            match direction {
+                Direction::Incoming => {
+                    self.hugr.connect(other, other_port.index(), node, i);
+                    self.hugr.connect(node, i, target, port.index());
+                }
+                Direction::Outgoing => {
+                    self.hugr.connect(target, port.index(), node, i);
+                    self.hugr.connect(node, i, other, other_port.index());
+                }
+            }
+        }
+        Ok(node)
+    }
+
+    /// Reorder the boundary of the circuit, without changing its contents.
+    ///
+    /// Position `i` of the new boundary takes the input and the output that
+    /// were at position `new_order[i]`. The same permutation is applied to
+    /// both sides, so the circuit must have as many inputs as outputs. The
+    /// circuit signature and its [`LinearUnit`]s follow the new order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `new_order` is not a permutation of the boundary
+    /// positions.
+    pub fn permute_boundary(&mut self, new_order: &[usize]) -> Result<(), CircuitMutError> {
+        let signature = self.circuit_signature();
+        let n = signature.input_count();
+        let is_permutation = new_order.len() == n
+            && signature.output_count() == n
+            && new_order.iter().sorted().copied().eq(0..n);
+        if !is_permutation {
+            return Err(CircuitMutError::InvalidBoundaryPermutation(
+                new_order.to_vec(),
+            ));
+        }
+
+        let parent = self.parent();
+        let [inp, out] = self.io_nodes();
+        let mut new_position = vec![0; n];
+        for (i, &old) in new_order.iter().enumerate() {
+            new_position[old] = i;
+        }
+
+        let hugr = self.hugr_mut();
+        let targets = (0..n)
+            .map(|p| hugr.linked_inputs(inp, p).collect_vec())
+            .collect_vec();
+        let sources = (0..n)
+            .map(|p| hugr.single_linked_output(out, p))
+            .collect_vec();
+        for p in 0..n {
+            hugr.disconnect(inp, OutgoingPort::from(p));
+            hugr.disconnect(out, IncomingPort::from(p));
+        }
+        for (i, &old) in new_order.iter().enumerate() {
+            for &(target, port) in &targets[old] {
+                if target != out {
+                    hugr.connect(inp, i, target, port);
+                }
+            }
+            match sources[old] {
+                Some((source, port)) if source == inp => {
+                    hugr.connect(inp, new_position[port.index()], out, i);
+                }
+                Some((source, port)) => hugr.connect(source, port, out, i),
+                None => {}
+            }
+        }
+
+        let permute = |types: &TypeRow| -> TypeRow {
+            new_order
+                .iter()
+                .map(|&old| types[old].clone())
+                .collect_vec()
+                .into()
+        };
+        let inp_types = permute(signature.input());
+        let out_types = permute(signature.output());
+        hugr.replace_op(inp, Input::new(inp_types.clone()))?;
+        hugr.replace_op(out, Output::new(out_types.clone()))?;
+        set_parent_signature(hugr, parent, inp_types, Some(out_types))
+    }
+}
+
+impl Circuit<Hugr> {
+    /// Compact the node indices of the underlying HUGR.
+    ///
+    /// Removing operations leaves gaps in the node indices. This renumbers the
+    /// nodes contiguously, ordering the commands of the circuit topologically
+    /// after its input and output nodes. The structure of the circuit is
+    /// unchanged, but any previously obtained [`Node`] handles are
+    /// invalidated.
+    pub fn compress_ids(&mut self) {
+        let commands = self.commands().map(|cmd| cmd.node()).collect_vec();
+        let mut prev = self.output_node();
+        for node in commands {
+            self.hugr.move_after_sibling(node, prev);
+            prev = node;
+        }
+
+        // Follow the circuit's parent as nodes are swapped around.
+        let mut parent = self.parent;
+        self.hugr.canonicalize_nodes(|source, target| {
+            if parent == source {
+                parent = target;
+            } else if parent == target {
+                parent = source;
+            }
+        });
+        self.parent = parent;
+    }
 }
 
 impl<T: HugrView> From<T> for Circuit<T> {
@@ -360,12 +1453,184 @@ fn check_hugr(hugr: &impl HugrView, parent: Node) -> Result<(), CircuitError> {
     }
 }
 
-/// Remove an empty wire in a dataflow HUGR.
+/// A structural description of a circuit command, independent of node
+/// indices.
 ///
-/// The wire to be removed is identified by the index of the outgoing port
-/// at the circuit input node.
+/// Classical inputs are identified by the position of their source command
+/// in the command sequence (or `None` for the circuit inputs) and its port.
+type CommandKey = (
+    OpType,
+    Vec<LinearUnit>,
+    Vec<LinearUnit>,
+    Vec<(Option<usize>, usize)>,
+);
+
+/// Returns the [`CommandKey`]s of a circuit, in command order.
+fn command_keys(circ: &Circuit<impl HugrView>) -> Vec<CommandKey> {
+    let mut positions: HashMap<Node, usize> = HashMap::new();
+    circ.commands()
+        .enumerate()
+        .map(|(i, cmd)| {
+            positions.insert(cmd.node(), i);
+            let classical_inputs = cmd
+                .input_wires()
+                .into_iter()
+                .filter(|(unit, _)| matches!(unit, CircuitUnit::Wire(_)))
+                .map(|(_, wire)| (positions.get(&wire.node()).copied(), wire.source().index()))
+                .collect();
+            (
+                cmd.optype().clone(),
+                cmd.linear_inputs().map(|(u, _, _)| u).collect(),
+                cmd.linear_outputs().map(|(u, _, _)| u).collect(),
+                classical_inputs,
+            )
+        })
+        .collect()
+}
+
+/// Reorder the [`CommandKey`]s of a circuit into a canonical topological
+/// order, independent of the order in which commuting commands were added.
 ///
-/// This will change the circuit signature and will shift all ports after
+/// Among the commands whose predecessors on their linear units and classical
+/// inputs are already placed, the smallest by serialized operation, units
+/// and classical inputs comes first. Classical inputs are renumbered to the
+/// new positions of their sources.
+fn canonical_command_order(keys: Vec<CommandKey>) -> Vec<CommandKey> {
+    // The commands depending on each command, and the number of commands each
+    // one depends on.
+    let mut successors: Vec<Vec<usize>> = vec![Vec::new(); keys.len()];
+    let mut n_predecessors = vec![0; keys.len()];
+    let mut last_on_unit: HashMap<LinearUnit, usize> = HashMap::new();
+    for (i, (_, inputs, outputs, classical)) in keys.iter().enumerate() {
+        let predecessors: BTreeSet<usize> = inputs
+            .iter()
+            .filter_map(|unit| last_on_unit.get(unit).copied())
+            .chain(classical.iter().filter_map(|&(pos, _)| pos))
+            .collect();
+        n_predecessors[i] = predecessors.len();
+        for p in predecessors {
+            successors[p].push(i);
+        }
+        for &unit in inputs.iter().chain(outputs) {
+            last_on_unit.insert(unit, i);
+        }
+    }
+
+    let mut new_position: Vec<Option<usize>> = vec![None; keys.len()];
+    let renumber = |classical: &[(Option<usize>, usize)], new_position: &[Option<usize>]| {
+        classical
+            .iter()
+            .map(|&(pos, port)| (pos.and_then(|p| new_position[p]), port))
+            .collect_vec()
+    };
+    let sort_key = |i: usize, new_position: &[Option<usize>]| {
+        let (op, inputs, outputs, classical) = &keys[i];
+        let op = serde_json::to_string(op).unwrap_or_default();
+        let classical = renumber(classical, new_position);
+        Reverse((op, inputs.clone(), outputs.clone(), classical, i))
+    };
+    let mut ready: BinaryHeap<_> = (0..keys.len())
+        .filter(|&i| n_predecessors[i] == 0)
+        .map(|i| sort_key(i, &new_position))
+        .collect();
+    let mut order = Vec::with_capacity(keys.len());
+    while let Some(Reverse((_, _, _, _, i))) = ready.pop() {
+        new_position[i] = Some(order.len());
+        order.push(i);
+        for &s in &successors[i] {
+            n_predecessors[s] -= 1;
+            if n_predecessors[s] == 0 {
+                ready.push(sort_key(s, &new_position));
+            }
+        }
+    }
+
+    order
+        .into_iter()
+        .map(|i| {
+            let (op, inputs, outputs, classical) = &keys[i];
+            let classical = renumber(classical, &new_position);
+            (op.clone(), inputs.clone(), outputs.clone(), classical)
+        })
+        .collect()
+}
+
+/// Compare two operations for equality.
+///
+/// Opaque constant values do not implement a meaningful equality, so
+/// constants are compared by their serialized form instead.
+fn ops_eq(a: &OpType, b: &OpType) -> bool {
+    match (a, b) {
+        (OpType::Const(_), OpType::Const(_)) => {
+            a == b || serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+        }
+        _ => a == b,
+    }
+}
+
+/// The commands of a circuit that are not parameter computations, in command
+/// order.
+fn gate_commands<T: HugrView>(circ: &Circuit<T>) -> Vec<Command<'_, T>> {
+    let mut params = NumericParams::default();
+    circ.commands()
+        .filter(|cmd| !params.process(cmd).unwrap_or(true))
+        .collect()
+}
+
+/// Compare the global phases of two circuits, see [`Circuit::approx_eq`].
+fn phase_approx_eq(a: &Circuit<impl HugrView>, b: &Circuit<impl HugrView>, tol: f64) -> bool {
+    fn phase(circ: &Circuit<impl HugrView>) -> &str {
+        circ.hugr()
+            .get_metadata(circ.parent(), METADATA_PHASE)
+            .and_then(|p| p.as_str())
+            .unwrap_or("0")
+    }
+    let (a, b) = (phase(a), phase(b));
+    match (symbols::eval_param(a), symbols::eval_param(b)) {
+        (Some(a), Some(b)) => {
+            // Phases are stored in half-turns, and are periodic in 2π.
+            let diff = ((a - b) * PI).rem_euclid(TAU);
+            diff.min(TAU - diff) <= tol
+        }
+        _ => simplify_param(a) == simplify_param(b),
+    }
+}
+
+/// Whether a command of a circuit is a unitary operation, see
+/// [`Circuit::is_unitary`].
+///
+/// Only known pure operations are accepted. Any other operation, such as
+/// operations from other extensions, may have side-effects.
+pub(crate) fn is_unitary_command<T: HugrView>(circ: &Circuit<T>, cmd: &Command<'_, T>) -> bool {
+    if let Ok(op) = Tk2Op::try_from(cmd.optype()) {
+        return op.is_quantum() || op == Tk2Op::AngleAdd;
+    }
+    let optype = cmd.optype();
+    if match_permutation_op(optype).is_some() || match_symb_const_op(optype).is_some() {
+        return true;
+    }
+    match optype {
+        OpType::DFG(_) => Circuit::new(circ.hugr().base_hugr(), cmd.node()).is_unitary(),
+        OpType::Const(_) | OpType::LoadConstant(_) => true,
+        OpType::CustomOp(op) => op.extension() == &float_ops::EXTENSION_ID,
+        _ => false,
+    }
+}
+
+/// Check that no linear unit is selected more than once.
+fn check_distinct_units(qubits: &[LinearUnit]) -> Result<(), CircuitMutError> {
+    match qubits.iter().duplicates().next() {
+        Some(&unit) => Err(CircuitMutError::RepeatedUnit(unit)),
+        None => Ok(()),
+    }
+}
+
+/// Remove an empty wire in a dataflow HUGR.
+///
+/// The wire to be removed is identified by the index of the outgoing port
+/// at the circuit input node.
+///
+/// This will change the circuit signature and will shift all ports after
 /// the removed wire by -1. If the wire is connected to the output node,
 /// this will also change the signature output and shift the ports after
 /// the removed wire by -1.
@@ -417,6 +1682,34 @@ pub(crate) fn remove_empty_wire(
     Ok(())
 }
 
+/// Add a dataflow port to an input or output node at position `index`, right
+/// before its order port.
+fn insert_value_port(hugr: &mut impl HugrMut, node: Node, direction: Direction, index: usize) {
+    let order_port = Port::new(direction, index);
+    let links = hugr.linked_ports(node, order_port).collect_vec();
+    hugr.disconnect(node, order_port);
+    hugr.add_ports(node, direction, 1);
+    for (other, other_port) in links {
+        match direction {
+            Direction::Incoming => hugr.connect(other, other_port.index(), node, index + 1),
+            Direction::Outgoing => hugr.connect(node, index + 1, other, other_port.index()),
+        }
+    }
+}
+
+/// Error returned by [`Circuit::try_measurement_map`] when a qubit is
+/// measured more than once.
+#[derive(Debug, Clone, Copy, Error, PartialEq)]
+#[error("Qubit {} is measured by both {first} and {second}.", qubit.index())]
+pub struct RepeatedMeasurement {
+    /// The qubit measured multiple times.
+    pub qubit: LinearUnit,
+    /// The node of the first measurement.
+    pub first: Node,
+    /// The node of a later measurement.
+    pub second: Node,
+}
+
 /// Errors that can occur when mutating a circuit.
 #[derive(Debug, Clone, Error, PartialEq)]
 pub enum CircuitError {
@@ -470,6 +1763,101 @@ pub enum CircuitMutError {
     #[from(ignore)]
     #[error("Wire {0} does not exist")]
     InvalidPortOffset(usize),
+    /// The signature of an inserted operation does not match the units it is
+    /// applied to.
+    #[from(ignore)]
+    #[error("Cannot apply an operation with signature {signature} to units of types {expected}")]
+    SignatureMismatch {
+        /// The types of the selected units.
+        expected: TypeRow,
+        /// The signature of the fragment.
+        signature: Signature,
+    },
+    /// The operation filling a placeholder does not match its signature.
+    #[from(ignore)]
+    #[error("Cannot fill placeholder '{name}' with signature {signature} using operation {}", op.name())]
+    PlaceholderMismatch {
+        /// The placeholder name.
+        name: String,
+        /// The signature declared by the placeholder.
+        signature: Signature,
+        /// The operation that was provided.
+        op: OpType,
+    },
+    /// The node is not a command in the circuit.
+    #[from(ignore)]
+    #[error("Node {0} is not a command in the circuit")]
+    NotACommand(Node),
+    /// The operation has no known inverse.
+    #[from(ignore)]
+    #[error("Operation {} has no known inverse", .0.name())]
+    NonInvertibleOp(OpType),
+    /// The angles of the operation are not numeric constants.
+    #[from(ignore)]
+    #[error("The angles of node {0} are not numeric constants")]
+    NonNumericAngle(Node),
+    /// The linear unit is not connected to the given command.
+    #[from(ignore)]
+    #[error("Linear unit {} is not connected to node {node}", unit.index())]
+    UnitNotInCommand {
+        /// The node of the command.
+        node: Node,
+        /// The missing unit.
+        unit: LinearUnit,
+    },
+    /// A linear unit was selected more than once for the same operation.
+    #[from(ignore)]
+    #[error("Linear unit {} is used more than once", .0.index())]
+    RepeatedUnit(LinearUnit),
+    /// The boundary order is not a permutation of the circuit's inputs and
+    /// outputs.
+    #[from(ignore)]
+    #[error("{0:?} is not a permutation of the circuit boundary")]
+    InvalidBoundaryPermutation(Vec<usize>),
+    /// The circuit's parent could not be replaced by a DFG node.
+    #[from(ignore)]
+    #[error("Cannot replace the circuit parent {parent} ({}) with a DFG: {source}", optype.name())]
+    ParentReplacement {
+        /// The parent node of the circuit.
+        parent: Node,
+        /// The operation of the parent node.
+        optype: OpType,
+        /// The error raised by the replacement.
+        source: hugr::hugr::HugrError,
+    },
+    /// The control flow output of a CFG block could not be removed when
+    /// extracting the block as a DFG.
+    #[from(ignore)]
+    #[error("Cannot unpack the output of CFG block {block}: {reason}")]
+    CfgUnpacking {
+        /// The dataflow block node.
+        block: Node,
+        /// Why the block output could not be unpacked.
+        reason: String,
+    },
+    /// The command does not produce a single bit that can be assigned to a
+    /// classical register.
+    #[from(ignore)]
+    #[error("Node {0} does not have a single bit output")]
+    NoBitOutput(Node),
+    /// A register cannot be renamed to a name that is already in use by
+    /// units of a different type, or by units with the same indices.
+    #[from(ignore)]
+    #[error("Cannot rename register {old} to {new}: the name is already in use")]
+    RegisterConflict {
+        /// The register being renamed.
+        old: String,
+        /// The new register name.
+        new: String,
+    },
+    /// A qubit was used where a classical bit was expected.
+    #[from(ignore)]
+    #[error("Unit {0} is a qubit, not a classical bit")]
+    NotABit(String),
+    /// Relabelling would give the same name to two distinct bits.
+    #[from(ignore)]
+    #[error("Cannot relabel bits: {0} would be used by several bits")]
+    DuplicateBit(String),
 }
 
 /// Shift ports in range (free_port + 1 .. max_ind) by -1.
@@ -542,6 +1930,32 @@ fn update_signature(
         out_types
     });
 
+    set_parent_signature(hugr, parent, inp_types, out_types)
+}
+
+/// Update the signature of the circuit parent to the given input types, and
+/// output types if provided.
+///
+/// The input and output nodes must already have the new types.
+fn set_parent_signature(
+    hugr: &mut impl HugrMut,
+    parent: Node,
+    inp_types: TypeRow,
+    out_types: Option<TypeRow>,
+) -> Result<(), CircuitMutError> {
+    let optype = parent_with_signature(hugr, parent, inp_types, out_types)?;
+    hugr.replace_op(parent, optype)?;
+    Ok(())
+}
+
+/// The operation of the circuit parent with its signature updated to the
+/// given input types, and output types if provided.
+fn parent_with_signature(
+    hugr: &impl HugrView,
+    parent: Node,
+    inp_types: TypeRow,
+    out_types: Option<TypeRow>,
+) -> Result<OpType, CircuitMutError> {
     // Update the parent's signature
     let mut optype = hugr.get_optype(parent).clone();
 
@@ -587,26 +2001,31 @@ fn update_signature(
         })?,
     }
 
-    hugr.replace_op(parent, optype)?;
-
-    Ok(())
+    Ok(optype)
 }
 
 #[cfg(test)]
 mod tests {
     use cool_asserts::assert_matches;
+    use itertools::Itertools;
     use rstest::{fixture, rstest};
 
     use hugr::types::Signature;
     use hugr::{
-        builder::{DFGBuilder, DataflowHugr},
-        extension::{prelude::BOOL_T, PRELUDE_REGISTRY},
+        builder::{DFGBuilder, Dataflow, DataflowHugr},
+        extension::{
+            prelude::{BOOL_T, QB_T},
+            PRELUDE_REGISTRY,
+        },
+        type_row, NodeIndex,
     };
 
     use super::*;
-    use crate::serialize::load_tk1_json_str;
+    use crate::extension::REGISTRY;
+    use crate::placeholder_op;
+    use crate::serialize::{load_tk1_json_str, TKETDecode};
     use crate::utils::{build_module_with_circuit, build_simple_circuit};
-    use crate::Tk2Op;
+    use tket_json_rs::circuit_json::SerialCircuit;
 
     #[fixture]
     fn tk1_circuit() -> Circuit {
@@ -684,6 +2103,753 @@ mod tests {
         assert_eq!(circ.qubits().count(), qubits);
     }
 
+    #[rstest]
+    fn commands_on_qubit(tk1_circuit: Circuit) {
+        let ops_on = |qb: usize| {
+            tk1_circuit
+                .commands_on_qubit(LinearUnit::new(qb))
+                .map(|cmd| Tk2Op::try_from(cmd.optype()).unwrap())
+                .collect_vec()
+        };
+        assert_eq!(ops_on(0), [Tk2Op::H, Tk2Op::CX]);
+        assert_eq!(ops_on(1), [Tk2Op::CX, Tk2Op::RzF64]);
+    }
+
+    #[test]
+    fn frontier() {
+        let circ = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            Ok(())
+        })
+        .unwrap();
+        let frontier = circ.frontier();
+        let h = circ.commands().next().unwrap().node();
+        assert_eq!(frontier.len(), 2);
+        assert_eq!(frontier[&LinearUnit::new(0)], h);
+        assert_eq!(frontier[&LinearUnit::new(1)], circ.input_node());
+    }
+
+    #[test]
+    fn implicit_permutation() {
+        let mut circ = build_simple_circuit(3, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(crate::permutation_op([2, 0, 1]), [0, 1, 2])?;
+            Ok(())
+        })
+        .unwrap();
+        // Explicit permutation operations keep the units on their ports.
+        assert_eq!(circ.implicit_permutation(), [0, 1, 2]);
+
+        crate::passes::push_permutations(&mut circ);
+        assert_eq!(circ.implicit_permutation(), [1, 2, 0]);
+    }
+
+    #[test]
+    fn connected_components() {
+        // Two independent Bell pairs.
+        let circ = build_simple_circuit(4, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::H, [2])?;
+            circ.append(Tk2Op::CX, [0, 3])?;
+            circ.append(Tk2Op::CX, [2, 1])?;
+            Ok(())
+        })
+        .unwrap();
+        let unit = LinearUnit::new;
+        assert_eq!(
+            circ.connected_components(),
+            [vec![unit(0), unit(3)], vec![unit(1), unit(2)]]
+        );
+
+        let single = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::H, [1])?;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(
+            single.connected_components(),
+            [vec![unit(0)], vec![unit(1)]]
+        );
+    }
+
+    #[test]
+    fn compress_ids() {
+        let circ = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(Tk2Op::T, [1])?;
+            Ok(())
+        })
+        .unwrap();
+        let (mut circ, _) =
+            crate::passes::cleanup(&circ, crate::passes::CleanupOptions::default()).unwrap();
+        let max_index = |circ: &Circuit| circ.hugr().nodes().map(|n| n.index()).max().unwrap();
+        assert!(max_index(&circ) + 1 > circ.hugr().node_count());
+
+        let ops = |circ: &Circuit| {
+            circ.commands()
+                .map(|cmd| cmd.optype().clone())
+                .collect_vec()
+        };
+        let before = ops(&circ);
+        circ.compress_ids();
+        circ.hugr().validate(&REGISTRY).unwrap();
+        assert_eq!(max_index(&circ) + 1, circ.hugr().node_count());
+        assert_eq!(ops(&circ), before);
+        assert!(circ
+            .commands()
+            .map(|cmd| cmd.node().index())
+            .tuple_windows()
+            .all(|(a, b)| a < b));
+    }
+
+    #[test]
+    fn topological_generations() {
+        // A diamond: both H gates depend on the first CX, and the second CX
+        // depends on both of them.
+        let circ = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::H, [1])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            Ok(())
+        })
+        .unwrap();
+
+        let generations = circ.topological_generations();
+        let ops = |gen: &[Node]| {
+            gen.iter()
+                .map(|&n| circ.hugr().get_optype(n).clone())
+                .collect_vec()
+        };
+        assert_eq!(generations.len(), 5);
+        assert_eq!(generations[0], [circ.input_node()]);
+        assert_eq!(ops(&generations[1]), [Tk2Op::CX.into()]);
+        assert_eq!(ops(&generations[2]), [Tk2Op::H.into(), Tk2Op::H.into()]);
+        assert_eq!(ops(&generations[3]), [Tk2Op::CX.into()]);
+        assert_eq!(generations[4], [circ.output_node()]);
+    }
+
+    #[test]
+    fn topological_generations_external_edges() {
+        use hugr::builder::{Container, DataflowSubContainer, HugrBuilder, ModuleBuilder};
+        use hugr::ops::handle::NodeHandle;
+        use hugr::ops::Value;
+        use hugr::std_extensions::arithmetic::float_types::{self, ConstF64};
+
+        // A function loading a module-level constant, and a nested DFG
+        // loading a constant defined in the function.
+        let mut module = ModuleBuilder::new();
+        let outer = module.add_constant(Value::extension(ConstF64::new(0.5)));
+        let signature =
+            Signature::new_endo(type_row![QB_T]).with_extension_delta(float_types::EXTENSION_ID);
+        let mut func = module.define_function("main", signature.clone()).unwrap();
+        let [q] = func.input_wires_arr();
+        let outer_load = func.load_const(&outer);
+        let [q] = func
+            .add_dataflow_op(Tk2Op::RzF64, [q, outer_load])
+            .unwrap()
+            .outputs_arr();
+        let inner = func.add_constant(Value::extension(ConstF64::new(0.25)));
+        let mut nested = func.dfg_builder(signature, [q]).unwrap();
+        let [q] = nested.input_wires_arr();
+        let angle = nested.load_const(&inner);
+        let [q] = nested
+            .add_dataflow_op(Tk2Op::RzF64, [q, angle])
+            .unwrap()
+            .outputs_arr();
+        let [q] = nested.finish_with_outputs([q]).unwrap().outputs_arr();
+        let func = func.finish_with_outputs([q]).unwrap();
+        let hugr = module.finish_hugr(&REGISTRY).unwrap();
+        let circ = Circuit::new(&hugr, func.node());
+
+        let generations = circ.topological_generations();
+        assert_eq!(
+            generations.iter().map(Vec::len).sum::<usize>(),
+            hugr.children(func.node()).count()
+        );
+        assert!(generations[0].contains(&circ.input_node()));
+        assert!(generations[0].contains(&outer_load.node()));
+        assert_eq!(generations.last().unwrap(), &[circ.output_node()]);
+    }
+
+    #[test]
+    fn approx_eq() {
+        use crate::ops::symbolic_constant_op;
+        use hugr::std_extensions::arithmetic::float_types::ConstF64;
+        use std::f64::consts::PI;
+
+        // An `Rz(π/2)` on the second qubit, with the angle given either as a
+        // numeric constant or as a symbolic expression in half-turns.
+        let build = |symbolic: bool, qubit: usize, phase: &str| {
+            let mut dfg = DFGBuilder::new(Signature::new_endo(vec![QB_T, QB_T])).unwrap();
+            let mut qbs = dfg.input_wires().collect_vec();
+            let angle = match symbolic {
+                true => dfg
+                    .add_dataflow_op(symbolic_constant_op("1/2".to_string()), [])
+                    .unwrap()
+                    .out_wire(0),
+                false => dfg.add_load_value(ConstF64::new(PI / 2.)),
+            };
+            let [q] = dfg
+                .add_dataflow_op(Tk2Op::RzF64, [qbs[qubit], angle])
+                .unwrap()
+                .outputs_arr();
+            qbs[qubit] = q;
+            let mut circ: Circuit = dfg.finish_hugr_with_outputs(qbs, &REGISTRY).unwrap().into();
+            let parent = circ.parent();
+            circ.hugr_mut().set_metadata(parent, METADATA_PHASE, phase);
+            circ
+        };
+
+        let numeric = build(false, 1, "0.5");
+        assert!(numeric.approx_eq(&build(true, 1, "1/2"), 1e-9));
+        assert!(numeric.approx_eq(&build(false, 1, "2.5"), 1e-9));
+        assert!(!numeric.approx_eq(&build(true, 1, "a"), 1e-9));
+        assert!(!numeric.approx_eq(&build(true, 0, "0.5"), 1e-9));
+        assert!(!numeric.structurally_eq(&build(true, 1, "0.5"), false));
+
+        // Swapping the outputs is not an approximation.
+        let dfg = DFGBuilder::new(Signature::new_endo(vec![QB_T, QB_T])).unwrap();
+        let [q0, q1] = dfg.input_wires_arr();
+        let swapped: Circuit = dfg
+            .finish_hugr_with_outputs([q1, q0], &REGISTRY)
+            .unwrap()
+            .into();
+        let identity = build_simple_circuit(2, |_| Ok(())).unwrap();
+        assert!(!identity.approx_eq(&swapped, 1e-9));
+    }
+
+    #[rstest]
+    fn structurally_eq(tk1_circuit: Circuit) {
+        let build = |last: Tk2Op| {
+            build_simple_circuit(2, |circ| {
+                circ.append(Tk2Op::H, [0])?;
+                circ.append(Tk2Op::CX, [0, 1])?;
+                circ.append(last, [1])?;
+                Ok(())
+            })
+            .unwrap()
+        };
+        assert!(build(Tk2Op::T).structurally_eq(&build(Tk2Op::T), false));
+        assert!(!build(Tk2Op::T).structurally_eq(&build(Tk2Op::Tdg), false));
+        assert!(!build(Tk2Op::T).structurally_eq(&build(Tk2Op::Tdg), true));
+        assert!(tk1_circuit.structurally_eq(&tk1_circuit.clone(), false));
+        assert!(!tk1_circuit.structurally_eq(&build(Tk2Op::T), false));
+
+        // Commuting gates added in a different order.
+        let h0_h1 = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::H, [1])?;
+            Ok(())
+        })
+        .unwrap();
+        let h1_h0 = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::H, [1])?;
+            circ.append(Tk2Op::H, [0])?;
+            Ok(())
+        })
+        .unwrap();
+        assert!(h0_h1.structurally_eq(&h1_h0, true));
+
+        // Commuting rotations, along with their angle constants.
+        let rotations = |angles: [f64; 2], reversed: bool| -> Circuit {
+            let mut dfg = DFGBuilder::new(Signature::new_endo(vec![QB_T, QB_T])).unwrap();
+            let mut qbs = dfg.input_wires().collect_vec();
+            let mut order = [0, 1];
+            if reversed {
+                order.reverse();
+            }
+            for q in order {
+                let angle = dfg.add_load_value(ConstF64::new(angles[q]));
+                [qbs[q]] = dfg
+                    .add_dataflow_op(Tk2Op::RzF64, [qbs[q], angle])
+                    .unwrap()
+                    .outputs_arr();
+            }
+            dfg.finish_hugr_with_outputs(qbs, &REGISTRY).unwrap().into()
+        };
+        let rz = rotations([0.5, 0.25], false);
+        assert!(rz.structurally_eq(&rotations([0.5, 0.25], true), true));
+        assert!(!rz.structurally_eq(&rotations([0.25, 0.5], true), true));
+
+        // The global phase is compared.
+        let mut phased = h0_h1.clone();
+        let parent = phased.parent();
+        phased
+            .hugr_mut()
+            .set_metadata(parent, METADATA_PHASE, "0.5");
+        assert!(!phased.structurally_eq(&h0_h1, true));
+
+        // The wiring of the linear units to the outputs is compared.
+        let identity = build_simple_circuit(2, |_| Ok(())).unwrap();
+        let dfg = DFGBuilder::new(Signature::new_endo(vec![QB_T, QB_T])).unwrap();
+        let [q0, q1] = dfg.input_wires_arr();
+        let swapped: Circuit = dfg
+            .finish_hugr_with_outputs([q1, q0], &REGISTRY)
+            .unwrap()
+            .into();
+        assert!(identity.structurally_eq(&identity.clone(), false));
+        assert!(!identity.structurally_eq(&swapped, false));
+        assert!(!identity.structurally_eq(&swapped, true));
+    }
+
+    #[test]
+    fn fill_placeholders() {
+        let one_qb = Signature::new_endo(type_row![QB_T]);
+        let mut circ = build_simple_circuit(2, |circ| {
+            circ.append(placeholder_op("layer", one_qb.clone()), [0])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(placeholder_op("other", one_qb.clone()), [1])?;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(
+            circ.placeholders()
+                .into_iter()
+                .map(|(_, name)| name)
+                .sorted()
+                .collect_vec(),
+            ["layer", "other"]
+        );
+
+        // A two-qubit operation cannot fill a single-qubit placeholder.
+        let ops = HashMap::from([("layer".to_string(), Tk2Op::CX.into())]);
+        assert_matches!(
+            circ.fill_placeholders(&ops),
+            Err(CircuitMutError::PlaceholderMismatch { name, .. }) => {
+                assert_eq!(name, "layer")
+            }
+        );
+        assert_eq!(circ.placeholders().len(), 2);
+
+        let ops = HashMap::from([("layer".to_string(), Tk2Op::H.into())]);
+        circ.fill_placeholders(&ops).unwrap();
+        circ.hugr().validate(&REGISTRY).unwrap();
+
+        let placeholders = circ.placeholders();
+        assert_eq!(placeholders.len(), 1);
+        assert_eq!(placeholders[0].1, "other");
+        let first = circ.commands_on_qubit(LinearUnit::new(0)).next().unwrap();
+        assert_eq!(first.optype(), &Tk2Op::H.into());
+    }
+
+    #[test]
+    fn ancilla_qubits() {
+        // Qubit 0 carries data, qubit 1 is prepared, used, measured and reset.
+        let circ = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::Reset, [1])?;
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(Tk2Op::Measure, [1])?;
+            circ.append(Tk2Op::Reset, [1])?;
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(circ.ancilla_qubits(), vec![LinearUnit::new(1)]);
+    }
+
+    #[test]
+    fn measurement_map() {
+        // Measure both qubits, returning the bits in the opposite order.
+        let mut dfg = DFGBuilder::new(Signature::new(
+            type_row![QB_T, QB_T],
+            type_row![QB_T, QB_T, BOOL_T, BOOL_T],
+        ))
+        .unwrap();
+        let [q0, q1] = dfg.input_wires_arr();
+        let [q0, b0] = dfg
+            .add_dataflow_op(Tk2Op::Measure, [q0])
+            .unwrap()
+            .outputs_arr();
+        let [q1, b1] = dfg
+            .add_dataflow_op(Tk2Op::Measure, [q1])
+            .unwrap()
+            .outputs_arr();
+        let circ: Circuit = dfg
+            .finish_hugr_with_outputs([q0, q1, b1, b0], &REGISTRY)
+            .unwrap()
+            .into();
+
+        let map = circ.try_measurement_map().unwrap();
+        assert_eq!(map, circ.measurement_map());
+        assert_eq!(map.len(), 2);
+        let [_, output] = circ.io_nodes();
+        let bit = |qb: usize| {
+            let result = map[&LinearUnit::new(qb)];
+            let (node, port) = circ
+                .hugr()
+                .linked_inputs(result.node(), result.source())
+                .exactly_one()
+                .ok()
+                .unwrap();
+            assert_eq!(node, output);
+            port.index()
+        };
+        assert_eq!(bit(0), 3);
+        assert_eq!(bit(1), 2);
+
+        // Repeated measurements report the last one, or fail.
+        let circ = build_simple_circuit(1, |circ| {
+            circ.append(Tk2Op::Measure, [0])?;
+            circ.append(Tk2Op::Measure, [0])?;
+            Ok(())
+        })
+        .unwrap();
+        let last = circ.commands().last().unwrap().node();
+        assert_eq!(
+            circ.measurement_map(),
+            HashMap::from([(LinearUnit::new(0), Wire::new(last, 1))])
+        );
+        assert_matches!(
+            circ.try_measurement_map(),
+            Err(RepeatedMeasurement { second, .. }) if second == last
+        );
+    }
+
+    #[test]
+    fn is_unitary() {
+        let circ = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            Ok(())
+        })
+        .unwrap();
+        assert!(circ.is_unitary());
+        assert_eq!(circ.n_measurements(), 0);
+
+        let measured = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::Measure, [0])?;
+            circ.append(Tk2Op::Measure, [1])?;
+            Ok(())
+        })
+        .unwrap();
+        assert!(!measured.is_unitary());
+        assert_eq!(measured.n_measurements(), 2);
+
+        let reset = build_simple_circuit(1, |circ| {
+            circ.append(Tk2Op::Reset, [0])?;
+            Ok(())
+        })
+        .unwrap();
+        assert!(!reset.is_unitary());
+        assert_eq!(reset.n_measurements(), 0);
+
+        // Boxes are checked recursively.
+        let qubits = [LinearUnit::new(0), LinearUnit::new(1)];
+        let mut boxed = circ.clone();
+        boxed.append_box("bell", &circ, &qubits).unwrap();
+        assert!(boxed.is_unitary());
+        boxed.append_box("measured", &measured, &qubits).unwrap();
+        assert!(!boxed.is_unitary());
+        assert_eq!(boxed.n_measurements(), 0);
+    }
+
+    #[test]
+    fn dagger() {
+        let mut circ = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::T, [0])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(Tk2Op::S, [1])?;
+            Ok(())
+        })
+        .unwrap();
+        let parent = circ.parent();
+        circ.hugr_mut().set_metadata(parent, METADATA_PHASE, "0.25");
+
+        let dagger = circ.dagger().unwrap();
+        dagger.hugr().validate(&REGISTRY).unwrap();
+        let ops = dagger
+            .commands()
+            .map(|cmd| Tk2Op::try_from(cmd.optype()).unwrap())
+            .collect_vec();
+        assert_eq!(ops, [Tk2Op::Sdg, Tk2Op::CX, Tk2Op::Tdg, Tk2Op::H]);
+        assert_eq!(crate::rewrite::circuit_phase(&dagger), -0.25);
+
+        // The unitary of the inverse is the conjugate transpose.
+        let u = circ.unitary().unwrap();
+        let u_dg = dagger.unitary().unwrap();
+        for (i, j) in (0..4).cartesian_product(0..4) {
+            assert!((u_dg[i * 4 + j] - u[j * 4 + i].conj()).norm() < 1e-9);
+        }
+
+        // `ZZMax` has no inverse gate, and is inverted as a rotation.
+        let zz = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::ZZMax, [0, 1])?;
+            Ok(())
+        })
+        .unwrap();
+        let zz_dagger = zz.dagger().unwrap();
+        zz_dagger.hugr().validate(&REGISTRY).unwrap();
+        let ops = zz_dagger
+            .operations()
+            .map(|cmd| Tk2Op::try_from(cmd.optype()).unwrap())
+            .collect_vec();
+        assert_eq!(ops, [Tk2Op::ZZPhase]);
+        let u = zz.unitary().unwrap();
+        let u_dg = zz_dagger.unitary().unwrap();
+        for (i, j) in (0..4).cartesian_product(0..4) {
+            assert!((u_dg[i * 4 + j] - u[j * 4 + i].conj()).norm() < 1e-9);
+        }
+
+        let reset = build_simple_circuit(1, |circ| {
+            circ.append(Tk2Op::Reset, [0])?;
+            Ok(())
+        })
+        .unwrap();
+        assert_matches!(reset.dagger(), Err(CircuitMutError::NonInvertibleOp(_)));
+    }
+
+    #[test]
+    fn dagger_rotations() {
+        use crate::circuit::simulate::matmul;
+        use crate::ops::symbolic_constant_op;
+        use hugr::std_extensions::arithmetic::float_types::ConstF64;
+
+        let mut dfg = DFGBuilder::new(Signature::new_endo(type_row![QB_T, QB_T])).unwrap();
+        let [q0, q1] = dfg.input_wires_arr();
+        let theta = dfg.add_load_value(ConstF64::new(0.7));
+        let [q0] = dfg
+            .add_dataflow_op(Tk2Op::RzF64, [q0, theta])
+            .unwrap()
+            .outputs_arr();
+        let [q0] = dfg.add_dataflow_op(Tk2Op::H, [q0]).unwrap().outputs_arr();
+        let phi = dfg.add_load_value(ConstF64::new(-1.3));
+        let [q0, q1] = dfg
+            .add_dataflow_op(Tk2Op::ZZPhase, [q0, q1, phi])
+            .unwrap()
+            .outputs_arr();
+        let circ: Circuit = dfg
+            .finish_hugr_with_outputs([q0, q1], &REGISTRY)
+            .unwrap()
+            .into();
+
+        let dagger = circ.dagger().unwrap();
+        dagger.hugr().validate(&REGISTRY).unwrap();
+        let ops = dagger
+            .operations()
+            .map(|cmd| Tk2Op::try_from(cmd.optype()).unwrap())
+            .collect_vec();
+        assert_eq!(ops, [Tk2Op::ZZPhase, Tk2Op::H, Tk2Op::RzF64]);
+
+        // U · U† = I
+        let product = matmul(&circ.unitary().unwrap(), &dagger.unitary().unwrap());
+        for (i, j) in (0..4).cartesian_product(0..4) {
+            let expected = if i == j { 1. } else { 0. };
+            assert!((product[i * 4 + j] - expected).norm() < 1e-9);
+        }
+
+        // Symbolic angles cannot be negated numerically.
+        let mut dfg = DFGBuilder::new(Signature::new_endo(type_row![QB_T])).unwrap();
+        let [q] = dfg.input_wires_arr();
+        let alpha = dfg
+            .add_dataflow_op(symbolic_constant_op("alpha".to_string()), [])
+            .unwrap();
+        let [q] = dfg
+            .add_dataflow_op(Tk2Op::RzF64, [q, alpha.out_wire(0)])
+            .unwrap()
+            .outputs_arr();
+        let symbolic: Circuit = dfg.finish_hugr_with_outputs([q], &REGISTRY).unwrap().into();
+        assert_matches!(
+            symbolic.dagger(),
+            Err(CircuitMutError::NonNumericAngle(node)) => assert_eq!(node, alpha.node())
+        );
+    }
+
+    #[test]
+    fn append_opaque() {
+        let mut circ = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::CX, [0, 1])?;
+            Ok(())
+        })
+        .unwrap();
+        let fragment = build_simple_circuit(1, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            Ok(())
+        })
+        .unwrap()
+        .into_hugr();
+
+        let node = circ
+            .append_opaque(fragment.clone(), &[LinearUnit::new(1)])
+            .unwrap();
+        circ.hugr().validate(&crate::extension::REGISTRY).unwrap();
+        assert_matches!(circ.hugr().get_optype(node), OpType::DFG(_));
+        assert_eq!(circ.num_operations(), 2);
+
+        let last = circ.commands().last().unwrap();
+        assert_eq!(last.node(), node);
+        assert_eq!(
+            last.input_qubits().map(|(qb, _, _)| qb).collect_vec(),
+            vec![LinearUnit::new(1)]
+        );
+
+        // The fragment must match the types of the selected units.
+        assert_matches!(
+            circ.append_opaque(fragment.clone(), &[LinearUnit::new(0), LinearUnit::new(1)]),
+            Err(CircuitMutError::SignatureMismatch { .. })
+        );
+        assert_eq!(
+            circ.append_opaque(fragment, &[LinearUnit::new(2)]),
+            Err(CircuitMutError::InvalidPortOffset(2))
+        );
+
+        // Repeated units are rejected without modifying the circuit.
+        let two_qubit = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::CX, [0, 1])?;
+            Ok(())
+        })
+        .unwrap()
+        .into_hugr();
+        let num_nodes = circ.hugr().node_count();
+        assert_eq!(
+            circ.append_opaque(two_qubit, &[LinearUnit::new(0), LinearUnit::new(0)]),
+            Err(CircuitMutError::RepeatedUnit(LinearUnit::new(0)))
+        );
+        assert_eq!(circ.hugr().node_count(), num_nodes);
+        circ.hugr().validate(&crate::extension::REGISTRY).unwrap();
+    }
+
+    #[test]
+    fn estimated_fidelity() {
+        let one_cx = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            Ok(())
+        })
+        .unwrap();
+        let two_cx = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(Tk2Op::CX, [1, 0])?;
+            Ok(())
+        })
+        .unwrap();
+
+        let equal_weights = |_: &OpType| 0.99;
+        assert!(
+            one_cx.estimated_fidelity(&equal_weights) > two_cx.estimated_fidelity(&equal_weights)
+        );
+        assert_eq!(one_cx.estimated_fidelity(&equal_weights), 0.99 * 0.99);
+
+        let model = cost::UniformErrorModel::default();
+        let fidelity = one_cx.estimated_fidelity(&model);
+        assert_eq!(fidelity, model.single_qubit * model.two_qubit);
+        assert!(two_cx.estimated_fidelity(&model) < fidelity);
+    }
+
+    #[test]
+    fn insert_op() {
+        let mut circ = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::CX, [0, 1])?;
+            Ok(())
+        })
+        .unwrap();
+        let cx = circ.commands().next().unwrap().node();
+
+        let h = circ
+            .insert_op_before(cx, Tk2Op::H, &[LinearUnit::new(0)])
+            .unwrap();
+        let x = circ
+            .insert_op_after(cx, Tk2Op::X, &[LinearUnit::new(1)])
+            .unwrap();
+        circ.hugr().validate(&crate::extension::REGISTRY).unwrap();
+        assert_eq!(
+            circ.commands().map(|cmd| cmd.node()).collect_vec(),
+            vec![h, cx, x]
+        );
+        assert_eq!(
+            circ.commands()
+                .next()
+                .unwrap()
+                .input_qubits()
+                .next()
+                .unwrap()
+                .0,
+            LinearUnit::new(0)
+        );
+
+        // Mismatched arity or types are rejected without modifying the circuit.
+        let num_nodes = circ.hugr().node_count();
+        assert_matches!(
+            circ.insert_op_before(cx, Tk2Op::CX, &[LinearUnit::new(0)]),
+            Err(CircuitMutError::SignatureMismatch { .. })
+        );
+        assert_matches!(
+            circ.insert_op_before(cx, Tk2Op::RzF64, &[LinearUnit::new(0)]),
+            Err(CircuitMutError::SignatureMismatch { .. })
+        );
+        assert_eq!(
+            circ.insert_op_before(cx, Tk2Op::H, &[LinearUnit::new(2)]),
+            Err(CircuitMutError::UnitNotInCommand {
+                node: cx,
+                unit: LinearUnit::new(2)
+            })
+        );
+        assert_eq!(
+            circ.insert_op_after(circ.input_node(), Tk2Op::H, &[LinearUnit::new(0)]),
+            Err(CircuitMutError::NotACommand(circ.input_node()))
+        );
+        assert_eq!(
+            circ.insert_op_after(cx, Tk2Op::CX, &[LinearUnit::new(1), LinearUnit::new(1)]),
+            Err(CircuitMutError::RepeatedUnit(LinearUnit::new(1)))
+        );
+        assert_eq!(circ.hugr().node_count(), num_nodes);
+        circ.hugr().validate(&crate::extension::REGISTRY).unwrap();
+    }
+
+    #[test]
+    fn append_new_qubits() {
+        let mut circ = build_simple_circuit(1, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            Ok(())
+        })
+        .unwrap();
+        let cx = circ
+            .append(Tk2Op::CX, &[LinearUnit::new(2), LinearUnit::new(1)])
+            .unwrap();
+        circ.hugr().validate(&crate::extension::REGISTRY).unwrap();
+        assert_eq!(circ.qubit_count(), 3);
+        assert_eq!(
+            circ.circuit_signature().input(),
+            &TypeRow::from(vec![QB_T; 3])
+        );
+        assert_eq!(
+            circ.circuit_signature().output(),
+            &TypeRow::from(vec![QB_T; 3])
+        );
+        let cx_cmd = circ.commands().find(|cmd| cmd.node() == cx).unwrap();
+        assert_eq!(
+            cx_cmd.input_qubits().map(|(u, _, _)| u).collect_vec(),
+            [LinearUnit::new(2), LinearUnit::new(1)]
+        );
+
+        // Appending on existing units does not extend the boundary.
+        circ.append(Tk2Op::CX, &[LinearUnit::new(0), LinearUnit::new(2)])
+            .unwrap();
+        assert_eq!(circ.qubit_count(), 3);
+
+        // Mismatched signatures are rejected without modifying the circuit.
+        assert_matches!(
+            circ.append(Tk2Op::CX, &[LinearUnit::new(4)]),
+            Err(CircuitMutError::SignatureMismatch { .. })
+        );
+        assert_eq!(circ.qubit_count(), 3);
+
+        // Repeated units are rejected without modifying the circuit.
+        let num_nodes = circ.hugr().node_count();
+        assert_eq!(
+            circ.append(Tk2Op::CX, &[LinearUnit::new(0), LinearUnit::new(0)]),
+            Err(CircuitMutError::RepeatedUnit(LinearUnit::new(0)))
+        );
+        assert_eq!(circ.hugr().node_count(), num_nodes);
+        circ.hugr().validate(&crate::extension::REGISTRY).unwrap();
+    }
+
     #[test]
     fn remove_qubit() {
         let mut circ = build_simple_circuit(2, |circ| {
@@ -701,6 +2867,145 @@ mod tests {
         );
     }
 
+    #[test]
+    fn extract_dfg_multiple_successors() {
+        use hugr::builder::{CFGBuilder, HugrBuilder};
+        use hugr::ops::handle::NodeHandle;
+        use hugr::ops::Tag;
+
+        // A CFG whose entry block branches to the exit on either of two
+        // successors.
+        let qbs = type_row![QB_T];
+        let mut cfg = CFGBuilder::new(Signature::new_endo(qbs.clone())).unwrap();
+        let block = {
+            let mut entry = cfg.simple_entry_builder(qbs.clone(), 2).unwrap();
+            let [q] = entry.input_wires_arr();
+            let [branch] = entry
+                .add_dataflow_op(Tag::new(0, vec![TypeRow::new(); 2]), [])
+                .unwrap()
+                .outputs_arr();
+            entry.finish_with_outputs(branch, [q]).unwrap()
+        };
+        let exit = cfg.exit_block();
+        cfg.branch(&block, 0, &exit).unwrap();
+        cfg.branch(&block, 1, &exit).unwrap();
+        let hugr = cfg.finish_hugr(&PRELUDE_REGISTRY).unwrap();
+
+        // The branch predicate is kept as an output.
+        let circ = Circuit::new(hugr, block.node());
+        let extracted = circ.extract_dfg().unwrap();
+        extracted.hugr().validate(&PRELUDE_REGISTRY).unwrap();
+        assert_eq!(extracted.circuit_signature(), circ.circuit_signature());
+    }
+
+    #[test]
+    fn permute_boundary() {
+        let signature = Signature::new_endo(type_row![QB_T, BOOL_T, QB_T]);
+        let mut dfg = DFGBuilder::new(signature).unwrap();
+        let [q0, b, q1] = dfg.input_wires_arr();
+        let [q0, q1] = dfg
+            .add_dataflow_op(Tk2Op::CX, [q0, q1])
+            .unwrap()
+            .outputs_arr();
+        let mut circ: Circuit = dfg
+            .finish_hugr_with_outputs([q0, b, q1], &REGISTRY)
+            .unwrap()
+            .into();
+
+        circ.permute_boundary(&[1, 2, 0]).unwrap();
+        circ.hugr().validate(&REGISTRY).unwrap();
+        assert_eq!(
+            circ.circuit_signature(),
+            Signature::new_endo(type_row![BOOL_T, QB_T, QB_T])
+        );
+        // The CX control is now the second linear unit.
+        let cx = circ.commands().next().unwrap();
+        assert_eq!(
+            cx.input_qubits().map(|(qb, _, _)| qb.index()).collect_vec(),
+            [1, 0]
+        );
+        // The bit is still connected straight through.
+        assert_eq!(
+            circ.hugr().single_linked_output(circ.output_node(), 0),
+            Some((circ.input_node(), OutgoingPort::from(0)))
+        );
+
+        assert_matches!(
+            circ.permute_boundary(&[0, 0, 1]),
+            Err(CircuitMutError::InvalidBoundaryPermutation(_))
+        );
+        assert_matches!(
+            circ.permute_boundary(&[0, 1]),
+            Err(CircuitMutError::InvalidBoundaryPermutation(_))
+        );
+    }
+
+    #[rstest]
+    #[case::zero_zero(false, false)]
+    #[case::zero_one(false, true)]
+    #[case::one_zero(true, false)]
+    #[case::one_one(true, true)]
+    fn tensor_unitary(#[case] flip_left: bool, #[case] flip_right: bool) {
+        // Single-qubit circuits, optionally preceded by an `X` to select the
+        // column of the unitary.
+        let single_qubit = |flip: bool, ops: &[Tk2Op]| {
+            build_simple_circuit(1, |circ| {
+                if flip {
+                    circ.append(Tk2Op::X, [0])?;
+                }
+                for &op in ops {
+                    circ.append(op, [0])?;
+                }
+                Ok(())
+            })
+            .unwrap()
+        };
+        let left = single_qubit(flip_left, &[Tk2Op::H, Tk2Op::T]);
+        let right = single_qubit(flip_right, &[Tk2Op::S, Tk2Op::H]);
+
+        let tensor = left.tensor(&right).unwrap();
+        tensor.hugr().validate(&REGISTRY).unwrap();
+        assert_eq!(tensor.qubit_count(), 2);
+        assert_eq!(
+            tensor.num_operations(),
+            left.num_operations() + right.num_operations()
+        );
+
+        let (left, right) = (left.statevector().unwrap(), right.statevector().unwrap());
+        let expected = left.iter().cartesian_product(&right).map(|(a, b)| a * b);
+        for (a, b) in tensor.statevector().unwrap().into_iter().zip(expected) {
+            assert!((a - b).norm() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn tensor_registers_and_phase() {
+        let circ = |phase: &str| {
+            load_tk1_json_str(&format!(
+                r#"{{
+                "phase": "{phase}",
+                "bits": [["c", [0]]],
+                "qubits": [["q", [0]]],
+                "commands": [{{"args": [["q", [0]], ["c", [0]]], "op": {{"type": "Measure"}}}}],
+                "implicit_permutation": [[["q", [0]], ["q", [0]]]]
+            }}"#
+            ))
+            .unwrap()
+        };
+        let tensor = circ("0.5").tensor(&circ("0.25")).unwrap();
+        tensor.hugr().validate(&REGISTRY).unwrap();
+        assert_eq!(tensor.qubit_count(), 2);
+
+        let phase = tensor.hugr().get_metadata(tensor.parent(), METADATA_PHASE);
+        assert_eq!(phase.and_then(|p| p.as_str()), Some("0.75"));
+
+        let serial = SerialCircuit::encode(&tensor).unwrap();
+        let register =
+            |name: &str, i| tket_json_rs::circuit_json::Register(name.to_string(), vec![i]);
+        assert_eq!(serial.qubits, [register("q", 0), register("q", 1)]);
+        assert_eq!(serial.bits, [register("c", 0), register("c", 1)]);
+    }
+
     #[test]
     fn test_invalid_parent() {
         let hugr = Hugr::default();
@@ -0,0 +1,237 @@
+//! Checking circuits against the constraints of a backend.
+
+use std::collections::HashSet;
+
+use hugr::ops::{NamedOp, OpTag, OpTrait};
+use hugr::{HugrView, Node};
+use itertools::Itertools;
+use thiserror::Error;
+
+use super::Circuit;
+
+/// The constraints a backend imposes on the circuits it runs.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BackendSpec {
+    /// The names of the operations supported by the backend, as given by
+    /// [`NamedOp::name`], e.g. `"quantum.tket2.CX"`.
+    pub gates: HashSet<String>,
+    /// The pairs of qubits that two-qubit gates can act on. Pairs are
+    /// undirected.
+    pub coupling: Vec<(usize, usize)>,
+    /// The number of qubits of the backend.
+    pub n_qubits: usize,
+}
+
+/// A way in which a circuit does not satisfy a [`BackendSpec`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[non_exhaustive]
+pub enum BackendViolation {
+    /// The circuit uses more qubits than the backend has.
+    #[error("The circuit uses {n_qubits} qubits, but the backend only has {max_qubits}.")]
+    TooManyQubits {
+        /// The number of qubits of the circuit.
+        n_qubits: usize,
+        /// The number of qubits of the backend.
+        max_qubits: usize,
+    },
+    /// An operation is not supported by the backend.
+    #[error("Operation {gate} at node {node} is not supported by the backend.")]
+    DisallowedGate {
+        /// The node of the operation.
+        node: Node,
+        /// The name of the operation.
+        gate: String,
+    },
+    /// A two-qubit gate acts on qubits that are not coupled on the backend.
+    #[error("Gate at node {node} acts on qubits {} and {}, which are not coupled.", qubits.0, qubits.1)]
+    UncoupledQubits {
+        /// The node of the gate.
+        node: Node,
+        /// The qubits the gate acts on.
+        qubits: (usize, usize),
+    },
+}
+
+impl<T: HugrView> Circuit<T> {
+    /// Check that the circuit can run on a backend.
+    ///
+    /// Every operation must be one of the backend's gates, every two-qubit
+    /// gate must act on a coupled pair of qubits, and the circuit must not use
+    /// more qubits than the backend has. This includes operations without
+    /// qubit inputs, such as qubit allocations and classical operations, but
+    /// not constants.
+    ///
+    /// Qubits are identified by their linear unit index. The qubits allocated
+    /// by the circuit count towards the number of qubits it uses, and may
+    /// reuse the index of a freed qubit.
+    ///
+    /// # Errors
+    ///
+    /// Returns all the violations found, in the order of the commands of the
+    /// circuit, after the qubit count violation if there is one.
+    pub fn check_backend(&self, spec: &BackendSpec) -> Result<(), Vec<BackendViolation>> {
+        let mut violations = Vec::new();
+        let n_qubits = self
+            .commands()
+            .flat_map(|cmd| {
+                cmd.output_qubits()
+                    .map(|(unit, _, _)| unit.index() + 1)
+                    .collect_vec()
+            })
+            .fold(self.qubit_count(), usize::max);
+        if n_qubits > spec.n_qubits {
+            violations.push(BackendViolation::TooManyQubits {
+                n_qubits,
+                max_qubits: spec.n_qubits,
+            });
+        }
+
+        let coupled: HashSet<(usize, usize)> = spec
+            .coupling
+            .iter()
+            .flat_map(|&(a, b)| [(a, b), (b, a)])
+            .collect();
+        for cmd in self.commands() {
+            let qubits = cmd
+                .input_qubits()
+                .map(|(unit, _, _)| unit.index())
+                .collect_vec();
+            let tag = cmd.optype().tag();
+            if tag == OpTag::Const || tag == OpTag::LoadConst {
+                continue;
+            }
+            let gate = cmd.optype().name().to_string();
+            if !spec.gates.contains(&gate) {
+                violations.push(BackendViolation::DisallowedGate {
+                    node: cmd.node(),
+                    gate,
+                });
+            }
+            if let [a, b] = qubits[..] {
+                if !coupled.contains(&(a, b)) {
+                    violations.push(BackendViolation::UncoupledQubits {
+                        node: cmd.node(),
+                        qubits: (a, b),
+                    });
+                }
+            }
+        }
+
+        match violations.is_empty() {
+            true => Ok(()),
+            false => Err(violations),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::extension::REGISTRY;
+    use crate::utils::build_simple_circuit;
+    use crate::Tk2Op;
+    use cool_asserts::assert_matches;
+    use hugr::builder::{DFGBuilder, Dataflow, DataflowHugr};
+    use hugr::extension::prelude::QB_T;
+    use hugr::std_extensions::arithmetic::float_types::ConstF64;
+    use hugr::type_row;
+    use hugr::types::Signature;
+
+    /// A line of three qubits supporting `H`, `CX` and `Rz`.
+    fn line_spec() -> BackendSpec {
+        BackendSpec {
+            gates: [Tk2Op::H, Tk2Op::CX, Tk2Op::RzF64]
+                .iter()
+                .map(|op| op.exposed_name().to_string())
+                .collect(),
+            coupling: vec![(0, 1), (1, 2)],
+            n_qubits: 3,
+        }
+    }
+
+    #[test]
+    fn valid_circuit() {
+        let circ = build_simple_circuit(3, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::CX, [1, 0])?;
+            circ.append(Tk2Op::CX, [1, 2])?;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(circ.check_backend(&line_spec()), Ok(()));
+    }
+
+    #[test]
+    fn all_violations() {
+        let circ = build_simple_circuit(4, |circ| {
+            circ.append(Tk2Op::T, [0])?;
+            circ.append(Tk2Op::CX, [0, 2])?;
+            circ.append(Tk2Op::CZ, [2, 1])?;
+            Ok(())
+        })
+        .unwrap();
+        let violations = circ.check_backend(&line_spec()).unwrap_err();
+        assert_eq!(violations.len(), 4);
+        assert_matches!(
+            violations[0],
+            BackendViolation::TooManyQubits {
+                n_qubits: 4,
+                max_qubits: 3
+            }
+        );
+        assert_matches!(&violations[1], BackendViolation::DisallowedGate { gate, .. } if gate.ends_with(".T"));
+        assert_matches!(
+            violations[2],
+            BackendViolation::UncoupledQubits { qubits: (0, 2), .. }
+        );
+        assert_matches!(&violations[3], BackendViolation::DisallowedGate { gate, .. } if gate.ends_with(".CZ"));
+    }
+
+    #[test]
+    fn allocated_qubits() {
+        let mut dfg = DFGBuilder::new(Signature::new_endo(type_row![QB_T, QB_T, QB_T])).unwrap();
+        let [a, b, c] = dfg.input_wires_arr();
+        let [d] = dfg
+            .add_dataflow_op(Tk2Op::QAlloc, [])
+            .unwrap()
+            .outputs_arr();
+        let [c, d] = dfg
+            .add_dataflow_op(Tk2Op::CX, [c, d])
+            .unwrap()
+            .outputs_arr();
+        dfg.add_dataflow_op(Tk2Op::QFree, [d]).unwrap();
+        let circ: Circuit = dfg
+            .finish_hugr_with_outputs([a, b, c], &REGISTRY)
+            .unwrap()
+            .into();
+
+        let violations = circ.check_backend(&line_spec()).unwrap_err();
+        assert_eq!(violations.len(), 4);
+        assert_matches!(
+            violations[0],
+            BackendViolation::TooManyQubits {
+                n_qubits: 4,
+                max_qubits: 3
+            }
+        );
+        assert_matches!(&violations[1], BackendViolation::DisallowedGate { gate, .. } if gate.ends_with(".QAlloc"));
+        assert_matches!(
+            violations[2],
+            BackendViolation::UncoupledQubits { qubits: (2, 3), .. }
+        );
+        assert_matches!(&violations[3], BackendViolation::DisallowedGate { gate, .. } if gate.ends_with(".QFree"));
+    }
+
+    #[test]
+    fn constants_are_allowed() {
+        let mut dfg = DFGBuilder::new(Signature::new_endo(type_row![QB_T])).unwrap();
+        let [q] = dfg.input_wires_arr();
+        let angle = dfg.add_load_value(ConstF64::new(0.5));
+        let [q] = dfg
+            .add_dataflow_op(Tk2Op::RzF64, [q, angle])
+            .unwrap()
+            .outputs_arr();
+        let rz: Circuit = dfg.finish_hugr_with_outputs([q], &REGISTRY).unwrap().into();
+        assert_eq!(rz.check_backend(&line_spec()), Ok(()));
+    }
+}
@@ -0,0 +1,210 @@
+//! A circuit builder that tracks the liveness of its linear units.
+//!
+//! [`hugr::builder::CircuitBuilder`] requires every operation to return each
+//! of its linear inputs, and does not allow terminating a qubit wire. The
+//! [`CheckedCircuitBuilder`] instead accepts operations that consume some of
+//! their inputs, such as [`Tk2Op::QFree`], and records the units they
+//! terminate. Any later attempt to use a terminated unit is reported as an
+//! error at construction time.
+//!
+//!   [`Tk2Op::QFree`]: crate::Tk2Op::QFree
+
+use hugr::builder::{BuildError, Dataflow};
+use hugr::ops::handle::NodeHandle;
+use hugr::ops::{NamedOp, OpTrait, OpType};
+use hugr::{CircuitUnit, Node, Wire};
+use thiserror::Error;
+
+/// Builder for circuit-like dataflow regions, where each tracked linear unit
+/// is either live or has been consumed by an operation.
+///
+/// Operations are appended by indexing the tracked units, as with
+/// [`hugr::builder::CircuitBuilder`]. An input unit is passed through an
+/// operation if the output at the same port has the same type, and is
+/// consumed otherwise.
+#[derive(Debug, PartialEq)]
+pub struct CheckedCircuitBuilder<'a, T: ?Sized> {
+    /// The current wire of each tracked unit, or the node that consumed it.
+    wires: Vec<Result<Wire, Node>>,
+    builder: &'a mut T,
+}
+
+impl<'a, T: Dataflow + ?Sized> CheckedCircuitBuilder<'a, T> {
+    /// Construct a new builder tracking the given wires, appending
+    /// operations to the dataflow builder `builder`.
+    pub fn new(wires: impl IntoIterator<Item = Wire>, builder: &'a mut T) -> Self {
+        Self {
+            wires: wires.into_iter().map(Ok).collect(),
+            builder,
+        }
+    }
+
+    /// Returns `true` if the unit with the given index has not been consumed.
+    pub fn is_live(&self, unit: usize) -> bool {
+        matches!(self.wires.get(unit), Some(Ok(_)))
+    }
+
+    /// Returns the node that consumed a unit, if any.
+    pub fn consumed_by(&self, unit: usize) -> Option<Node> {
+        self.wires.get(unit)?.err()
+    }
+
+    /// Append an operation acting on the tracked units with the given indices.
+    ///
+    /// Returns a reference to self to allow chaining.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the units has already been consumed.
+    pub fn append(
+        &mut self,
+        op: impl Into<OpType>,
+        units: impl IntoIterator<Item = usize>,
+    ) -> Result<&mut Self, LivenessError> {
+        self.append_with_outputs(op, units)?;
+        Ok(self)
+    }
+
+    /// Append an operation with some inputs being tracked units.
+    ///
+    /// Inputs of the form [`CircuitUnit::Linear`] index the tracked units.
+    /// Their wires are replaced by the outputs at the same ports, or marked
+    /// as consumed if the operation does not return them. The remaining
+    /// outputs are returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an input unit is invalid or has already been
+    /// consumed.
+    pub fn append_with_outputs<A: Into<CircuitUnit>>(
+        &mut self,
+        op: impl Into<OpType>,
+        inputs: impl IntoIterator<Item = A>,
+    ) -> Result<Vec<Wire>, LivenessError> {
+        let op = op.into();
+        let mut linear_inputs = Vec::new();
+        let input_wires = inputs
+            .into_iter()
+            .enumerate()
+            .map(|(port, input)| match input.into() {
+                CircuitUnit::Wire(wire) => Ok(wire),
+                CircuitUnit::Linear(unit) => {
+                    linear_inputs.push((port, unit));
+                    match self.wires.get(unit) {
+                        Some(Ok(wire)) => Ok(*wire),
+                        Some(Err(consumed_by)) => Err(LivenessError::UseAfterConsume {
+                            unit,
+                            op: op.clone(),
+                            consumed_by: *consumed_by,
+                        }),
+                        None => Err(LivenessError::InvalidUnit {
+                            unit,
+                            op: op.clone(),
+                        }),
+                    }
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let signature = op.dataflow_signature();
+        let node = self.builder.add_dataflow_op(op, input_wires)?;
+        let mut outputs: Vec<Option<Wire>> = node.outputs().map(Some).collect();
+        for (port, unit) in linear_inputs {
+            let passed_through = signature.as_ref().is_some_and(|sig| {
+                let input = sig.input_types().get(port);
+                input.is_some() && input == sig.output_types().get(port)
+            });
+            self.wires[unit] = match passed_through {
+                true => Ok(outputs[port].take().unwrap()),
+                false => Err(node.node()),
+            };
+        }
+        Ok(outputs.into_iter().flatten().collect())
+    }
+
+    /// Finish building, returning the wires of the live units in order.
+    pub fn finish(self) -> Vec<Wire> {
+        self.wires.into_iter().filter_map(Result::ok).collect()
+    }
+}
+
+/// Error in [`CheckedCircuitBuilder`].
+#[derive(Debug, Clone, PartialEq, Error)]
+#[non_exhaustive]
+pub enum LivenessError {
+    /// A unit was used after being consumed by another operation.
+    #[error("Unit {unit} was consumed by {consumed_by} and cannot be used by operation {}.", .op.name())]
+    UseAfterConsume {
+        /// The index of the unit.
+        unit: usize,
+        /// The operation that tried to use the unit.
+        op: OpType,
+        /// The node that consumed the unit.
+        consumed_by: Node,
+    },
+    /// The unit index is not tracked by the builder.
+    #[error("Invalid unit index {unit} in operation {}.", .op.name())]
+    InvalidUnit {
+        /// The index of the unit.
+        unit: usize,
+        /// The operation.
+        op: OpType,
+    },
+    /// An error occurred while adding the operation.
+    #[error("{0}")]
+    BuildError(#[from] BuildError),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::extension::REGISTRY;
+    use crate::{Circuit, Tk2Op};
+    use cool_asserts::assert_matches;
+    use hugr::builder::{DFGBuilder, DataflowHugr};
+    use hugr::extension::prelude::{BOOL_T, QB_T};
+    use hugr::type_row;
+    use hugr::types::Signature;
+
+    #[test]
+    fn use_after_consume() {
+        let signature = Signature::new(type_row![QB_T, QB_T], type_row![QB_T, BOOL_T]);
+        let mut dfg = DFGBuilder::new(signature).unwrap();
+        let qbs = dfg.input_wires();
+        let mut circ = CheckedCircuitBuilder::new(qbs, &mut dfg);
+
+        // Measurements return their qubit, so it can still be used.
+        let [bit] = circ
+            .append_with_outputs(Tk2Op::Measure, [0])
+            .unwrap()
+            .try_into()
+            .unwrap();
+        circ.append(Tk2Op::H, [0]).unwrap();
+
+        // Freeing the qubit consumes it.
+        circ.append(Tk2Op::QFree, [1]).unwrap();
+        assert!(!circ.is_live(1));
+        let consumer = circ.consumed_by(1).unwrap();
+        assert_matches!(
+            circ.append(Tk2Op::H, [1]),
+            Err(LivenessError::UseAfterConsume { unit: 1, consumed_by, .. }) => {
+                assert_eq!(consumed_by, consumer);
+            }
+        );
+        assert_matches!(
+            circ.append(Tk2Op::CX, [0, 1]),
+            Err(LivenessError::UseAfterConsume { unit: 1, .. })
+        );
+        assert_matches!(
+            circ.append(Tk2Op::H, [2]),
+            Err(LivenessError::InvalidUnit { unit: 2, .. })
+        );
+
+        let [qb] = circ.finish().try_into().unwrap();
+        let circ: Circuit = dfg
+            .finish_hugr_with_outputs([qb, bit], &REGISTRY)
+            .unwrap()
+            .into();
+        assert_eq!(circ.operations().count(), 3);
+    }
+}
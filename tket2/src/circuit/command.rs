@@ -4,6 +4,7 @@
 
 use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet};
+use std::f64::consts::TAU;
 use std::iter::FusedIterator;
 
 use hugr::hugr::views::{HierarchyView, SiblingGraph};
@@ -14,8 +15,10 @@ use itertools::Either::{self, Left, Right};
 use itertools::{EitherOrBoth, Itertools};
 use petgraph::visit as pv;
 
+use super::params::{param_value, ParamValue};
 use super::units::{filter, DefaultUnitLabeller, LinearUnit, UnitLabeller, Units};
 use super::Circuit;
+use crate::Tk2Op;
 
 pub use hugr::ops::OpType;
 pub use hugr::types::{EdgeKind, Type, TypeRow};
@@ -46,6 +49,55 @@ impl<'circ, T: HugrView> Command<'circ, T> {
         self.circ.hugr().get_optype(self.node)
     }
 
+    /// The values of the non-linear inputs of the command, or `None` for
+    /// inputs that are not angle parameters.
+    fn param_values(&self) -> Vec<Option<ParamValue>> {
+        self.inputs()
+            .filter_map(|(unit, _, _)| match unit {
+                CircuitUnit::Wire(wire) => Some(param_value(self.circ.hugr(), wire)),
+                CircuitUnit::Linear(_) => None,
+            })
+            .collect()
+    }
+
+    /// Check whether two commands apply the same operation with approximately
+    /// equal parameters.
+    ///
+    /// Numeric parameters are compared in radians within `tol`, modulo the
+    /// period of the operation in that parameter. Symbolic parameters are
+    /// compared after simplification with [`simplify_param`], and evaluated
+    /// when they simplify to a number. The units the commands act on are not
+    /// compared.
+    ///
+    /// [`simplify_param`]: super::simplify_param
+    pub fn approx_eq(&self, other: &Command<'_, impl HugrView>, tol: f64) -> bool {
+        if self.optype() != other.optype() {
+            return false;
+        }
+        let params = self.param_values();
+        let other_params = other.param_values();
+        let period = |index| param_period(self.optype(), index);
+        params.len() == other_params.len()
+            && params
+                .into_iter()
+                .zip(other_params)
+                .enumerate()
+                .all(|(i, params)| match params {
+                    (Some(ParamValue::Numeric(a)), Some(ParamValue::Numeric(b))) => {
+                        let diff = match period(i) {
+                            Some(period) => {
+                                let diff = (a - b).rem_euclid(period);
+                                diff.min(period - diff)
+                            }
+                            None => (a - b).abs(),
+                        };
+                        diff <= tol
+                    }
+                    (Some(ParamValue::Symbolic(a)), Some(ParamValue::Symbolic(b))) => a == b,
+                    _ => false,
+                })
+    }
+
     /// Returns the units of this command in a given direction.
     #[inline]
     pub fn units(
@@ -165,12 +217,14 @@ impl<'circ, T: HugrView> Command<'circ, T> {
 
 impl<'a, 'circ, T: HugrView> UnitLabeller for &'a Command<'circ, T> {
     #[inline]
-    fn assign_linear(&self, _: Node, port: Port, _linear_count: usize) -> LinearUnit {
+    fn assign_linear(&self, _: Node, port: Port, linear_count: usize) -> LinearUnit {
         let units = match port.direction() {
             Direction::Incoming => &self.input_linear_units,
             Direction::Outgoing => &self.output_linear_units,
         };
-        *units.get(port.index()).unwrap_or_else(|| {
+        // Non-linear ports may precede the linear ones, e.g. the predicate of
+        // a `Conditional`.
+        *units.get(linear_count).unwrap_or_else(|| {
             panic!(
                 "Could not assign a linear unit to port {port:?} of node {:?}",
                 self.node
@@ -230,6 +284,20 @@ impl<'circ, T: HugrView> std::hash::Hash for Command<'circ, T> {
     }
 }
 
+/// The period of an operation in its `index`-th parameter, in radians.
+///
+/// Rotations are periodic with period 4π, while shifting the phase of a
+/// `PhasedX` gate by 2π leaves it unchanged.
+fn param_period(op: &OpType, index: usize) -> Option<f64> {
+    match Tk2Op::try_from(op).ok()? {
+        Tk2Op::PhasedX if index == 1 => Some(TAU),
+        Tk2Op::RzF64 | Tk2Op::RxF64 | Tk2Op::ZZPhase | Tk2Op::PhasedX | Tk2Op::TK1 => {
+            Some(2. * TAU)
+        }
+        _ => None,
+    }
+}
+
 /// A non-borrowing topological walker over the nodes of a circuit.
 type NodeWalker = pv::Topo<Node, HashSet<Node>>;
 
@@ -733,4 +801,50 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn approx_eq() {
+        use crate::ops::symbolic_constant_op;
+        use std::f64::consts::PI;
+
+        let mut dfg = DFGBuilder::new(Signature::new_endo(vec![QB_T])).unwrap();
+        let [mut q] = dfg.input_wires_arr();
+        let mut add = |dfg: &mut DFGBuilder<_>, op: Tk2Op, angle| {
+            let [out] = dfg.add_dataflow_op(op, [q, angle]).unwrap().outputs_arr();
+            q = out;
+        };
+        for angle in [PI / 2., PI / 2. + 4. * PI, PI / 2. + 2. * PI] {
+            let angle = dfg.add_load_value(ConstF64::new(angle));
+            add(&mut dfg, Tk2Op::RzF64, angle);
+        }
+        for expr in ["1.0/2.0", "a", "a + a - a", "b"] {
+            let [angle] = dfg
+                .add_dataflow_op(symbolic_constant_op(expr.to_string()), [])
+                .unwrap()
+                .outputs_arr();
+            add(&mut dfg, Tk2Op::RzF64, angle);
+        }
+        let angle = dfg.add_load_value(ConstF64::new(PI / 2.));
+        add(&mut dfg, Tk2Op::RxF64, angle);
+        let circ: Circuit = dfg.finish_hugr_with_outputs([q], &REGISTRY).unwrap().into();
+
+        let [rz, rz_4pi, rz_2pi, rz_sym, a, a_expr, b, rx] = circ
+            .commands()
+            .filter(|cmd| {
+                matches!(
+                    Tk2Op::try_from(cmd.optype()),
+                    Ok(Tk2Op::RzF64 | Tk2Op::RxF64)
+                )
+            })
+            .collect_vec()
+            .try_into()
+            .unwrap();
+        assert!(rz.approx_eq(&rz_sym, 1e-9));
+        assert!(rz.approx_eq(&rz_4pi, 1e-9));
+        assert!(!rz.approx_eq(&rz_2pi, 1e-9));
+        assert!(a.approx_eq(&a_expr, 1e-9));
+        assert!(!a.approx_eq(&b, 1e-9));
+        assert!(!a.approx_eq(&rz, 1e-9));
+        assert!(!rz.approx_eq(&rx, 1e-9));
+    }
 }
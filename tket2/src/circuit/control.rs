@@ -0,0 +1,378 @@
+//! Construction of controlled versions of circuits.
+//!
+//! Each gate of the circuit is replaced by a decomposition of its controlled
+//! version into `CX` gates and single-qubit rotations. Two-qubit gates are
+//! first expressed with `CX` gates, so that each control only requires
+//! controlled single-qubit gates and Toffoli gates. Adding several controls
+//! repeats the construction once per control.
+
+use std::f64::consts::PI;
+
+use hugr::builder::{DFGBuilder, Dataflow, DataflowHugr};
+use hugr::extension::prelude::QB_T;
+use hugr::ops::NamedOp;
+use hugr::std_extensions::arithmetic::float_types::ConstF64;
+use hugr::types::{Signature, TypeRow};
+use hugr::{HugrView, Node};
+use itertools::Itertools;
+use num_complex::Complex64;
+use thiserror::Error;
+
+use super::params::{NumericParams, ParamError};
+use super::simulate::{gate_matrix, matmul};
+use super::synth::euler_gates;
+use super::Circuit;
+use crate::extension::REGISTRY;
+use crate::rewrite::add_circuit_phase;
+use crate::serialize::pytket::METADATA_PHASE;
+use crate::Tk2Op;
+
+/// Tolerance below which rotations are omitted.
+const EPS: f64 = 1e-12;
+
+/// A gate with its qubits and numeric angles.
+type Gate = (Tk2Op, Vec<usize>, Vec<f64>);
+
+/// Build a controlled version of a circuit.
+///
+/// The result applies the circuit when all of the `n_controls` new qubits are
+/// in the `|1⟩` state, and the identity otherwise. The control qubits are
+/// prepended to the circuit's qubits. The global phase of the circuit becomes
+/// a phase on the controls.
+///
+/// # Errors
+///
+/// Returns an error if the circuit has non-qubit inputs or outputs, if it
+/// contains an operation without a controlled version such as `Measure`, or
+/// if its angles or global phase are symbolic.
+pub fn controlled(
+    circ: &Circuit<impl HugrView>,
+    n_controls: usize,
+) -> Result<Circuit, ControlError> {
+    let signature = circ.circuit_signature();
+    let all_qubits = |row: &TypeRow| row.iter().all(|ty| ty == &QB_T);
+    if !all_qubits(signature.input()) || !all_qubits(signature.output()) {
+        return Err(ControlError::NonQubitBoundary);
+    }
+    let mut n_qubits = circ.qubit_count();
+    let mut gates = circuit_gates(circ)?;
+    let mut phase = match circ
+        .hugr()
+        .get_metadata(circ.parent(), METADATA_PHASE)
+        .and_then(|p| p.as_str())
+    {
+        Some(p) => p.parse::<f64>().map_err(|_| ControlError::SymbolicPhase {
+            phase: p.to_string(),
+        })?,
+        None => 0.,
+    };
+    for _ in 0..n_controls {
+        (gates, phase) = control_gates(&gates, phase);
+        n_qubits += 1;
+    }
+    Ok(build_circuit(&gates, n_qubits, phase))
+}
+
+impl<T: HugrView> Circuit<T> {
+    /// Build a controlled version of the circuit, with `n_controls` new
+    /// control qubits prepended.
+    ///
+    /// See [`controlled`] for more details.
+    pub fn controlled(&self, n_controls: usize) -> Result<Circuit, ControlError> {
+        controlled(self, n_controls)
+    }
+}
+
+/// The gates of a circuit, in a topological order.
+fn circuit_gates(circ: &Circuit<impl HugrView>) -> Result<Vec<Gate>, ControlError> {
+    let mut params = NumericParams::default();
+    let mut gates = Vec::new();
+    for cmd in circ.commands() {
+        let node = cmd.node();
+        let param_error = |e| match e {
+            ParamError::Symbolic(expr) => ControlError::SymbolicParam { expr, node },
+            ParamError::Unresolved => ControlError::UnresolvedParam { node },
+        };
+        if params.process(&cmd).map_err(param_error)? {
+            continue;
+        }
+        let not_controllable = || ControlError::NotControllable {
+            op: cmd.optype().name().to_string(),
+            node,
+        };
+        let op = Tk2Op::try_from(cmd.optype()).map_err(|_| not_controllable())?;
+        if gate_matrix(op, &[0.; 3]).is_none() {
+            return Err(not_controllable());
+        }
+        let qubits = cmd.input_qubits().map(|(qb, _, _)| qb.index()).collect();
+        let angles = params.inputs(&cmd).map_err(param_error)?;
+        gates.push((op, qubits, angles));
+    }
+    Ok(gates)
+}
+
+/// Control a sequence of gates with a new qubit 0, shifting the other qubits
+/// by one. The global phase, in half-turns, is applied conditionally on the
+/// control, and the new global phase is returned.
+fn control_gates(gates: &[Gate], phase: f64) -> (Vec<Gate>, f64) {
+    let mut out = Vec::new();
+    let mut new_phase = 0.;
+    let mut phase_on_control = |out: &mut Vec<Gate>, radians: f64| {
+        // diag(1, e^{iφ}) = e^{iφ/2} Rz(φ)
+        push_rotation(out, Tk2Op::RzF64, 0, radians);
+        new_phase += radians / (2. * PI);
+    };
+    phase_on_control(&mut out, phase * PI);
+
+    for (op, qubits, angles) in gates {
+        let qubits = qubits.iter().map(|q| q + 1).collect_vec();
+        match (op, qubits.as_slice()) {
+            (Tk2Op::CX, &[a, b]) => toffoli(&mut out, 0, a, b),
+            (Tk2Op::CZ, &[a, b]) => {
+                out.push((Tk2Op::H, vec![b], vec![]));
+                toffoli(&mut out, 0, a, b);
+                out.push((Tk2Op::H, vec![b], vec![]));
+            }
+            (Tk2Op::ZZMax | Tk2Op::ZZPhase, &[a, b]) => {
+                // ZZPhase(θ) = CX · (I ⊗ Rz(θ)) · CX
+                let angle = angles.first().copied().unwrap_or(PI / 2.);
+                toffoli(&mut out, 0, a, b);
+                controlled_rz(&mut out, 0, b, angle);
+                toffoli(&mut out, 0, a, b);
+            }
+            (Tk2Op::X, &[t]) => out.push((Tk2Op::CX, vec![0, t], vec![])),
+            (Tk2Op::Y, &[t]) => {
+                out.push((Tk2Op::Sdg, vec![t], vec![]));
+                out.push((Tk2Op::CX, vec![0, t], vec![]));
+                out.push((Tk2Op::S, vec![t], vec![]));
+            }
+            (Tk2Op::Z, &[t]) => {
+                out.push((Tk2Op::H, vec![t], vec![]));
+                out.push((Tk2Op::CX, vec![0, t], vec![]));
+                out.push((Tk2Op::H, vec![t], vec![]));
+            }
+            (Tk2Op::RzF64, &[t]) => controlled_rz(&mut out, 0, t, angles[0]),
+            (Tk2Op::RxF64, &[t]) => controlled_rx(&mut out, 0, t, angles[0]),
+            (_, &[t]) => {
+                // U = e^{iφ} Rz(α) Rx(β) Rz(γ)
+                let u = gate_matrix(*op, angles).expect("Gates are unitary.");
+                let [gamma, beta, alpha] = euler_gates(&u, &[0])
+                    .into_iter()
+                    .map(|(_, _, angle)| angle.unwrap())
+                    .collect_vec()
+                    .try_into()
+                    .unwrap();
+                let rz = |a| gate_matrix(Tk2Op::RzF64, &[a]).unwrap();
+                let rx = gate_matrix(Tk2Op::RxF64, &[beta]).unwrap();
+                let w = matmul(&rz(alpha), &matmul(&rx, &rz(gamma)));
+                let overlap: Complex64 = w.iter().zip(&u).map(|(w, u)| w.conj() * u).sum();
+                controlled_rz(&mut out, 0, t, gamma);
+                controlled_rx(&mut out, 0, t, beta);
+                controlled_rz(&mut out, 0, t, alpha);
+                phase_on_control(&mut out, overlap.arg());
+            }
+            _ => unreachable!("Controllable gates act on one or two qubits."),
+        }
+    }
+    (out, new_phase)
+}
+
+/// Append a rotation, unless its angle is zero.
+fn push_rotation(out: &mut Vec<Gate>, op: Tk2Op, qubit: usize, angle: f64) {
+    if angle.abs() > EPS {
+        out.push((op, vec![qubit], vec![angle]));
+    }
+}
+
+/// Append a controlled `Rz(θ)`.
+fn controlled_rz(out: &mut Vec<Gate>, control: usize, target: usize, angle: f64) {
+    if angle.abs() <= EPS {
+        return;
+    }
+    push_rotation(out, Tk2Op::RzF64, target, angle / 2.);
+    out.push((Tk2Op::CX, vec![control, target], vec![]));
+    push_rotation(out, Tk2Op::RzF64, target, -angle / 2.);
+    out.push((Tk2Op::CX, vec![control, target], vec![]));
+}
+
+/// Append a controlled `Rx(θ)`.
+fn controlled_rx(out: &mut Vec<Gate>, control: usize, target: usize, angle: f64) {
+    if angle.abs() <= EPS {
+        return;
+    }
+    out.push((Tk2Op::H, vec![target], vec![]));
+    controlled_rz(out, control, target, angle);
+    out.push((Tk2Op::H, vec![target], vec![]));
+}
+
+/// Append a Toffoli gate, decomposed into `CX`, `H` and `T` gates.
+fn toffoli(out: &mut Vec<Gate>, a: usize, b: usize, t: usize) {
+    let gates: [(Tk2Op, &[usize]); 15] = [
+        (Tk2Op::H, &[t]),
+        (Tk2Op::CX, &[b, t]),
+        (Tk2Op::Tdg, &[t]),
+        (Tk2Op::CX, &[a, t]),
+        (Tk2Op::T, &[t]),
+        (Tk2Op::CX, &[b, t]),
+        (Tk2Op::Tdg, &[t]),
+        (Tk2Op::CX, &[a, t]),
+        (Tk2Op::T, &[b]),
+        (Tk2Op::T, &[t]),
+        (Tk2Op::H, &[t]),
+        (Tk2Op::CX, &[a, b]),
+        (Tk2Op::T, &[a]),
+        (Tk2Op::Tdg, &[b]),
+        (Tk2Op::CX, &[a, b]),
+    ];
+    out.extend(gates.map(|(op, qubits)| (op, qubits.to_vec(), vec![])));
+}
+
+/// Build a circuit from a list of gates and a global phase in half-turns.
+fn build_circuit(gates: &[Gate], n_qubits: usize, phase: f64) -> Circuit {
+    let mut dfg = DFGBuilder::new(Signature::new_endo(vec![QB_T; n_qubits])).unwrap();
+    let mut qbs = dfg.input_wires().collect_vec();
+    for (op, qubits, angles) in gates {
+        let inputs = qubits
+            .iter()
+            .map(|&q| qbs[q])
+            .chain(angles.iter().map(|&a| dfg.add_load_value(ConstF64::new(a))))
+            .collect_vec();
+        let outputs = dfg.add_dataflow_op(*op, inputs).unwrap().outputs();
+        for (&q, wire) in qubits.iter().zip(outputs) {
+            qbs[q] = wire;
+        }
+    }
+    let mut circ: Circuit = dfg.finish_hugr_with_outputs(qbs, &REGISTRY).unwrap().into();
+    add_circuit_phase(&mut circ, phase);
+    circ
+}
+
+/// Error type for the construction of controlled circuits.
+#[derive(Debug, Clone, PartialEq, Error)]
+#[non_exhaustive]
+pub enum ControlError {
+    /// The operation has no controlled version.
+    #[error("Operation {op} in {node} cannot be controlled.")]
+    NotControllable {
+        /// The operation name.
+        op: String,
+        /// The node.
+        node: Node,
+    },
+    /// A parameter is a symbolic expression.
+    #[error("Symbolic parameter '{expr}' in {node} cannot be controlled. Only numeric parameters are supported.")]
+    SymbolicParam {
+        /// The symbolic expression.
+        expr: String,
+        /// The node.
+        node: Node,
+    },
+    /// A parameter input could not be resolved to a numeric constant.
+    #[error("A parameter of the operation in {node} is not a numeric constant.")]
+    UnresolvedParam {
+        /// The node.
+        node: Node,
+    },
+    /// The global phase of the circuit is symbolic.
+    #[error("The symbolic global phase '{phase}' cannot be controlled.")]
+    SymbolicPhase {
+        /// The phase expression.
+        phase: String,
+    },
+    /// The circuit has inputs or outputs that are not qubits.
+    #[error("Only circuits whose inputs and outputs are all qubits can be controlled.")]
+    NonQubitBoundary,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::build_simple_circuit;
+    use cool_asserts::assert_matches;
+    use hugr::hugr::hugrmut::HugrMut;
+
+    /// The unitary applying `u` when the first `n_controls` qubits are set.
+    fn controlled_unitary(u: &[Complex64], n_controls: usize) -> Vec<Complex64> {
+        let dim = (u.len() as f64).sqrt() as usize;
+        let full = dim << n_controls;
+        let offset = full - dim;
+        (0..full * full)
+            .map(|k| {
+                let (row, col) = (k / full, k % full);
+                match (row >= offset, col >= offset) {
+                    (true, true) => u[(row - offset) * dim + col - offset],
+                    _ if row == col => Complex64::new(1., 0.),
+                    _ => Complex64::new(0., 0.),
+                }
+            })
+            .collect()
+    }
+
+    fn assert_close(a: &[Complex64], b: &[Complex64]) {
+        assert_eq!(a.len(), b.len());
+        for (x, y) in a.iter().zip(b) {
+            assert!((x - y).norm() < 1e-9, "{x} != {y}");
+        }
+    }
+
+    #[test]
+    fn controlled_x() {
+        let circ = build_simple_circuit(1, |circ| {
+            circ.append(Tk2Op::X, [0])?;
+            Ok(())
+        })
+        .unwrap();
+        let controlled = circ.controlled(1).unwrap();
+        controlled.hugr().validate(&REGISTRY).unwrap();
+        assert_eq!(controlled.qubit_count(), 2);
+        let [cmd] = controlled.commands().collect_vec().try_into().unwrap();
+        assert_eq!(cmd.optype(), &Tk2Op::CX.into());
+        let qubits = cmd
+            .input_qubits()
+            .map(|(qb, _, _)| qb.index())
+            .collect_vec();
+        assert_eq!(qubits, [0, 1]);
+    }
+
+    #[test]
+    fn controlled_unitaries() {
+        let mut circ = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::T, [1])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(Tk2Op::Y, [0])?;
+            circ.append(Tk2Op::ZZMax, [1, 0])?;
+            circ.append(Tk2Op::CZ, [0, 1])?;
+            circ.append(Tk2Op::Sdg, [1])?;
+            Ok(())
+        })
+        .unwrap();
+        let parent = circ.parent();
+        circ.hugr_mut().set_metadata(parent, METADATA_PHASE, "0.25");
+        let u = circ.unitary().unwrap();
+
+        for n_controls in 0..=2 {
+            let controlled = controlled(&circ, n_controls).unwrap();
+            controlled.hugr().validate(&REGISTRY).unwrap();
+            assert_close(
+                &controlled.unitary().unwrap(),
+                &controlled_unitary(&u, n_controls),
+            );
+        }
+    }
+
+    #[test]
+    fn not_controllable() {
+        let circ = build_simple_circuit(1, |circ| {
+            circ.append(Tk2Op::Measure, [0])?;
+            Ok(())
+        })
+        .unwrap();
+        assert_matches!(
+            circ.controlled(1),
+            Err(ControlError::NotControllable { op, .. }) => {
+                assert!(op.contains("Measure"))
+            }
+        );
+    }
+}
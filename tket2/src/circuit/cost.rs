@@ -202,6 +202,64 @@ pub fn is_quantum(op: &OpType) -> bool {
     op.is_quantum()
 }
 
+/// A model assigning a success probability to each operation in a circuit.
+///
+/// Used to estimate the fidelity of a circuit, see
+/// [`Circuit::estimated_fidelity`].
+///
+///   [`Circuit::estimated_fidelity`]: crate::Circuit::estimated_fidelity
+pub trait ErrorModel {
+    /// The probability that the operation is applied without error.
+    fn op_fidelity(&self, op: &OpType) -> f64;
+}
+
+impl<F: Fn(&OpType) -> f64> ErrorModel for F {
+    fn op_fidelity(&self, op: &OpType) -> f64 {
+        self(op)
+    }
+}
+
+/// An [`ErrorModel`] assigning the same fidelity to all the operations of a
+/// given class.
+///
+/// Operations that are not [`Tk2Op`]s, and classical or allocation
+/// operations, are assumed to be error-free.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UniformErrorModel {
+    /// Fidelity of single-qubit gates.
+    pub single_qubit: f64,
+    /// Fidelity of two-qubit gates.
+    pub two_qubit: f64,
+    /// Fidelity of measurements and resets.
+    pub measurement: f64,
+}
+
+impl Default for UniformErrorModel {
+    /// Typical fidelities for current trapped-ion devices.
+    fn default() -> Self {
+        Self {
+            single_qubit: 0.99995,
+            two_qubit: 0.998,
+            measurement: 0.997,
+        }
+    }
+}
+
+impl ErrorModel for UniformErrorModel {
+    fn op_fidelity(&self, op: &OpType) -> f64 {
+        use Tk2Op::*;
+        let Ok(op): Result<Tk2Op, _> = op.try_into() else {
+            return 1.0;
+        };
+        match op {
+            H | T | S | X | Y | Z | Tdg | Sdg | RzF64 | RxF64 | PhasedX | TK1 => self.single_qubit,
+            CX | ZZMax | ZZPhase | CZ => self.two_qubit,
+            Measure | Reset => self.measurement,
+            AngleAdd | QAlloc | QFree => 1.0,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
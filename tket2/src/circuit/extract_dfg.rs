@@ -1,7 +1,7 @@
 //! Internal implementation of `Circuit::extract_dfg`.
 
 use hugr::hugr::hugrmut::HugrMut;
-use hugr::ops::{OpTrait, OpType, Output, DFG};
+use hugr::ops::{NamedOp, OpTrait, OpType, Output, DFG};
 use hugr::types::{Signature, SumType, TypeEnum};
 use hugr::HugrView;
 use hugr_core::hugr::internal::HugrMutInternals;
@@ -24,7 +24,14 @@ pub(super) fn rewrite_into_dfg(circ: &mut Circuit) -> Result<(), CircuitMutError
         _ => signature,
     };
 
-    circ.hugr.replace_op(circ.parent(), DFG { signature })?;
+    let parent = circ.parent();
+    circ.hugr
+        .replace_op(parent, DFG { signature })
+        .map_err(|source| CircuitMutError::ParentReplacement {
+            parent,
+            optype: circ.hugr.get_optype(parent).clone(),
+            source,
+        })?;
 
     Ok(())
 }
@@ -32,7 +39,9 @@ pub(super) fn rewrite_into_dfg(circ: &mut Circuit) -> Result<(), CircuitMutError
 /// Remove an empty sum from a cfg's DataflowBlock output node, if possible.
 ///
 /// Bails out if it cannot match the exact pattern, without modifying the
-/// circuit.
+/// circuit. The reason is logged at the debug level. Returns a
+/// [`CircuitMutError::CfgUnpacking`] error if the block's output node has no
+/// dataflow signature.
 ///
 /// TODO: This function is specialized towards the specific functions generated
 ///     by guppy. We should generalize this to work with non-empty sum types
@@ -42,31 +51,46 @@ fn remove_cfg_empty_output_tuple(
     signature: Signature,
 ) -> Result<Signature, CircuitMutError> {
     let sig = signature;
+    let block = circ.parent();
     let input_node = circ.input_node();
+    let bail = |reason: String| {
+        tracing::debug!("Not unpacking the output of CFG block {block}: {reason}");
+        Ok(sig.clone())
+    };
 
     let output_node = circ.output_node();
     let output_op = circ.hugr.get_optype(output_node).clone();
 
-    let output_sig = output_op
-        .dataflow_signature()
-        .expect("Exit node with no dataflow signature.");
+    let output_sig =
+        output_op
+            .dataflow_signature()
+            .ok_or_else(|| CircuitMutError::CfgUnpacking {
+                block,
+                reason: "the output node has no dataflow signature".to_string(),
+            })?;
 
     // Only remove the port if it's an empty sum type.
+    let branch_type = &output_sig.input[0];
     if !matches!(
-        output_sig.input[0].as_type_enum(),
+        branch_type.as_type_enum(),
         TypeEnum::Sum(SumType::Unit { size: 1 })
     ) {
-        return Ok(sig);
+        return bail(format!(
+            "the branch predicate has type {branch_type}, only blocks with a single successor are supported"
+        ));
     }
 
     // There must be a zero-sized `Tag` operation.
     let Some((tag_node, _)) = circ.hugr.single_linked_output(output_node, 0) else {
-        return Ok(sig);
+        return bail("the branch predicate is not connected".to_string());
     };
 
     let tag_op = circ.hugr.get_optype(tag_node);
     if !matches!(tag_op, OpType::Tag(_)) {
-        return Ok(sig);
+        return bail(format!(
+            "the branch predicate is produced by {} in {tag_node} instead of a Tag operation",
+            tag_op.name()
+        ));
     }
 
     // Hacky replacement for the nodes.
@@ -6,6 +6,7 @@ use fxhash::{FxHashMap, FxHasher64};
 use hugr::hugr::views::{HierarchyView, SiblingGraph};
 use hugr::ops::{NamedOp, OpType};
 use hugr::{HugrView, Node};
+use itertools::Itertools;
 use petgraph::visit::{self as pg, Walker};
 use thiserror::Error;
 
@@ -62,6 +63,88 @@ where
     }
 }
 
+/// A circuit usable as a key in hash maps and sets, such as the set of
+/// circuits already visited by an optimiser.
+///
+/// Keys are hashed by the [`CircuitHash`] of the circuit. Two keys are equal
+/// if their hashes agree, if they apply the same multiset of operations to the
+/// same linear units, and if the circuits are
+/// [structurally equal](Circuit::structurally_eq). The comparisons are done in
+/// that order, so the full structural comparison only runs on hash
+/// collisions. Distinct circuits are thus never identified, while circuits
+/// that only differ in the order of their commuting commands may be treated as
+/// distinct.
+#[derive(Debug, Clone)]
+pub struct CircuitKey {
+    /// The structural hash of the circuit.
+    hash: u64,
+    /// A hash of the sorted commands of the circuit.
+    fingerprint: u64,
+    /// The circuit, used to resolve collisions.
+    circ: Circuit,
+}
+
+impl CircuitKey {
+    /// Create a key for a circuit.
+    ///
+    /// # Errors
+    ///
+    /// If the circuit cannot be hashed, see [`CircuitHash::circuit_hash`].
+    pub fn new(circ: &Circuit<impl HugrView>) -> Result<Self, HashError> {
+        let hash = circ.circuit_hash()?;
+        Ok(Self::with_hash(circ.to_owned(), hash))
+    }
+
+    /// Create a key for a circuit with a precomputed structural hash.
+    fn with_hash(circ: Circuit, hash: u64) -> Self {
+        let commands = circ
+            .commands()
+            .map(|cmd| {
+                let op = fxhash::hash64(&hashable_op(cmd.optype()));
+                let units = cmd.linear_inputs().map(|(u, _, _)| u.index()).collect_vec();
+                (op, units)
+            })
+            .sorted_unstable()
+            .collect_vec();
+        Self {
+            hash,
+            fingerprint: fxhash::hash64(&commands),
+            circ,
+        }
+    }
+
+    /// The structural hash of the circuit.
+    pub fn circuit_hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// The circuit of the key.
+    pub fn circuit(&self) -> &Circuit {
+        &self.circ
+    }
+
+    /// Returns the circuit of the key.
+    pub fn into_circuit(self) -> Circuit {
+        self.circ
+    }
+}
+
+impl PartialEq for CircuitKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash
+            && self.fingerprint == other.fingerprint
+            && self.circ.structurally_eq(&other.circ, false)
+    }
+}
+
+impl Eq for CircuitKey {}
+
+impl Hash for CircuitKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.hash.hash(state);
+    }
+}
+
 /// Auxiliary data for circuit hashing.
 ///
 /// Contains previously computed hashes.
@@ -151,8 +234,14 @@ pub enum HashError {
 
 #[cfg(test)]
 mod test {
+    use std::collections::HashSet;
+
+    use hugr::builder::{DFGBuilder, Dataflow, DataflowHugr};
+    use hugr::extension::prelude::QB_T;
+    use hugr::types::Signature;
     use tket_json_rs::circuit_json;
 
+    use crate::extension::REGISTRY;
     use crate::serialize::TKETDecode;
     use crate::utils::build_simple_circuit;
     use crate::Tk2Op;
@@ -195,6 +284,46 @@ mod test {
         assert_ne!(hash1, hash3);
     }
 
+    #[test]
+    fn key_collision() {
+        let cx_h = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(Tk2Op::H, [0])?;
+            Ok(())
+        })
+        .unwrap();
+        let h_cx = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(CircuitKey::new(&cx_h), CircuitKey::new(&cx_h.clone()));
+        assert_ne!(CircuitKey::new(&cx_h), CircuitKey::new(&h_cx));
+
+        // The circuits have the same commands, so only the structural
+        // comparison tells them apart when their hashes collide.
+        let a = CircuitKey::with_hash(cx_h, 42);
+        let b = CircuitKey::with_hash(h_cx, 42);
+        assert_eq!(a.fingerprint, b.fingerprint);
+        assert_ne!(a, b);
+        let visited: HashSet<CircuitKey> = [a.clone(), b, a].into_iter().collect();
+        assert_eq!(visited.len(), 2);
+
+        // Circuits without commands differ in how their outputs are wired.
+        let identity = build_simple_circuit(2, |_| Ok(())).unwrap();
+        let dfg = DFGBuilder::new(Signature::new_endo(vec![QB_T, QB_T])).unwrap();
+        let [q0, q1] = dfg.input_wires_arr();
+        let swapped: Circuit = dfg
+            .finish_hugr_with_outputs([q1, q0], &REGISTRY)
+            .unwrap()
+            .into();
+        assert_ne!(
+            CircuitKey::with_hash(identity, 42),
+            CircuitKey::with_hash(swapped, 42)
+        );
+    }
+
     #[test]
     fn hash_constants() {
         let c_str = r#"{"bits": [], "commands": [{"args": [["q", [0]]], "op": {"params": ["0.5"], "type": "Rz"}}], "created_qubits": [], "discarded_qubits": [], "implicit_permutation": [[["q", [0]], ["q", [0]]]], "phase": "0.0", "qubits": [["q", [0]]]}"#;
@@ -0,0 +1,387 @@
+//! Numeric evaluation of the float parameters of a circuit.
+
+use std::collections::HashMap;
+use std::f64::consts::{FRAC_PI_4, PI, TAU};
+
+use hugr::hugr::hugrmut::HugrMut;
+use hugr::ops::{Const, LoadConstant, OpType, Value};
+use hugr::std_extensions::arithmetic::float_types::{ConstF64, FLOAT64_TYPE};
+use hugr::{CircuitUnit, HugrView, Wire};
+use itertools::Itertools;
+
+use super::symbols::eval_param;
+use super::{simplify_param, Circuit, Command};
+use crate::ops::match_symb_const_op;
+use crate::serialize::pytket::METADATA_PHASE;
+use crate::Tk2Op;
+
+/// Tracks the numeric value of float wires while traversing the commands of a
+/// circuit in topological order.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct NumericParams {
+    values: HashMap<Wire, f64>,
+}
+
+/// A parameter that cannot be evaluated to a number.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ParamError {
+    /// The parameter is a symbolic expression.
+    Symbolic(String),
+    /// The parameter does not come from a numeric constant computation.
+    Unresolved,
+}
+
+impl NumericParams {
+    /// Evaluate a classical parameter computation, recording the value of its
+    /// outputs.
+    ///
+    /// Returns `true` if the command was a parameter computation, and `false`
+    /// if it is some other operation that should be processed by the caller.
+    pub fn process(&mut self, cmd: &Command<'_, impl HugrView>) -> Result<bool, ParamError> {
+        let value = match cmd.optype() {
+            OpType::Const(c) => c.value().get_custom_value::<ConstF64>().map(|f| **f),
+            OpType::LoadConstant(_) => match self.inputs(cmd).as_deref() {
+                Ok(&[value]) => Some(value),
+                _ => None,
+            },
+            op if Tk2Op::try_from(op) == Ok(Tk2Op::AngleAdd) => {
+                Some(self.inputs(cmd)?.into_iter().sum())
+            }
+            op => match match_symb_const_op(op) {
+                Some(expr) => return Err(ParamError::Symbolic(expr)),
+                None => return Ok(false),
+            },
+        };
+        if let Some(value) = value {
+            for (unit, _, _) in cmd.outputs() {
+                if let CircuitUnit::Wire(wire) = unit {
+                    self.values.insert(wire, value);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// The numeric value of a float wire, if it has been computed.
+    pub fn value(&self, wire: Wire) -> Option<f64> {
+        self.values.get(&wire).copied()
+    }
+
+    /// The numeric value of each non-linear input of a command, in port order.
+    pub fn inputs(&self, cmd: &Command<'_, impl HugrView>) -> Result<Vec<f64>, ParamError> {
+        cmd.inputs()
+            .filter_map(|(unit, _, _)| match unit {
+                CircuitUnit::Wire(wire) => Some(wire),
+                CircuitUnit::Linear(_) => None,
+            })
+            .map(|wire| {
+                self.values
+                    .get(&wire)
+                    .copied()
+                    .ok_or(ParamError::Unresolved)
+            })
+            .collect()
+    }
+}
+
+/// A numeric angle, in radians, known up to a tolerance.
+///
+/// Used to decide whether a rotation is close enough to the identity to be
+/// treated as one, allowing approximate optimisations controlled by a user
+/// tolerance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundedParam {
+    /// The angle, in radians.
+    pub value: f64,
+    /// The absolute tolerance on the angle, in radians.
+    pub tol: f64,
+}
+
+impl BoundedParam {
+    /// Create a new angle with a tolerance.
+    pub fn new(value: f64, tol: f64) -> Self {
+        Self { value, tol }
+    }
+
+    /// Whether the angle is within the tolerance of a multiple of 2π.
+    pub fn equiv_0(&self) -> bool {
+        self.full_turns().is_some()
+    }
+
+    /// If a rotation by this angle is the identity up to a global phase,
+    /// returns that phase in half-turns.
+    ///
+    /// Rotations by an even multiple of 2π are the identity, and rotations by
+    /// an odd multiple of 2π are its negation.
+    pub fn identity_up_to_phase(&self) -> Option<f64> {
+        self.full_turns().map(|turns| turns.rem_euclid(2.))
+    }
+
+    /// The nearest number of full turns, if the angle is within the
+    /// tolerance of it.
+    fn full_turns(&self) -> Option<f64> {
+        let turns = (self.value / TAU).round();
+        ((self.value - turns * TAU).abs() <= self.tol).then_some(turns)
+    }
+}
+
+/// The value of a float parameter.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ParamValue {
+    /// A numeric angle, in radians.
+    Numeric(f64),
+    /// A simplified symbolic expression, in half-turns.
+    Symbolic(String),
+}
+
+impl ParamValue {
+    /// Parse a symbolic expression in half-turns, evaluating it if it
+    /// simplifies to a number.
+    fn from_expr(expr: &str) -> Self {
+        match eval_param(expr) {
+            Some(half_turns) => Self::Numeric(half_turns * PI),
+            None => Self::Symbolic(simplify_param(expr)),
+        }
+    }
+
+    /// The value as an expression in half-turns.
+    fn to_expr(&self) -> String {
+        match self {
+            Self::Numeric(radians) => (radians / PI).to_string(),
+            Self::Symbolic(expr) => expr.clone(),
+        }
+    }
+}
+
+/// The angle of a multiple of `π/4`, in units of `π/4`.
+pub(crate) fn quarter_turns(angle: f64) -> Option<i64> {
+    let k = angle / FRAC_PI_4;
+    ((k - k.round()).abs() < 1e-9).then_some(k.round() as i64)
+}
+
+/// Remove the constant parameter computations left without consumers.
+///
+/// These are constants, their loads, and the `AngleAdd` operations combining
+/// them. Symbolic parameters are kept.
+pub(crate) fn remove_dead_constants(circ: &mut Circuit<impl HugrMut>) {
+    loop {
+        let hugr = circ.hugr();
+        let dead = hugr
+            .children(circ.parent())
+            .filter(|&node| {
+                let op = hugr.get_optype(node);
+                let is_param = matches!(op, OpType::Const(_) | OpType::LoadConstant(_))
+                    || Tk2Op::try_from(op) == Ok(Tk2Op::AngleAdd);
+                is_param && hugr.all_linked_inputs(node).next().is_none()
+            })
+            .collect_vec();
+        if dead.is_empty() {
+            break;
+        }
+        for node in dead {
+            circ.hugr_mut().remove_node(node);
+        }
+    }
+}
+
+/// Evaluate a float wire by following it back through the computation
+/// producing it.
+///
+/// Returns `None` if the wire is not computed from numeric constants and
+/// symbolic parameters by `AngleAdd` operations.
+pub(crate) fn param_value(hugr: &impl HugrView, wire: Wire) -> Option<ParamValue> {
+    let node = wire.node();
+    match hugr.get_optype(node) {
+        OpType::LoadConstant(_) => {
+            let (konst, _) = hugr.single_linked_output(node, 0)?;
+            let OpType::Const(c) = hugr.get_optype(konst) else {
+                return None;
+            };
+            let value = c.value().get_custom_value::<ConstF64>()?;
+            Some(ParamValue::Numeric(**value))
+        }
+        op if Tk2Op::try_from(op) == Ok(Tk2Op::AngleAdd) => {
+            let inputs = hugr
+                .in_value_types(node)
+                .map(|(port, _)| {
+                    let (src, src_port) = hugr.single_linked_output(node, port)?;
+                    param_value(hugr, Wire::new(src, src_port))
+                })
+                .collect::<Option<Vec<_>>>()?;
+            let numeric = inputs
+                .iter()
+                .map(|v| match v {
+                    ParamValue::Numeric(radians) => Some(*radians),
+                    ParamValue::Symbolic(_) => None,
+                })
+                .collect::<Option<Vec<_>>>();
+            match numeric {
+                Some(values) => Some(ParamValue::Numeric(values.into_iter().sum())),
+                None => {
+                    let sum = inputs
+                        .iter()
+                        .map(|v| format!("({})", v.to_expr()))
+                        .join(" + ");
+                    Some(ParamValue::from_expr(&sum))
+                }
+            }
+        }
+        op => Some(ParamValue::from_expr(&match_symb_const_op(op)?)),
+    }
+}
+
+impl<T: HugrMut> Circuit<T> {
+    /// Replace every numeric angle parameter of the circuit's operations, and
+    /// its global phase, with the result of applying `f` to it.
+    ///
+    /// Angles are given to `f` in radians. The global phase is converted from
+    /// half-turns to radians and back. Symbolic parameters are not modified.
+    ///
+    /// Each numeric parameter input is reconnected to a new constant holding
+    /// its mapped value, so parameters shared between operations get a
+    /// constant each. The parameter computations left unused, including
+    /// `AngleAdd` operations, are removed.
+    pub fn map_params(&mut self, f: impl Fn(f64) -> f64) {
+        let mut params = NumericParams::default();
+        let mut mapped = Vec::new();
+        for cmd in self.commands() {
+            if params.process(&cmd).unwrap_or(true) {
+                continue;
+            }
+            for (unit, port, _) in cmd.inputs() {
+                if let CircuitUnit::Wire(wire) = unit {
+                    if let Some(&value) = params.values.get(&wire) {
+                        mapped.push((cmd.node(), port, f(value)));
+                    }
+                }
+            }
+        }
+
+        let parent = self.parent();
+        let hugr = self.hugr_mut();
+        for (node, port, value) in mapped {
+            let konst = hugr
+                .add_node_with_parent(parent, Const::new(Value::extension(ConstF64::new(value))));
+            let load = hugr.add_node_with_parent(
+                parent,
+                LoadConstant {
+                    datatype: FLOAT64_TYPE,
+                },
+            );
+            hugr.connect(konst, 0, load, 0);
+            hugr.disconnect(node, port);
+            hugr.connect(load, 0, node, port);
+        }
+
+        let phase = hugr
+            .get_metadata(parent, METADATA_PHASE)
+            .and_then(|p| p.as_str())
+            .and_then(|p| p.parse::<f64>().ok());
+        if let Some(phase) = phase {
+            let new_phase = f(phase * PI) / PI;
+            hugr.set_metadata(parent, METADATA_PHASE, new_phase.to_string());
+        }
+        remove_dead_constants(self);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::extension::REGISTRY;
+    use crate::rewrite::circuit_phase;
+    use hugr::builder::{DFGBuilder, Dataflow, DataflowHugr};
+    use hugr::extension::prelude::QB_T;
+    use hugr::types::Signature;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(1e-10, 1e-8, Some(0.))]
+    #[case(1e-10, 1e-12, None)]
+    #[case(TAU - 1e-10, 1e-8, Some(1.))]
+    #[case(-2. * TAU, 0., Some(0.))]
+    #[case(PI, 1e-8, None)]
+    fn bounded_param(#[case] value: f64, #[case] tol: f64, #[case] phase: Option<f64>) {
+        let param = BoundedParam::new(value, tol);
+        assert_eq!(param.identity_up_to_phase(), phase);
+        assert_eq!(param.equiv_0(), phase.is_some());
+    }
+
+    #[test]
+    fn map_params() {
+        // Two rotations sharing the same angle.
+        let mut dfg = DFGBuilder::new(Signature::new_endo(vec![QB_T, QB_T])).unwrap();
+        let [q0, q1] = dfg.input_wires_arr();
+        let angle = dfg.add_load_value(ConstF64::new(0.5));
+        let [q0] = dfg
+            .add_dataflow_op(Tk2Op::RzF64, [q0, angle])
+            .unwrap()
+            .outputs_arr();
+        let [q0, q1] = dfg
+            .add_dataflow_op(Tk2Op::CX, [q0, q1])
+            .unwrap()
+            .outputs_arr();
+        let [q1] = dfg
+            .add_dataflow_op(Tk2Op::RzF64, [q1, angle])
+            .unwrap()
+            .outputs_arr();
+        let mut circ: Circuit = dfg
+            .finish_hugr_with_outputs([q0, q1], &REGISTRY)
+            .unwrap()
+            .into();
+        let parent = circ.parent();
+        circ.hugr_mut().set_metadata(parent, METADATA_PHASE, "0.25");
+        let n_commands = circ.commands().count();
+
+        circ.map_params(|angle| 2. * angle);
+        circ.hugr().validate(&REGISTRY).unwrap();
+
+        let mut params = NumericParams::default();
+        let mut rz_angles = Vec::new();
+        for cmd in circ.commands() {
+            if !params.process(&cmd).unwrap() && cmd.optype() == &Tk2Op::RzF64.into() {
+                rz_angles.push(params.inputs(&cmd).unwrap());
+            }
+        }
+        assert_eq!(rz_angles, [[1.], [1.]]);
+        assert_eq!(circuit_phase(&circ), 0.5);
+        // Each angle now has its own constant.
+        assert_eq!(circ.commands().count(), n_commands + 2);
+    }
+
+    #[test]
+    fn map_params_removes_angle_add() {
+        let mut dfg = DFGBuilder::new(Signature::new_endo(vec![QB_T])).unwrap();
+        let [q] = dfg.input_wires_arr();
+        let a = dfg.add_load_value(ConstF64::new(0.25));
+        let b = dfg.add_load_value(ConstF64::new(0.5));
+        let [angle] = dfg
+            .add_dataflow_op(Tk2Op::AngleAdd, [a, b])
+            .unwrap()
+            .outputs_arr();
+        let [q] = dfg
+            .add_dataflow_op(Tk2Op::RzF64, [q, angle])
+            .unwrap()
+            .outputs_arr();
+        let mut circ: Circuit = dfg.finish_hugr_with_outputs([q], &REGISTRY).unwrap().into();
+
+        circ.map_params(|angle| -angle);
+        circ.hugr().validate(&REGISTRY).unwrap();
+
+        let ops = circ
+            .commands()
+            .filter_map(|cmd| Tk2Op::try_from(cmd.optype()).ok())
+            .collect_vec();
+        assert_eq!(ops, [Tk2Op::RzF64]);
+        let mut params = NumericParams::default();
+        let mut angles = Vec::new();
+        for cmd in circ.commands() {
+            if !params.process(&cmd).unwrap() {
+                angles.push(params.inputs(&cmd).unwrap());
+            }
+        }
+        assert_eq!(angles, [[-0.75]]);
+        // Only the constant of the mapped angle is left.
+        assert_eq!(circ.commands().count(), 3);
+    }
+}
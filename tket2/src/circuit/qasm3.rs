@@ -0,0 +1,369 @@
+//! Emission of circuits as OpenQASM 3 programs.
+//!
+//! Qubits are declared as a single `qubit` register, indexed by their linear
+//! unit, and each measurement writes to a new entry of a `bit` register.
+//! `Conditional` nodes branching on a measured bit, or on the comparison of a
+//! [`Register`](super::register::Register) of measured bits with a value, are
+//! emitted as `if` statements, and adjoint gates use the `inv @` modifier.
+//! Custom gates are emitted by name, without a definition.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use hugr::extension::prelude::BOOL_T;
+use hugr::ops::{NamedOp, OpType};
+use hugr::{CircuitUnit, Hugr, HugrView, Node, Wire};
+use itertools::Itertools;
+use thiserror::Error;
+
+use super::params::{NumericParams, ParamError};
+use super::{Circuit, Command};
+use crate::ops::{match_custom_gate_op, match_register_eq_op};
+use crate::Tk2Op;
+
+/// Emit a circuit as an OpenQASM 3 program.
+///
+/// # Errors
+///
+/// Returns an error if the circuit contains operations without an OpenQASM 3
+/// equivalent, if a rotation angle is not a numeric constant, or if a
+/// conditional does not branch on measured bits.
+pub fn to_qasm3(circ: &Circuit<impl HugrView>) -> Result<String, Qasm3Error> {
+    // Conditional branches are traversed as circuits borrowing the HUGR.
+    let circ = circ.to_owned();
+    let circ = Circuit::new(circ.hugr(), circ.parent());
+    let mut emitter = Emitter::default();
+    let qubits = (0..circ.qubit_count()).collect_vec();
+    emitter.emit_circuit(&circ, &qubits, 0)?;
+
+    let mut qasm = String::new();
+    writeln!(qasm, "OPENQASM 3.0;").unwrap();
+    writeln!(qasm, "include \"stdgates.inc\";").unwrap();
+    writeln!(qasm).unwrap();
+    writeln!(qasm, "qubit[{}] q;", emitter.num_qubits.max(qubits.len())).unwrap();
+    if !emitter.bits.is_empty() {
+        writeln!(qasm, "bit[{}] c;", emitter.bits.len()).unwrap();
+    }
+    writeln!(qasm).unwrap();
+    qasm.push_str(&emitter.body);
+    Ok(qasm)
+}
+
+impl<T: HugrView> Circuit<T> {
+    /// Emit the circuit as an OpenQASM 3 program.
+    ///
+    /// See [`to_qasm3`] for more details.
+    pub fn to_qasm3(&self) -> Result<String, Qasm3Error> {
+        to_qasm3(self)
+    }
+}
+
+/// Accumulates the statements of a program while traversing a circuit and its
+/// nested conditional branches.
+#[derive(Debug, Default)]
+struct Emitter {
+    params: NumericParams,
+    /// The bit storing each measured boolean wire.
+    bits: HashMap<Wire, usize>,
+    /// The number of qubits acted on.
+    num_qubits: usize,
+    body: String,
+}
+
+impl Emitter {
+    /// Emit the commands of a circuit, where the `i`-th linear unit of the
+    /// circuit is the qubit `qubits[i]` of the program.
+    fn emit_circuit(
+        &mut self,
+        circ: &Circuit<&Hugr>,
+        qubits: &[usize],
+        depth: usize,
+    ) -> Result<(), Qasm3Error> {
+        for cmd in circ.commands() {
+            let node = cmd.node();
+            let param_error = |e| match e {
+                ParamError::Symbolic(expr) => Qasm3Error::SymbolicParam { expr, node },
+                ParamError::Unresolved => Qasm3Error::UnresolvedParam { node },
+            };
+
+            // Classical parameter computations.
+            if self.params.process(&cmd).map_err(param_error)? {
+                continue;
+            }
+
+            let cmd_qubits = cmd
+                .input_qubits()
+                .map(|(qb, _, _)| qubits.get(qb.index()).copied().unwrap_or(qb.index()))
+                .collect_vec();
+            if let Some(&max) = cmd_qubits.iter().max() {
+                self.num_qubits = self.num_qubits.max(max + 1);
+            }
+            let indent = "  ".repeat(depth);
+
+            if let OpType::Conditional(_) = cmd.optype() {
+                self.emit_conditional(circ, &cmd, &cmd_qubits, depth)?;
+                continue;
+            }
+            // Register comparisons are emitted as part of the conditionals
+            // branching on them.
+            if match_register_eq_op(cmd.optype()).is_some() {
+                continue;
+            }
+
+            let unsupported = || Qasm3Error::Unsupported {
+                op: cmd.optype().name().to_string(),
+                node,
+            };
+            let args = cmd_qubits.iter().map(|q| format!("q[{q}]")).join(", ");
+            if let Some(gate) = match_custom_gate_op(cmd.optype()) {
+                let angles = self.angles(&cmd).map_err(param_error)?;
+                writeln!(self.body, "{indent}{}{angles} {args};", gate.name).unwrap();
+                continue;
+            }
+            let op: Tk2Op = cmd.optype().try_into().map_err(|_| unsupported())?;
+            if op == Tk2Op::Measure {
+                let bit = self.bits.len();
+                let wire = Wire::new(node, 1);
+                self.bits.insert(wire, bit);
+                writeln!(self.body, "{indent}c[{bit}] = measure {args};").unwrap();
+                continue;
+            }
+
+            let gate = qasm3_gate(op).ok_or_else(unsupported)?;
+            let angles = self.angles(&cmd).map_err(param_error)?;
+            writeln!(self.body, "{indent}{gate}{angles} {args};").unwrap();
+        }
+        Ok(())
+    }
+
+    /// The parenthesised angle arguments of a gate, or an empty string if it
+    /// has none.
+    fn angles(&self, cmd: &Command<'_, &Hugr>) -> Result<String, ParamError> {
+        let angles = self.params.inputs(cmd)?;
+        Ok(match angles.is_empty() {
+            true => String::new(),
+            false => format!("({})", angles.iter().map(|a| format!("{a:?}")).join(", ")),
+        })
+    }
+
+    /// The OpenQASM 3 condition for a boolean wire, if it is a measured bit
+    /// or the comparison of a register of measured bits with a value.
+    fn condition(&self, hugr: &Hugr, predicate: Wire) -> Option<String> {
+        if let Some(bit) = self.bits.get(&predicate) {
+            return Some(format!("c[{bit}]"));
+        }
+        let node = predicate.node();
+        let (width, value) = match_register_eq_op(hugr.get_optype(node))?;
+        let terms = (0..width)
+            .map(|i| {
+                let (src, src_port) = hugr.single_linked_output(node, i)?;
+                let bit = self.bits.get(&Wire::new(src, src_port))?;
+                let set = value.checked_shr(i as u32).unwrap_or(0) & 1 == 1;
+                let negation = if set { "" } else { "!" };
+                Some(format!("{negation}c[{bit}]"))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        match terms.is_empty() {
+            true => Some("true".to_string()),
+            false => Some(terms.join(" && ")),
+        }
+    }
+
+    /// Emit a `Conditional` node branching on measured bits as an `if`
+    /// statement.
+    fn emit_conditional(
+        &mut self,
+        circ: &Circuit<&Hugr>,
+        cmd: &Command<'_, &Hugr>,
+        qubits: &[usize],
+        depth: usize,
+    ) -> Result<(), Qasm3Error> {
+        let node = cmd.node();
+        let invalid = || Qasm3Error::UnsupportedCondition { node };
+        let hugr: &Hugr = circ.hugr();
+
+        // The predicate must be a measured bit or a register comparison, and
+        // the other inputs qubits.
+        let (predicate, _, typ) = cmd.inputs().next().ok_or_else(invalid)?;
+        let CircuitUnit::Wire(predicate) = predicate else {
+            return Err(invalid());
+        };
+        if typ != BOOL_T || cmd.inputs().skip(1).any(|(u, _, _)| u.is_wire()) {
+            return Err(invalid());
+        }
+        let condition = self.condition(hugr, predicate).ok_or_else(invalid)?;
+
+        let [case_false, case_true] = hugr
+            .children(node)
+            .map(|case| Circuit::new(hugr, case))
+            .collect_vec()
+            .try_into()
+            .map_err(|_| invalid())?;
+        let indent = "  ".repeat(depth);
+        let (condition, then_case, else_case) = match case_false.num_operations() {
+            0 => (condition, case_true, None),
+            _ if case_true.num_operations() == 0 => match self.bits.get(&predicate) {
+                Some(_) => (format!("!{condition}"), case_false, None),
+                None => (format!("!({condition})"), case_false, None),
+            },
+            _ => (condition, case_true, Some(case_false)),
+        };
+        writeln!(self.body, "{indent}if ({condition}) {{").unwrap();
+        self.emit_circuit(&then_case, qubits, depth + 1)?;
+        if let Some(else_case) = else_case {
+            writeln!(self.body, "{indent}}} else {{").unwrap();
+            self.emit_circuit(&else_case, qubits, depth + 1)?;
+        }
+        writeln!(self.body, "{indent}}}").unwrap();
+        Ok(())
+    }
+}
+
+/// Returns the OpenQASM 3 gate implementing an operation.
+fn qasm3_gate(op: Tk2Op) -> Option<&'static str> {
+    let gate = match op {
+        Tk2Op::H => "h",
+        Tk2Op::X => "x",
+        Tk2Op::Y => "y",
+        Tk2Op::Z => "z",
+        Tk2Op::S => "s",
+        Tk2Op::Sdg => "inv @ s",
+        Tk2Op::T => "t",
+        Tk2Op::Tdg => "inv @ t",
+        Tk2Op::CX => "cx",
+        Tk2Op::CZ => "cz",
+        Tk2Op::RzF64 => "rz",
+        Tk2Op::RxF64 => "rx",
+        Tk2Op::Reset => "reset",
+        _ => return None,
+    };
+    Some(gate)
+}
+
+/// Error type for the OpenQASM 3 emission of a circuit.
+#[derive(Debug, Clone, PartialEq, Error)]
+#[non_exhaustive]
+pub enum Qasm3Error {
+    /// The operation has no OpenQASM 3 equivalent.
+    #[error("Operation {op} in {node} is not supported by the OpenQASM 3 emitter.")]
+    Unsupported {
+        /// The operation name.
+        op: String,
+        /// The node.
+        node: Node,
+    },
+    /// A parameter is a symbolic expression.
+    #[error("Symbolic parameter '{expr}' in {node} cannot be emitted as OpenQASM 3. Only numeric parameters are supported.")]
+    SymbolicParam {
+        /// The symbolic expression.
+        expr: String,
+        /// The node.
+        node: Node,
+    },
+    /// A parameter input could not be resolved to a numeric constant.
+    #[error("A parameter of the operation in {node} is not a numeric constant.")]
+    UnresolvedParam {
+        /// The node.
+        node: Node,
+    },
+    /// A conditional does not branch on a measured bit or a register of
+    /// measured bits, or has non-qubit inputs.
+    #[error("The conditional in {node} must branch on measured bits and act only on qubits.")]
+    UnsupportedCondition {
+        /// The node.
+        node: Node,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::extension::REGISTRY;
+    use crate::utils::build_simple_circuit;
+    use crate::CustomGate;
+    use cool_asserts::assert_matches;
+    use hugr::builder::{DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer, SubContainer};
+    use hugr::extension::prelude::QB_T;
+    use hugr::type_row;
+    use hugr::types::Signature;
+
+    #[test]
+    fn classically_controlled_x() {
+        let mut dfg = DFGBuilder::new(Signature::new_endo(type_row![QB_T, QB_T])).unwrap();
+        let [q0, q1] = dfg.input_wires_arr();
+        let [q0] = dfg.add_dataflow_op(Tk2Op::H, [q0]).unwrap().outputs_arr();
+        let [q0, bit] = dfg
+            .add_dataflow_op(Tk2Op::Measure, [q0])
+            .unwrap()
+            .outputs_arr();
+        let mut cond = dfg
+            .conditional_builder(
+                ([type_row![], type_row![]], bit),
+                [(QB_T, q1)],
+                type_row![QB_T],
+            )
+            .unwrap();
+        let case = cond.case_builder(0).unwrap();
+        let [q] = case.input_wires_arr();
+        case.finish_with_outputs([q]).unwrap();
+        let mut case = cond.case_builder(1).unwrap();
+        let [q] = case.input_wires_arr();
+        let [q] = case.add_dataflow_op(Tk2Op::X, [q]).unwrap().outputs_arr();
+        case.finish_with_outputs([q]).unwrap();
+        let [q1] = cond.finish_sub_container().unwrap().outputs_arr();
+        let circ: Circuit = dfg
+            .finish_hugr_with_outputs([q0, q1], &REGISTRY)
+            .unwrap()
+            .into();
+
+        assert_eq!(
+            circ.to_qasm3().unwrap(),
+            [
+                "OPENQASM 3.0;",
+                "include \"stdgates.inc\";",
+                "",
+                "qubit[2] q;",
+                "bit[1] c;",
+                "",
+                "h q[0];",
+                "c[0] = measure q[0];",
+                "if (c[0]) {",
+                "  x q[1];",
+                "}",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn modifiers_and_errors() {
+        let circ = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::Tdg, [1])?;
+            circ.append(Tk2Op::CZ, [1, 0])?;
+            Ok(())
+        })
+        .unwrap();
+        let qasm = circ.to_qasm3().unwrap();
+        assert!(qasm.contains("inv @ t q[1];\ncz q[1], q[0];\n"));
+        assert!(!qasm.contains("\nbit["));
+
+        let circ = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::ZZMax, [0, 1])?;
+            Ok(())
+        })
+        .unwrap();
+        assert_matches!(circ.to_qasm3(), Err(Qasm3Error::Unsupported { .. }));
+    }
+
+    #[test]
+    fn custom_gate() {
+        let circ = build_simple_circuit(2, |circ| {
+            circ.append(CustomGate::new("iswap", 2, 0), [1, 0])?;
+            Ok(())
+        })
+        .unwrap();
+        let qasm = circ.to_qasm3().unwrap();
+        assert!(qasm.contains("iswap q[1], q[0];\n"));
+    }
+}
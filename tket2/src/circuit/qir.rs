@@ -0,0 +1,261 @@
+//! Emission of circuits as QIR, an LLVM IR representation of quantum programs.
+//!
+//! Circuits are emitted as a single entry point function calling the
+//! `__quantum__qis__*` intrinsics of the QIR base profile. Qubits are
+//! statically allocated, with each qubit identified by its index in the
+//! circuit's input boundary. Each measurement writes to a new `%Result`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use hugr::extension::prelude::QB_T;
+use hugr::ops::NamedOp;
+use hugr::{HugrView, Node};
+use thiserror::Error;
+
+use super::params::{NumericParams, ParamError};
+use super::Circuit;
+use crate::Tk2Op;
+
+/// Emit a circuit as a QIR module in LLVM IR textual format.
+///
+/// # Errors
+///
+/// Returns an error if the circuit contains operations without a QIR
+/// intrinsic, or if a rotation angle is not a numeric constant.
+pub fn to_qir(circ: &Circuit<impl HugrView>) -> Result<String, QirError> {
+    let mut params = NumericParams::default();
+    let mut declarations: BTreeMap<&'static str, &'static str> = BTreeMap::new();
+    let mut body = String::new();
+    let mut num_results = 0;
+
+    for cmd in circ.commands() {
+        let optype = cmd.optype();
+        let node = cmd.node();
+        let param_error = |e| match e {
+            ParamError::Symbolic(expr) => QirError::SymbolicParam { expr, node },
+            ParamError::Unresolved => QirError::UnresolvedParam { node },
+        };
+
+        // Classical parameter computations.
+        if params.process(&cmd).map_err(param_error)? {
+            continue;
+        }
+
+        let unsupported = || QirError::Unsupported {
+            op: optype.name().to_string(),
+            node,
+        };
+        let op: Tk2Op = optype.try_into().map_err(|_| unsupported())?;
+        let (intrinsic, signature) = qir_intrinsic(op).ok_or_else(unsupported)?;
+        declarations.insert(intrinsic, signature);
+
+        let mut args = params
+            .inputs(&cmd)
+            .map_err(param_error)?
+            .into_iter()
+            .map(|angle| format!("double {}", fmt_double(angle)))
+            .collect::<Vec<_>>();
+        args.extend(
+            cmd.input_qubits()
+                .map(|(qb, _, _)| format!("%Qubit* {}", fmt_ptr("Qubit", qb.index()))),
+        );
+        if op == Tk2Op::Measure {
+            args.push(format!("%Result* {}", fmt_ptr("Result", num_results)));
+            num_results += 1;
+        }
+        writeln!(body, "  call void @{intrinsic}({})", args.join(", ")).unwrap();
+    }
+
+    let name = circ.name().unwrap_or("main");
+    let num_qubits = circ.units().filter(|(_, _, typ)| typ == &QB_T).count();
+    let mut qir = String::new();
+    writeln!(qir, "%Qubit = type opaque").unwrap();
+    writeln!(qir, "%Result = type opaque").unwrap();
+    writeln!(qir).unwrap();
+    writeln!(qir, "define void @{name}() #0 {{").unwrap();
+    writeln!(qir, "entry:").unwrap();
+    qir.push_str(&body);
+    writeln!(qir, "  ret void").unwrap();
+    writeln!(qir, "}}").unwrap();
+    writeln!(qir).unwrap();
+    for (intrinsic, signature) in declarations {
+        writeln!(qir, "declare void @{intrinsic}({signature})").unwrap();
+    }
+    writeln!(qir).unwrap();
+    writeln!(
+        qir,
+        "attributes #0 = {{ \"entry_point\" \"required_num_qubits\"=\"{num_qubits}\" \"required_num_results\"=\"{num_results}\" }}"
+    )
+    .unwrap();
+    Ok(qir)
+}
+
+impl<T: HugrView> Circuit<T> {
+    /// Emit the circuit as a QIR module in LLVM IR textual format.
+    ///
+    /// See [`to_qir`] for more details.
+    pub fn to_qir(&self) -> Result<String, QirError> {
+        to_qir(self)
+    }
+}
+
+/// Returns the QIR intrinsic implementing an operation, and its argument types.
+fn qir_intrinsic(op: Tk2Op) -> Option<(&'static str, &'static str)> {
+    let intrinsic = match op {
+        Tk2Op::H => ("__quantum__qis__h__body", "%Qubit*"),
+        Tk2Op::X => ("__quantum__qis__x__body", "%Qubit*"),
+        Tk2Op::Y => ("__quantum__qis__y__body", "%Qubit*"),
+        Tk2Op::Z => ("__quantum__qis__z__body", "%Qubit*"),
+        Tk2Op::S => ("__quantum__qis__s__body", "%Qubit*"),
+        Tk2Op::Sdg => ("__quantum__qis__s__adj", "%Qubit*"),
+        Tk2Op::T => ("__quantum__qis__t__body", "%Qubit*"),
+        Tk2Op::Tdg => ("__quantum__qis__t__adj", "%Qubit*"),
+        Tk2Op::CX => ("__quantum__qis__cnot__body", "%Qubit*, %Qubit*"),
+        Tk2Op::CZ => ("__quantum__qis__cz__body", "%Qubit*, %Qubit*"),
+        Tk2Op::RzF64 => ("__quantum__qis__rz__body", "double, %Qubit*"),
+        Tk2Op::RxF64 => ("__quantum__qis__rx__body", "double, %Qubit*"),
+        Tk2Op::Measure => ("__quantum__qis__mz__body", "%Qubit*, %Result*"),
+        Tk2Op::Reset => ("__quantum__qis__reset__body", "%Qubit*"),
+        _ => return None,
+    };
+    Some(intrinsic)
+}
+
+/// Format a statically allocated `%Qubit*` or `%Result*` pointer.
+fn fmt_ptr(typ: &str, index: usize) -> String {
+    match index {
+        0 => "null".to_string(),
+        i => format!("inttoptr (i64 {i} to %{typ}*)"),
+    }
+}
+
+/// Format a double constant as an LLVM IR literal.
+///
+/// Values that cannot be written as a plain decimal literal are emitted in
+/// LLVM's hexadecimal double format.
+fn fmt_double(value: f64) -> String {
+    let decimal = format!("{value:?}");
+    if value.is_finite() && !decimal.contains('e') {
+        decimal
+    } else {
+        format!("0x{:016X}", value.to_bits())
+    }
+}
+
+/// Error type for the QIR emission of a circuit.
+#[derive(Debug, Clone, PartialEq, Error)]
+#[non_exhaustive]
+pub enum QirError {
+    /// The operation has no corresponding QIR intrinsic.
+    #[error("Operation {op} in {node} is not supported by the QIR emitter.")]
+    Unsupported {
+        /// The operation name.
+        op: String,
+        /// The node.
+        node: Node,
+    },
+    /// A parameter is a symbolic expression.
+    #[error("Symbolic parameter '{expr}' in {node} cannot be emitted as QIR. Only numeric parameters are supported.")]
+    SymbolicParam {
+        /// The symbolic expression.
+        expr: String,
+        /// The node.
+        node: Node,
+    },
+    /// A parameter input could not be resolved to a numeric constant.
+    #[error("A parameter of the operation in {node} is not a numeric constant.")]
+    UnresolvedParam {
+        /// The node.
+        node: Node,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::extension::REGISTRY;
+    use crate::ops::symbolic_constant_op;
+    use crate::utils::build_simple_circuit;
+    use cool_asserts::assert_matches;
+    use hugr::builder::{DFGBuilder, Dataflow, DataflowHugr};
+    use hugr::std_extensions::arithmetic::float_types::ConstF64;
+    use hugr::type_row;
+    use hugr::types::Signature;
+
+    #[test]
+    fn bell_measure() {
+        let circ = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(Tk2Op::Measure, [0])?;
+            circ.append(Tk2Op::Measure, [1])?;
+            Ok(())
+        })
+        .unwrap();
+        let qir = circ.to_qir().unwrap();
+
+        let calls = qir
+            .lines()
+            .filter(|l| l.trim_start().starts_with("call"))
+            .map(str::trim)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            calls[..2],
+            [
+                "call void @__quantum__qis__h__body(%Qubit* null)",
+                "call void @__quantum__qis__cnot__body(%Qubit* null, %Qubit* inttoptr (i64 1 to %Qubit*))",
+            ]
+        );
+        // The two measurements are independent, so they may be emitted in any order.
+        assert_eq!(calls.len(), 4);
+        assert!(calls[2..]
+            .iter()
+            .all(|c| c.starts_with("call void @__quantum__qis__mz__body")));
+        assert!(calls[2].ends_with("%Result* null)"));
+        assert!(calls[3].ends_with("%Result* inttoptr (i64 1 to %Result*))"));
+        assert!(qir.contains("define void @main() #0 {"));
+        assert!(qir.contains("declare void @__quantum__qis__mz__body(%Qubit*, %Result*)"));
+        assert!(qir.contains("\"required_num_qubits\"=\"2\" \"required_num_results\"=\"2\""));
+    }
+
+    #[test]
+    fn numeric_rotation() {
+        let mut dfg = DFGBuilder::new(Signature::new_endo(type_row![QB_T])).unwrap();
+        let [q] = dfg.input_wires_arr();
+        let angle = dfg.add_load_value(ConstF64::new(0.5));
+        let [q] = dfg
+            .add_dataflow_op(Tk2Op::RzF64, [q, angle])
+            .unwrap()
+            .outputs_arr();
+        let circ: Circuit = dfg.finish_hugr_with_outputs([q], &REGISTRY).unwrap().into();
+
+        let qir = circ.to_qir().unwrap();
+        assert!(qir.contains("call void @__quantum__qis__rz__body(double 0.5, %Qubit* null)"));
+    }
+
+    #[test]
+    fn unsupported() {
+        let circ = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::ZZMax, [0, 1])?;
+            Ok(())
+        })
+        .unwrap();
+        assert_matches!(circ.to_qir(), Err(QirError::Unsupported { .. }));
+
+        let mut dfg = DFGBuilder::new(Signature::new_endo(type_row![QB_T])).unwrap();
+        let [q] = dfg.input_wires_arr();
+        let [angle] = dfg
+            .add_dataflow_op(symbolic_constant_op("alpha".to_string()), [])
+            .unwrap()
+            .outputs_arr();
+        let [q] = dfg
+            .add_dataflow_op(Tk2Op::RzF64, [q, angle])
+            .unwrap()
+            .outputs_arr();
+        let circ: Circuit = dfg.finish_hugr_with_outputs([q], &REGISTRY).unwrap().into();
+        assert_matches!(circ.to_qir(), Err(QirError::SymbolicParam { expr, .. }) => {
+            assert_eq!(expr, "alpha");
+        });
+    }
+}
@@ -0,0 +1,255 @@
+//! Export of circuits as LaTeX diagrams using the `quantikz` package.
+//!
+//! Each qubit of the circuit is drawn as a row of the diagram, and commands
+//! are placed in the first column where all the rows they span are free.
+//! Rotation angles are shown as multiples of π, keeping symbolic parameters
+//! as expressions. Operations outside the common gate set are drawn as boxes
+//! labelled with their name.
+
+use std::collections::{BTreeSet, HashMap};
+use std::f64::consts::PI;
+use std::fmt::Write;
+
+use hugr::ops::NamedOp;
+use hugr::{CircuitUnit, HugrView, Wire};
+use itertools::Itertools;
+
+use super::params::{NumericParams, ParamError};
+use super::{Circuit, Command};
+use crate::Tk2Op;
+
+/// Emit a circuit as a `quantikz` environment.
+///
+/// The output can be included in a LaTeX document loading the `quantikz`
+/// TikZ library.
+pub fn to_quantikz(circ: &Circuit<impl HugrView>) -> String {
+    let mut params = NumericParams::default();
+    let mut symbolic: HashMap<Wire, String> = HashMap::new();
+    let mut rows: Vec<Vec<String>> = vec![Vec::new(); circ.qubit_count()];
+
+    for cmd in circ.commands() {
+        // Classical parameter computations.
+        match params.process(&cmd) {
+            Ok(true) => continue,
+            Ok(false) => {}
+            Err(err) => {
+                let label = match err {
+                    ParamError::Symbolic(expr) => format!(r"({})\pi", escape(&expr)),
+                    ParamError::Unresolved => param_labels(&cmd, &params, &symbolic).join(" + "),
+                };
+                for (_, wire) in cmd.output_wires() {
+                    symbolic.insert(wire, label.clone());
+                }
+                continue;
+            }
+        }
+
+        let qubits = cmd
+            .linear_inputs()
+            .map(|(unit, _, _)| unit.index())
+            .chain(cmd.linear_outputs().map(|(unit, _, _)| unit.index()))
+            .unique()
+            .collect_vec();
+        if qubits.is_empty() {
+            continue;
+        }
+        let span = BTreeSet::from_iter(qubits.iter().copied());
+        let (&first, &last) = (span.first().unwrap(), span.last().unwrap());
+        if rows.len() <= last {
+            rows.resize(last + 1, Vec::new());
+        }
+
+        // Place the command in the first column free on all spanned rows.
+        let column = (first..=last).map(|r| rows[r].len()).max().unwrap();
+        for row in &mut rows[first..=last] {
+            row.resize(column + 1, r"\qw".to_string());
+        }
+
+        let labels = param_labels(&cmd, &params, &symbolic);
+        let op = Tk2Op::try_from(cmd.optype()).ok();
+        match (op, qubits.as_slice()) {
+            (Some(Tk2Op::CX), &[ctrl, tgt]) => {
+                rows[ctrl][column] = format!(r"\ctrl{{{}}}", tgt as isize - ctrl as isize);
+                rows[tgt][column] = r"\targ{}".to_string();
+            }
+            (Some(Tk2Op::CZ), &[ctrl, tgt]) => {
+                rows[ctrl][column] = format!(r"\ctrl{{{}}}", tgt as isize - ctrl as isize);
+                rows[tgt][column] = r"\control{}".to_string();
+            }
+            (Some(Tk2Op::Measure), &[qb]) => {
+                rows[qb][column] = r"\meter{}".to_string();
+            }
+            (_, &[qb]) => {
+                rows[qb][column] = format!(r"\gate{{{}}}", gate_label(&cmd, op, &labels));
+            }
+            _ => {
+                let label = gate_label(&cmd, op, &labels);
+                let contiguous = qubits.iter().tuple_windows().all(|(a, b)| a + 1 == *b);
+                if contiguous {
+                    rows[first][column] = format!(r"\gate[{}]{{{label}}}", qubits.len());
+                } else {
+                    for (a, b) in span.iter().tuple_windows() {
+                        rows[*a][column] = format!(r"\gate{{{label}}} \vqw{{{}}}", b - a);
+                    }
+                    rows[last][column] = format!(r"\gate{{{label}}}");
+                }
+            }
+        }
+    }
+
+    let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let mut tex = String::new();
+    writeln!(tex, r"\begin{{quantikz}}").unwrap();
+    let n_rows = rows.len();
+    for (i, mut row) in rows.into_iter().enumerate() {
+        row.resize(width + 1, r"\qw".to_string());
+        let end = if i + 1 < n_rows { r" \\" } else { "" };
+        writeln!(tex, r"\lstick{{$q_{{{i}}}$}} & {}{end}", row.join(" & ")).unwrap();
+    }
+    writeln!(tex, r"\end{{quantikz}}").unwrap();
+    tex
+}
+
+impl<T: HugrView> Circuit<T> {
+    /// Emit the circuit as a `quantikz` environment.
+    ///
+    /// See [`to_quantikz`] for more details.
+    pub fn to_quantikz(&self) -> String {
+        to_quantikz(self)
+    }
+}
+
+/// The labels of the parameter inputs of a command, as multiples of π.
+fn param_labels(
+    cmd: &Command<'_, impl HugrView>,
+    params: &NumericParams,
+    symbolic: &HashMap<Wire, String>,
+) -> Vec<String> {
+    cmd.inputs()
+        .filter_map(|(unit, _, _)| match unit {
+            CircuitUnit::Wire(wire) => Some(wire),
+            CircuitUnit::Linear(_) => None,
+        })
+        .map(|wire| match params.value(wire) {
+            Some(value) => fmt_angle(value),
+            None => symbolic
+                .get(&wire)
+                .cloned()
+                .unwrap_or_else(|| "?".to_string()),
+        })
+        .collect()
+}
+
+/// The label of a gate drawn as a box.
+fn gate_label(cmd: &Command<'_, impl HugrView>, op: Option<Tk2Op>, params: &[String]) -> String {
+    let name = match op {
+        Some(Tk2Op::H) => "H",
+        Some(Tk2Op::X) => "X",
+        Some(Tk2Op::Y) => "Y",
+        Some(Tk2Op::Z) => "Z",
+        Some(Tk2Op::S) => "S",
+        Some(Tk2Op::Sdg) => r"S^\dagger",
+        Some(Tk2Op::T) => "T",
+        Some(Tk2Op::Tdg) => r"T^\dagger",
+        Some(Tk2Op::RzF64) => "R_z",
+        Some(Tk2Op::RxF64) => "R_x",
+        Some(Tk2Op::PhasedX) => r"\mathrm{PhX}",
+        Some(Tk2Op::ZZPhase) => "ZZ",
+        Some(Tk2Op::ZZMax) => r"ZZ_{\max}",
+        Some(Tk2Op::TK1) => r"\mathrm{TK1}",
+        Some(Tk2Op::Reset | Tk2Op::QAlloc) => r"\ket{0}",
+        _ => return format!(r"\text{{{}}}", escape(&cmd.optype().name())),
+    };
+    match params {
+        [] => name.to_string(),
+        params => format!("{name}({})", params.join(", ")),
+    }
+}
+
+/// Format an angle in radians as a multiple of π.
+fn fmt_angle(radians: f64) -> String {
+    let turns = format!("{:.4}", radians / PI);
+    let turns = turns.trim_end_matches('0').trim_end_matches('.');
+    match turns {
+        "0" | "-0" => "0".to_string(),
+        "1" => r"\pi".to_string(),
+        "-1" => r"-\pi".to_string(),
+        t => format!(r"{t}\pi"),
+    }
+}
+
+/// Escape the LaTeX special characters in a string.
+fn escape(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '_' | '&' | '%' | '#' | '$' | '{' | '}' => format!(r"\{c}"),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::extension::REGISTRY;
+    use crate::ops::symbolic_constant_op;
+    use crate::utils::build_simple_circuit;
+    use hugr::builder::{DFGBuilder, Dataflow, DataflowHugr};
+    use hugr::extension::prelude::QB_T;
+    use hugr::std_extensions::arithmetic::float_types::ConstF64;
+    use hugr::type_row;
+    use hugr::types::Signature;
+
+    #[test]
+    fn bell_measure() {
+        let circ = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(Tk2Op::Measure, [0])?;
+            circ.append(Tk2Op::Measure, [1])?;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(
+            circ.to_quantikz(),
+            [
+                r"\begin{quantikz}",
+                r"\lstick{$q_{0}$} & \gate{H} & \ctrl{1} & \meter{} & \qw \\",
+                r"\lstick{$q_{1}$} & \qw & \targ{} & \meter{} & \qw",
+                r"\end{quantikz}",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn params_and_generic_gates() {
+        let mut dfg = DFGBuilder::new(Signature::new_endo(type_row![QB_T, QB_T, QB_T])).unwrap();
+        let [q0, q1, q2] = dfg.input_wires_arr();
+        let angle = dfg.add_load_value(ConstF64::new(PI / 2.));
+        let [q0] = dfg
+            .add_dataflow_op(Tk2Op::RzF64, [q0, angle])
+            .unwrap()
+            .outputs_arr();
+        let [alpha] = dfg
+            .add_dataflow_op(symbolic_constant_op("a_1".to_string()), [])
+            .unwrap()
+            .outputs_arr();
+        let [q2, q0] = dfg
+            .add_dataflow_op(Tk2Op::ZZPhase, [q2, q0, alpha])
+            .unwrap()
+            .outputs_arr();
+        let circ: Circuit = dfg
+            .finish_hugr_with_outputs([q0, q1, q2], &REGISTRY)
+            .unwrap()
+            .into();
+
+        let tex = circ.to_quantikz();
+        assert!(tex.contains(
+            r"\lstick{$q_{0}$} & \gate{R_z(0.5\pi)} & \gate{ZZ((a\_1)\pi)} \vqw{2} & \qw \\"
+        ));
+        assert!(tex.contains(r"\lstick{$q_{1}$} & \qw & \qw & \qw \\"));
+        assert!(tex.contains(r"\lstick{$q_{2}$} & \qw & \gate{ZZ((a\_1)\pi)} & \qw"));
+    }
+}
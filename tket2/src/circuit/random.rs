@@ -0,0 +1,113 @@
+//! Random circuit generation, for benchmarking and fuzzing.
+
+use crate::utils::build_simple_circuit;
+use crate::{Circuit, Tk2Op};
+
+impl Circuit {
+    /// Generate a random Clifford circuit on `n_qubits` qubits, made of `H`,
+    /// `S` and `CX` gates.
+    ///
+    /// The circuit is made of `depth` layers. In each layer the qubits are
+    /// randomly paired up, and each pair receives either a `CX` in a random
+    /// direction or an independent random single-qubit gate on each qubit.
+    ///
+    /// The same `seed` always produces the same circuit.
+    pub fn random_clifford(n_qubits: usize, depth: usize, seed: u64) -> Circuit {
+        let mut rng = SplitMix64::new(seed);
+        let mut qubits: Vec<usize> = (0..n_qubits).collect();
+
+        build_simple_circuit(n_qubits, |circ| {
+            for _ in 0..depth {
+                rng.shuffle(&mut qubits);
+                for pair in qubits.chunks(2) {
+                    match *pair {
+                        [a, b] if rng.below(3) == 0 => {
+                            let (ctrl, tgt) = if rng.below(2) == 0 { (a, b) } else { (b, a) };
+                            circ.append(Tk2Op::CX, [ctrl, tgt])?;
+                        }
+                        _ => {
+                            for &q in pair {
+                                let op = [Tk2Op::H, Tk2Op::S][rng.below(2)];
+                                circ.append(op, [q])?;
+                            }
+                        }
+                    }
+                }
+            }
+            Ok(())
+        })
+        .expect("Random Clifford circuit construction should not fail.")
+    }
+}
+
+/// A small, self-contained seeded pseudo-random number generator.
+///
+/// See <https://prng.di.unimi.it/splitmix64.c>.
+#[derive(Debug, Clone)]
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a value in `0..n`.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// Fisher-Yates shuffle.
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.below(i + 1);
+            items.swap(i, j);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::extension::REGISTRY;
+    use hugr::ops::NamedOp;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::single_qubit(1, 10)]
+    #[case::small(3, 5)]
+    #[case::wide(8, 20)]
+    fn random_clifford(#[case] n_qubits: usize, #[case] depth: usize) {
+        let circ = Circuit::random_clifford(n_qubits, depth, 42);
+        circ.hugr().validate(&REGISTRY).unwrap();
+
+        assert_eq!(circ.qubit_count(), n_qubits);
+        assert!(circ.num_operations() > 0);
+        for cmd in circ.commands() {
+            let op: Tk2Op = cmd.optype().try_into().unwrap();
+            assert!(op.is_clifford(), "{op:?} is not a Clifford gate");
+        }
+    }
+
+    #[test]
+    fn random_clifford_seeded() {
+        let a = Circuit::random_clifford(4, 10, 7);
+        let b = Circuit::random_clifford(4, 10, 7);
+        let c = Circuit::random_clifford(4, 10, 8);
+        let ops = |circ: &Circuit| {
+            circ.commands()
+                .map(|c| c.optype().name())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(ops(&a), ops(&b));
+        assert_ne!(ops(&a), ops(&c));
+    }
+}
@@ -0,0 +1,161 @@
+//! Classical registers, grouping bits into unsigned integers.
+
+use std::ops::Range;
+
+use hugr::builder::{BuildError, Dataflow};
+use hugr::Wire;
+
+use crate::ops::register_eq_op;
+
+/// A classical register, grouping boolean wires into an unsigned integer.
+///
+/// The bits are ordered from least to most significant, so that the `i`-th
+/// bit has weight `2^i`. Registers are used while building a circuit, to
+/// branch on the value of several measured bits at once.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Register {
+    bits: Vec<Wire>,
+}
+
+impl Register {
+    /// Create a new register from its bits, least significant first.
+    pub fn new(bits: impl IntoIterator<Item = Wire>) -> Self {
+        Self {
+            bits: bits.into_iter().collect(),
+        }
+    }
+
+    /// The number of bits in the register.
+    pub fn width(&self) -> usize {
+        self.bits.len()
+    }
+
+    /// The bits of the register, least significant first.
+    pub fn bits(&self) -> &[Wire] {
+        &self.bits
+    }
+
+    /// Returns the register holding a range of the bits of this register.
+    ///
+    /// # Panics
+    ///
+    /// If the range is out of bounds.
+    pub fn range(&self, range: Range<usize>) -> Self {
+        Self::new(self.bits[range].iter().copied())
+    }
+
+    /// Replace the bits of the register starting at `start` with the bits of
+    /// `other`.
+    ///
+    /// # Panics
+    ///
+    /// If `other` does not fit in the register from `start`.
+    pub fn set_range(&mut self, start: usize, other: &Register) {
+        self.bits[start..start + other.width()].copy_from_slice(&other.bits);
+    }
+
+    /// Add an operation checking whether the register holds `value`.
+    ///
+    /// Returns a boolean wire, which can be used as the predicate of a
+    /// `Conditional` firing when the register equals `value`.
+    ///
+    /// # Panics
+    ///
+    /// If `value` does not fit in the register.
+    pub fn equals(&self, builder: &mut impl Dataflow, value: u64) -> Result<Wire, BuildError> {
+        let op = register_eq_op(self.width(), value);
+        let handle = builder.add_dataflow_op(op, self.bits.iter().copied())?;
+        Ok(handle.out_wire(0))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hugr::builder::{DFGBuilder, DataflowHugr, DataflowSubContainer, SubContainer};
+    use hugr::extension::prelude::{BOOL_T, QB_T};
+    use hugr::type_row;
+    use hugr::types::Signature;
+    use rstest::rstest;
+
+    use super::*;
+    use crate::extension::REGISTRY;
+    use crate::{Circuit, Tk2Op};
+
+    /// Measure two qubits into a register, and apply `X` to a third qubit
+    /// when the register equals `value`.
+    fn register_conditional(value: u64) -> Circuit {
+        let qbs = type_row![QB_T, QB_T, QB_T];
+        let mut dfg = DFGBuilder::new(Signature::new_endo(qbs)).unwrap();
+        let [q0, q1, q2] = dfg.input_wires_arr();
+        let [q0, b0] = dfg
+            .add_dataflow_op(Tk2Op::Measure, [q0])
+            .unwrap()
+            .outputs_arr();
+        let [q1, b1] = dfg
+            .add_dataflow_op(Tk2Op::Measure, [q1])
+            .unwrap()
+            .outputs_arr();
+        let register = Register::new([b0, b1]);
+        let predicate = register.equals(&mut dfg, value).unwrap();
+
+        let mut cond = dfg
+            .conditional_builder(
+                ([type_row![], type_row![]], predicate),
+                [(QB_T, q2)],
+                type_row![QB_T],
+            )
+            .unwrap();
+        let case0 = cond.case_builder(0).unwrap();
+        let [q] = case0.input_wires_arr();
+        case0.finish_with_outputs([q]).unwrap();
+        let mut case1 = cond.case_builder(1).unwrap();
+        let [q] = case1.input_wires_arr();
+        let q = case1.add_dataflow_op(Tk2Op::X, [q]).unwrap().out_wire(0);
+        case1.finish_with_outputs([q]).unwrap();
+        let [q2] = cond.finish_sub_container().unwrap().outputs_arr();
+
+        dfg.finish_hugr_with_outputs([q0, q1, q2], &REGISTRY)
+            .unwrap()
+            .into()
+    }
+
+    #[rstest]
+    #[case(3, "c[{b0}] && c[{b1}]")]
+    #[case(2, "!c[{b0}] && c[{b1}]")]
+    fn conditional_on_register(#[case] value: u64, #[case] condition: &str) {
+        let circ = register_conditional(value);
+        circ.hugr().validate(&REGISTRY).unwrap();
+
+        // The measurements may be emitted in any order.
+        let qasm = circ.to_qasm3().unwrap();
+        let bit = |qubit: usize| {
+            let line = qasm
+                .lines()
+                .find(|l| l.ends_with(&format!("= measure q[{qubit}];")))
+                .unwrap();
+            line[2..line.find(']').unwrap()].to_string()
+        };
+        let condition = condition.replace("{b0}", &bit(0)).replace("{b1}", &bit(1));
+        assert!(qasm.contains(&format!("if ({condition}) {{\n  x q[2];\n}}")));
+    }
+
+    #[test]
+    fn register_ranges() {
+        let dfg = DFGBuilder::new(Signature::new_endo(vec![BOOL_T; 4])).unwrap();
+        let bits: Vec<Wire> = dfg.input_wires().collect();
+        let mut register = Register::new(bits.clone());
+        assert_eq!(register.width(), 4);
+
+        let high = register.range(2..4);
+        assert_eq!(high.bits(), &bits[2..4]);
+
+        register.set_range(0, &high);
+        assert_eq!(register.bits(), &[bits[2], bits[3], bits[2], bits[3]]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn value_too_large() {
+        register_conditional(4);
+    }
+}
@@ -0,0 +1,180 @@
+//! Duration-aware scheduling of circuit operations.
+
+use std::collections::HashMap;
+
+use hugr::ops::OpType;
+use hugr::{HugrView, Node};
+use itertools::Itertools;
+
+use super::Circuit;
+use crate::Tk2Op;
+
+/// A model for the time taken to apply each operation.
+pub trait DurationModel {
+    /// The duration of the operation, in arbitrary time units.
+    fn op_duration(&self, op: &OpType) -> f64;
+}
+
+impl<F: Fn(&OpType) -> f64> DurationModel for F {
+    fn op_duration(&self, op: &OpType) -> f64 {
+        self(op)
+    }
+}
+
+/// A [`DurationModel`] assigning the same duration to all the operations of a
+/// given class.
+///
+/// Operations that are not [`Tk2Op`]s, and classical or allocation
+/// operations, are assumed to be instantaneous.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UniformDurationModel {
+    /// Duration of single-qubit gates.
+    pub single_qubit: f64,
+    /// Duration of two-qubit gates.
+    pub two_qubit: f64,
+    /// Duration of measurements and resets.
+    pub measurement: f64,
+}
+
+impl DurationModel for UniformDurationModel {
+    fn op_duration(&self, op: &OpType) -> f64 {
+        use Tk2Op::*;
+        let Ok(op): Result<Tk2Op, _> = op.try_into() else {
+            return 0.0;
+        };
+        match op {
+            H | T | S | X | Y | Z | Tdg | Sdg | RzF64 | RxF64 | PhasedX | TK1 => self.single_qubit,
+            CX | ZZMax | ZZPhase | CZ => self.two_qubit,
+            Measure | Reset => self.measurement,
+            AngleAdd | QAlloc | QFree => 0.0,
+        }
+    }
+}
+
+/// The start times of the commands of a circuit.
+///
+/// See [`Circuit::schedule`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Schedule {
+    /// The start time and duration of each command.
+    times: HashMap<Node, (f64, f64)>,
+    /// The total duration of the circuit.
+    total: f64,
+}
+
+impl Schedule {
+    /// The time at which a command starts, if it is part of the schedule.
+    pub fn start_time(&self, node: Node) -> Option<f64> {
+        self.times.get(&node).map(|&(start, _)| start)
+    }
+
+    /// The time at which a command ends, if it is part of the schedule.
+    pub fn end_time(&self, node: Node) -> Option<f64> {
+        self.times
+            .get(&node)
+            .map(|&(start, duration)| start + duration)
+    }
+
+    /// The total duration of the circuit.
+    pub fn total_duration(&self) -> f64 {
+        self.total
+    }
+
+    /// The scheduled commands with their start times, ordered by start time.
+    pub fn iter(&self) -> impl Iterator<Item = (Node, f64)> + '_ {
+        self.times
+            .iter()
+            .map(|(&node, &(start, _))| (node, start))
+            .sorted_by(|(n1, t1), (n2, t2)| t1.total_cmp(t2).then(n1.cmp(n2)))
+    }
+}
+
+impl<T: HugrView> Circuit<T> {
+    /// Schedule the commands of the circuit as late as possible, given the
+    /// duration of each operation.
+    ///
+    /// Each command starts once all the commands it depends on have ended.
+    /// The total duration is the length of the critical path of the circuit,
+    /// which is the minimum latency allowed by its dependencies. Commands off
+    /// the critical path are delayed as much as possible without increasing
+    /// it, so that qubits stay idle at the start of the circuit rather than
+    /// between operations.
+    pub fn schedule(&self, model: &impl DurationModel) -> Schedule {
+        let hugr = self.hugr();
+        let commands = self
+            .commands()
+            .map(|cmd| (cmd.node(), model.op_duration(cmd.optype())))
+            .collect_vec();
+
+        // The time from the start of each command to the end of the circuit.
+        let mut tails: HashMap<Node, f64> = HashMap::new();
+        for &(node, duration) in commands.iter().rev() {
+            let tail = hugr
+                .output_neighbours(node)
+                .filter_map(|next| tails.get(&next).copied())
+                .fold(0., f64::max);
+            tails.insert(node, duration + tail);
+        }
+
+        let total = tails.values().copied().fold(0., f64::max);
+        let times = commands
+            .into_iter()
+            .map(|(node, duration)| (node, (total - tails[&node], duration)))
+            .collect();
+        Schedule { times, total }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::build_simple_circuit;
+
+    /// `H` gates take 2 time units and `X` gates take 3.
+    fn duration(op: &OpType) -> f64 {
+        match Tk2Op::try_from(op) {
+            Ok(Tk2Op::H) => 2.,
+            Ok(Tk2Op::X) => 3.,
+            _ => 0.,
+        }
+    }
+
+    #[test]
+    fn serial_gates() {
+        let circ = build_simple_circuit(1, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::X, [0])?;
+            Ok(())
+        })
+        .unwrap();
+        let schedule = circ.schedule(&duration);
+        assert_eq!(schedule.total_duration(), 5.);
+
+        let starts = schedule.iter().map(|(_, start)| start).collect_vec();
+        assert_eq!(starts, [0., 2.]);
+    }
+
+    #[test]
+    fn parallel_gates() {
+        let circ = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::X, [1])?;
+            Ok(())
+        })
+        .unwrap();
+        let schedule = circ.schedule(&duration);
+        assert_eq!(schedule.total_duration(), 3.);
+
+        // The shorter gate is delayed to end with the circuit.
+        let node = |op: Tk2Op| {
+            circ.commands()
+                .find(|cmd| cmd.optype() == &op.into())
+                .unwrap()
+                .node()
+        };
+        let (h, x) = (node(Tk2Op::H), node(Tk2Op::X));
+        assert_eq!(schedule.start_time(h), Some(1.));
+        assert_eq!(schedule.start_time(x), Some(0.));
+        assert_eq!(schedule.end_time(h), schedule.end_time(x));
+    }
+}
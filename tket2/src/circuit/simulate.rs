@@ -0,0 +1,479 @@
+//! Statevector simulation of small circuits.
+//!
+//! This is intended as a reference for testing the correctness of circuit
+//! transformations, not as a performant simulator. Each gate updates the
+//! amplitudes of the statevector in parallel.
+
+use std::f64::consts::{FRAC_1_SQRT_2, FRAC_PI_2, PI};
+
+use hugr::ops::NamedOp;
+use hugr::{HugrView, Node};
+use num_complex::Complex64;
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
+use thiserror::Error;
+
+use super::params::{NumericParams, ParamError};
+use super::Circuit;
+use crate::ops::match_permutation_op;
+use crate::serialize::pytket::METADATA_PHASE;
+use crate::Tk2Op;
+
+/// The maximum number of qubits supported by [`statevector`].
+pub const MAX_QUBITS: usize = 20;
+
+/// The maximum number of qubits supported by [`unitary`].
+pub const MAX_UNITARY_QUBITS: usize = 10;
+
+/// The minimum number of amplitudes updated by each parallel task.
+const PAR_MIN_AMPLITUDES: usize = 1 << 12;
+
+/// Compute the statevector produced by a circuit from the all-zero state.
+///
+/// Only unitary circuits are supported. Amplitudes are indexed in big-endian
+/// order: the qubit at output 0 is the most significant bit of the basis state
+/// index. Qubits permuted by the wiring of the circuit (see
+/// [`Circuit::implicit_permutation`]) are moved to their outputs, and the
+/// global phase of the circuit is included.
+///
+/// # Errors
+///
+/// Returns an error if the circuit has more than [`MAX_QUBITS`] qubits, if it
+/// contains non-unitary or unknown operations, or if a rotation angle or the
+/// global phase is not a numeric constant.
+pub fn statevector(circ: &Circuit<impl HugrView>) -> Result<Vec<Complex64>, SimError> {
+    let n_qubits = circ.qubit_count();
+    if n_qubits > MAX_QUBITS {
+        return Err(SimError::TooManyQubits { n_qubits });
+    }
+    let mut state = vec![Complex64::new(0., 0.); 1 << n_qubits];
+    state[0] = Complex64::from_polar(1., global_phase(circ)? * PI);
+    simulate(circ, &mut state)?;
+    Ok(state)
+}
+
+/// Compute the unitary matrix implemented by a circuit, in row-major order.
+///
+/// Basis states are indexed as in [`statevector`], including the permutation
+/// of the outputs, and the global phase of the circuit is included. The
+/// circuit can have at most [`MAX_UNITARY_QUBITS`] qubits.
+///
+/// # Errors
+///
+/// Returns an error if the circuit has more than [`MAX_UNITARY_QUBITS`]
+/// qubits, if it contains non-unitary or unknown operations, or if a rotation
+/// angle or the global phase is not a numeric constant.
+pub fn unitary(circ: &Circuit<impl HugrView>) -> Result<Vec<Complex64>, SimError> {
+    let n_qubits = circ.qubit_count();
+    if n_qubits > MAX_UNITARY_QUBITS {
+        return Err(SimError::TooManyQubits { n_qubits });
+    }
+    let phase = Complex64::from_polar(1., global_phase(circ)? * PI);
+    let dim = 1 << n_qubits;
+    let mut matrix = vec![Complex64::new(0., 0.); dim * dim];
+    for col in 0..dim {
+        let mut state = vec![Complex64::new(0., 0.); dim];
+        state[col] = phase;
+        simulate(circ, &mut state)?;
+        for (row, amp) in state.into_iter().enumerate() {
+            matrix[row * dim + col] = amp;
+        }
+    }
+    Ok(matrix)
+}
+
+/// Apply the operations of a circuit to a statevector.
+fn simulate(circ: &Circuit<impl HugrView>, state: &mut [Complex64]) -> Result<(), SimError> {
+    let n_qubits = circ.qubit_count();
+    let mut params = NumericParams::default();
+    for cmd in circ.commands() {
+        let node = cmd.node();
+        let param_error = |e| match e {
+            ParamError::Symbolic(expr) => SimError::SymbolicParam { expr, node },
+            ParamError::Unresolved => SimError::UnresolvedParam { node },
+        };
+        if params.process(&cmd).map_err(param_error)? {
+            continue;
+        }
+
+        let unsupported = || SimError::Unsupported {
+            op: cmd.optype().name().to_string(),
+            node,
+        };
+        let matrix = match match_permutation_op(cmd.optype()) {
+            Some(perm) => permutation_matrix(&basis_permutation(&perm)),
+            None => {
+                let op = Tk2Op::try_from(cmd.optype()).map_err(|_| unsupported())?;
+                let angles = params.inputs(&cmd).map_err(param_error)?;
+                gate_matrix(op, &angles).ok_or_else(unsupported)?
+            }
+        };
+        let qubits: Vec<usize> = cmd.input_qubits().map(|(qb, _, _)| qb.index()).collect();
+        apply_gate(state, n_qubits, &qubits, &matrix);
+    }
+
+    // Move each qubit to the output it is wired to.
+    let perm = circ.implicit_permutation();
+    if perm.iter().enumerate().any(|(k, &qb)| k != qb) {
+        let mut inverse = vec![0; perm.len()];
+        for (k, &qb) in perm.iter().enumerate() {
+            inverse[qb] = k;
+        }
+        let amps = state.to_vec();
+        for (k, p) in basis_permutation(&inverse).into_iter().enumerate() {
+            state[p] = amps[k];
+        }
+    }
+    Ok(())
+}
+
+/// The global phase of a circuit, in half-turns.
+fn global_phase(circ: &Circuit<impl HugrView>) -> Result<f64, SimError> {
+    let Some(phase) = circ
+        .hugr()
+        .get_metadata(circ.parent(), METADATA_PHASE)
+        .and_then(|p| p.as_str())
+    else {
+        return Ok(0.);
+    };
+    phase.parse().map_err(|_| SimError::SymbolicParam {
+        expr: phase.to_string(),
+        node: circ.parent(),
+    })
+}
+
+impl<T: HugrView> Circuit<T> {
+    /// Compute the statevector produced by the circuit from the all-zero state.
+    ///
+    /// See [`statevector`] for more details.
+    pub fn statevector(&self) -> Result<Vec<Complex64>, SimError> {
+        statevector(self)
+    }
+
+    /// Compute the unitary matrix implemented by the circuit.
+    ///
+    /// See [`unitary`] for more details.
+    pub fn unitary(&self) -> Result<Vec<Complex64>, SimError> {
+        unitary(self)
+    }
+}
+
+/// The unitary matrix of a gate in row-major order, with its qubits in
+/// big-endian order. Returns `None` for non-unitary operations.
+pub(crate) fn gate_matrix(op: Tk2Op, angles: &[f64]) -> Option<Vec<Complex64>> {
+    let c = |re: f64, im: f64| Complex64::new(re, im);
+    let phase = |theta: f64| Complex64::from_polar(1., theta);
+    let (zero, one, i) = (c(0., 0.), c(1., 0.), c(0., 1.));
+    let h = FRAC_1_SQRT_2;
+
+    let rz = |theta: f64| vec![phase(-theta / 2.), zero, zero, phase(theta / 2.)];
+    let rx = |theta: f64| {
+        let (sin, cos) = (theta / 2.).sin_cos();
+        vec![c(cos, 0.), c(0., -sin), c(0., -sin), c(cos, 0.)]
+    };
+    let zz_phase = |theta: f64| {
+        let (a, b) = (phase(-theta / 2.), phase(theta / 2.));
+        diagonal(&[a, b, b, a])
+    };
+
+    let matrix = match op {
+        Tk2Op::H => vec![c(h, 0.), c(h, 0.), c(h, 0.), c(-h, 0.)],
+        Tk2Op::X => vec![zero, one, one, zero],
+        Tk2Op::Y => vec![zero, -i, i, zero],
+        Tk2Op::Z => vec![one, zero, zero, -one],
+        Tk2Op::S => vec![one, zero, zero, i],
+        Tk2Op::Sdg => vec![one, zero, zero, -i],
+        Tk2Op::T => vec![one, zero, zero, phase(FRAC_PI_2 / 2.)],
+        Tk2Op::Tdg => vec![one, zero, zero, phase(-FRAC_PI_2 / 2.)],
+        Tk2Op::RzF64 => rz(angles[0]),
+        Tk2Op::RxF64 => rx(angles[0]),
+        // PhasedX(θ, φ) = Rz(φ) Rx(θ) Rz(-φ)
+        Tk2Op::PhasedX => matmul(&rz(angles[1]), &matmul(&rx(angles[0]), &rz(-angles[1]))),
+        // TK1(α, β, γ) = Rz(α) Rx(β) Rz(γ)
+        Tk2Op::TK1 => matmul(&rz(angles[0]), &matmul(&rx(angles[1]), &rz(angles[2]))),
+        Tk2Op::CX => permutation_matrix(&[0, 1, 3, 2]),
+        Tk2Op::CZ => diagonal(&[one, one, one, -one]),
+        Tk2Op::ZZMax => zz_phase(FRAC_PI_2),
+        Tk2Op::ZZPhase => zz_phase(angles[0]),
+        Tk2Op::AngleAdd | Tk2Op::Measure | Tk2Op::QAlloc | Tk2Op::QFree | Tk2Op::Reset => {
+            return None
+        }
+    };
+    Some(matrix)
+}
+
+/// A diagonal matrix.
+pub(crate) fn diagonal(diag: &[Complex64]) -> Vec<Complex64> {
+    let dim = diag.len();
+    let mut matrix = vec![Complex64::new(0., 0.); dim * dim];
+    for (k, &d) in diag.iter().enumerate() {
+        matrix[k * dim + k] = d;
+    }
+    matrix
+}
+
+/// The matrix mapping each basis state `k` to `perm[k]`.
+fn permutation_matrix(perm: &[usize]) -> Vec<Complex64> {
+    let dim = perm.len();
+    let mut matrix = vec![Complex64::new(0., 0.); dim * dim];
+    for (k, &p) in perm.iter().enumerate() {
+        matrix[p * dim + k] = Complex64::new(1., 0.);
+    }
+    matrix
+}
+
+/// The permutation of the basis states induced by a permutation of qubits,
+/// sending qubit `i` to qubit `perm[i]`.
+fn basis_permutation(perm: &[usize]) -> Vec<usize> {
+    let n = perm.len();
+    (0..1 << n)
+        .map(|k: usize| {
+            perm.iter()
+                .enumerate()
+                .filter(|&(i, _)| (k >> (n - 1 - i)) & 1 == 1)
+                .fold(0, |acc, (_, &p)| acc | 1 << (n - 1 - p))
+        })
+        .collect()
+}
+
+/// The product of two square matrices.
+pub(crate) fn matmul(a: &[Complex64], b: &[Complex64]) -> Vec<Complex64> {
+    let dim = (a.len() as f64).sqrt() as usize;
+    (0..dim * dim)
+        .map(|k| {
+            let (row, col) = (k / dim, k % dim);
+            (0..dim).map(|j| a[row * dim + j] * b[j * dim + col]).sum()
+        })
+        .collect()
+}
+
+/// Apply a gate to the given qubits of a statevector.
+///
+/// Each amplitude of the new state is computed independently from the old
+/// one, so large states are updated in parallel.
+pub(crate) fn apply_gate(
+    state: &mut [Complex64],
+    n_qubits: usize,
+    qubits: &[usize],
+    matrix: &[Complex64],
+) {
+    let dim = 1 << qubits.len();
+    let masks: Vec<usize> = qubits.iter().map(|&q| 1 << (n_qubits - 1 - q)).collect();
+    let all_masks = masks.iter().fold(0, |acc, m| acc | m);
+    // The index in the full state of the local basis state `j` of the gate.
+    let index = |base: usize, j: usize| {
+        masks
+            .iter()
+            .enumerate()
+            .filter(|(b, _)| (j >> (qubits.len() - 1 - b)) & 1 == 1)
+            .fold(base, |acc, (_, m)| acc | m)
+    };
+    // The local basis state of the gate at an index of the full state.
+    let local = |i: usize| {
+        masks
+            .iter()
+            .fold(0, |acc, m| (acc << 1) | usize::from(i & m != 0))
+    };
+
+    let old = state.to_vec();
+    state
+        .par_iter_mut()
+        .with_min_len(PAR_MIN_AMPLITUDES)
+        .enumerate()
+        .for_each(|(i, amp)| {
+            let (base, row) = (i & !all_masks, local(i));
+            *amp = (0..dim)
+                .map(|j| matrix[row * dim + j] * old[index(base, j)])
+                .sum();
+        });
+}
+
+/// Error type for the simulation of a circuit.
+#[derive(Debug, Clone, PartialEq, Error)]
+#[non_exhaustive]
+pub enum SimError {
+    /// The operation cannot be simulated.
+    #[error("Operation {op} in {node} cannot be simulated. Only unitary gates are supported.")]
+    Unsupported {
+        /// The operation name.
+        op: String,
+        /// The node.
+        node: Node,
+    },
+    /// A parameter is a symbolic expression.
+    #[error("Symbolic parameter '{expr}' in {node} cannot be simulated.")]
+    SymbolicParam {
+        /// The symbolic expression.
+        expr: String,
+        /// The node.
+        node: Node,
+    },
+    /// A parameter input could not be resolved to a numeric constant.
+    #[error("A parameter of the operation in {node} is not a numeric constant.")]
+    UnresolvedParam {
+        /// The node.
+        node: Node,
+    },
+    /// The circuit is too large to be simulated.
+    #[error("Cannot simulate a circuit with {n_qubits} qubits. At most {MAX_QUBITS} are supported for statevectors, and {MAX_UNITARY_QUBITS} for unitaries.")]
+    TooManyQubits {
+        /// The number of qubits in the circuit.
+        n_qubits: usize,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::extension::REGISTRY;
+    use crate::utils::build_simple_circuit;
+    use cool_asserts::assert_matches;
+    use hugr::builder::{DFGBuilder, Dataflow, DataflowHugr};
+    use hugr::extension::prelude::QB_T;
+    use hugr::hugr::hugrmut::HugrMut;
+    use hugr::std_extensions::arithmetic::float_types::ConstF64;
+    use hugr::type_row;
+    use hugr::types::Signature;
+
+    fn assert_state_eq(state: &[Complex64], expected: &[Complex64]) {
+        assert_eq!(state.len(), expected.len());
+        for (a, b) in state.iter().zip(expected) {
+            assert!((a - b).norm() < 1e-10, "{state:?} != {expected:?}");
+        }
+    }
+
+    #[test]
+    fn bell_state() {
+        let circ = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            Ok(())
+        })
+        .unwrap();
+        let h = Complex64::new(FRAC_1_SQRT_2, 0.);
+        let zero = Complex64::new(0., 0.);
+        assert_state_eq(&circ.statevector().unwrap(), &[h, zero, zero, h]);
+    }
+
+    #[test]
+    fn ghz_state() {
+        // Large enough for the gates to be applied in parallel.
+        let n = 14;
+        let circ = build_simple_circuit(n, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            for q in 1..n {
+                circ.append(Tk2Op::CX, [q - 1, q])?;
+            }
+            Ok(())
+        })
+        .unwrap();
+        let mut expected = vec![Complex64::new(0., 0.); 1 << n];
+        expected[0] = Complex64::new(FRAC_1_SQRT_2, 0.);
+        expected[(1 << n) - 1] = Complex64::new(FRAC_1_SQRT_2, 0.);
+        assert_state_eq(&circ.statevector().unwrap(), &expected);
+    }
+
+    #[test]
+    fn qubit_order() {
+        // X on qubit 1 of 3 gives |010>.
+        let circ = build_simple_circuit(3, |circ| {
+            circ.append(Tk2Op::X, [1])?;
+            Ok(())
+        })
+        .unwrap();
+        let state = circ.statevector().unwrap();
+        assert_eq!(state[0b010], Complex64::new(1., 0.));
+    }
+
+    #[test]
+    fn rotations() {
+        // Rx(π) = -iX
+        let mut dfg = DFGBuilder::new(Signature::new_endo(type_row![QB_T])).unwrap();
+        let [q] = dfg.input_wires_arr();
+        let angle = dfg.add_load_value(ConstF64::new(PI));
+        let [q] = dfg
+            .add_dataflow_op(Tk2Op::RxF64, [q, angle])
+            .unwrap()
+            .outputs_arr();
+        let circ: Circuit = dfg.finish_hugr_with_outputs([q], &REGISTRY).unwrap().into();
+
+        let zero = Complex64::new(0., 0.);
+        assert_state_eq(
+            &circ.statevector().unwrap(),
+            &[zero, Complex64::new(0., -1.)],
+        );
+    }
+
+    #[test]
+    fn permutation() {
+        // Moving an excited qubit from position 0 to position 2.
+        let circ = build_simple_circuit(3, |circ| {
+            circ.append(Tk2Op::X, [0])?;
+            circ.append(crate::permutation_op([2, 0, 1]), [0, 1, 2])?;
+            Ok(())
+        })
+        .unwrap();
+        let state = circ.statevector().unwrap();
+        assert_eq!(state[0b001], Complex64::new(1., 0.));
+    }
+
+    #[test]
+    fn output_permutation() {
+        // Pushing the permutation into the wiring keeps the state.
+        let mut circ = build_simple_circuit(3, |circ| {
+            circ.append(Tk2Op::X, [0])?;
+            circ.append(crate::permutation_op([2, 0, 1]), [0, 1, 2])?;
+            Ok(())
+        })
+        .unwrap();
+        crate::passes::push_permutations(&mut circ);
+        assert_eq!(circ.implicit_permutation(), [1, 2, 0]);
+        let state = circ.statevector().unwrap();
+        assert_eq!(state[0b001], Complex64::new(1., 0.));
+    }
+
+    #[test]
+    fn global_phase() {
+        let mut circ = build_simple_circuit(1, |circ| {
+            circ.append(Tk2Op::X, [0])?;
+            Ok(())
+        })
+        .unwrap();
+        let parent = circ.parent();
+        circ.hugr_mut().set_metadata(parent, METADATA_PHASE, "0.5");
+        let zero = Complex64::new(0., 0.);
+        assert_state_eq(
+            &circ.statevector().unwrap(),
+            &[zero, Complex64::new(0., 1.)],
+        );
+
+        assert_state_eq(
+            &circ.unitary().unwrap(),
+            &[zero, Complex64::new(0., 1.), Complex64::new(0., 1.), zero],
+        );
+
+        circ.hugr_mut().set_metadata(parent, METADATA_PHASE, "a");
+        assert_matches!(circ.statevector(), Err(SimError::SymbolicParam { .. }));
+        assert_matches!(circ.unitary(), Err(SimError::SymbolicParam { .. }));
+    }
+
+    #[test]
+    fn unsupported() {
+        let circ = build_simple_circuit(1, |circ| {
+            circ.append(Tk2Op::Measure, [0])?;
+            Ok(())
+        })
+        .unwrap();
+        assert_matches!(circ.statevector(), Err(SimError::Unsupported { .. }));
+    }
+
+    #[test]
+    fn cx_unitary() {
+        let circ = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::CX, [1, 0])?;
+            Ok(())
+        })
+        .unwrap();
+        let expected = permutation_matrix(&[0, 3, 2, 1]);
+        assert_state_eq(&circ.unitary().unwrap(), &expected);
+    }
+}
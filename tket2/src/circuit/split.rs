@@ -0,0 +1,345 @@
+//! Splitting circuits at pytket barriers.
+
+use std::collections::{HashMap, HashSet};
+
+use hugr::hugr::hugrmut::HugrMut;
+use hugr::hugr::views::{DescendantsGraph, HierarchyView};
+use hugr::ops::dataflow::IOTrait;
+use hugr::ops::{Input, OpTrait, OpType, Output, DFG};
+use hugr::types::Signature;
+use hugr::{Hugr, HugrView, Node};
+use itertools::Itertools;
+use tket_json_rs::optype::OpType as Tk1OpType;
+
+use super::Circuit;
+use crate::serialize::pytket::{OpaqueTk1Op, METADATA_PHASE};
+
+impl<T: HugrView> Circuit<T> {
+    /// Split the circuit at its full-width barriers.
+    ///
+    /// Returns the sequence of circuits between consecutive barriers. A barrier
+    /// is used as a cut if it acts on every linear unit of the circuit and no
+    /// other wire crosses it, so the outputs of each piece are exactly the
+    /// inputs of the next one, in the same order. The barriers themselves are
+    /// not included in the pieces. Partial-width barriers, and barriers
+    /// crossed by a classical wire, are kept as ordinary operations.
+    ///
+    /// Composing the pieces in sequence gives back the original circuit
+    /// without its cutting barriers. The global phase is assigned to the first
+    /// piece, and the remaining root metadata is copied to every piece.
+    pub fn split_at_barriers(&self) -> Vec<Circuit> {
+        let n_linear = self.linear_units().count();
+        let mut barriers = self
+            .commands()
+            .filter(|cmd| is_barrier(cmd.optype()) && cmd.linear_inputs().count() == n_linear)
+            .map(|cmd| cmd.node())
+            .collect_vec();
+
+        // Discard the barriers crossed by some wire, until all the remaining
+        // ones are valid cuts.
+        let regions = loop {
+            let regions = Regions::new(self, &barriers);
+            let crossed = regions.crossed_barriers(self);
+            if crossed.is_empty() {
+                break regions;
+            }
+            barriers = barriers
+                .into_iter()
+                .enumerate()
+                .filter(|(i, _)| !crossed.contains(i))
+                .map(|(_, node)| node)
+                .collect();
+        };
+
+        let [input, output] = self.io_nodes();
+        let boundaries = [input]
+            .into_iter()
+            .chain(barriers.iter().copied())
+            .zip(barriers.iter().copied().chain([output]));
+        boundaries
+            .enumerate()
+            .map(|(k, (left, right))| self.extract_region(&regions, k, left, right))
+            .collect()
+    }
+
+    /// Extract the nodes in region `k` as a new circuit, between the `left`
+    /// and `right` boundary nodes.
+    fn extract_region(&self, regions: &Regions, k: usize, left: Node, right: Node) -> Circuit {
+        let hugr = self.hugr();
+        let extension_reqs = self.circuit_signature().extension_reqs;
+        let input_types = hugr
+            .get_optype(left)
+            .dataflow_signature()
+            .map_or_else(|| self.circuit_signature().input, |sig| sig.output);
+        let output_types = hugr
+            .get_optype(right)
+            .dataflow_signature()
+            .map_or_else(|| self.circuit_signature().output, |sig| sig.input);
+        let signature = Signature::new(input_types.clone(), output_types.clone())
+            .with_extension_delta(extension_reqs);
+
+        let mut piece = Hugr::new(OpType::DFG(DFG { signature }));
+        let root = piece.root();
+        let new_input = piece.add_node_with_parent(root, Input::new(input_types));
+        let new_output = piece.add_node_with_parent(root, Output::new(output_types));
+
+        // Copy each node with its descendants, so nested regions such as
+        // boxes and conditionals are kept whole.
+        let nodes = regions.nodes(k);
+        let mut node_map: HashMap<Node, Node> = HashMap::new();
+        for &node in &nodes {
+            let subtree: DescendantsGraph = DescendantsGraph::try_new(hugr, node).unwrap();
+            let new_node = piece.insert_from_view(root, &subtree).new_root;
+            node_map.insert(node, new_node);
+        }
+        node_map.insert(left, new_input);
+        node_map.insert(right, new_output);
+        for node in nodes.into_iter().chain([right]) {
+            for port in hugr.node_inputs(node) {
+                for (src, src_port) in hugr.linked_outputs(node, port) {
+                    piece.connect(node_map[&src], src_port, node_map[&node], port);
+                }
+            }
+        }
+
+        if let Some(meta) = hugr.get_node_metadata(self.parent()) {
+            for (key, value) in meta {
+                if key != METADATA_PHASE || k == 0 {
+                    piece.set_metadata(root, key, value.clone());
+                }
+            }
+        }
+        piece.into()
+    }
+}
+
+/// Returns `true` if the operation is a pytket barrier.
+fn is_barrier(op: &OpType) -> bool {
+    match OpaqueTk1Op::try_from_tket2(op) {
+        Ok(Some(tk1op)) => tk1op.serialised_op().op_type == Tk1OpType::Barrier,
+        _ => false,
+    }
+}
+
+/// An assignment of the nodes of a circuit to the regions between a sequence
+/// of full-width barriers.
+///
+/// Region `k` lies between barrier `k - 1` and barrier `k`, where the input and
+/// output nodes of the circuit act as the outermost boundaries.
+#[derive(Debug, Clone)]
+struct Regions {
+    /// The region of each node that is not a boundary.
+    region: HashMap<Node, usize>,
+    /// The index of each barrier.
+    barriers: HashMap<Node, usize>,
+    /// The nodes of the circuit, in topological order.
+    order: Vec<Node>,
+}
+
+impl Regions {
+    /// Assign each node of the circuit to a region.
+    ///
+    /// Operations on linear units are ordered with respect to every
+    /// full-width barrier. Other operations, such as constant parameters, are
+    /// placed in the earliest region that uses them.
+    fn new(circ: &Circuit<impl HugrView>, barriers: &[Node]) -> Self {
+        let hugr = circ.hugr();
+        let barriers: HashMap<Node, usize> =
+            barriers.iter().enumerate().map(|(i, &n)| (n, i)).collect();
+        let mut region = HashMap::new();
+        let mut order = Vec::new();
+        let mut deferred = Vec::new();
+        let mut current = 0;
+        for cmd in circ.commands() {
+            let node = cmd.node();
+            order.push(node);
+            if let Some(&i) = barriers.get(&node) {
+                current = i + 1;
+            } else if cmd.linear_inputs().next().is_some() || cmd.linear_outputs().next().is_some()
+            {
+                region.insert(node, current);
+            } else {
+                deferred.push(node);
+            }
+        }
+
+        let [input, output] = circ.io_nodes();
+        let n_regions = barriers.len() + 1;
+        for &node in deferred.iter().rev() {
+            let consumers = hugr.output_neighbours(node).filter_map(|next| {
+                if next == output {
+                    Some(n_regions - 1)
+                } else {
+                    barriers.get(&next).or(region.get(&next)).copied()
+                }
+            });
+            let node_region = consumers.min().unwrap_or_else(|| {
+                hugr.input_neighbours(node)
+                    .filter_map(|prev| {
+                        if prev == input {
+                            Some(0)
+                        } else {
+                            barriers
+                                .get(&prev)
+                                .map(|i| i + 1)
+                                .or(region.get(&prev).copied())
+                        }
+                    })
+                    .max()
+                    .unwrap_or(0)
+            });
+            region.insert(node, node_region);
+        }
+
+        Self {
+            region,
+            barriers,
+            order,
+        }
+    }
+
+    /// The indices of the barriers crossed by some wire.
+    fn crossed_barriers(&self, circ: &Circuit<impl HugrView>) -> HashSet<usize> {
+        let hugr = circ.hugr();
+        let [input, output] = circ.io_nodes();
+        let n_regions = self.barriers.len() + 1;
+        // The region a wire is in when leaving or entering a node.
+        let source_region = |node: Node| match self.barriers.get(&node) {
+            Some(i) => i + 1,
+            None if node == input => 0,
+            None => self.region[&node],
+        };
+        let target_region = |node: Node| match self.barriers.get(&node) {
+            Some(&i) => i,
+            None if node == output => n_regions - 1,
+            None => self.region[&node],
+        };
+
+        let mut crossed = HashSet::new();
+        for node in self.order.iter().copied().chain([output]) {
+            let to = target_region(node);
+            for port in hugr.node_inputs(node) {
+                for (src, _) in hugr.linked_outputs(node, port) {
+                    let from = source_region(src);
+                    crossed.extend(from.min(to)..from.max(to));
+                }
+            }
+        }
+        crossed
+    }
+
+    /// The nodes in region `k`, in topological order.
+    fn nodes(&self, k: usize) -> Vec<Node> {
+        self.order
+            .iter()
+            .copied()
+            .filter(|node| self.region.get(node) == Some(&k))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::circuit::simulate::matmul;
+    use crate::circuit::units::LinearUnit;
+    use crate::extension::REGISTRY;
+    use crate::serialize::load_tk1_json_str;
+    use crate::utils::build_simple_circuit;
+    use crate::Tk2Op;
+    use hugr::ops::NamedOp;
+    use rstest::rstest;
+
+    /// A pytket circuit on two qubits, with an optional barrier after the
+    /// first gate.
+    fn circuit(barrier: Option<&str>) -> Circuit {
+        let barrier = match barrier {
+            Some(args) => {
+                let signature = vec![r#""Q""#; args.matches("q").count()].join(", ");
+                format!(
+                    r#"{{"args": [{args}], "op": {{"type": "Barrier", "signature": [{signature}]}}}},"#
+                )
+            }
+            None => String::new(),
+        };
+        load_tk1_json_str(&format!(
+            r#"{{
+            "phase": "0",
+            "bits": [],
+            "qubits": [["q", [0]], ["q", [1]]],
+            "commands": [
+                {{"args": [["q", [0]]], "op": {{"type": "H"}}}},
+                {barrier}
+                {{"args": [["q", [0]], ["q", [1]]], "op": {{"type": "CX"}}}},
+                {{"args": [["q", [1]]], "op": {{"params": ["0.25"], "type": "Rz"}}}}
+            ],
+            "implicit_permutation": [[["q", [0]], ["q", [0]]], [["q", [1]], ["q", [1]]]]
+        }}"#
+        ))
+        .unwrap()
+    }
+
+    fn op_names(circ: &Circuit) -> Vec<String> {
+        circ.operations()
+            .map(|cmd| cmd.optype().name().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn split_full_barrier() {
+        let circ = circuit(Some(r#"["q", [0]], ["q", [1]]"#));
+        let pieces = circ.split_at_barriers();
+        assert_eq!(pieces.len(), 2);
+        for piece in &pieces {
+            piece.hugr().validate(&REGISTRY).unwrap();
+            assert_eq!(piece.qubit_count(), 2);
+        }
+
+        let original = circuit(None);
+        let concatenated = pieces.iter().flat_map(op_names).collect_vec();
+        assert_eq!(concatenated, op_names(&original));
+
+        // Applying the pieces in sequence implements the original unitary.
+        let [first, second] = [&pieces[0], &pieces[1]].map(|c| c.unitary().unwrap());
+        let composed = matmul(&second, &first);
+        let expected = original.unitary().unwrap();
+        for (a, b) in composed.iter().zip(&expected) {
+            assert!((a - b).norm() < 1e-9);
+        }
+    }
+
+    #[rstest]
+    #[case::no_barrier(None)]
+    #[case::partial_barrier(Some(r#"["q", [0]]"#))]
+    fn no_split(#[case] barrier: Option<&str>) {
+        let circ = circuit(barrier);
+        let pieces = circ.split_at_barriers();
+        assert_eq!(pieces.len(), 1);
+        pieces[0].hugr().validate(&REGISTRY).unwrap();
+        assert_eq!(op_names(&pieces[0]), op_names(&circ));
+    }
+
+    #[test]
+    fn split_nested_box() {
+        let mut circ = circuit(Some(r#"["q", [0]], ["q", [1]]"#));
+        let bell = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            Ok(())
+        })
+        .unwrap();
+        let qubits = [LinearUnit::new(0), LinearUnit::new(1)];
+        let boxed = circ.append_box("bell", &bell, &qubits).unwrap();
+        let n_children = circ.hugr().children(boxed).count();
+
+        let pieces = circ.split_at_barriers();
+        assert_eq!(pieces.len(), 2);
+        pieces[1].hugr().validate(&REGISTRY).unwrap();
+        let new_box = pieces[1]
+            .commands()
+            .map(|cmd| cmd.node())
+            .find(|&node| pieces[1].box_name(node) == Some("bell"))
+            .unwrap();
+        assert_eq!(pieces[1].hugr().children(new_box).count(), n_children);
+    }
+}
@@ -0,0 +1,496 @@
+//! Summary statistics of a circuit, for reporting optimisation results.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::mem;
+
+use hugr::extension::prelude::BOOL_T;
+use hugr::ops::{NamedOp, OpType};
+use hugr::{HugrView, Node, Port};
+use serde::{Deserialize, Serialize};
+
+use super::params::{quarter_turns, NumericParams};
+use super::units::LinearUnit;
+use super::Circuit;
+use crate::ops::match_symb_const_op;
+use crate::serialize::pytket::METADATA_PHASE;
+use crate::Tk2Op;
+
+/// A machine-readable summary of a circuit.
+///
+/// Computed by [`Circuit::stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CircuitStats {
+    /// Number of qubit inputs to the circuit.
+    pub qubits: usize,
+    /// Number of boolean inputs to the circuit.
+    pub bits: usize,
+    /// Number of operations in the circuit.
+    pub gates: usize,
+    /// Number of operations acting on two or more qubits.
+    pub two_qubit_gates: usize,
+    /// Number of layers of operations acting on qubits.
+    pub depth: usize,
+    /// Number of non-Clifford `π/4` rotations, see [`Circuit::t_count`].
+    pub t_count: usize,
+    /// Number of operations of each type, indexed by operation name.
+    pub gate_histogram: BTreeMap<String, usize>,
+    /// The global phase of the circuit, as a symbolic expression.
+    pub global_phase: String,
+}
+
+/// Resource estimates of a circuit for fault-tolerant compilation.
+///
+/// Computed by [`Circuit::ft_resources`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FtResources {
+    /// Number of non-Clifford `π/4` rotations, see [`Circuit::t_count`].
+    pub t_count: usize,
+    /// Number of `T` layers: the largest number of `T` gates on any path
+    /// through the circuit.
+    pub t_depth: usize,
+    /// Number of qubit inputs to the circuit.
+    pub qubit_count: usize,
+    /// Number of measurement operations.
+    pub measurement_count: usize,
+}
+
+/// The difference between two [`CircuitStats`].
+///
+/// Computed by [`stats_diff`]. Each field is `after - before`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CircuitStatsDiff {
+    /// Change in the number of qubits.
+    pub qubits: isize,
+    /// Change in the number of bits.
+    pub bits: isize,
+    /// Change in the number of operations.
+    pub gates: isize,
+    /// Change in the number of multi-qubit operations.
+    pub two_qubit_gates: isize,
+    /// Change in the circuit depth.
+    pub depth: isize,
+    /// Change in the `T`-count.
+    pub t_count: isize,
+    /// Change in the number of operations of each type.
+    ///
+    /// Operations whose count did not change are omitted.
+    pub gate_histogram: BTreeMap<String, isize>,
+}
+
+/// Compute the change in statistics between two versions of a circuit.
+pub fn stats_diff(before: &CircuitStats, after: &CircuitStats) -> CircuitStatsDiff {
+    let delta = |b: usize, a: usize| a as isize - b as isize;
+
+    let mut gate_histogram = BTreeMap::new();
+    for name in before
+        .gate_histogram
+        .keys()
+        .chain(after.gate_histogram.keys())
+    {
+        let b = before.gate_histogram.get(name).copied().unwrap_or_default();
+        let a = after.gate_histogram.get(name).copied().unwrap_or_default();
+        if a != b {
+            gate_histogram.insert(name.clone(), delta(b, a));
+        }
+    }
+
+    CircuitStatsDiff {
+        qubits: delta(before.qubits, after.qubits),
+        bits: delta(before.bits, after.bits),
+        gates: delta(before.gates, after.gates),
+        two_qubit_gates: delta(before.two_qubit_gates, after.two_qubit_gates),
+        depth: delta(before.depth, after.depth),
+        t_count: delta(before.t_count, after.t_count),
+        gate_histogram,
+    }
+}
+
+impl<T: HugrView> Circuit<T> {
+    /// Compute summary statistics of the circuit.
+    ///
+    /// Only the top-level operations of the circuit are considered, see
+    /// [`Circuit::operations`].
+    pub fn stats(&self) -> CircuitStats {
+        let mut stats = CircuitStats {
+            qubits: self.qubit_count(),
+            bits: self.units().filter(|(_, _, typ)| typ == &BOOL_T).count(),
+            t_count: self.t_count(),
+            global_phase: self
+                .hugr()
+                .get_metadata(self.parent(), METADATA_PHASE)
+                .and_then(|p| p.as_str())
+                .unwrap_or("0")
+                .to_string(),
+            ..Default::default()
+        };
+
+        for cmd in self.operations() {
+            stats.gates += 1;
+            *stats
+                .gate_histogram
+                .entry(cmd.optype().name().to_string())
+                .or_default() += 1;
+
+            if cmd.input_qubits().count() >= 2 {
+                stats.two_qubit_gates += 1;
+            }
+        }
+        stats.depth = asap_layers(self).len();
+
+        stats
+    }
+
+    /// The `T`-count of the circuit: its number of non-Clifford `π/4`
+    /// rotations.
+    ///
+    /// These are the `T` and `Tdg` gates, and the `Rz` rotations whose numeric
+    /// angle is an odd multiple of `π/4`. This is the count reported by
+    /// [`CircuitStats::t_count`] and [`FtResources::t_count`].
+    pub fn t_count(&self) -> usize {
+        t_gates(self).len()
+    }
+
+    /// Estimate the resources needed to run the circuit fault-tolerantly.
+    ///
+    /// The `T` gates are the ones counted by [`Circuit::t_count`]. The
+    /// `T`-depth only counts these gates, while all other operations
+    /// propagate the `T`-depth between the qubits they act on.
+    pub fn ft_resources(&self) -> FtResources {
+        let t_gates = t_gates(self);
+        // The number of `T` gates before each qubit.
+        let mut qubit_depth: HashMap<LinearUnit, usize> = HashMap::new();
+        let mut t_depth = 0;
+        let mut measurement_count = 0;
+        for cmd in self.operations() {
+            if cmd.optype() == &Tk2Op::Measure.into() {
+                measurement_count += 1;
+            }
+            let qubits: Vec<LinearUnit> = cmd.input_qubits().map(|(qb, _, _)| qb).collect();
+            let mut depth = qubits
+                .iter()
+                .map(|qb| qubit_depth.get(qb).copied().unwrap_or_default())
+                .max()
+                .unwrap_or_default();
+            depth += t_gates.contains(&cmd.node()) as usize;
+            for qb in qubits {
+                qubit_depth.insert(qb, depth);
+            }
+            t_depth = t_depth.max(depth);
+        }
+        FtResources {
+            t_count: t_gates.len(),
+            t_depth,
+            qubit_count: self.qubit_count(),
+            measurement_count,
+        }
+    }
+
+    /// The number of active and idle qubits in each layer of the circuit.
+    ///
+    /// Operations are scheduled as soon as possible, with the same layers
+    /// used to compute [`CircuitStats::depth`]. A qubit is active in a layer
+    /// if an operation acts on it, and idle otherwise. Qubits allocated
+    /// during the circuit count towards the total from the start.
+    pub fn utilisation(&self) -> Vec<(usize, usize)> {
+        let layers = asap_layers(self);
+        let n_qubits = layers
+            .iter()
+            .flatten()
+            .flatten()
+            .map(|qb| qb.index() + 1)
+            .max()
+            .unwrap_or_default()
+            .max(self.qubit_count());
+        layers
+            .into_iter()
+            .map(|layer| {
+                let active = layer.iter().map(Vec::len).sum::<usize>();
+                (active, n_qubits - active)
+            })
+            .collect()
+    }
+
+    /// The number of operations in each layer of the circuit.
+    ///
+    /// Operations acting on qubits are scheduled as soon as possible, with the
+    /// same layers used to compute [`CircuitStats::depth`], and the `d`-th
+    /// entry counts the operations in layer `d`. Long stretches of layers with
+    /// few operations point to serial bottlenecks in the circuit.
+    pub fn depth_histogram(&self) -> Vec<usize> {
+        asap_layers(self).iter().map(Vec::len).collect()
+    }
+
+    /// A rough estimate of the heap memory used by the circuit, in bytes.
+    ///
+    /// This counts the nodes and ports of the underlying HUGR, along with the
+    /// symbolic parameter expressions and the global phase. The estimate is
+    /// not precise, but grows with the size of the circuit.
+    pub fn approx_memory_bytes(&self) -> usize {
+        let hugr = self.hugr();
+        let node_bytes = hugr.nodes().map(|node| {
+            let op = hugr.get_optype(node);
+            let ports = hugr.num_inputs(node) + hugr.num_outputs(node);
+            let expr = match_symb_const_op(op).map_or(0, |expr| expr.len());
+            NODE_BYTES + ports * PORT_BYTES + expr
+        });
+        let phase_bytes = hugr
+            .get_metadata(self.parent(), METADATA_PHASE)
+            .and_then(|p| p.as_str())
+            .map_or(0, str::len);
+        node_bytes.sum::<usize>() + phase_bytes
+    }
+}
+
+/// Estimated memory used by each node in a HUGR, without its ports.
+///
+/// Includes the operation, and the node's entries in the graph and hierarchy.
+const NODE_BYTES: usize = mem::size_of::<OpType>() + 8 * mem::size_of::<Node>();
+
+/// Estimated memory used by each port in a HUGR, including its link.
+const PORT_BYTES: usize = 2 * mem::size_of::<(Node, Port)>();
+
+/// The nodes of the non-Clifford `π/4` rotations of a circuit.
+///
+/// These are the `T` and `Tdg` gates, and the `Rz` rotations whose numeric
+/// angle is an odd multiple of `π/4`.
+pub(crate) fn t_gates(circ: &Circuit<impl HugrView>) -> HashSet<Node> {
+    let mut params = NumericParams::default();
+    circ.commands()
+        .filter(|cmd| {
+            if params.process(cmd).unwrap_or(true) {
+                return false;
+            }
+            match Tk2Op::try_from(cmd.optype()) {
+                Ok(Tk2Op::T | Tk2Op::Tdg) => true,
+                Ok(Tk2Op::RzF64) => params
+                    .inputs(cmd)
+                    .ok()
+                    .and_then(|angles| quarter_turns(angles[0]))
+                    .is_some_and(|k| k % 2 != 0),
+                _ => false,
+            }
+        })
+        .map(|cmd| cmd.node())
+        .collect()
+}
+
+/// Schedule the operations acting on qubits as soon as possible, and return
+/// the qubits acted on by each operation in each layer.
+fn asap_layers(circ: &Circuit<impl HugrView>) -> Vec<Vec<Vec<LinearUnit>>> {
+    // The number of layers before each qubit is free.
+    let mut qubit_depth: HashMap<LinearUnit, usize> = HashMap::new();
+    let mut layers: Vec<Vec<Vec<LinearUnit>>> = Vec::new();
+    for cmd in circ.operations() {
+        let qubits: Vec<LinearUnit> = cmd.input_qubits().map(|(qb, _, _)| qb).collect();
+        if qubits.is_empty() {
+            continue;
+        }
+        let layer = qubits
+            .iter()
+            .map(|qb| qubit_depth.get(qb).copied().unwrap_or_default())
+            .max()
+            .unwrap_or_default();
+        for &qb in &qubits {
+            qubit_depth.insert(qb, layer + 1);
+        }
+        if layers.len() <= layer {
+            layers.resize_with(layer + 1, Vec::new);
+        }
+        layers[layer].push(qubits);
+    }
+    layers
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::extension::REGISTRY;
+    use crate::serialize::load_tk1_json_str;
+    use crate::utils::build_simple_circuit;
+    use hugr::builder::{DFGBuilder, Dataflow, DataflowHugr};
+    use hugr::extension::prelude::QB_T;
+    use hugr::std_extensions::arithmetic::float_types::ConstF64;
+    use hugr::types::Signature;
+    use std::f64::consts::{FRAC_PI_2, FRAC_PI_4};
+    use std::str::FromStr;
+
+    #[test]
+    fn circuit_stats() {
+        let circ = load_tk1_json_str(
+            r#"{
+            "phase": "0.5",
+            "bits": [["c", [0]]],
+            "qubits": [["q", [0]], ["q", [1]], ["q", [2]]],
+            "commands": [
+                {"args": [["q", [0]]], "op": {"type": "H"}},
+                {"args": [["q", [0]], ["q", [1]]], "op": {"type": "CX"}},
+                {"args": [["q", [2]]], "op": {"type": "T"}},
+                {"args": [["q", [1]], ["q", [2]]], "op": {"type": "CX"}},
+                {"args": [["q", [0]]], "op": {"type": "Tdg"}}
+            ],
+            "implicit_permutation": [[["q", [0]], ["q", [0]]], [["q", [1]], ["q", [1]]], [["q", [2]], ["q", [2]]]]
+        }"#,
+        )
+        .unwrap();
+        let stats = circ.stats();
+
+        let histogram = [("CX", 2), ("H", 1), ("T", 1), ("Tdg", 1)]
+            .into_iter()
+            .map(|(op, n)| (Tk2Op::from_str(op).unwrap().exposed_name().to_string(), n))
+            .collect();
+        let expected = CircuitStats {
+            qubits: 3,
+            bits: 1,
+            gates: 5,
+            two_qubit_gates: 2,
+            depth: 3,
+            t_count: 2,
+            gate_histogram: histogram,
+            global_phase: "0.5".to_string(),
+        };
+        assert_eq!(stats, expected);
+
+        let json = serde_json::to_string(&stats).unwrap();
+        let roundtrip: CircuitStats = serde_json::from_str(&json).unwrap();
+        assert_eq!(roundtrip, stats);
+    }
+
+    #[test]
+    fn circuit_stats_diff() {
+        let before = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(Tk2Op::T, [0])?;
+            Ok(())
+        })
+        .unwrap()
+        .stats();
+        let after = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::T, [0])?;
+            Ok(())
+        })
+        .unwrap()
+        .stats();
+
+        let diff = stats_diff(&before, &after);
+        assert_eq!(diff.gates, -2);
+        assert_eq!(diff.two_qubit_gates, -2);
+        assert_eq!(diff.depth, -2);
+        assert_eq!(diff.t_count, 0);
+        assert_eq!(
+            diff.gate_histogram,
+            BTreeMap::from([(Tk2Op::CX.exposed_name().to_string(), -2)])
+        );
+    }
+
+    #[test]
+    fn utilisation() {
+        let circ = build_simple_circuit(3, |circ| {
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(Tk2Op::H, [2])?;
+            circ.append(Tk2Op::T, [2])?;
+            circ.append(Tk2Op::X, [2])?;
+            circ.append(Tk2Op::CX, [1, 2])?;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(circ.utilisation(), [(3, 0), (1, 2), (1, 2), (2, 1)]);
+        assert_eq!(circ.utilisation().len(), circ.stats().depth);
+    }
+
+    #[test]
+    fn depth_histogram() {
+        let parallel = build_simple_circuit(4, |circ| {
+            for qb in 0..4 {
+                circ.append(Tk2Op::H, [qb])?;
+            }
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(parallel.depth_histogram(), [4]);
+
+        let serial = build_simple_circuit(1, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::T, [0])?;
+            circ.append(Tk2Op::X, [0])?;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(serial.depth_histogram(), [1, 1, 1]);
+
+        let circ = build_simple_circuit(3, |circ| {
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(Tk2Op::H, [2])?;
+            circ.append(Tk2Op::CX, [1, 2])?;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(circ.depth_histogram(), [2, 1]);
+    }
+
+    #[test]
+    fn ft_resources() {
+        let circ = build_simple_circuit(3, |circ| {
+            circ.append(Tk2Op::T, [0])?;
+            circ.append(Tk2Op::T, [1])?;
+            circ.append(Tk2Op::H, [2])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(Tk2Op::Tdg, [1])?;
+            circ.append(Tk2Op::CX, [1, 2])?;
+            circ.append(Tk2Op::T, [2])?;
+            circ.append(Tk2Op::T, [0])?;
+            circ.append(Tk2Op::Measure, [0])?;
+            circ.append(Tk2Op::Measure, [2])?;
+            Ok(())
+        })
+        .unwrap();
+        let expected = FtResources {
+            t_count: 5,
+            t_depth: 3,
+            qubit_count: 3,
+            measurement_count: 2,
+        };
+        assert_eq!(circ.ft_resources(), expected);
+        assert_eq!(circ.ft_resources().t_count, circ.stats().t_count);
+    }
+
+    #[test]
+    fn t_count_rotations() {
+        // Only the `Rz` rotations by odd multiples of `π/4` are counted.
+        let mut dfg = DFGBuilder::new(Signature::new_endo(vec![QB_T])).unwrap();
+        let [mut q] = dfg.input_wires_arr();
+        for angle in [FRAC_PI_4, FRAC_PI_2, 3. * FRAC_PI_4] {
+            let angle = dfg.add_load_value(ConstF64::new(angle));
+            [q] = dfg
+                .add_dataflow_op(Tk2Op::RzF64, [q, angle])
+                .unwrap()
+                .outputs_arr();
+        }
+        [q] = dfg.add_dataflow_op(Tk2Op::T, [q]).unwrap().outputs_arr();
+        let circ: Circuit = dfg.finish_hugr_with_outputs([q], &REGISTRY).unwrap().into();
+
+        assert_eq!(circ.t_count(), 3);
+        assert_eq!(circ.stats().t_count, 3);
+        assert_eq!(circ.ft_resources().t_count, 3);
+    }
+
+    #[test]
+    fn approx_memory_bytes() {
+        let small = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::CX, [0, 1])?;
+            Ok(())
+        })
+        .unwrap();
+        let large = build_simple_circuit(4, |circ| {
+            for _ in 0..10 {
+                circ.append(Tk2Op::CX, [0, 1])?;
+                circ.append(Tk2Op::H, [2])?;
+                circ.append(Tk2Op::CX, [2, 3])?;
+            }
+            Ok(())
+        })
+        .unwrap();
+        assert!(small.approx_memory_bytes() > 0);
+        assert!(large.approx_memory_bytes() > small.approx_memory_bytes());
+    }
+}
@@ -0,0 +1,160 @@
+//! Export of stabiliser circuits in the textual format of the Stim simulator.
+//!
+//! Each command is emitted as a single Stim instruction, with qubits
+//! identified by their index in the circuit's input boundary. Measurement
+//! results are recorded by Stim in the order the measurements are emitted.
+//!
+//! Stim: <https://github.com/quantumlib/Stim>.
+
+use std::fmt::Write;
+
+use hugr::ops::NamedOp;
+use hugr::{HugrView, Node};
+use itertools::Itertools;
+use thiserror::Error;
+
+use super::params::NumericParams;
+use super::Circuit;
+use crate::Tk2Op;
+
+/// Emit a Clifford and measurement circuit as a Stim program.
+///
+/// # Errors
+///
+/// Returns an error if the circuit contains a non-Clifford gate, or an
+/// operation without a Stim instruction.
+pub fn to_stim(circ: &Circuit<impl HugrView>) -> Result<String, StimError> {
+    let mut params = NumericParams::default();
+    let mut stim = String::new();
+
+    for cmd in circ.commands() {
+        // Classical parameter computations, only used by non-Clifford gates.
+        if params.process(&cmd).unwrap_or(true) {
+            continue;
+        }
+
+        let optype = cmd.optype();
+        let node = cmd.node();
+        let unsupported = || StimError::Unsupported {
+            op: optype.name().to_string(),
+            node,
+        };
+        let op: Tk2Op = optype.try_into().map_err(|_| unsupported())?;
+        if op.is_quantum() && !op.is_clifford() {
+            return Err(StimError::NonClifford {
+                op: optype.name().to_string(),
+                node,
+            });
+        }
+        let instruction = stim_instruction(op).ok_or_else(unsupported)?;
+        let targets = cmd.input_qubits().map(|(qb, _, _)| qb.index()).join(" ");
+        writeln!(stim, "{instruction} {targets}").unwrap();
+    }
+    Ok(stim)
+}
+
+impl<T: HugrView> Circuit<T> {
+    /// Emit the circuit as a Stim program.
+    ///
+    /// See [`to_stim`] for more details.
+    pub fn to_stim(&self) -> Result<String, StimError> {
+        to_stim(self)
+    }
+}
+
+/// Returns the Stim instruction implementing an operation, up to a global
+/// phase.
+fn stim_instruction(op: Tk2Op) -> Option<&'static str> {
+    let instruction = match op {
+        Tk2Op::H => "H",
+        Tk2Op::S => "S",
+        Tk2Op::Sdg => "S_DAG",
+        Tk2Op::X => "X",
+        Tk2Op::Y => "Y",
+        Tk2Op::Z => "Z",
+        Tk2Op::CX => "CX",
+        Tk2Op::CZ => "CZ",
+        Tk2Op::ZZMax => "SQRT_ZZ",
+        Tk2Op::Measure => "M",
+        Tk2Op::Reset => "R",
+        _ => return None,
+    };
+    Some(instruction)
+}
+
+/// Error type for the Stim export of a circuit.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[non_exhaustive]
+pub enum StimError {
+    /// The circuit contains a non-Clifford gate, which cannot be simulated by
+    /// Stim.
+    #[error("Operation {op} in {node} is not a Clifford gate.")]
+    NonClifford {
+        /// The operation name.
+        op: String,
+        /// The node.
+        node: Node,
+    },
+    /// The operation has no corresponding Stim instruction.
+    #[error("Operation {op} in {node} is not supported by the Stim exporter.")]
+    Unsupported {
+        /// The operation name.
+        op: String,
+        /// The node.
+        node: Node,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::build_simple_circuit;
+    use cool_asserts::assert_matches;
+
+    #[test]
+    fn bell_measure() {
+        let circ = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(Tk2Op::Measure, [0])?;
+            circ.append(Tk2Op::Measure, [1])?;
+            Ok(())
+        })
+        .unwrap();
+        let stim = circ.to_stim().unwrap();
+
+        let lines = stim.lines().collect_vec();
+        assert_eq!(lines[..2], ["H 0", "CX 0 1"]);
+        // The two measurements are independent, so they may be emitted in any order.
+        let mut measurements = lines[2..].to_vec();
+        measurements.sort();
+        assert_eq!(measurements, ["M 0", "M 1"]);
+    }
+
+    #[test]
+    fn clifford_gates() {
+        let circ = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::S, [1])?;
+            circ.append(Tk2Op::Sdg, [1])?;
+            circ.append(Tk2Op::CZ, [1, 0])?;
+            circ.append(Tk2Op::Y, [0])?;
+            circ.append(Tk2Op::Reset, [0])?;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(circ.to_stim().unwrap(), "S 1\nS_DAG 1\nCZ 1 0\nY 0\nR 0\n");
+    }
+
+    #[test]
+    fn non_clifford() {
+        let circ = build_simple_circuit(1, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::T, [0])?;
+            Ok(())
+        })
+        .unwrap();
+        assert_matches!(circ.to_stim(), Err(StimError::NonClifford { op, .. }) => {
+            assert_eq!(op, "quantum.tket2.T");
+        });
+    }
+}
@@ -0,0 +1,212 @@
+//! Free symbols in the parameters of a circuit.
+//!
+//! Symbolic parameters are encoded as [`symbolic_constant_op`] nodes holding
+//! a string expression, and the circuit's global phase is stored as an
+//! expression in the [`METADATA_PHASE`] metadata of the parent node.
+
+use std::collections::BTreeSet;
+
+use hugr::hugr::hugrmut::HugrMut;
+use hugr::{HugrView, Node};
+use hugr_core::hugr::internal::HugrMutInternals;
+
+use crate::ops::{match_symb_const_op, symbolic_constant_op};
+use crate::serialize::pytket::METADATA_PHASE;
+use crate::Circuit;
+
+mod simplify;
+pub(crate) use simplify::eval_param;
+pub use simplify::simplify_param;
+
+/// Named constants that may appear in an expression without being free symbols.
+const CONSTANTS: [&str; 4] = ["pi", "e", "E", "I"];
+
+impl<T: HugrView> Circuit<T> {
+    /// Returns the free symbols appearing in the circuit's symbolic parameters
+    /// and in its global phase.
+    pub fn free_symbols(&self) -> BTreeSet<String> {
+        let mut symbols = BTreeSet::new();
+        let mut collect = |expr: &str| {
+            map_symbols(expr, |sym| {
+                symbols.insert(sym.to_string());
+                sym.to_string()
+            });
+        };
+        for node in self.descendants() {
+            if let Some(expr) = match_symb_const_op(self.hugr().get_optype(node)) {
+                collect(&expr);
+            }
+        }
+        if let Some(phase) = self.global_phase_expr() {
+            collect(phase);
+        }
+        symbols
+    }
+
+    /// Returns an owned copy of the circuit where every free symbol in its
+    /// parameters and global phase has `suffix` appended to its name.
+    ///
+    /// Symbols that already end with `suffix` are left unchanged, so applying
+    /// the same suffix twice is a no-op.
+    pub fn clone_with_param_suffix(&self, suffix: &str) -> Circuit {
+        let rename = |expr: &str| {
+            map_symbols(expr, |sym| match sym.ends_with(suffix) {
+                true => sym.to_string(),
+                false => format!("{sym}{suffix}"),
+            })
+        };
+
+        let mut circ = self.to_owned();
+        let parent = circ.parent();
+        let nodes = circ.descendants().collect::<Vec<_>>();
+        for node in nodes {
+            if let Some(expr) = match_symb_const_op(circ.hugr().get_optype(node)) {
+                circ.hugr_mut()
+                    .replace_op(node, symbolic_constant_op(rename(&expr)))
+                    .expect("Symbolic constant ops have the same signature.");
+            }
+        }
+        if let Some(phase) = circ.global_phase_expr().map(rename) {
+            circ.hugr_mut().set_metadata(parent, METADATA_PHASE, phase);
+        }
+        circ
+    }
+
+    /// All the nodes in the circuit's hierarchy, excluding the parent.
+    fn descendants(&self) -> impl Iterator<Item = Node> + '_ {
+        let mut stack = vec![self.parent()];
+        std::iter::from_fn(move || {
+            let node = stack.pop()?;
+            stack.extend(self.hugr().children(node));
+            Some(node)
+        })
+        .skip(1)
+    }
+
+    /// The global phase expression stored in the circuit metadata, if any.
+    fn global_phase_expr(&self) -> Option<&str> {
+        self.hugr()
+            .get_metadata(self.parent(), METADATA_PHASE)
+            .and_then(|p| p.as_str())
+    }
+}
+
+/// Rebuild an expression, replacing each free symbol by the result of `f`.
+///
+/// Identifiers followed by an opening parenthesis are function names, and
+/// are left untouched along with the named constants in [`CONSTANTS`].
+/// Numeric literals, including exponents such as `1e-3`, are never symbols.
+fn map_symbols(expr: &str, mut f: impl FnMut(&str) -> String) -> String {
+    let mut result = String::with_capacity(expr.len());
+    let mut rest = expr;
+    while let Some(c) = rest.chars().next() {
+        let len = if c.is_ascii_digit() || c == '.' {
+            numeric_literal_len(rest)
+        } else if c.is_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let ident = &rest[..len];
+            let is_function = rest[len..].trim_start().starts_with('(');
+            if !is_function && !CONSTANTS.contains(&ident) {
+                result.push_str(&f(ident));
+                rest = &rest[len..];
+                continue;
+            }
+            len
+        } else {
+            c.len_utf8()
+        };
+        result.push_str(&rest[..len]);
+        rest = &rest[len..];
+    }
+    result
+}
+
+/// Length of the numeric literal at the start of `s`.
+fn numeric_literal_len(s: &str) -> usize {
+    let bytes = s.as_bytes();
+    let mut len = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    if matches!(bytes.get(len), Some(b'e' | b'E')) {
+        let mut exp = len + 1;
+        if matches!(bytes.get(exp), Some(b'+' | b'-')) {
+            exp += 1;
+        }
+        if bytes.get(exp).is_some_and(u8::is_ascii_digit) {
+            len = exp
+                + s[exp..]
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(s.len() - exp);
+        }
+    }
+    len
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::extension::REGISTRY;
+    use crate::Tk2Op;
+    use hugr::builder::{DFGBuilder, Dataflow, DataflowHugr};
+    use hugr::extension::prelude::QB_T;
+    use hugr::type_row;
+    use hugr::types::Signature;
+    use rstest::rstest;
+
+    /// A single-qubit circuit with two symbolic rotations and a symbolic phase.
+    fn ansatz_layer() -> Circuit {
+        let mut dfg = DFGBuilder::new(Signature::new_endo(type_row![QB_T])).unwrap();
+        let [mut q] = dfg.input_wires_arr();
+        for expr in ["theta", "2*phi + 1e-3"] {
+            let [angle] = dfg
+                .add_dataflow_op(symbolic_constant_op(expr.to_string()), [])
+                .unwrap()
+                .outputs_arr();
+            [q] = dfg
+                .add_dataflow_op(Tk2Op::RzF64, [q, angle])
+                .unwrap()
+                .outputs_arr();
+        }
+        let mut hugr = dfg.finish_hugr_with_outputs([q], &REGISTRY).unwrap();
+        let root = hugr.root();
+        hugr.set_metadata(root, METADATA_PHASE, "sin(theta)/pi");
+        hugr.into()
+    }
+
+    #[rstest]
+    #[case("theta", "theta_0")]
+    #[case("2*theta + phi_0", "2*theta_0 + phi_0")]
+    #[case("cos(a) * pi + 1.5e-3", "cos(a_0) * pi + 1.5e-3")]
+    #[case("0.5", "0.5")]
+    fn rename_expr(#[case] expr: &str, #[case] expected: &str) {
+        let renamed = map_symbols(expr, |sym| match sym.ends_with("_0") {
+            true => sym.to_string(),
+            false => format!("{sym}_0"),
+        });
+        assert_eq!(renamed, expected);
+    }
+
+    #[test]
+    fn param_suffix() {
+        let circ = ansatz_layer();
+        assert_eq!(
+            circ.free_symbols(),
+            BTreeSet::from(["phi".to_string(), "theta".to_string()])
+        );
+
+        let circ_0 = circ.clone_with_param_suffix("_0");
+        let circ_1 = circ.clone_with_param_suffix("_1");
+        assert_eq!(
+            circ_0.free_symbols(),
+            BTreeSet::from(["phi_0".to_string(), "theta_0".to_string()])
+        );
+        assert!(circ_0.free_symbols().is_disjoint(&circ_1.free_symbols()));
+        assert_eq!(circ_0.global_phase_expr(), Some("sin(theta_0)/pi"));
+
+        // Re-applying the suffix is a no-op.
+        let circ_00 = circ_0.clone_with_param_suffix("_0");
+        assert_eq!(circ_00.free_symbols(), circ_0.free_symbols());
+    }
+}
@@ -0,0 +1,353 @@
+//! Algebraic simplification of symbolic parameter expressions.
+
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+
+use hugr::hugr::hugrmut::HugrMut;
+use hugr::ops::{Const, LoadConstant, Value};
+use hugr::std_extensions::arithmetic::float_types::{ConstF64, FLOAT64_TYPE};
+
+use super::numeric_literal_len;
+use crate::ops::{match_symb_const_op, symbolic_constant_op};
+use crate::serialize::pytket::METADATA_PHASE;
+use crate::Circuit;
+
+/// Tolerance below which coefficients are considered zero.
+const EPSILON: f64 = 1e-12;
+
+/// Simplify a symbolic parameter expression.
+///
+/// Expressions built from numbers, symbols, `+`, `-`, `*`, `/` and
+/// parentheses are expanded into a sum of products of symbols, with like
+/// terms collected. A common integer factor of all the terms is pulled out,
+/// constant expressions are evaluated, and rational coefficients are written
+/// as fractions. For example `a + a` becomes `2*a`, `2*a + 2*b` becomes
+/// `2*(a + b)`, `0.5*a` becomes `a/2` and `1.0/2.0` becomes `1/2`.
+///
+/// Expressions with function calls, powers, or divisions by a symbolic
+/// value are returned unchanged.
+pub fn simplify_param(expr: &str) -> String {
+    match Parser::new(expr).parse() {
+        Some(poly) => format_poly(&poly),
+        None => expr.to_string(),
+    }
+}
+
+/// Evaluate an expression with [`simplify_param`]'s rules, if it simplifies
+/// to a number.
+pub(crate) fn eval_param(expr: &str) -> Option<f64> {
+    constant(&Parser::new(expr).parse()?)
+}
+
+impl<T: HugrMut> Circuit<T> {
+    /// Simplify every symbolic parameter of the circuit, and its global phase,
+    /// with [`simplify_param`].
+    ///
+    /// Parameters that simplify to a number are replaced with numeric
+    /// constants, converting them from half-turns to radians, so that passes
+    /// acting on numeric angles can use them.
+    pub fn simplify_params(&mut self) {
+        let nodes = self
+            .descendants()
+            .filter_map(|node| Some((node, match_symb_const_op(self.hugr().get_optype(node))?)))
+            .collect::<Vec<_>>();
+        let hugr = self.hugr_mut();
+        for (node, expr) in nodes {
+            let simplified = simplify_param(&expr);
+            if let Some(half_turns) = eval_param(&expr) {
+                let parent = hugr.get_parent(node).unwrap();
+                let value = Value::extension(ConstF64::new(half_turns * PI));
+                let konst = hugr.add_node_with_parent(parent, Const::new(value));
+                let load = hugr.add_node_with_parent(
+                    parent,
+                    LoadConstant {
+                        datatype: FLOAT64_TYPE,
+                    },
+                );
+                hugr.connect(konst, 0, load, 0);
+                let targets = hugr.linked_inputs(node, 0).collect::<Vec<_>>();
+                for (target, port) in targets {
+                    hugr.disconnect(target, port);
+                    hugr.connect(load, 0, target, port);
+                }
+                hugr.remove_node(node);
+            } else if simplified != expr {
+                hugr.replace_op(node, symbolic_constant_op(simplified))
+                    .expect("Symbolic constant ops have the same signature.");
+            }
+        }
+
+        let phase = self.global_phase_expr().map(|phase| {
+            eval_param(phase).map_or_else(|| simplify_param(phase), |p| p.to_string())
+        });
+        if let Some(phase) = phase {
+            let parent = self.parent();
+            self.hugr_mut().set_metadata(parent, METADATA_PHASE, phase);
+        }
+    }
+}
+
+/// A polynomial, mapping each monomial to its coefficient. Monomials are
+/// sorted lists of symbols, and the empty monomial is the constant term.
+type Poly = BTreeMap<Vec<String>, f64>;
+
+/// Add `coeff` times `monomial` to a polynomial.
+fn add_term(poly: &mut Poly, monomial: Vec<String>, coeff: f64) {
+    *poly.entry(monomial).or_default() += coeff;
+}
+
+fn add(mut a: Poly, b: Poly, sign: f64) -> Poly {
+    for (monomial, coeff) in b {
+        add_term(&mut a, monomial, sign * coeff);
+    }
+    a
+}
+
+fn mul(a: &Poly, b: &Poly) -> Poly {
+    let mut product = Poly::new();
+    for (m1, c1) in a {
+        for (m2, c2) in b {
+            let mut monomial = [m1.as_slice(), m2.as_slice()].concat();
+            monomial.sort();
+            add_term(&mut product, monomial, c1 * c2);
+        }
+    }
+    product
+}
+
+/// The value of a polynomial, if it is a constant.
+fn constant(poly: &Poly) -> Option<f64> {
+    poly.iter()
+        .filter(|(_, c)| c.abs() > EPSILON)
+        .try_fold(0., |_, (m, &c)| m.is_empty().then_some(c))
+}
+
+/// A recursive descent parser for arithmetic expressions.
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn new(expr: &'a str) -> Self {
+        Self { rest: expr }
+    }
+
+    /// Parse the whole expression.
+    fn parse(mut self) -> Option<Poly> {
+        let poly = self.expr()?;
+        self.rest.trim().is_empty().then_some(poly)
+    }
+
+    /// Consume the next character if it is one of `chars`.
+    fn eat(&mut self, chars: &[char]) -> Option<char> {
+        self.rest = self.rest.trim_start();
+        let c = self.rest.chars().next().filter(|c| chars.contains(c))?;
+        // Reject the `**` power operator.
+        if c == '*' && self.rest[1..].starts_with('*') {
+            return None;
+        }
+        self.rest = &self.rest[1..];
+        Some(c)
+    }
+
+    fn expr(&mut self) -> Option<Poly> {
+        let mut poly = self.term()?;
+        while let Some(op) = self.eat(&['+', '-']) {
+            let sign = if op == '+' { 1. } else { -1. };
+            poly = add(poly, self.term()?, sign);
+        }
+        Some(poly)
+    }
+
+    fn term(&mut self) -> Option<Poly> {
+        let mut poly = self.factor()?;
+        while let Some(op) = self.eat(&['*', '/']) {
+            let rhs = self.factor()?;
+            poly = match op {
+                '*' => mul(&poly, &rhs),
+                _ => {
+                    let divisor = constant(&rhs).filter(|d| d.abs() > EPSILON)?;
+                    poly.into_iter().map(|(m, c)| (m, c / divisor)).collect()
+                }
+            };
+        }
+        Some(poly)
+    }
+
+    fn factor(&mut self) -> Option<Poly> {
+        if let Some(op) = self.eat(&['+', '-']) {
+            let sign = if op == '+' { 1. } else { -1. };
+            return Some(add(Poly::new(), self.factor()?, sign));
+        }
+        if self.eat(&['(']).is_some() {
+            let poly = self.expr()?;
+            self.eat(&[')'])?;
+            return Some(poly);
+        }
+        self.rest = self.rest.trim_start();
+        let c = self.rest.chars().next()?;
+        let len = if c.is_ascii_digit() || c == '.' {
+            numeric_literal_len(self.rest)
+        } else if c.is_alphabetic() || c == '_' {
+            self.rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(self.rest.len())
+        } else {
+            return None;
+        };
+        let (token, rest) = self.rest.split_at(len);
+        self.rest = rest;
+        let mut poly = Poly::new();
+        match token.parse::<f64>() {
+            Ok(value) => add_term(&mut poly, vec![], value),
+            // Function calls are not supported.
+            Err(_) if rest.trim_start().starts_with('(') => return None,
+            Err(_) => add_term(&mut poly, vec![token.to_string()], 1.),
+        }
+        Some(poly)
+    }
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    match b {
+        0 => a,
+        _ => gcd(b, a % b),
+    }
+}
+
+/// Format a polynomial, with the constant term last and a common integer
+/// factor pulled out.
+fn format_poly(poly: &Poly) -> String {
+    let terms = poly
+        .iter()
+        .filter(|(_, c)| c.abs() > EPSILON)
+        .map(|(m, &c)| (m.join("*"), c))
+        .collect::<Vec<_>>();
+    let (constants, mut terms): (Vec<_>, Vec<_>) =
+        terms.into_iter().partition(|(m, _)| m.is_empty());
+    terms.extend(constants);
+
+    let is_integer = |c: f64| c.fract() == 0. && c.abs() < u64::MAX as f64;
+    let factor = match terms.len() > 1 && terms.iter().all(|&(_, c)| is_integer(c)) {
+        true => terms.iter().fold(0, |g, &(_, c)| gcd(g, c.abs() as u64)),
+        false => 1,
+    };
+    if factor > 1 {
+        let inner = terms
+            .iter()
+            .map(|(m, c)| (m.clone(), c / factor as f64))
+            .collect::<Vec<_>>();
+        return format!("{factor}*({})", format_terms(&inner));
+    }
+    format_terms(&terms)
+}
+
+/// The largest denominator of a coefficient written as a fraction.
+const MAX_DENOMINATOR: u32 = 1000;
+
+/// Write a non-negative coefficient as a fraction `p/q` with a small
+/// denominator, if it is rational.
+fn as_fraction(c: f64) -> Option<(f64, u32)> {
+    (1..=MAX_DENOMINATOR).find_map(|q| {
+        let p = c * q as f64;
+        ((p - p.round()).abs() < EPSILON * q as f64).then_some((p.round(), q))
+    })
+}
+
+/// Format a sum of terms.
+fn format_terms(terms: &[(String, f64)]) -> String {
+    if terms.is_empty() {
+        return "0".to_string();
+    }
+    let mut result = String::new();
+    for (i, (monomial, coeff)) in terms.iter().enumerate() {
+        let c = coeff.abs();
+        let term = match (monomial.is_empty(), as_fraction(c)) {
+            (true, Some((p, 1))) => p.to_string(),
+            (true, Some((p, q))) => format!("{p}/{q}"),
+            (true, None) => c.to_string(),
+            (false, Some((1., 1))) => monomial.clone(),
+            (false, Some((p, 1))) => format!("{p}*{monomial}"),
+            (false, Some((1., q))) => format!("{monomial}/{q}"),
+            (false, Some((p, q))) => format!("{p}*{monomial}/{q}"),
+            (false, None) => format!("{c}*{monomial}"),
+        };
+        match (i, coeff.is_sign_negative()) {
+            (0, true) => result.push_str(&format!("-{term}")),
+            (0, false) => result.push_str(&term),
+            (_, true) => result.push_str(&format!(" - {term}")),
+            (_, false) => result.push_str(&format!(" + {term}")),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::extension::REGISTRY;
+    use crate::passes::{cleanup, CleanupOptions};
+    use crate::Tk2Op;
+    use hugr::builder::{DFGBuilder, Dataflow, DataflowHugr};
+    use hugr::extension::prelude::QB_T;
+    use hugr::type_row;
+    use hugr::types::Signature;
+    use hugr::HugrView;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("a + a", "2*a")]
+    #[case("2*a + 2*b", "2*(a + b)")]
+    #[case("1.0/2.0", "1/2")]
+    #[case("0.1*a + 0.25", "a/10 + 1/4")]
+    #[case("2*a/3", "2*a/3")]
+    #[case("0.1234567891*a", "0.1234567891*a")]
+    #[case("b*a - a*b + 1", "1")]
+    #[case("-(x - 3*y) / 2", "-x/2 + 3*y/2")]
+    #[case("a - a", "0")]
+    #[case("sin(a) + sin(a)", "sin(a) + sin(a)")]
+    #[case("a**2", "a**2")]
+    #[case("1/a", "1/a")]
+    fn simplify(#[case] expr: &str, #[case] expected: &str) {
+        assert_eq!(simplify_param(expr), expected);
+    }
+
+    #[test]
+    fn simplify_circuit() {
+        // `Rz(0.25 + 0.25) Rz(1.5)` is a full turn once the first angle is
+        // evaluated. The second rotation is numeric, in radians.
+        let mut dfg = DFGBuilder::new(Signature::new_endo(type_row![QB_T])).unwrap();
+        let [q] = dfg.input_wires_arr();
+        let [symbolic] = dfg
+            .add_dataflow_op(symbolic_constant_op("0.25 + 0.25".to_string()), [])
+            .unwrap()
+            .outputs_arr();
+        let numeric = dfg.add_load_value(ConstF64::new(1.5 * PI));
+        let [q] = dfg
+            .add_dataflow_op(Tk2Op::RzF64, [q, symbolic])
+            .unwrap()
+            .outputs_arr();
+        let [q] = dfg
+            .add_dataflow_op(Tk2Op::RzF64, [q, numeric])
+            .unwrap()
+            .outputs_arr();
+        let mut hugr = dfg.finish_hugr_with_outputs([q], &REGISTRY).unwrap();
+        let root = hugr.root();
+        hugr.set_metadata(root, METADATA_PHASE, "a + a");
+        let mut circ: Circuit = hugr.into();
+
+        let rotations = |circ: &Circuit| {
+            let (cleaned, _) = cleanup(circ, CleanupOptions::default()).unwrap();
+            cleaned
+                .operations()
+                .filter(|cmd| cmd.optype() == &Tk2Op::RzF64.into())
+                .count()
+        };
+        assert_eq!(rotations(&circ), 2);
+
+        circ.simplify_params();
+        circ.hugr().validate(&REGISTRY).unwrap();
+        assert_eq!(circ.global_phase_expr(), Some("2*a"));
+        assert_eq!(rotations(&circ), 0);
+    }
+}
@@ -0,0 +1,159 @@
+//! Synthesis of circuits from compact descriptions of their action.
+
+mod kak;
+mod one_qubit;
+mod pauli;
+pub(crate) use kak::{euler_gates, kak_gates};
+pub use kak::{kak, SynthError};
+pub use one_qubit::one_qubit;
+pub use pauli::{from_pauli_rotations, PauliString};
+
+use hugr::builder::{DFGBuilder, Dataflow, DataflowHugr};
+use hugr::extension::prelude::QB_T;
+use hugr::types::Signature;
+use thiserror::Error;
+
+use super::Circuit;
+use crate::extension::REGISTRY;
+use crate::Tk2Op;
+
+/// Synthesise a `CX`-only circuit implementing a linear reversible function.
+///
+/// The function is given by an invertible `n × n` parity matrix over GF(2),
+/// as a list of rows. The circuit maps each basis state `x` to `M x`, where
+/// the bit `x[i]` is the state of qubit `i`. That is, row `i` of the matrix
+/// lists the input qubits whose parity ends up on qubit `i`.
+///
+/// The circuit is obtained by Gaussian elimination, using at most `n²` `CX`
+/// gates.
+///
+/// # Errors
+///
+/// Returns an error if the matrix is not square or not invertible.
+pub fn cnot_from_matrix(matrix: &[Vec<bool>]) -> Result<Circuit, CnotSynthError> {
+    let n = matrix.len();
+    if let Some(row) = matrix.iter().position(|row| row.len() != n) {
+        return Err(CnotSynthError::NotSquare { n_rows: n, row });
+    }
+
+    // Reduce the matrix to the identity with row additions, recording each
+    // `row[target] ^= row[control]` as a `CX(control, target)`.
+    let mut m = matrix.to_vec();
+    let mut row_ops = Vec::new();
+    let mut add_row = |m: &mut [Vec<bool>], control: usize, target: usize| {
+        let control_row = m[control].clone();
+        for (bit, control_bit) in m[target].iter_mut().zip(control_row) {
+            *bit ^= control_bit;
+        }
+        row_ops.push((control, target));
+    };
+    for col in 0..n {
+        if !m[col][col] {
+            let pivot = (col + 1..n)
+                .find(|&row| m[row][col])
+                .ok_or(CnotSynthError::NotInvertible)?;
+            add_row(&mut m, pivot, col);
+        }
+        for row in 0..n {
+            if row != col && m[row][col] {
+                add_row(&mut m, col, row);
+            }
+        }
+    }
+
+    // The eliminations satisfy `E_k ⋯ E_1 M = I`, so `M = E_1 ⋯ E_k` and the
+    // row operations are applied to the state in reverse order.
+    let qb_row = vec![QB_T; n];
+    let mut dfg = DFGBuilder::new(Signature::new_endo(qb_row)).unwrap();
+    let qbs = dfg.input_wires();
+    let mut circ = dfg.as_circuit(qbs);
+    for (control, target) in row_ops.into_iter().rev() {
+        circ.append(Tk2Op::CX, [control, target]).unwrap();
+    }
+    let qbs = circ.finish();
+    Ok(dfg.finish_hugr_with_outputs(qbs, &REGISTRY).unwrap().into())
+}
+
+/// Error type for the synthesis of `CX` circuits.
+#[derive(Debug, Clone, PartialEq, Error)]
+#[non_exhaustive]
+pub enum CnotSynthError {
+    /// The parity matrix is not square.
+    #[error("Row {row} of the parity matrix does not have {n_rows} columns.")]
+    NotSquare {
+        /// The number of rows in the matrix.
+        n_rows: usize,
+        /// The first row with a different length.
+        row: usize,
+    },
+    /// The parity matrix is singular, so it does not describe a reversible
+    /// function.
+    #[error("The parity matrix is not invertible.")]
+    NotInvertible,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cool_asserts::assert_matches;
+    use itertools::Itertools;
+    use rstest::rstest;
+
+    /// Parse a parity matrix from rows of `0` and `1` characters.
+    fn matrix(rows: &[&str]) -> Vec<Vec<bool>> {
+        rows.iter()
+            .map(|row| row.chars().map(|c| c == '1').collect())
+            .collect()
+    }
+
+    /// Apply a `CX` circuit to a classical basis state.
+    fn apply(circ: &Circuit, mut bits: Vec<bool>) -> Vec<bool> {
+        for cmd in circ.commands() {
+            assert_eq!(cmd.optype(), &Tk2Op::CX.into());
+            let [control, target] = cmd
+                .input_qubits()
+                .map(|(qb, _, _)| qb.index())
+                .collect_vec()
+                .try_into()
+                .unwrap();
+            bits[target] ^= bits[control];
+        }
+        bits
+    }
+
+    #[rstest]
+    #[case::identity(&["100", "010", "001"])]
+    #[case::cx(&["10", "11"])]
+    #[case::swap(&["01", "10"])]
+    #[case::cycle(&["0010", "1000", "0100", "0001"])]
+    #[case::dense(&["1101", "0111", "1011", "0101"])]
+    fn synthesise(#[case] rows: &[&str]) {
+        let m = matrix(rows);
+        let n = m.len();
+        let circ = cnot_from_matrix(&m).unwrap();
+        circ.hugr().validate(&REGISTRY).unwrap();
+        assert_eq!(circ.qubit_count(), n);
+        assert!(circ.commands().count() <= n * n);
+
+        for x in 0..1 << n {
+            let bits = (0..n).map(|i| (x >> i) & 1 == 1).collect_vec();
+            let expected = m
+                .iter()
+                .map(|row| row.iter().zip(&bits).filter(|(&a, &b)| a && b).count() % 2 == 1)
+                .collect_vec();
+            assert_eq!(apply(&circ, bits), expected);
+        }
+    }
+
+    #[test]
+    fn invalid_matrix() {
+        assert_matches!(
+            cnot_from_matrix(&matrix(&["11", "11"])),
+            Err(CnotSynthError::NotInvertible)
+        );
+        assert_matches!(
+            cnot_from_matrix(&matrix(&["10", "1"])),
+            Err(CnotSynthError::NotSquare { n_rows: 2, row: 1 })
+        );
+    }
+}
@@ -0,0 +1,587 @@
+//! Synthesis of two-qubit unitaries using the KAK decomposition.
+//!
+//! Any two-qubit unitary can be written as `(A1 ⊗ B1) · N(a, b, c) · (A2 ⊗
+//! B2)`, where the single-qubit gates are local and the interaction
+//! `N(a, b, c) = exp(i(a XX + b YY + c ZZ))` requires at most three `CX`
+//! gates. In the magic basis, local gates are real orthogonal matrices and
+//! the interaction is diagonal, so the decomposition reduces to the
+//! simultaneous diagonalisation of the real and imaginary parts of a
+//! symmetric unitary matrix.
+
+use std::f64::consts::{FRAC_1_SQRT_2, FRAC_PI_2, FRAC_PI_4, PI};
+
+use hugr::builder::{DFGBuilder, Dataflow, DataflowHugr};
+use hugr::extension::prelude::QB_T;
+use hugr::hugr::hugrmut::HugrMut;
+use hugr::std_extensions::arithmetic::float_types::ConstF64;
+use hugr::types::Signature;
+use itertools::Itertools;
+use num_complex::Complex64;
+use thiserror::Error;
+
+use crate::circuit::simulate::{diagonal, gate_matrix, matmul};
+use crate::circuit::Circuit;
+use crate::extension::REGISTRY;
+use crate::serialize::pytket::METADATA_PHASE;
+use crate::Tk2Op;
+
+/// Numerical tolerance for the synthesis.
+const EPS: f64 = 1e-9;
+
+/// A gate of the synthesised circuit, with its qubits and rotation angle.
+pub(crate) type Gate = (Tk2Op, &'static [usize], Option<f64>);
+
+/// Synthesise a circuit implementing a two-qubit unitary.
+///
+/// The unitary is a 4×4 matrix in row-major order, with basis states indexed
+/// in big-endian order as in [`Circuit::unitary`]. The circuit uses the
+/// minimal number of `CX` gates for the unitary, at most three, along with
+/// single-qubit rotations. The global phase of the unitary is stored in the
+/// circuit.
+///
+/// # Errors
+///
+/// Returns an error if the matrix is not 4×4, if it is not unitary, or if
+/// its numerical decomposition fails.
+pub fn kak(u: &[Complex64]) -> Result<Circuit, SynthError> {
+    let (gates, phase) = kak_gates(u)?;
+    let mut circ = build_circuit(&gates);
+    if phase.abs() > EPS {
+        let parent = circ.parent();
+        circ.hugr_mut()
+            .set_metadata(parent, METADATA_PHASE, phase.to_string());
+    }
+    Ok(circ)
+}
+
+/// The gates of the circuit synthesised by [`kak`], along with its global
+/// phase in half-turns.
+pub(crate) fn kak_gates(u: &[Complex64]) -> Result<(Vec<Gate>, f64), SynthError> {
+    if u.len() != 16 {
+        return Err(SynthError::InvalidShape {
+            dim: 4,
+            len: u.len(),
+        });
+    }
+    let identity = diagonal(&[one(); 4]);
+    if !approx_eq(&matmul(u, &dagger(u)), &identity) {
+        return Err(SynthError::NotUnitary);
+    }
+
+    // The decomposition of the target, normalised to SU(4), in the magic
+    // basis.
+    let u_su = special_unitary(u);
+    let target = MagicDecomposition::new(&u_su)?;
+
+    // Build a circuit with the same interaction as the target, and decompose
+    // its unitary so that it shares the interaction term of the target.
+    let template = interaction_gates(target.coordinates());
+    let template_u = special_unitary(&gates_unitary(&template));
+    // The normalisation to SU(4) is only defined up to a power of `i`.
+    let aligned = [one(), Complex64::i()]
+        .into_iter()
+        .find_map(|scale| {
+            let scaled = template_u.iter().map(|&x| x * scale).collect_vec();
+            MagicDecomposition::new(&scaled).ok()?.aligned_to(&target)
+        })
+        .ok_or(SynthError::DecompositionFailed)?;
+
+    // target = (B K1 K1'^T B†) · template · (B P' P^T B†)
+    let magic = magic_basis();
+    let to_comp = |m: &[Complex64]| matmul(&magic, &matmul(m, &dagger(&magic)));
+    let after = to_comp(&matmul(&target.k1(), &transpose(&aligned.k1())));
+    let before = to_comp(&matmul(&aligned.p, &transpose(&target.p)));
+
+    let mut gates = Vec::new();
+    let (before_0, before_1) = kron_factor(&before);
+    gates.extend(euler_gates(&before_0, &[0]));
+    gates.extend(euler_gates(&before_1, &[1]));
+    gates.extend(template);
+    let (after_0, after_1) = kron_factor(&after);
+    gates.extend(euler_gates(&after_0, &[0]));
+    gates.extend(euler_gates(&after_1, &[1]));
+    gates.retain(|(_, _, angle)| !angle.is_some_and(is_trivial_rotation));
+
+    // Recover the global phase of the target.
+    let synth_u = gates_unitary(&gates);
+    let overlap: Complex64 = synth_u.iter().zip(u).map(|(s, t)| s.conj() * t).sum();
+    let phase = overlap.arg() / PI;
+    Ok((gates, phase))
+}
+
+/// The decomposition `U = K1 · D · Pᵀ` of a special unitary matrix in the
+/// magic basis, where `K1` and `P` are real orthogonal with unit
+/// determinant, and `D` is diagonal.
+#[derive(Debug, Clone)]
+struct MagicDecomposition {
+    /// The matrix in the magic basis.
+    u: Vec<Complex64>,
+    /// The orthogonal matrix `P`.
+    p: Vec<Complex64>,
+    /// The diagonal of `D`.
+    d: [Complex64; 4],
+}
+
+impl MagicDecomposition {
+    /// Decompose a 4×4 special unitary matrix.
+    ///
+    /// Returns an error if the diagonalisation does not converge to the
+    /// numerical tolerance.
+    fn new(u_su: &[Complex64]) -> Result<Self, SynthError> {
+        let magic = magic_basis();
+        let u = matmul(&dagger(&magic), &matmul(u_su, &magic));
+
+        // `Uᵀ U` is symmetric and unitary, so its real and imaginary parts
+        // are commuting real symmetric matrices. They are diagonalised by the
+        // eigenvectors of a generic linear combination.
+        let m = matmul(&transpose(&u), &u);
+        let (p, d2) = [0.31, 1.37, 2.53, 0.79]
+            .into_iter()
+            .find_map(|r| {
+                let combination: Vec<f64> = m.iter().map(|x| x.re + r * x.im).collect();
+                let p = jacobi_eigenvectors(&combination);
+                let p = p.iter().map(|&x| Complex64::new(x, 0.)).collect_vec();
+                let d = matmul(&transpose(&p), &matmul(&m, &p));
+                let off_diag = (0..16)
+                    .filter(|k| k / 4 != k % 4)
+                    .all(|k| d[k].norm() < EPS);
+                off_diag.then(|| (p, [d[0], d[5], d[10], d[15]]))
+            })
+            .ok_or(SynthError::DecompositionFailed)?;
+
+        let mut d = d2.map(|x| x.sqrt());
+        if d.into_iter().product::<Complex64>().re < 0. {
+            d[0] = -d[0];
+        }
+        let mut decomposition = Self { u, p, d };
+        decomposition.fix_determinant();
+        Ok(decomposition)
+    }
+
+    /// The orthogonal matrix `K1 = U · P · D⁻¹`.
+    fn k1(&self) -> Vec<Complex64> {
+        let d_inv = diagonal(&self.d.map(|x| one() / x));
+        matmul(&self.u, &matmul(&self.p, &d_inv))
+    }
+
+    /// Negate a column of `P` if needed so that its determinant is one.
+    fn fix_determinant(&mut self) {
+        if det(&self.p).re < 0. {
+            for row in 0..4 {
+                self.p[row * 4] = -self.p[row * 4];
+            }
+        }
+    }
+
+    /// The interaction coefficients `(a, b, c)` of the decomposition.
+    fn coordinates(&self) -> [f64; 3] {
+        let magic = magic_basis();
+        let angles = self.d.map(|x| x.arg());
+        [Tk2Op::X, Tk2Op::Y, Tk2Op::Z].map(|pauli| {
+            let pp = matmul(&dagger(&magic), &matmul(&pauli_pair(pauli), &magic));
+            (0..4).map(|k| angles[k] * pp[k * 5].re).sum::<f64>() / 4.
+        })
+    }
+
+    /// Reorder and rescale the diagonal of this decomposition to match the
+    /// diagonal of `other`.
+    ///
+    /// Returns `None` if the interactions of the two decompositions are not
+    /// equivalent.
+    fn aligned_to(&self, other: &Self) -> Option<Self> {
+        let perm = (0..4).permutations(4).find(|perm| {
+            (0..4).all(|k| {
+                let ratio = other.d[k] / self.d[perm[k]];
+                (ratio.im.abs() < EPS) && (ratio.re.abs() - 1.).abs() < EPS
+            })
+        })?;
+        let mut p = vec![Complex64::new(0., 0.); 16];
+        for (row, k) in (0..4).cartesian_product(0..4) {
+            p[row * 4 + k] = self.p[row * 4 + perm[k]];
+        }
+        let mut aligned = Self {
+            u: self.u.clone(),
+            p,
+            d: other.d,
+        };
+        aligned.fix_determinant();
+        Some(aligned)
+    }
+}
+
+/// The gates implementing an interaction `exp(i(a XX + b YY + c ZZ))`, up to
+/// single-qubit gates, using as few `CX` gates as possible.
+fn interaction_gates(coordinates: [f64; 3]) -> Vec<Gate> {
+    // Shifting a coefficient by π/2 only adds a local Pauli gate.
+    let [a, b, c] = coordinates.map(|x| x - (x / FRAC_PI_2).round() * FRAC_PI_2);
+    let non_zero = [a, b, c]
+        .into_iter()
+        .filter(|x| x.abs() > EPS)
+        .collect_vec();
+    match non_zero[..] {
+        [] => vec![],
+        [x] if (x.abs() - FRAC_PI_4).abs() < EPS => vec![(Tk2Op::CX, &[0, 1], None)],
+        // CX (Rx(-2p) ⊗ Rz(-2q)) CX = exp(i(p XX + q ZZ))
+        [p] | [p, _] => {
+            let q = non_zero.get(1).copied().unwrap_or(0.);
+            vec![
+                (Tk2Op::CX, &[0, 1], None),
+                (Tk2Op::RxF64, &[0], Some(-2. * p)),
+                (Tk2Op::RzF64, &[1], Some(-2. * q)),
+                (Tk2Op::CX, &[0, 1], None),
+            ]
+        }
+        // Conjugating by the first CX maps the interaction to
+        // exp(i(a XI - b XZ + c IZ)), and the XZ term is a CZ-conjugated X
+        // rotation. The CZ is merged with the last CX.
+        _ => vec![
+            (Tk2Op::Sdg, &[1], None),
+            (Tk2Op::CX, &[0, 1], None),
+            (Tk2Op::S, &[1], None),
+            (Tk2Op::S, &[0], None),
+            (Tk2Op::RxF64, &[0], Some(2. * b)),
+            (Tk2Op::H, &[1], None),
+            (Tk2Op::CX, &[0, 1], None),
+            (Tk2Op::H, &[1], None),
+            (Tk2Op::RxF64, &[0], Some(-2. * a)),
+            (Tk2Op::RzF64, &[1], Some(-2. * c)),
+            (Tk2Op::CX, &[0, 1], None),
+        ],
+    }
+}
+
+/// The single-qubit rotations implementing a 2×2 unitary, up to global phase.
+///
+/// Uses the decomposition `Rz(α) Rx(β) Rz(γ)`.
+pub(crate) fn euler_gates(u: &[Complex64], qubit: &'static [usize]) -> Vec<Gate> {
+    let v = {
+        let det = u[0] * u[3] - u[1] * u[2];
+        let norm = det.sqrt();
+        u.iter().map(|x| x / norm).collect_vec()
+    };
+    let beta = 2. * v[2].norm().atan2(v[0].norm());
+    let sum = match v[3].norm() > EPS {
+        true => 2. * v[3].arg(),
+        false => 0.,
+    };
+    let diff = match v[2].norm() > EPS {
+        true => 2. * (Complex64::i() * v[2]).arg(),
+        false => 0.,
+    };
+    let (alpha, gamma) = ((sum + diff) / 2., (sum - diff) / 2.);
+    vec![
+        (Tk2Op::RzF64, qubit, Some(gamma)),
+        (Tk2Op::RxF64, qubit, Some(beta)),
+        (Tk2Op::RzF64, qubit, Some(alpha)),
+    ]
+}
+
+/// Whether a rotation by `angle` is the identity, up to global phase.
+fn is_trivial_rotation(angle: f64) -> bool {
+    let turns = angle / (2. * PI);
+    (turns - turns.round()).abs() < EPS
+}
+
+/// Split a 4×4 matrix `A ⊗ B` into its 2×2 factors.
+fn kron_factor(m: &[Complex64]) -> (Vec<Complex64>, Vec<Complex64>) {
+    let block = |r: usize, s: usize| {
+        [(0, 0), (0, 1), (1, 0), (1, 1)]
+            .map(|(i, j)| m[(2 * r + i) * 4 + 2 * s + j])
+            .to_vec()
+    };
+    let norm = |b: &[Complex64]| b.iter().map(|x| x.norm_sqr()).sum::<f64>();
+    let (r, s) = (0..2)
+        .cartesian_product(0..2)
+        .max_by(|&(r1, s1), &(r2, s2)| norm(&block(r1, s1)).total_cmp(&norm(&block(r2, s2))))
+        .unwrap();
+    let b = block(r, s);
+    let scale = (norm(&b) / 2.).sqrt();
+    let b = b.iter().map(|&x| x / scale).collect_vec();
+    let a = (0..2)
+        .cartesian_product(0..2)
+        .map(|(i, j)| {
+            let overlap: Complex64 = b.iter().zip(block(i, j)).map(|(x, y)| x.conj() * y).sum();
+            overlap / 2.
+        })
+        .collect();
+    (a, b)
+}
+
+/// The unitary of a list of gates on two qubits.
+fn gates_unitary(gates: &[Gate]) -> Vec<Complex64> {
+    let identity = diagonal(&[one(); 2]);
+    gates
+        .iter()
+        .fold(diagonal(&[one(); 4]), |acc, &(op, qubits, angle)| {
+            let angles = angle.into_iter().collect_vec();
+            let matrix = gate_matrix(op, &angles).expect("Synthesis gates are unitary.");
+            let full = match qubits {
+                [0] => kron(&matrix, &identity),
+                [1] => kron(&identity, &matrix),
+                _ => matrix,
+            };
+            matmul(&full, &acc)
+        })
+}
+
+/// Build a two-qubit circuit from a list of gates.
+fn build_circuit(gates: &[Gate]) -> Circuit {
+    let mut dfg = DFGBuilder::new(Signature::new_endo(vec![QB_T, QB_T])).unwrap();
+    let mut qbs = dfg.input_wires().collect_vec();
+    for &(op, qubits, angle) in gates {
+        let mut inputs = qubits.iter().map(|&q| qbs[q]).collect_vec();
+        if let Some(angle) = angle {
+            inputs.push(dfg.add_load_value(ConstF64::new(angle)));
+        }
+        let outputs = dfg.add_dataflow_op(op, inputs).unwrap().outputs();
+        for (&q, wire) in qubits.iter().zip(outputs) {
+            qbs[q] = wire;
+        }
+    }
+    dfg.finish_hugr_with_outputs(qbs, &REGISTRY).unwrap().into()
+}
+
+/// The columns of the magic basis, in which local gates are real.
+fn magic_basis() -> Vec<Complex64> {
+    let (r, i, z) = (
+        Complex64::new(FRAC_1_SQRT_2, 0.),
+        Complex64::new(0., FRAC_1_SQRT_2),
+        Complex64::new(0., 0.),
+    );
+    vec![r, z, z, i, z, i, r, z, z, i, -r, z, r, z, z, -i]
+}
+
+/// The two-qubit Pauli operator `P ⊗ P`.
+fn pauli_pair(pauli: Tk2Op) -> Vec<Complex64> {
+    let p = gate_matrix(pauli, &[]).unwrap();
+    kron(&p, &p)
+}
+
+/// Normalise a 4×4 unitary to have unit determinant.
+fn special_unitary(u: &[Complex64]) -> Vec<Complex64> {
+    let det = det(u);
+    let norm = Complex64::from_polar(det.norm().powf(0.25), det.arg() / 4.);
+    u.iter().map(|x| x / norm).collect()
+}
+
+/// The eigenvectors of a real symmetric 4×4 matrix, as the columns of an
+/// orthogonal matrix, computed with the cyclic Jacobi method.
+fn jacobi_eigenvectors(m: &[f64]) -> Vec<f64> {
+    let mut a = m.to_vec();
+    let mut v = (0..16)
+        .map(|k| if k / 4 == k % 4 { 1. } else { 0. })
+        .collect_vec();
+    for _ in 0..100 {
+        let off_diag: f64 = (0..16)
+            .filter(|k| k / 4 != k % 4)
+            .map(|k| a[k] * a[k])
+            .sum();
+        if off_diag < 1e-30 {
+            break;
+        }
+        for (p, q) in (0..4).tuple_combinations() {
+            if a[p * 4 + q].abs() < 1e-300 {
+                continue;
+            }
+            let theta = (a[q * 4 + q] - a[p * 4 + p]) / (2. * a[p * 4 + q]);
+            let t = theta.signum() / (theta.abs() + (theta * theta + 1.).sqrt());
+            let t = if theta == 0. { 1. } else { t };
+            let c = 1. / (t * t + 1.).sqrt();
+            let s = t * c;
+            // A ← Jᵀ A J and V ← V J, for the rotation J in the (p, q) plane.
+            for k in 0..4 {
+                let (akp, akq) = (a[k * 4 + p], a[k * 4 + q]);
+                a[k * 4 + p] = c * akp - s * akq;
+                a[k * 4 + q] = s * akp + c * akq;
+            }
+            for k in 0..4 {
+                let (apk, aqk) = (a[p * 4 + k], a[q * 4 + k]);
+                a[p * 4 + k] = c * apk - s * aqk;
+                a[q * 4 + k] = s * apk + c * aqk;
+            }
+            for k in 0..4 {
+                let (vkp, vkq) = (v[k * 4 + p], v[k * 4 + q]);
+                v[k * 4 + p] = c * vkp - s * vkq;
+                v[k * 4 + q] = s * vkp + c * vkq;
+            }
+        }
+    }
+    v
+}
+
+/// The determinant of a 4×4 matrix, by Gaussian elimination.
+fn det(m: &[Complex64]) -> Complex64 {
+    let mut a = m.to_vec();
+    let mut det = one();
+    for col in 0..4 {
+        let pivot = (col..4)
+            .max_by(|&r1, &r2| a[r1 * 4 + col].norm().total_cmp(&a[r2 * 4 + col].norm()))
+            .unwrap();
+        if a[pivot * 4 + col].norm() == 0. {
+            return Complex64::new(0., 0.);
+        }
+        if pivot != col {
+            for k in 0..4 {
+                a.swap(pivot * 4 + k, col * 4 + k);
+            }
+            det = -det;
+        }
+        det *= a[col * 4 + col];
+        for row in col + 1..4 {
+            let factor = a[row * 4 + col] / a[col * 4 + col];
+            for k in col..4 {
+                let sub = factor * a[col * 4 + k];
+                a[row * 4 + k] -= sub;
+            }
+        }
+    }
+    det
+}
+
+/// The Kronecker product of two 2×2 matrices.
+fn kron(a: &[Complex64], b: &[Complex64]) -> Vec<Complex64> {
+    (0..16)
+        .map(|k| {
+            let (row, col) = (k / 4, k % 4);
+            a[(row / 2) * 2 + col / 2] * b[(row % 2) * 2 + col % 2]
+        })
+        .collect()
+}
+
+/// The transpose of a square matrix.
+fn transpose(m: &[Complex64]) -> Vec<Complex64> {
+    let dim = (m.len() as f64).sqrt() as usize;
+    (0..dim * dim)
+        .map(|k| m[(k % dim) * dim + k / dim])
+        .collect()
+}
+
+/// The conjugate transpose of a square matrix.
+fn dagger(m: &[Complex64]) -> Vec<Complex64> {
+    transpose(m).into_iter().map(|x| x.conj()).collect()
+}
+
+/// Whether two matrices are equal up to numerical error.
+fn approx_eq(a: &[Complex64], b: &[Complex64]) -> bool {
+    a.iter().zip(b).all(|(x, y)| (x - y).norm() < 1e-8)
+}
+
+fn one() -> Complex64 {
+    Complex64::new(1., 0.)
+}
+
+/// Error type for the synthesis of circuits from unitary matrices.
+#[derive(Debug, Clone, PartialEq, Error)]
+#[non_exhaustive]
+pub enum SynthError {
+    /// The matrix does not have the expected number of entries.
+    #[error("Expected a {dim}×{dim} matrix with {} entries, but got {len}.", dim * dim)]
+    InvalidShape {
+        /// The expected dimension of the matrix.
+        dim: usize,
+        /// The number of entries in the matrix.
+        len: usize,
+    },
+    /// The matrix is not unitary.
+    #[error("The matrix is not unitary.")]
+    NotUnitary,
+    /// The numerical decomposition of the matrix failed. This can happen for
+    /// degenerate unitaries, where the tolerance of the synthesis is not met.
+    #[error("The numerical decomposition of the unitary failed.")]
+    DecompositionFailed,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::build_simple_circuit;
+    use crate::utils::test::cx_count;
+    use cool_asserts::assert_matches;
+    use rstest::rstest;
+
+    /// A pseudo-random two-qubit unitary, made of layers of random
+    /// single-qubit rotations and `CX` gates.
+    fn random_unitary(seed: u64) -> Vec<Complex64> {
+        let mut state = seed;
+        let mut angle = || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 11) as f64 / (1u64 << 53) as f64 * 2. * PI
+        };
+        let mut u = diagonal(&[one(); 4]);
+        for layer in 0..4 {
+            let local = |angle: &mut dyn FnMut() -> f64| {
+                gate_matrix(Tk2Op::TK1, &[angle(), angle(), angle()]).unwrap()
+            };
+            let (a, b) = (local(&mut angle), local(&mut angle));
+            u = matmul(&kron(&a, &b), &u);
+            if layer < 3 {
+                u = matmul(&gate_matrix(Tk2Op::CX, &[]).unwrap(), &u);
+            }
+        }
+        let phase = Complex64::from_polar(1., angle());
+        u.into_iter().map(|x| x * phase).collect()
+    }
+
+    #[rstest]
+    #[case(1)]
+    #[case(2)]
+    #[case(3)]
+    #[case(42)]
+    #[case(1234)]
+    fn random_su4(#[case] seed: u64) {
+        let u = random_unitary(seed);
+        let circ = kak(&u).unwrap();
+        circ.hugr().validate(&REGISTRY).unwrap();
+        assert!(cx_count(&circ) <= 3);
+        assert!(approx_eq(&circ.unitary().unwrap(), &u));
+    }
+
+    #[rstest]
+    #[case::identity(build_simple_circuit(2, |_| Ok(())).unwrap(), 0)]
+    #[case::local(build_simple_circuit(2, |circ| {
+        circ.append(Tk2Op::H, [0])?;
+        circ.append(Tk2Op::T, [1])?;
+        Ok(())
+    }).unwrap(), 0)]
+    #[case::cx(build_simple_circuit(2, |circ| {
+        circ.append(Tk2Op::CX, [1, 0])?;
+        Ok(())
+    }).unwrap(), 1)]
+    #[case::two_cx(build_simple_circuit(2, |circ| {
+        circ.append(Tk2Op::CX, [0, 1])?;
+        circ.append(Tk2Op::H, [0])?;
+        circ.append(Tk2Op::T, [1])?;
+        circ.append(Tk2Op::CX, [0, 1])?;
+        Ok(())
+    }).unwrap(), 2)]
+    #[case::cz_zzmax(build_simple_circuit(2, |circ| {
+        circ.append(Tk2Op::CZ, [0, 1])?;
+        circ.append(Tk2Op::ZZMax, [0, 1])?;
+        Ok(())
+    }).unwrap(), 0)]
+    #[case::swap(build_simple_circuit(2, |circ| {
+        circ.append(Tk2Op::CX, [0, 1])?;
+        circ.append(Tk2Op::CX, [1, 0])?;
+        circ.append(Tk2Op::CX, [0, 1])?;
+        Ok(())
+    }).unwrap(), 3)]
+    fn optimal_cx_count(#[case] circ: Circuit, #[case] expected: usize) {
+        let u = circ.unitary().unwrap();
+        let synth = kak(&u).unwrap();
+        assert_eq!(cx_count(&synth), expected);
+        assert!(approx_eq(&synth.unitary().unwrap(), &u));
+    }
+
+    #[test]
+    fn invalid_input() {
+        let mut u = diagonal(&[one(); 4]);
+        u[1] = one();
+        assert_matches!(kak(&u), Err(SynthError::NotUnitary));
+        assert_matches!(
+            kak(&u[..9]),
+            Err(SynthError::InvalidShape { dim: 4, len: 9 })
+        );
+    }
+}
@@ -0,0 +1,133 @@
+//! Synthesis of single-qubit unitaries as Euler rotations.
+
+use std::f64::consts::PI;
+
+use hugr::builder::{DFGBuilder, Dataflow, DataflowHugr};
+use hugr::extension::prelude::QB_T;
+use hugr::hugr::hugrmut::HugrMut;
+use hugr::std_extensions::arithmetic::float_types::ConstF64;
+use hugr::types::Signature;
+use num_complex::Complex64;
+
+use super::kak::euler_gates;
+use super::SynthError;
+use crate::circuit::simulate::{gate_matrix, matmul};
+use crate::circuit::Circuit;
+use crate::extension::REGISTRY;
+use crate::serialize::pytket::METADATA_PHASE;
+use crate::Tk2Op;
+
+/// Numerical tolerance for the synthesis.
+const EPS: f64 = 1e-9;
+
+/// Synthesise a circuit implementing a single-qubit unitary.
+///
+/// The unitary is a 2×2 matrix in row-major order. The circuit consists of a
+/// single `TK1(α, β, γ) = Rz(α) Rx(β) Rz(γ)` gate, and stores the global
+/// phase of the unitary, so that its matrix is exactly the input.
+///
+/// # Errors
+///
+/// Returns an error if the matrix is not 2×2, or if it is not unitary.
+pub fn one_qubit(u: &[Complex64]) -> Result<Circuit, SynthError> {
+    if u.len() != 4 {
+        return Err(SynthError::InvalidShape {
+            dim: 2,
+            len: u.len(),
+        });
+    }
+    let u_dagger = [u[0].conj(), u[2].conj(), u[1].conj(), u[3].conj()];
+    let identity = [1., 0., 0., 1.].map(|x| Complex64::new(x, 0.));
+    let product = matmul(u, &u_dagger);
+    if product
+        .iter()
+        .zip(identity)
+        .any(|(a, b)| (a - b).norm() > EPS)
+    {
+        return Err(SynthError::NotUnitary);
+    }
+
+    // `euler_gates` applies `Rz(γ)`, then `Rx(β)`, then `Rz(α)`.
+    let [gamma, beta, alpha]: [f64; 3] = euler_gates(u, &[0])
+        .into_iter()
+        .map(|(_, _, angle)| angle.unwrap())
+        .collect::<Vec<_>>()
+        .try_into()
+        .unwrap();
+    let angles = [alpha, beta, gamma];
+    let synth_u = gate_matrix(Tk2Op::TK1, &angles).unwrap();
+    let overlap: Complex64 = synth_u.iter().zip(u).map(|(s, t)| s.conj() * t).sum();
+    let phase = overlap.arg() / PI;
+
+    let mut dfg = DFGBuilder::new(Signature::new_endo(vec![QB_T])).unwrap();
+    let [q] = dfg.input_wires_arr();
+    let angles = angles.map(|a| dfg.add_load_value(ConstF64::new(a)));
+    let tk1 = dfg
+        .add_dataflow_op(Tk2Op::TK1, [q].into_iter().chain(angles))
+        .unwrap();
+    let mut circ: Circuit = dfg
+        .finish_hugr_with_outputs(tk1.outputs(), &REGISTRY)
+        .unwrap()
+        .into();
+    if phase.abs() > EPS {
+        let parent = circ.parent();
+        circ.hugr_mut()
+            .set_metadata(parent, METADATA_PHASE, phase.to_string());
+    }
+    Ok(circ)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cool_asserts::assert_matches;
+    use rstest::rstest;
+
+    /// A pseudo-random single-qubit unitary with a random global phase.
+    fn random_unitary(seed: u64) -> Vec<Complex64> {
+        let mut state = seed;
+        let mut angle = || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 11) as f64 / (1u64 << 53) as f64 * 2. * PI
+        };
+        let u = gate_matrix(Tk2Op::TK1, &[angle(), angle(), angle()]).unwrap();
+        let phase = Complex64::from_polar(1., angle());
+        u.into_iter().map(|x| x * phase).collect()
+    }
+
+    #[rstest]
+    #[case::random_1(random_unitary(1))]
+    #[case::random_2(random_unitary(2))]
+    #[case::random_42(random_unitary(42))]
+    #[case::random_1234(random_unitary(1234))]
+    #[case::h(gate_matrix(Tk2Op::H, &[]).unwrap())]
+    #[case::x(gate_matrix(Tk2Op::X, &[]).unwrap())]
+    #[case::y(gate_matrix(Tk2Op::Y, &[]).unwrap())]
+    #[case::s(gate_matrix(Tk2Op::S, &[]).unwrap())]
+    #[case::minus_identity([-1., 0., 0., -1.].map(|x| Complex64::new(x, 0.)).to_vec())]
+    fn synthesise(#[case] u: Vec<Complex64>) {
+        let circ = one_qubit(&u).unwrap();
+        circ.hugr().validate(&REGISTRY).unwrap();
+        let ops = circ
+            .commands()
+            .filter(|cmd| cmd.optype() == &Tk2Op::TK1.into());
+        assert_eq!(ops.count(), 1);
+        let synth_u = circ.unitary().unwrap();
+        for (a, b) in synth_u.iter().zip(&u) {
+            assert!((a - b).norm() < 1e-9, "{synth_u:?} != {u:?}");
+        }
+    }
+
+    #[test]
+    fn invalid_matrix() {
+        let u = random_unitary(3);
+        assert_matches!(
+            one_qubit(&u[..3]),
+            Err(SynthError::InvalidShape { dim: 2, len: 3 })
+        );
+        let scaled = u.iter().map(|x| *x * 2.).collect::<Vec<_>>();
+        assert_matches!(one_qubit(&scaled), Err(SynthError::NotUnitary));
+    }
+}
@@ -0,0 +1,507 @@
+//! Synthesis of sequences of Pauli rotations as phase gadgets, and their
+//! extraction from Clifford + rotation circuits.
+
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+use derive_more::{From, Into};
+use hugr::builder::{DFGBuilder, Dataflow, DataflowHugr};
+use hugr::extension::prelude::QB_T;
+use hugr::std_extensions::arithmetic::float_types::ConstF64;
+use hugr::types::Signature;
+use hugr::HugrView;
+use itertools::Itertools;
+
+use crate::circuit::params::NumericParams;
+use crate::circuit::Circuit;
+use crate::extension::REGISTRY;
+use crate::ops::Pauli;
+use crate::rewrite::add_circuit_phase;
+use crate::Tk2Op;
+
+/// A tensor product of single-qubit Pauli operators.
+///
+/// The `i`-th Pauli acts on qubit `i`. Qubits past the end of the string are
+/// acted on by the identity.
+#[derive(Debug, Clone, Default, PartialEq, From, Into)]
+pub struct PauliString(pub Vec<Pauli>);
+
+impl PauliString {
+    /// The qubits acted on by a non-identity Pauli.
+    pub fn support(&self) -> impl Iterator<Item = usize> + '_ {
+        self.0.iter().positions(|&p| p != Pauli::I)
+    }
+}
+
+impl FromIterator<Pauli> for PauliString {
+    fn from_iter<I: IntoIterator<Item = Pauli>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+/// Synthesise a circuit applying a sequence of Pauli rotations.
+///
+/// Each rotation `(P, θ)` implements `exp(-i θ/2 P)`, with the angle in
+/// radians, and is applied after the previous ones. It is synthesised as a
+/// phase gadget: Clifford gates map each Pauli of the string to `Z`, a ladder
+/// of `CX` gates computes the parity of the support onto its last qubit,
+/// which is rotated by `Rz(θ)`, and the ladder and basis changes are then
+/// undone. Rotations by the identity only contribute to the global phase of
+/// the circuit.
+///
+/// # Panics
+///
+/// Panics if a Pauli string acts non-trivially on a qubit index of at least
+/// `qubits`.
+pub fn from_pauli_rotations(rotations: &[(PauliString, f64)], qubits: usize) -> Circuit {
+    let mut dfg = DFGBuilder::new(Signature::new_endo(vec![QB_T; qubits])).unwrap();
+    let mut qbs = dfg.input_wires().collect_vec();
+    let mut phase = 0.;
+    let mut append = |dfg: &mut DFGBuilder<_>, op: Tk2Op, qubits: &[usize], angle: Option<f64>| {
+        let mut inputs = qubits.iter().map(|&q| qbs[q]).collect_vec();
+        if let Some(angle) = angle {
+            inputs.push(dfg.add_load_value(ConstF64::new(angle)));
+        }
+        let outputs = dfg.add_dataflow_op(op, inputs).unwrap().outputs();
+        for (&q, wire) in qubits.iter().zip(outputs) {
+            qbs[q] = wire;
+        }
+    };
+
+    for (pauli, theta) in rotations {
+        let support = pauli.support().collect_vec();
+        if let Some(&q) = support.iter().find(|&&q| q >= qubits) {
+            panic!("Pauli string acts on qubit {q}, but the circuit has {qubits} qubits.");
+        }
+        let Some(&last) = support.last() else {
+            // exp(-i θ/2 I) is a global phase of -θ/2 radians.
+            phase -= theta / (2. * PI);
+            continue;
+        };
+
+        // Map each Pauli to `Z`.
+        for &q in &support {
+            match pauli.0[q] {
+                Pauli::X => append(&mut dfg, Tk2Op::H, &[q], None),
+                Pauli::Y => {
+                    append(&mut dfg, Tk2Op::Sdg, &[q], None);
+                    append(&mut dfg, Tk2Op::H, &[q], None);
+                }
+                Pauli::I | Pauli::Z => {}
+            }
+        }
+        for (&a, &b) in support.iter().tuple_windows() {
+            append(&mut dfg, Tk2Op::CX, &[a, b], None);
+        }
+        append(&mut dfg, Tk2Op::RzF64, &[last], Some(*theta));
+        for (&a, &b) in support
+            .iter()
+            .tuple_windows()
+            .collect_vec()
+            .into_iter()
+            .rev()
+        {
+            append(&mut dfg, Tk2Op::CX, &[a, b], None);
+        }
+        for &q in &support {
+            match pauli.0[q] {
+                Pauli::X => append(&mut dfg, Tk2Op::H, &[q], None),
+                Pauli::Y => {
+                    append(&mut dfg, Tk2Op::H, &[q], None);
+                    append(&mut dfg, Tk2Op::S, &[q], None);
+                }
+                Pauli::I | Pauli::Z => {}
+            }
+        }
+    }
+
+    let mut circ: Circuit = dfg.finish_hugr_with_outputs(qbs, &REGISTRY).unwrap().into();
+    add_circuit_phase(&mut circ, phase);
+    circ
+}
+
+impl<T: HugrView> Circuit<T> {
+    /// Express the circuit as a sequence of Pauli rotations.
+    ///
+    /// This is the inverse of [`from_pauli_rotations`]: the returned rotations
+    /// `(P, θ)`, each implementing `exp(-i θ/2 P)` with the angle in radians,
+    /// compose to the unitary of the circuit up to a global phase.
+    ///
+    /// The circuit may only contain Clifford gates (`H`, `S`, `Sdg`, `X`, `Y`,
+    /// `Z`, `CX` and `CZ`) and rotations (`Rz`, `Rx`, `T`, `Tdg`, `ZZMax`,
+    /// `ZZPhase`, `PhasedX` and `TK1`) with numeric angles. The Clifford gates
+    /// are pushed to the end of the circuit, conjugating the rotations they
+    /// pass through, and must cancel out once they all reach the end.
+    ///
+    /// Returns `None` if the circuit contains any other operation, if its
+    /// Clifford gates do not cancel out, or if its wiring permutes the qubits
+    /// (see [`Circuit::implicit_permutation`]).
+    pub fn as_pauli_rotations(&self) -> Option<Vec<(PauliString, f64)>> {
+        let permutation = self.implicit_permutation();
+        if permutation.iter().enumerate().any(|(i, &j)| i != j) {
+            return None;
+        }
+        let n = self.qubit_count();
+        let mut frame = CliffordFrame::new(n);
+        let mut params = NumericParams::default();
+        let mut rotations = Vec::new();
+        for cmd in self.commands() {
+            if params.process(&cmd).ok()? {
+                continue;
+            }
+            let op = Tk2Op::try_from(cmd.optype()).ok()?;
+            let qbs = cmd
+                .input_qubits()
+                .map(|(qb, _, _)| qb.index())
+                .collect_vec();
+            let angles = params.inputs(&cmd).ok()?;
+            let mut rotate = |pauli: SymplecticPauli, theta: f64| {
+                let (pauli, negative) = pauli.into_hermitian();
+                rotations.push((pauli, if negative { -theta } else { theta }));
+            };
+            match op {
+                Tk2Op::RzF64 => rotate(frame.z(qbs[0]), angles[0]),
+                Tk2Op::RxF64 => rotate(frame.x(qbs[0]), angles[0]),
+                Tk2Op::T => rotate(frame.z(qbs[0]), FRAC_PI_4),
+                Tk2Op::Tdg => rotate(frame.z(qbs[0]), -FRAC_PI_4),
+                Tk2Op::ZZMax => rotate(frame.z(qbs[0]).mul(&frame.z(qbs[1])), FRAC_PI_2),
+                Tk2Op::ZZPhase => rotate(frame.z(qbs[0]).mul(&frame.z(qbs[1])), angles[0]),
+                // PhasedX(θ, φ) = Rz(φ) Rx(θ) Rz(-φ)
+                Tk2Op::PhasedX => {
+                    rotate(frame.z(qbs[0]), -angles[1]);
+                    rotate(frame.x(qbs[0]), angles[0]);
+                    rotate(frame.z(qbs[0]), angles[1]);
+                }
+                // TK1(α, β, γ) = Rz(α) Rx(β) Rz(γ)
+                Tk2Op::TK1 => {
+                    rotate(frame.z(qbs[0]), angles[2]);
+                    rotate(frame.x(qbs[0]), angles[1]);
+                    rotate(frame.z(qbs[0]), angles[0]);
+                }
+                op => frame.apply(op, &qbs)?,
+            }
+        }
+        frame.is_identity().then_some(rotations)
+    }
+}
+
+/// A Pauli operator `i^phase X^x Z^z`, in symplectic form.
+#[derive(Debug, Clone, PartialEq)]
+struct SymplecticPauli {
+    x: Vec<bool>,
+    z: Vec<bool>,
+    /// The exponent of `i`, modulo 4.
+    phase: u8,
+}
+
+impl SymplecticPauli {
+    /// The single-qubit `X` or `Z` operator acting on qubit `q`.
+    fn single(n: usize, q: usize, is_x: bool) -> Self {
+        let mut x = vec![false; n];
+        let mut z = vec![false; n];
+        if is_x {
+            x[q] = true;
+        } else {
+            z[q] = true;
+        }
+        Self { x, z, phase: 0 }
+    }
+
+    /// The product `self * other`.
+    fn mul(&self, other: &Self) -> Self {
+        // Moving the `X`s of `other` past the `Z`s of `self` flips the sign
+        // on every qubit where they anticommute.
+        let flips = self.z.iter().zip(&other.x).filter(|(&z, &x)| z && x);
+        let phase = self.phase + other.phase + 2 * (flips.count() % 2) as u8;
+        Self {
+            x: self.x.iter().zip(&other.x).map(|(a, b)| a ^ b).collect(),
+            z: self.z.iter().zip(&other.z).map(|(a, b)| a ^ b).collect(),
+            phase: phase % 4,
+        }
+    }
+
+    /// Multiply the operator by `i^phase`.
+    fn with_phase(mut self, phase: u8) -> Self {
+        self.phase = (self.phase + phase) % 4;
+        self
+    }
+
+    /// Split a Hermitian operator into a Pauli string and whether it is
+    /// negated.
+    fn into_hermitian(self) -> (PauliString, bool) {
+        let mut n_y = 0;
+        let pauli = self
+            .x
+            .iter()
+            .zip(&self.z)
+            .map(|(&x, &z)| match (x, z) {
+                (false, false) => Pauli::I,
+                (true, false) => Pauli::X,
+                (false, true) => Pauli::Z,
+                (true, true) => {
+                    // Y = i X Z
+                    n_y += 1;
+                    Pauli::Y
+                }
+            })
+            .collect();
+        let phase = (self.phase + 4 - n_y % 4) % 4;
+        debug_assert!(phase % 2 == 0, "Pauli operator is not Hermitian");
+        (pauli, phase == 2)
+    }
+}
+
+/// A Clifford operator `F`, stored as the conjugates `F† X_q F` and
+/// `F† Z_q F` of the single-qubit Pauli operators.
+///
+/// A rotation `exp(-i θ/2 P)` applied after `F` equals `F` followed by the
+/// rotation `exp(-i θ/2 F† P F)`, so the frame maps the rotations of a
+/// circuit to the start of its Clifford gates.
+#[derive(Debug, Clone)]
+struct CliffordFrame {
+    xs: Vec<SymplecticPauli>,
+    zs: Vec<SymplecticPauli>,
+}
+
+impl CliffordFrame {
+    /// The identity frame on `n` qubits.
+    fn new(n: usize) -> Self {
+        Self {
+            xs: (0..n)
+                .map(|q| SymplecticPauli::single(n, q, true))
+                .collect(),
+            zs: (0..n)
+                .map(|q| SymplecticPauli::single(n, q, false))
+                .collect(),
+        }
+    }
+
+    /// The conjugate `F† X_q F`.
+    fn x(&self, q: usize) -> SymplecticPauli {
+        self.xs[q].clone()
+    }
+
+    /// The conjugate `F† Z_q F`.
+    fn z(&self, q: usize) -> SymplecticPauli {
+        self.zs[q].clone()
+    }
+
+    /// Apply a Clifford gate `G` after the frame, mapping it to `G F`.
+    ///
+    /// Returns `None` if the gate is not a supported Clifford gate.
+    fn apply(&mut self, op: Tk2Op, qbs: &[usize]) -> Option<()> {
+        // The new conjugates are `F† (G† P G) F`, computed from the
+        // decomposition of `G† P G` into single-qubit Paulis.
+        let (x, z) = (self.x(qbs[0]), self.z(qbs[0]));
+        match op {
+            Tk2Op::H => {
+                self.xs[qbs[0]] = z;
+                self.zs[qbs[0]] = x;
+            }
+            // S† X S = -Y = -i X Z
+            Tk2Op::S => self.xs[qbs[0]] = x.mul(&z).with_phase(3),
+            // S X S† = Y = i X Z
+            Tk2Op::Sdg => self.xs[qbs[0]] = x.mul(&z).with_phase(1),
+            Tk2Op::X => self.zs[qbs[0]] = z.with_phase(2),
+            Tk2Op::Y => {
+                self.xs[qbs[0]] = x.with_phase(2);
+                self.zs[qbs[0]] = z.with_phase(2);
+            }
+            Tk2Op::Z => self.xs[qbs[0]] = x.with_phase(2),
+            Tk2Op::CX => {
+                let (c, t) = (qbs[0], qbs[1]);
+                self.xs[c] = self.xs[c].mul(&self.xs[t]);
+                self.zs[t] = self.zs[c].mul(&self.zs[t]);
+            }
+            Tk2Op::CZ => {
+                let (a, b) = (qbs[0], qbs[1]);
+                self.xs[a] = self.xs[a].mul(&self.zs[b]);
+                self.xs[b] = self.zs[a].mul(&self.xs[b]);
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+
+    /// Whether the frame is the identity, up to a global phase.
+    fn is_identity(&self) -> bool {
+        let n = self.xs.len();
+        (0..n).all(|q| {
+            self.xs[q] == SymplecticPauli::single(n, q, true)
+                && self.zs[q] == SymplecticPauli::single(n, q, false)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::circuit::simulate::matmul;
+    use crate::utils::build_simple_circuit;
+    use num_complex::Complex64;
+    use rstest::rstest;
+
+    fn assert_close(a: &[Complex64], b: &[Complex64]) {
+        assert_eq!(a.len(), b.len());
+        for (x, y) in a.iter().zip(b) {
+            assert!((x - y).norm() < 1e-9, "{a:?} != {b:?}");
+        }
+    }
+
+    /// The matrix `cos(θ/2) I - i sin(θ/2) P` of `exp(-i θ/2 P)`, with qubit 0
+    /// as the most significant bit.
+    fn pauli_exponential(pauli: &PauliString, n: usize, theta: f64) -> Vec<Complex64> {
+        let dim = 1 << n;
+        let (sin, cos) = (theta / 2.).sin_cos();
+        let mut matrix = vec![Complex64::new(0., 0.); dim * dim];
+        for col in 0..dim {
+            // Apply `P` to the basis state `col`.
+            let mut row = col;
+            let mut amp = Complex64::new(1., 0.);
+            for (q, p) in pauli.0.iter().enumerate() {
+                let mask = 1 << (n - 1 - q);
+                let bit = col & mask != 0;
+                match p {
+                    Pauli::I => {}
+                    Pauli::X => row ^= mask,
+                    Pauli::Y => {
+                        row ^= mask;
+                        amp *= if bit { -Complex64::i() } else { Complex64::i() };
+                    }
+                    Pauli::Z if bit => amp = -amp,
+                    Pauli::Z => {}
+                }
+            }
+            matrix[row * dim + col] += Complex64::new(0., -sin) * amp;
+            matrix[col * dim + col] += Complex64::new(cos, 0.);
+        }
+        matrix
+    }
+
+    #[test]
+    fn zz_rotation() {
+        let theta = 0.37;
+        let zz = PauliString(vec![Pauli::Z, Pauli::Z]);
+        let circ = from_pauli_rotations(&[(zz, theta)], 2);
+        circ.hugr().validate(&REGISTRY).unwrap();
+
+        let mut dfg = DFGBuilder::new(Signature::new_endo(vec![QB_T, QB_T])).unwrap();
+        let [q0, q1] = dfg.input_wires_arr();
+        let angle = dfg.add_load_value(ConstF64::new(theta));
+        let zz_phase = dfg
+            .add_dataflow_op(Tk2Op::ZZPhase, [q0, q1, angle])
+            .unwrap()
+            .outputs();
+        let expected: Circuit = dfg
+            .finish_hugr_with_outputs(zz_phase, &REGISTRY)
+            .unwrap()
+            .into();
+        assert_close(&circ.unitary().unwrap(), &expected.unitary().unwrap());
+    }
+
+    #[rstest]
+    #[case::x(&[Pauli::X], 1)]
+    #[case::y(&[Pauli::Y], 1)]
+    #[case::identity(&[Pauli::I, Pauli::I], 2)]
+    #[case::xyz(&[Pauli::X, Pauli::Y, Pauli::Z], 3)]
+    #[case::sparse(&[Pauli::Y, Pauli::I, Pauli::X], 3)]
+    #[case::padded(&[Pauli::I, Pauli::Y], 3)]
+    fn pauli_rotation(#[case] paulis: &[Pauli], #[case] n: usize) {
+        let pauli = PauliString::from_iter(paulis.iter().copied());
+        let theta = -1.1;
+        let circ = from_pauli_rotations(&[(pauli.clone(), theta)], n);
+        circ.hugr().validate(&REGISTRY).unwrap();
+        assert_close(
+            &circ.unitary().unwrap(),
+            &pauli_exponential(&pauli, n, theta),
+        );
+    }
+
+    #[test]
+    fn zz_phase_roundtrip() {
+        let theta = 0.37;
+        let mut dfg = DFGBuilder::new(Signature::new_endo(vec![QB_T, QB_T])).unwrap();
+        let [q0, q1] = dfg.input_wires_arr();
+        let angle = dfg.add_load_value(ConstF64::new(theta));
+        let zz_phase = dfg
+            .add_dataflow_op(Tk2Op::ZZPhase, [q0, q1, angle])
+            .unwrap()
+            .outputs();
+        let circ: Circuit = dfg
+            .finish_hugr_with_outputs(zz_phase, &REGISTRY)
+            .unwrap()
+            .into();
+
+        let zz = PauliString(vec![Pauli::Z, Pauli::Z]);
+        let rotations = circ.as_pauli_rotations().unwrap();
+        assert_eq!(rotations, vec![(zz, theta)]);
+        let synthesised = from_pauli_rotations(&rotations, 2);
+        assert_eq!(synthesised.as_pauli_rotations(), Some(rotations));
+        assert_close(&synthesised.unitary().unwrap(), &circ.unitary().unwrap());
+    }
+
+    #[rstest]
+    #[case::x(&[Pauli::X], 1)]
+    #[case::y(&[Pauli::Y], 1)]
+    #[case::xyz(&[Pauli::X, Pauli::Y, Pauli::Z], 3)]
+    #[case::sparse(&[Pauli::Y, Pauli::I, Pauli::X], 3)]
+    fn pauli_rotation_roundtrip(#[case] paulis: &[Pauli], #[case] n: usize) {
+        let pauli = PauliString::from_iter(paulis.iter().copied());
+        let z = PauliString(vec![Pauli::Z; n]);
+        let rotations = vec![(pauli, -1.1), (z, 0.3)];
+        let circ = from_pauli_rotations(&rotations, n);
+        assert_eq!(circ.as_pauli_rotations(), Some(rotations));
+    }
+
+    #[test]
+    fn not_pauli_rotations() {
+        let circ = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::T, [0])?;
+            Ok(())
+        })
+        .unwrap();
+        // The final `H` does not cancel out.
+        assert_eq!(circ.as_pauli_rotations(), None);
+
+        // A permutation of the outputs is not a sequence of rotations.
+        let dfg = DFGBuilder::new(Signature::new_endo(vec![QB_T, QB_T])).unwrap();
+        let [q0, q1] = dfg.input_wires_arr();
+        let swapped: Circuit = dfg
+            .finish_hugr_with_outputs([q1, q0], &REGISTRY)
+            .unwrap()
+            .into();
+        assert_eq!(swapped.as_pauli_rotations(), None);
+
+        let circ = build_simple_circuit(1, |circ| {
+            circ.append(Tk2Op::Measure, [0])?;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(circ.as_pauli_rotations(), None);
+
+        // `X Z X Z = -I`, a global phase.
+        let circ = build_simple_circuit(1, |circ| {
+            for _ in 0..2 {
+                circ.append(Tk2Op::X, [0])?;
+                circ.append(Tk2Op::Z, [0])?;
+            }
+            circ.append(Tk2Op::T, [0])?;
+            Ok(())
+        })
+        .unwrap();
+        let z = PauliString(vec![Pauli::Z]);
+        assert_eq!(circ.as_pauli_rotations(), Some(vec![(z, FRAC_PI_4)]));
+    }
+
+    #[test]
+    fn trotter_sequence() {
+        let xx = PauliString(vec![Pauli::X, Pauli::X]);
+        let z = PauliString(vec![Pauli::Z]);
+        let circ = from_pauli_rotations(&[(xx.clone(), 0.4), (z.clone(), 0.9)], 2);
+        let u = matmul(
+            &pauli_exponential(&z, 2, 0.9),
+            &pauli_exponential(&xx, 2, 0.4),
+        );
+        assert_close(&circ.unitary().unwrap(), &u);
+    }
+}
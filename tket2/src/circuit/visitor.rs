@@ -0,0 +1,99 @@
+//! Visitors for writing custom analyses over the operations of a circuit.
+
+use std::collections::BTreeMap;
+
+use hugr::ops::{NamedOp, OpType};
+use hugr::{HugrView, Node};
+use itertools::Itertools;
+
+use super::units::LinearUnit;
+use super::Circuit;
+
+/// An analysis run over the operations of a circuit.
+///
+/// Visitors are driven by [`Circuit::accept`], which calls
+/// [`CircuitVisitor::visit_op`] once for each top-level operation in a
+/// topological order.
+pub trait CircuitVisitor {
+    /// Visit an operation of the circuit.
+    ///
+    /// `qubits` are the qubits the operation acts on, in the order of its
+    /// input ports, and `node` is the node of the operation in the circuit.
+    fn visit_op(&mut self, op: &OpType, qubits: &[LinearUnit], node: Node);
+}
+
+impl<T: HugrView> Circuit<T> {
+    /// Run a visitor over the operations of the circuit.
+    ///
+    /// The operations are visited in the order of [`Circuit::operations`].
+    pub fn accept(&self, visitor: &mut impl CircuitVisitor) {
+        for cmd in self.operations() {
+            let qubits = cmd.input_qubits().map(|(unit, _, _)| unit).collect_vec();
+            visitor.visit_op(cmd.optype(), &qubits, cmd.node());
+        }
+    }
+}
+
+/// A visitor counting the operations of a circuit, by name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GateCounter {
+    /// The total number of operations.
+    pub gates: usize,
+    /// The number of operations with each name.
+    pub histogram: BTreeMap<String, usize>,
+}
+
+impl GateCounter {
+    /// Create a new, empty, gate counter.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CircuitVisitor for GateCounter {
+    fn visit_op(&mut self, op: &OpType, _qubits: &[LinearUnit], _node: Node) {
+        self.gates += 1;
+        *self.histogram.entry(op.name().to_string()).or_default() += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::build_simple_circuit;
+    use crate::Tk2Op;
+
+    /// Records the qubits of every visited operation.
+    #[derive(Default)]
+    struct QubitRecorder(Vec<Vec<usize>>);
+
+    impl CircuitVisitor for QubitRecorder {
+        fn visit_op(&mut self, _op: &OpType, qubits: &[LinearUnit], _node: Node) {
+            self.0.push(qubits.iter().map(|q| q.index()).collect());
+        }
+    }
+
+    #[test]
+    fn gate_counter() {
+        let circ = build_simple_circuit(3, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(Tk2Op::T, [2])?;
+            circ.append(Tk2Op::CX, [2, 1])?;
+            Ok(())
+        })
+        .unwrap();
+
+        let mut counter = GateCounter::new();
+        circ.accept(&mut counter);
+        let stats = circ.stats();
+        assert_eq!(counter.gates, stats.gates);
+        assert_eq!(counter.histogram, stats.gate_histogram);
+
+        let mut recorder = QubitRecorder::default();
+        circ.accept(&mut recorder);
+        let mut qubits = recorder.0;
+        qubits.sort();
+        assert_eq!(qubits, vec![vec![0], vec![0, 1], vec![2], vec![2, 1]]);
+    }
+}
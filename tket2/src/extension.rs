@@ -2,15 +2,16 @@
 //!
 //! This includes a extension for the opaque TKET1 operations.
 
+use crate::ops::CustomGate;
 use crate::serialize::pytket::OpaqueTk1Op;
 use crate::Tk2Op;
-use hugr::extension::prelude::PRELUDE;
+use hugr::extension::prelude::{BOOL_T, PRELUDE, QB_T};
 use hugr::extension::simple_op::MakeOpDef;
 use hugr::extension::{CustomSignatureFunc, ExtensionId, ExtensionRegistry, SignatureError};
 use hugr::hugr::IdentList;
 use hugr::std_extensions::arithmetic::float_types::{EXTENSION as FLOAT_EXTENSION, FLOAT64_TYPE};
 use hugr::types::type_param::{TypeArg, TypeParam};
-use hugr::types::{CustomType, PolyFuncType, PolyFuncTypeRV, Signature};
+use hugr::types::{CustomType, PolyFuncType, PolyFuncTypeRV, Signature, Type, TypeBound, TypeRow};
 use hugr::{type_row, Extension};
 use lazy_static::lazy_static;
 use smol_str::SmolStr;
@@ -80,6 +81,118 @@ impl CustomSignatureFunc for Tk1Signature {
     }
 }
 
+/// Signature of a placeholder operation, parametrised by its name and its
+/// input and output types.
+struct PlaceholderSignature([TypeParam; 3]);
+
+impl CustomSignatureFunc for PlaceholderSignature {
+    fn compute_signature<'o, 'a: 'o>(
+        &'a self,
+        arg_values: &[TypeArg],
+        _def: &'o hugr::extension::OpDef,
+        _extension_registry: &ExtensionRegistry,
+    ) -> Result<PolyFuncTypeRV, SignatureError> {
+        let [_, inputs, outputs] = arg_values else {
+            // This should have already been checked.
+            panic!("Wrong number of arguments");
+        };
+        let type_row = |arg: &TypeArg| -> TypeRow {
+            let TypeArg::Sequence { elems } = arg else {
+                panic!("Placeholder types must be a list of types");
+            };
+            elems
+                .iter()
+                .map(|elem| match elem {
+                    TypeArg::Type { ty } => ty.clone(),
+                    _ => panic!("Placeholder types must be a list of types"),
+                })
+                .collect::<Vec<Type>>()
+                .into()
+        };
+        let poly_func: PolyFuncType = Signature::new(type_row(inputs), type_row(outputs)).into();
+        Ok(poly_func.into())
+    }
+
+    fn static_params(&self) -> &[TypeParam] {
+        &self.0
+    }
+}
+
+/// Signature of a user-defined gate, parametrised by its serialised
+/// [`CustomGate`] definition.
+struct CustomGateSignature([TypeParam; 1]);
+
+impl CustomSignatureFunc for CustomGateSignature {
+    fn compute_signature<'o, 'a: 'o>(
+        &'a self,
+        arg_values: &[TypeArg],
+        _def: &'o hugr::extension::OpDef,
+        _extension_registry: &ExtensionRegistry,
+    ) -> Result<PolyFuncTypeRV, SignatureError> {
+        let [TypeArg::String { arg }] = arg_values else {
+            // This should have already been checked.
+            panic!("Wrong number of arguments");
+        };
+        let gate: CustomGate =
+            serde_json::from_str(arg).map_err(|_| SignatureError::InvalidTypeArgs)?;
+        let poly_func: PolyFuncType = gate.signature().into();
+        Ok(poly_func.into())
+    }
+
+    fn static_params(&self) -> &[TypeParam] {
+        &self.0
+    }
+}
+
+/// Signature of a qubit permutation operation, parametrised by the permutation.
+struct PermutationSignature([TypeParam; 1]);
+
+impl CustomSignatureFunc for PermutationSignature {
+    fn compute_signature<'o, 'a: 'o>(
+        &'a self,
+        arg_values: &[TypeArg],
+        _def: &'o hugr::extension::OpDef,
+        _extension_registry: &ExtensionRegistry,
+    ) -> Result<PolyFuncTypeRV, SignatureError> {
+        let [TypeArg::Sequence { elems }] = arg_values else {
+            // This should have already been checked.
+            panic!("Wrong number of arguments");
+        };
+        let qubits: TypeRow = vec![QB_T; elems.len()].into();
+        let poly_func: PolyFuncType = Signature::new_endo(qubits).into();
+        Ok(poly_func.into())
+    }
+
+    fn static_params(&self) -> &[TypeParam] {
+        &self.0
+    }
+}
+
+/// Signature of a classical register comparison, parametrised by the width
+/// of the register and the compared value.
+struct RegisterEqSignature([TypeParam; 2]);
+
+impl CustomSignatureFunc for RegisterEqSignature {
+    fn compute_signature<'o, 'a: 'o>(
+        &'a self,
+        arg_values: &[TypeArg],
+        _def: &'o hugr::extension::OpDef,
+        _extension_registry: &ExtensionRegistry,
+    ) -> Result<PolyFuncTypeRV, SignatureError> {
+        let [TypeArg::BoundedNat { n: width }, _] = arg_values else {
+            // This should have already been checked.
+            panic!("Wrong number of arguments");
+        };
+        let bits: TypeRow = vec![BOOL_T; *width as usize].into();
+        let poly_func: PolyFuncType = Signature::new(bits, type_row![BOOL_T]).into();
+        Ok(poly_func.into())
+    }
+
+    fn static_params(&self) -> &[TypeParam] {
+        &self.0
+    }
+}
+
 /// Angle type with given log denominator.
 pub fn angle_custom_type(log_denom: u8) -> CustomType {
     angle::angle_custom_type(&TKET2_EXTENSION, angle::type_arg(log_denom))
@@ -94,6 +207,21 @@ pub const SYM_EXPR_NAME: SmolStr = SmolStr::new_inline("SymExpr");
 /// The name of the symbolic expression opaque type arg.
 pub const SYM_OP_ID: SmolStr = SmolStr::new_inline("symbolic_float");
 
+/// The name of the placeholder operation, to be substituted by a concrete
+/// operation with [`Circuit::fill_placeholders`](crate::Circuit::fill_placeholders).
+pub const PLACEHOLDER_OP_ID: SmolStr = SmolStr::new_inline("placeholder");
+
+/// The name of the qubit permutation operation.
+pub const PERMUTATION_OP_ID: SmolStr = SmolStr::new_inline("permutation");
+
+/// The name of the user-defined gate operation, see
+/// [`CustomGate`](crate::ops::CustomGate).
+pub const CUSTOM_GATE_OP_ID: SmolStr = SmolStr::new_inline("custom_gate");
+
+/// The name of the classical register comparison operation, see
+/// [`register_eq_op`](crate::ops::register_eq_op).
+pub const REGISTER_EQ_OP_ID: SmolStr = SmolStr::new_inline("register_eq");
+
 lazy_static! {
 /// The type of the symbolic expression opaque type arg.
 pub static ref SYM_EXPR_T: CustomType =
@@ -111,6 +239,38 @@ pub static ref TKET2_EXTENSION: Extension = {
     )
     .unwrap();
 
+    e.add_op(
+        PLACEHOLDER_OP_ID,
+        "A named placeholder for an operation with the given input and output types.".to_string(),
+        PlaceholderSignature([
+            TypeParam::String,
+            TypeParam::new_list(TypeBound::Any),
+            TypeParam::new_list(TypeBound::Any),
+        ]),
+    )
+    .unwrap();
+
+    e.add_op(
+        PERMUTATION_OP_ID,
+        "A relabelling of qubits, sending the i-th input to the output given by the i-th entry of the permutation.".to_string(),
+        PermutationSignature([TypeParam::new_list(TypeParam::max_nat())]),
+    )
+    .unwrap();
+
+    e.add_op(
+        CUSTOM_GATE_OP_ID,
+        "A user-defined gate acting on qubits, given by its serialised definition.".to_string(),
+        CustomGateSignature([TypeParam::String]),
+    )
+    .unwrap();
+
+    e.add_op(
+        REGISTER_EQ_OP_ID,
+        "Compare the unsigned integer value of a little-endian register of bits with a constant.".to_string(),
+        RegisterEqSignature([TypeParam::max_nat(), TypeParam::max_nat()]),
+    )
+    .unwrap();
+
     angle::add_to_extension(&mut e);
     e
 };
@@ -58,4 +58,7 @@ mod utils;
 
 pub use circuit::{Circuit, CircuitError, CircuitMutError};
 pub use hugr::Hugr;
-pub use ops::{op_matches, symbolic_constant_op, Pauli, Tk2Op};
+pub use ops::{
+    op_matches, permutation_op, placeholder_op, register_eq_op, symbolic_constant_op, CustomGate,
+    Pauli, Tk2Op,
+};
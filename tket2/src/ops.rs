@@ -1,5 +1,6 @@
 use crate::extension::{
-    SYM_OP_ID, TKET2_EXTENSION as EXTENSION, TKET2_EXTENSION_ID as EXTENSION_ID,
+    CUSTOM_GATE_OP_ID, PERMUTATION_OP_ID, PLACEHOLDER_OP_ID, REGISTER_EQ_OP_ID, SYM_OP_ID,
+    TKET2_EXTENSION as EXTENSION, TKET2_EXTENSION_ID as EXTENSION_ID,
 };
 use hugr::ops::custom::ExtensionOp;
 use hugr::ops::NamedOp;
@@ -75,6 +76,35 @@ impl Tk2Op {
         <Self as MakeRegisteredOp>::to_extension_op(self)
             .expect("Failed to convert to extension op.")
     }
+
+    /// The inverse of a non-parametric gate, if it is also a [`Tk2Op`].
+    ///
+    /// Rotation gates are inverted by changing their angles instead, see
+    /// [`Tk2Op::inverse_angles`].
+    pub fn inverse(&self) -> Option<Tk2Op> {
+        match self {
+            Tk2Op::H | Tk2Op::X | Tk2Op::Y | Tk2Op::Z | Tk2Op::CX | Tk2Op::CZ => Some(*self),
+            Tk2Op::S => Some(Tk2Op::Sdg),
+            Tk2Op::Sdg => Some(Tk2Op::S),
+            Tk2Op::T => Some(Tk2Op::Tdg),
+            Tk2Op::Tdg => Some(Tk2Op::T),
+            _ => None,
+        }
+    }
+
+    /// The angles of the inverse of a rotation gate with the given angles.
+    ///
+    /// The inverse is the same operation applied with the returned angles.
+    /// Returns `None` if the operation is not a rotation, or if the number of
+    /// angles does not match its float inputs.
+    pub fn inverse_angles(&self, angles: &[f64]) -> Option<Vec<f64>> {
+        match (self, angles) {
+            (Tk2Op::RzF64 | Tk2Op::RxF64 | Tk2Op::ZZPhase, &[theta]) => Some(vec![-theta]),
+            (Tk2Op::PhasedX, &[theta, phi]) => Some(vec![-theta, phi]),
+            (Tk2Op::TK1, &[a, b, c]) => Some(vec![-c, -b, -a]),
+            _ => None,
+        }
+    }
 }
 
 /// Whether an op is a given Tk2Op.
@@ -185,6 +215,59 @@ impl Tk2Op {
             AngleAdd | Measure | QAlloc | QFree | Reset => false,
         }
     }
+
+    /// Check if this op is a Clifford gate.
+    ///
+    /// Parametric rotations are never considered Clifford, as their angles are
+    /// not known statically.
+    pub fn is_clifford(&self) -> bool {
+        use Tk2Op::*;
+        match self {
+            H | CX | S | Sdg | X | Y | Z | ZZMax | CZ => true,
+            T | Tdg | RzF64 | RxF64 | PhasedX | ZZPhase | TK1 | AngleAdd | Measure | QAlloc
+            | QFree | Reset => false,
+        }
+    }
+
+    /// Check if this op is a gate diagonal in the computational basis.
+    ///
+    /// Parametric rotations are classified by their axis alone, so `Rz` is
+    /// diagonal even when its angle is symbolic. Non-unitary operations are
+    /// never diagonal.
+    pub fn is_diagonal(&self) -> bool {
+        use Tk2Op::*;
+        match self {
+            Z | S | Sdg | T | Tdg | RzF64 | ZZMax | ZZPhase | CZ => true,
+            H | CX | X | Y | RxF64 | PhasedX | TK1 | AngleAdd | Measure | QAlloc | QFree
+            | Reset => false,
+        }
+    }
+
+    /// The global phase of the gate, in half-turns, relative to its canonical
+    /// form.
+    ///
+    /// The canonical form of a gate is the rotation `exp(-iπt/2 P)` by `t`
+    /// half-turns about its Pauli generator `P`, as used by `Rz`, `Rx` and
+    /// `ZZPhase`. For example `S = exp(iπ/4) Rz(π/2)`, so `S` has a global
+    /// phase of `0.25`, while `ZZMax` is exactly `ZZPhase(π/2)` and has no
+    /// global phase. `CX` and `CZ` are products of commuting rotations by
+    /// `π/2` on each qubit and `-π/2` on both.
+    ///
+    /// Passes substituting a gate for another one equal up to a global phase
+    /// must add the difference of their phases to the circuit. Parametric
+    /// rotations and non-unitary operations have no global phase.
+    pub fn global_phase(&self) -> f64 {
+        use Tk2Op::*;
+        match self {
+            H | X | Y | Z => 0.5,
+            S | CX | CZ => 0.25,
+            Sdg => -0.25,
+            T => 0.125,
+            Tdg => -0.125,
+            ZZMax | RzF64 | RxF64 | PhasedX | ZZPhase | TK1 | AngleAdd | Measure | QAlloc
+            | QFree | Reset => 0.,
+        }
+    }
 }
 
 /// Initialize a new custom symbolic expression constant op from a string.
@@ -195,6 +278,242 @@ pub fn symbolic_constant_op(arg: String) -> OpType {
         .into()
 }
 
+/// Initialize a new named placeholder op with the given signature.
+///
+/// Placeholders can be substituted by concrete operations with
+/// [`Circuit::fill_placeholders`](crate::Circuit::fill_placeholders).
+pub fn placeholder_op(name: impl Into<String>, signature: Signature) -> OpType {
+    let types_arg = |row: &hugr::types::TypeRow| TypeArg::Sequence {
+        elems: row.iter().map(|ty| ty.clone().into()).collect(),
+    };
+    let args = vec![
+        name.into().into(),
+        types_arg(&signature.input),
+        types_arg(&signature.output),
+    ];
+    EXTENSION
+        .instantiate_extension_op(&PLACEHOLDER_OP_ID, args, &REGISTRY)
+        .unwrap()
+        .into()
+}
+
+/// Initialize a new qubit permutation op, sending the `i`-th input qubit to
+/// the output `perm[i]`.
+///
+/// Permutations are pure relabellings of the qubits and can be removed from a
+/// circuit with [`push_permutations`](crate::passes::push_permutations).
+///
+/// # Panics
+///
+/// If `perm` is not a permutation of `0..perm.len()`.
+pub fn permutation_op(perm: impl Into<Vec<usize>>) -> OpType {
+    let perm = perm.into();
+    let mut seen = vec![false; perm.len()];
+    for &p in &perm {
+        assert!(
+            p < perm.len() && !std::mem::replace(&mut seen[p], true),
+            "{perm:?} is not a permutation"
+        );
+    }
+    let arg = TypeArg::Sequence {
+        elems: perm
+            .into_iter()
+            .map(|n| TypeArg::BoundedNat { n: n as u64 })
+            .collect(),
+    };
+    EXTENSION
+        .instantiate_extension_op(&PERMUTATION_OP_ID, vec![arg], &REGISTRY)
+        .unwrap()
+        .into()
+}
+
+/// Initialize a new classical register comparison op, checking whether a
+/// register of `width` bits holds `value`.
+///
+/// The op takes the bits of the register, least significant first, and
+/// returns a boolean that can be used as the predicate of a `Conditional`.
+/// See [`Register::equals`](crate::circuit::register::Register::equals).
+///
+/// # Panics
+///
+/// If `value` does not fit in `width` bits.
+pub fn register_eq_op(width: usize, value: u64) -> OpType {
+    assert!(
+        width >= 64 || value >> width == 0,
+        "{value} does not fit in a register of {width} bits"
+    );
+    let args = vec![
+        TypeArg::BoundedNat { n: width as u64 },
+        TypeArg::BoundedNat { n: value },
+    ];
+    EXTENSION
+        .instantiate_extension_op(&REGISTER_EQ_OP_ID, args, &REGISTRY)
+        .unwrap()
+        .into()
+}
+
+/// A user-defined gate without a [`Tk2Op`] counterpart, such as a
+/// hardware-specific unitary.
+///
+/// Custom gates act on `num_qubits` qubits and take `num_params` angle inputs
+/// after the qubits. Optimisation passes do not know their semantics, so they
+/// are treated as opaque barriers. They are emitted by name when exporting a
+/// circuit to OpenQASM 3. Pytket requires a definition for its custom gates,
+/// so circuits containing them cannot be encoded as pytket circuits.
+///
+/// Convert a gate into an operation with [`OpType::from`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CustomGate {
+    /// The name of the gate.
+    pub name: String,
+    /// The number of qubits the gate acts on.
+    pub num_qubits: usize,
+    /// The number of angle parameters of the gate.
+    pub num_params: usize,
+    /// The name of the inverse gate, if known.
+    ///
+    /// The inverse has the same qubits and parameters.
+    #[serde(default)]
+    pub dagger: Option<String>,
+}
+
+impl CustomGate {
+    /// Define a new custom gate.
+    pub fn new(name: impl Into<String>, num_qubits: usize, num_params: usize) -> Self {
+        Self {
+            name: name.into(),
+            num_qubits,
+            num_params,
+            dagger: None,
+        }
+    }
+
+    /// Set the name of the inverse gate.
+    pub fn with_dagger(mut self, dagger: impl Into<String>) -> Self {
+        self.dagger = Some(dagger.into());
+        self
+    }
+
+    /// Returns the inverse gate, if known.
+    ///
+    /// The inverse of the returned gate is this gate.
+    pub fn dagger(&self) -> Option<Self> {
+        let name = self.dagger.clone()?;
+        Some(Self {
+            name,
+            dagger: Some(self.name.clone()),
+            ..*self
+        })
+    }
+
+    /// The signature of the gate.
+    ///
+    /// The gate takes `num_qubits` qubits followed by `num_params` `f64`
+    /// inputs, and returns the qubits.
+    pub fn signature(&self) -> Signature {
+        let qubits = vec![QB_T; self.num_qubits];
+        let params = vec![FLOAT64_TYPE; self.num_params];
+        Signature::new([qubits.clone(), params].concat(), qubits)
+    }
+}
+
+impl From<&CustomGate> for OpType {
+    fn from(gate: &CustomGate) -> Self {
+        let arg = serde_json::to_string(gate).unwrap();
+        EXTENSION
+            .instantiate_extension_op(&CUSTOM_GATE_OP_ID, vec![arg.into()], &REGISTRY)
+            .unwrap()
+            .into()
+    }
+}
+
+impl From<CustomGate> for OpType {
+    fn from(gate: CustomGate) -> Self {
+        (&gate).into()
+    }
+}
+
+/// match against a custom gate op, returning its definition.
+pub(crate) fn match_custom_gate_op(op: &OpType) -> Option<CustomGate> {
+    let OpType::CustomOp(custom_op) = op else {
+        return None;
+    };
+    let (name, ext, args) = match custom_op {
+        CustomOp::Extension(e) => (e.def().name(), e.def().extension(), e.args()),
+        CustomOp::Opaque(e) => (e.name(), e.extension(), e.args()),
+    };
+    if name != &CUSTOM_GATE_OP_ID || ext != &EXTENSION_ID {
+        return None;
+    }
+    match args {
+        [TypeArg::String { arg }] => serde_json::from_str(arg).ok(),
+        _ => panic!("Found an invalid type arg in a custom gate operation node."),
+    }
+}
+
+/// match against a classical register comparison op, returning the width of
+/// the register and the compared value.
+pub(crate) fn match_register_eq_op(op: &OpType) -> Option<(usize, u64)> {
+    let OpType::CustomOp(custom_op) = op else {
+        return None;
+    };
+    let (name, ext, args) = match custom_op {
+        CustomOp::Extension(e) => (e.def().name(), e.def().extension(), e.args()),
+        CustomOp::Opaque(e) => (e.name(), e.extension(), e.args()),
+    };
+    if name != &REGISTER_EQ_OP_ID || ext != &EXTENSION_ID {
+        return None;
+    }
+    match args {
+        [TypeArg::BoundedNat { n: width }, TypeArg::BoundedNat { n: value }] => {
+            Some((*width as usize, *value))
+        }
+        _ => panic!("Found an invalid type arg in a register comparison operation node."),
+    }
+}
+
+/// match against a qubit permutation op, returning the permutation.
+pub(crate) fn match_permutation_op(op: &OpType) -> Option<Vec<usize>> {
+    let OpType::CustomOp(custom_op) = op else {
+        return None;
+    };
+    let (name, ext, args) = match custom_op {
+        CustomOp::Extension(e) => (e.def().name(), e.def().extension(), e.args()),
+        CustomOp::Opaque(e) => (e.name(), e.extension(), e.args()),
+    };
+    if name != &PERMUTATION_OP_ID || ext != &EXTENSION_ID {
+        return None;
+    }
+    match args {
+        [TypeArg::Sequence { elems }] => elems
+            .iter()
+            .map(|elem| match elem {
+                TypeArg::BoundedNat { n } => Some(*n as usize),
+                _ => None,
+            })
+            .collect(),
+        _ => panic!("Found an invalid type arg in a permutation operation node."),
+    }
+}
+
+/// match against a placeholder op, returning its name.
+pub(crate) fn match_placeholder_op(op: &OpType) -> Option<String> {
+    let OpType::CustomOp(custom_op) = op else {
+        return None;
+    };
+    let (name, ext, args) = match custom_op {
+        CustomOp::Extension(e) => (e.def().name(), e.def().extension(), e.args()),
+        CustomOp::Opaque(e) => (e.name(), e.extension(), e.args()),
+    };
+    if name != &PLACEHOLDER_OP_ID || ext != &EXTENSION_ID {
+        return None;
+    }
+    match args.first() {
+        Some(TypeArg::String { arg }) => Some(arg.clone()),
+        _ => panic!("Found an invalid type arg in a placeholder operation node."),
+    }
+}
+
 /// match against a symbolic constant
 pub(crate) fn match_symb_const_op(op: &OpType) -> Option<String> {
     // Extract the symbol for a symbolic operation node.
@@ -249,15 +568,18 @@ pub(crate) mod test {
     use std::sync::Arc;
 
     use hugr::extension::simple_op::MakeOpDef;
-    use hugr::extension::OpDef;
-    use hugr::ops::NamedOp;
-    use hugr::CircuitUnit;
+    use hugr::extension::{OpDef, SignatureError};
+    use hugr::ops::{NamedOp, OpTrait};
+    use hugr::{CircuitUnit, HugrView};
     use rstest::{fixture, rstest};
     use strum::IntoEnumIterator;
 
-    use super::Tk2Op;
+    use super::*;
+    use crate::circuit::simulate::{gate_matrix, matmul};
+    use crate::circuit::units::LinearUnit;
     use crate::circuit::Circuit;
     use crate::extension::{TKET2_EXTENSION as EXTENSION, TKET2_EXTENSION_ID as EXTENSION_ID};
+    use crate::passes::{cleanup, CleanupOptions};
     use crate::utils::build_simple_circuit;
     use crate::Pauli;
     fn get_opdef(op: impl NamedOp) -> Option<&'static Arc<OpDef>> {
@@ -272,6 +594,52 @@ pub(crate) mod test {
         }
     }
 
+    #[test]
+    fn diagonal_ops() {
+        assert!(Tk2Op::RzF64.is_diagonal());
+        assert!(!Tk2Op::RxF64.is_diagonal());
+
+        // The classification matches the matrices of the gates.
+        let angles = [0.3, 0.7, 1.1];
+        for op in Tk2Op::iter().filter(Tk2Op::is_quantum) {
+            let sig = OpType::from(op).dataflow_signature().unwrap();
+            let n_params = sig.input_count() - sig.output_count();
+            let matrix = gate_matrix(op, &angles[..n_params]).unwrap();
+            let dim = (matrix.len() as f64).sqrt() as usize;
+            let diagonal = (0..dim * dim)
+                .filter(|i| i / dim != i % dim)
+                .all(|i| matrix[i].norm() < 1e-12);
+            assert_eq!(op.is_diagonal(), diagonal, "{op:?}");
+        }
+    }
+
+    #[test]
+    fn inverse_angles() {
+        let angles = [0.3, 0.7, 1.1];
+        for op in [
+            Tk2Op::RzF64,
+            Tk2Op::RxF64,
+            Tk2Op::PhasedX,
+            Tk2Op::ZZPhase,
+            Tk2Op::TK1,
+        ] {
+            let sig = OpType::from(op).dataflow_signature().unwrap();
+            let angles = &angles[..sig.input_count() - sig.output_count()];
+            let inverse = op.inverse_angles(angles).unwrap();
+            let product = matmul(
+                &gate_matrix(op, &inverse).unwrap(),
+                &gate_matrix(op, angles).unwrap(),
+            );
+            let dim = (product.len() as f64).sqrt() as usize;
+            for (i, x) in product.iter().enumerate() {
+                let expected = if i / dim == i % dim { 1. } else { 0. };
+                assert!((x - expected).norm() < 1e-12, "{op:?}");
+            }
+        }
+        assert_eq!(Tk2Op::H.inverse_angles(&[]), None);
+        assert_eq!(Tk2Op::RzF64.inverse_angles(&[0.3, 0.7]), None);
+    }
+
     #[fixture]
     pub(crate) fn t2_bell_circuit() -> Circuit {
         let h = build_simple_circuit(2, |circ| {
@@ -337,4 +705,50 @@ pub(crate) mod test {
             assert_eq!(op.qubit_commutation(), &[(0, *pauli)]);
         }
     }
+
+    #[test]
+    fn custom_gate() {
+        let gate = CustomGate::new("MS", 2, 1).with_dagger("MSdg");
+        let dagger = gate.dagger().unwrap();
+        assert_eq!(dagger.name, "MSdg");
+        assert_eq!(dagger.dagger(), Some(gate.clone()));
+        assert_eq!(CustomGate::new("G", 1, 0).dagger(), None);
+
+        let mut circ = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            Ok(())
+        })
+        .unwrap();
+        let op: OpType = CustomGate::new("iSWAP", 2, 0).into();
+        let node = circ
+            .append(op, &[LinearUnit::new(0), LinearUnit::new(1)])
+            .unwrap();
+        circ.hugr().validate(&REGISTRY).unwrap();
+        assert_eq!(
+            match_custom_gate_op(circ.hugr().get_optype(node)),
+            Some(CustomGate::new("iSWAP", 2, 0))
+        );
+        assert_eq!(match_custom_gate_op(&Tk2Op::H.into()), None);
+
+        // Malformed definitions are rejected when computing the signature.
+        let invalid = EXTENSION.instantiate_extension_op(
+            &CUSTOM_GATE_OP_ID,
+            vec![TypeArg::String {
+                arg: "not a gate".to_string(),
+            }],
+            &REGISTRY,
+        );
+        assert_eq!(invalid, Err(SignatureError::InvalidTypeArgs));
+
+        // Custom gates are barriers for the optimisation passes.
+        let circ = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(CustomGate::new("iSWAP", 2, 0), [0, 1])?;
+            circ.append(Tk2Op::H, [0])?;
+            Ok(())
+        })
+        .unwrap();
+        let (circ, _) = cleanup(&circ, CleanupOptions::default()).unwrap();
+        assert_eq!(circ.num_operations(), 3);
+    }
 }
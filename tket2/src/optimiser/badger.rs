@@ -16,17 +16,21 @@ mod hugr_pchannel;
 mod hugr_pqueue;
 pub mod log;
 mod qtz_circuit;
+mod state;
 mod worker;
 
 use crossbeam_channel::select;
 pub use eq_circ_class::{load_eccs_json_file, EqCircClass};
-use fxhash::FxHashSet;
 use hugr::hugr::HugrError;
 use hugr::HugrView;
 pub use log::BadgerLogger;
-use rayon::iter::{IndexedParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
+use rayon::iter::{
+    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefMutIterator, ParallelIterator,
+};
+pub use state::{BadgerState, BadgerStateError};
 
 use std::num::NonZeroUsize;
+use std::path::Path;
 use std::time::{Duration, Instant};
 use std::{mem, thread};
 
@@ -165,39 +169,96 @@ where
         }
     }
 
+    /// Run the Badger optimiser on a batch of circuits.
+    ///
+    /// The circuits are optimised in parallel, each one with the given
+    /// `options`. The results are returned in the same order as the input.
+    ///
+    /// Since the batch is already distributed across threads, it is usually
+    /// best to set [`BadgerOptions::n_threads`] to `1`.
+    pub fn optimise_batch(
+        &self,
+        circs: impl IntoIterator<Item = Circuit>,
+        options: BadgerOptions,
+    ) -> impl Iterator<Item = Circuit> {
+        let circs: Vec<Circuit> = circs.into_iter().collect();
+        let optimised: Vec<Circuit> = circs
+            .into_par_iter()
+            .map(|circ| self.optimise(&circ, options))
+            .collect();
+        optimised.into_iter()
+    }
+
+    /// Run the Badger optimiser on a circuit, periodically saving its search
+    /// state to a checkpoint file.
+    ///
+    /// The search starts from `state`, which is either a fresh
+    /// [`BadgerState::new`] or a state loaded from a previous checkpoint with
+    /// [`BadgerState::load`]. When resuming, the optimiser continues
+    /// processing the saved frontier and skips any circuit it has already
+    /// seen.
+    ///
+    /// The state is saved to `checkpoint` every `checkpoint_interval`
+    /// processed circuits, and once more when the optimisation terminates.
+    /// The optimisation always runs on a single thread, ignoring
+    /// [`BadgerOptions::n_threads`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a checkpoint cannot be written.
+    pub fn optimise_resumable(
+        &self,
+        state: BadgerState,
+        options: BadgerOptions,
+        checkpoint: impl AsRef<Path>,
+        checkpoint_interval: NonZeroUsize,
+    ) -> Result<Circuit, BadgerStateError> {
+        let checkpoint = Some((checkpoint.as_ref(), checkpoint_interval));
+        self.badger_from_state(state, Default::default(), options, checkpoint)
+    }
+
     /// Run the Badger optimiser on a circuit, using a single thread.
     #[tracing::instrument(target = "badger::metrics", skip(self, circ, logger))]
     fn badger(
         &self,
         circ: &Circuit<impl HugrView>,
-        mut logger: BadgerLogger,
+        logger: BadgerLogger,
         opt: BadgerOptions,
     ) -> Circuit {
+        BadgerState::new(circ)
+            .and_then(|state| self.badger_from_state(state, logger, opt, None))
+            .expect("The circuit is hashable and no checkpoints are written.")
+    }
+
+    /// Run the single-threaded Badger optimiser from a saved search state,
+    /// optionally saving checkpoints at regular intervals.
+    fn badger_from_state(
+        &self,
+        state: BadgerState,
+        mut logger: BadgerLogger,
+        opt: BadgerOptions,
+        checkpoint: Option<(&Path, NonZeroUsize)>,
+    ) -> Result<Circuit, BadgerStateError> {
         let start_time = Instant::now();
         let mut last_best_time = Instant::now();
 
-        let circ = circ.to_owned();
-        let mut best_circ = circ.clone();
-        let mut best_circ_cost = self.cost(&circ);
+        let (mut best_circ, frontier, mut seen_hashes, mut circ_cnt) = state.into_parts();
+        let mut best_circ_cost = self.cost(&best_circ);
         let num_rewrites = best_circ.rewrite_trace().map(|rs| rs.len());
         logger.log_best(&best_circ_cost, num_rewrites);
 
-        // Hash of seen circuits. Dot not store circuits as this map gets huge
-        let hash = circ.circuit_hash().unwrap();
-        let mut seen_hashes = FxHashSet::default();
-        seen_hashes.insert(hash);
-
         // The priority queue of circuits to be processed (this should not get big)
         let cost_fn = {
             let strategy = self.strategy.clone();
             move |circ: &'_ Circuit| strategy.circuit_cost(circ)
         };
-        let cost = (cost_fn)(&circ);
-
         let mut pq = HugrPQ::new(cost_fn, opt.queue_size);
-        pq.push_unchecked(circ.to_owned(), hash, cost);
+        for circ in frontier {
+            let hash = circ.circuit_hash().unwrap();
+            let cost = (pq.cost_fn())(&circ);
+            pq.push_unchecked(circ, hash, cost);
+        }
 
-        let mut circ_cnt = 0;
         let mut timeout_flag = false;
         while let Some(Entry { circ, cost, .. }) = pq.pop() {
             if cost < best_circ_cost {
@@ -240,6 +301,13 @@ where
                 logger.log_progress(circ_cnt, Some(pq.len()), seen_hashes.len());
             }
 
+            if let Some((path, interval)) = checkpoint {
+                if circ_cnt % interval.get() == 0 {
+                    BadgerState::from_parts(&best_circ, pq.circuits(), &seen_hashes, circ_cnt)
+                        .save(path)?;
+                }
+            }
+
             if let Some(timeout) = opt.timeout {
                 if start_time.elapsed().as_secs() > timeout {
                     timeout_flag = true;
@@ -260,6 +328,11 @@ where
             }
         }
 
+        if let Some((path, _)) = checkpoint {
+            BadgerState::from_parts(&best_circ, pq.circuits(), &seen_hashes, circ_cnt)
+                .save(path)?;
+        }
+
         logger.log_processing_end(
             circ_cnt,
             Some(seen_hashes.len()),
@@ -268,7 +341,7 @@ where
             timeout_flag,
             start_time.elapsed(),
         );
-        best_circ
+        Ok(best_circ)
     }
 
     /// Run the Badger optimiser on a circuit, using multiple threads.
@@ -549,8 +622,10 @@ mod tests {
     };
     use rstest::{fixture, rstest};
 
-    use crate::optimiser::badger::BadgerOptions;
+    use crate::circuit::CircuitHash;
+    use crate::optimiser::badger::{BadgerOptions, BadgerState};
     use crate::serialize::load_tk1_json_str;
+    use crate::utils::build_simple_circuit;
     use crate::{extension::REGISTRY, Circuit, Tk2Op};
 
     use super::{BadgerOptimiser, DefaultBadgerOptimiser};
@@ -641,6 +716,30 @@ mod tests {
         assert_eq!(gates(&opt_rz), vec![Tk2Op::AngleAdd, Tk2Op::RzF64]);
     }
 
+    #[rstest]
+    fn optimise_batch(
+        rz_rz: Circuit,
+        non_composable_rw_hugr: Circuit,
+        badger_opt_compiled: DefaultBadgerOptimiser,
+    ) {
+        let options = BadgerOptions {
+            queue_size: 4,
+            max_circuit_count: Some(50),
+            ..Default::default()
+        };
+        let circs = vec![rz_rz, non_composable_rw_hugr];
+        let expected: Vec<_> = circs
+            .iter()
+            .map(|circ| badger_opt_compiled.optimise(circ, options))
+            .collect();
+
+        let batch: Vec<_> = badger_opt_compiled.optimise_batch(circs, options).collect();
+        assert_eq!(batch.len(), expected.len());
+        for (circ, exp) in batch.iter().zip(&expected) {
+            assert_eq!(circ.circuit_hash().unwrap(), exp.circuit_hash().unwrap());
+        }
+    }
+
     #[rstest]
     #[case::compiled(badger_opt_compiled())]
     #[case::json(badger_opt_json())]
@@ -696,6 +795,52 @@ mod tests {
         opt.hugr_mut().update_validate(&REGISTRY).unwrap();
     }
 
+    #[rstest]
+    fn checkpoint_resume(badger_opt_json: DefaultBadgerOptimiser) {
+        let circ = build_simple_circuit(2, |circ| {
+            for q in [0, 1, 0] {
+                circ.append(Tk2Op::T, [q])?;
+                circ.append(Tk2Op::X, [q])?;
+                circ.append(Tk2Op::Tdg, [q])?;
+                circ.append(Tk2Op::H, [q])?;
+                circ.append(Tk2Op::CX, [0, 1])?;
+            }
+            Ok(())
+        })
+        .unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let checkpoint = dir.path().join("checkpoint.json");
+        let options = |max_circuit_count| BadgerOptions {
+            queue_size: 4,
+            max_circuit_count: Some(max_circuit_count),
+            ..Default::default()
+        };
+        let interval = 2.try_into().unwrap();
+
+        let state = BadgerState::new(&circ).unwrap();
+        let opt = badger_opt_json
+            .optimise_resumable(state, options(10), &checkpoint, interval)
+            .unwrap();
+        let saved = BadgerState::load(&checkpoint).unwrap();
+        assert!(saved.seen_count() >= 10);
+        assert!(saved.frontier_size() > 0);
+        let saved_count = saved.circuit_count();
+        let saved_best = saved.best_circuit();
+        assert_eq!(
+            badger_opt_json.cost(&saved_best),
+            badger_opt_json.cost(&opt)
+        );
+
+        let resumed = badger_opt_json
+            .optimise_resumable(saved, options(50), &checkpoint, interval)
+            .unwrap();
+        let resumed_state = BadgerState::load(&checkpoint).unwrap();
+
+        assert!(resumed_state.circuit_count() > saved_count);
+        assert!(badger_opt_json.cost(&resumed) <= badger_opt_json.cost(&saved_best));
+        resumed.hugr().validate(&REGISTRY).unwrap();
+    }
+
     #[test]
     fn load_precompiled_bin() {
         let opt =
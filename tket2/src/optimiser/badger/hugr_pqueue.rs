@@ -106,8 +106,12 @@ impl<P: Ord, C> HugrPQ<P, C> {
         }
     }
 
+    /// Iterate over the circuits in the queue, in no particular order.
+    pub fn circuits(&self) -> impl Iterator<Item = &Circuit> {
+        self.hash_lookup.values()
+    }
+
     /// The cost function used by the queue.
-    #[allow(unused)]
     pub fn cost_fn(&self) -> &C {
         &self.cost_fn
     }
@@ -4,13 +4,15 @@ use std::path::Path;
 
 use hugr::builder::{DFGBuilder, Dataflow, DataflowHugr};
 use hugr::extension::prelude::QB_T;
+use hugr::hugr::hugrmut::HugrMut;
 use hugr::ops::OpType as Op;
 use hugr::std_extensions::arithmetic::float_types::FLOAT64_TYPE;
 use hugr::types::{Signature, Type};
-use hugr::{CircuitUnit, Hugr};
+use hugr::{CircuitUnit, Hugr, HugrView};
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
+use crate::serialize::pytket::METADATA_PHASE;
 use crate::{Circuit, Tk2Op};
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -31,6 +33,10 @@ struct MetaData {
     num_gates: u64,
     id: Vec<String>,
     fingerprint: Vec<f64>,
+    /// Global phase of the circuit in radians, relative to the other circuits
+    /// of its equivalence class. Optional, defaults to zero.
+    #[serde(default)]
+    phase: f64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -39,9 +45,9 @@ struct RepCircData {
     circ: RepCirc,
 }
 
-fn map_op(opstr: &str) -> Op {
+fn map_op(opstr: &str) -> Option<Op> {
     // TODO, more
-    match opstr {
+    let op = match opstr {
         "h" => Tk2Op::H,
         "cx" => Tk2Op::CX,
         "t" => Tk2Op::T,
@@ -53,9 +59,9 @@ fn map_op(opstr: &str) -> Op {
         "sdg" => Tk2Op::Sdg,
         "rz" => Tk2Op::RzF64,
         "add" => Tk2Op::AngleAdd,
-        x => panic!("unknown op {x}"),
-    }
-    .into()
+        _ => return None,
+    };
+    Some(op.into())
 }
 
 // TODO change to TryFrom
@@ -91,7 +97,7 @@ impl From<RepCircData> for Circuit<Hugr> {
             outputs,
         } in rc.0
         {
-            let op = map_op(&opstr);
+            let op = map_op(&opstr).unwrap_or_else(|| panic!("unknown op {opstr}"));
 
             // Translate the quartz inputs into circuit units.
             let inputs = inputs.iter().map(|inp| *input_units.get(inp).unwrap());
@@ -103,10 +109,15 @@ impl From<RepCircData> for Circuit<Hugr> {
         }
 
         let circ_outputs = circ.finish();
-        builder
+        let mut hugr = builder
             .finish_hugr_with_outputs(circ_outputs, &crate::extension::REGISTRY)
-            .unwrap()
-            .into()
+            .unwrap();
+        if meta.phase != 0.0 {
+            let root = hugr.root();
+            let half_turns = meta.phase / std::f64::consts::PI;
+            hugr.set_metadata(root, METADATA_PHASE, half_turns.to_string());
+        }
+        hugr.into()
     }
 }
 
@@ -114,8 +125,22 @@ pub(super) fn load_ecc_set(
     path: impl AsRef<Path>,
 ) -> io::Result<HashMap<String, Vec<Circuit<Hugr>>>> {
     let jsons = std::fs::read_to_string(path)?;
+    parse_ecc_set(&jsons)
+}
+
+/// Parse a set of equivalence classes in the Quartz JSON format.
+///
+/// The file structure is validated before decoding the circuits, so malformed
+/// files produce an [`io::ErrorKind::InvalidData`] error pointing at the
+/// offending class and field.
+fn parse_ecc_set(jsons: &str) -> io::Result<HashMap<String, Vec<Circuit<Hugr>>>> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+    let value: serde_json::Value =
+        serde_json::from_str(jsons).map_err(|e| invalid(format!("Invalid ECC file: {e}")))?;
+    validate_ecc_set(&value).map_err(|msg| invalid(format!("Invalid ECC file: {msg}")))?;
+
     let (_, ecc_map): (Vec<()>, HashMap<String, Vec<RepCircData>>) =
-        serde_json::from_str(&jsons).unwrap();
+        serde_json::from_value(value).map_err(|e| invalid(format!("Invalid ECC file: {e}")))?;
 
     Ok(ecc_map
         .into_values()
@@ -127,9 +152,66 @@ pub(super) fn load_ecc_set(
         .collect())
 }
 
+/// Check the structure of a Quartz ECC file, returning a description of the
+/// first problem found.
+///
+/// The expected structure is `[[], {class_id: [[metadata, gates], ...], ...}]`,
+/// where each class is a non-empty list of circuits.
+fn validate_ecc_set(value: &serde_json::Value) -> Result<(), String> {
+    use serde_json::Value;
+
+    let classes = match value.as_array().map(Vec::as_slice) {
+        Some([_, Value::Object(classes)]) => classes,
+        _ => return Err("expected a top-level list `[[], {...}]` of two elements".to_string()),
+    };
+    for (class_idx, (class_id, circs)) in classes.iter().enumerate() {
+        let class_err = |msg: String| format!("class {class_idx} ('{class_id}'): {msg}");
+        let circs = match circs.as_array() {
+            Some(circs) if !circs.is_empty() => circs,
+            _ => {
+                return Err(class_err(
+                    "expected a non-empty list of circuits".to_string(),
+                ))
+            }
+        };
+        for (circ_idx, circ) in circs.iter().enumerate() {
+            let circ_err = |msg: &str| class_err(format!("circuit {circ_idx}: {msg}"));
+            let Some([meta, gates]) = circ.as_array().map(Vec::as_slice) else {
+                return Err(circ_err("expected a list `[metadata, gates]`"));
+            };
+            match meta.as_array().map(Vec::as_slice) {
+                Some([n_qb, n_in, n_total, n_gates, Value::Array(id), Value::Array(_), ..])
+                    if [n_qb, n_in, n_total, n_gates].iter().all(|n| n.is_u64())
+                        && id.first().is_some_and(Value::is_string) => {}
+                _ => {
+                    return Err(circ_err(
+                        "invalid metadata, expected `[n_qb, n_input_param, n_total_param, num_gates, [id, ...], fingerprint]`",
+                    ))
+                }
+            }
+            let Some(gates) = gates.as_array() else {
+                return Err(circ_err("expected a list of gates"));
+            };
+            for (gate_idx, gate) in gates.iter().enumerate() {
+                let gate_err = |msg: &str| circ_err(&format!("gate {gate_idx}: {msg}"));
+                let Some([Value::String(op), Value::Array(_), Value::Array(_)]) =
+                    gate.as_array().map(Vec::as_slice)
+                else {
+                    return Err(gate_err("expected a list `[op, inputs, outputs]`"));
+                };
+                if map_op(op).is_none() {
+                    return Err(gate_err(&format!("unknown op '{op}'")));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rstest::rstest;
 
     fn load_representative_set(path: &str) -> HashMap<String, Circuit<Hugr>> {
         let jsons = std::fs::read_to_string(path).unwrap();
@@ -150,6 +232,31 @@ mod tests {
         }
     }
 
+    #[rstest]
+    #[case::truncated(r#"[[], {"0_2": [[[1,0,0,2,["a"],[0.1"#, "EOF while parsing")]
+    #[case::not_a_pair(r#"{"0_2": []}"#, "top-level list")]
+    #[case::empty_class(r#"[[], {"0_2": []}]"#, "class 0 ('0_2'): expected a non-empty list")]
+    #[case::missing_gates(
+        r#"[[], {"0_2": [[[1,0,0,2,["a"],[0.1,0.2]]]]}]"#,
+        "class 0 ('0_2'): circuit 0: expected a list `[metadata, gates]`"
+    )]
+    #[case::bad_metadata(
+        r#"[[], {"0_2": [[[1,0,0,["a"],[0.1,0.2]], []]]}]"#,
+        "class 0 ('0_2'): circuit 0: invalid metadata"
+    )]
+    #[case::unknown_op(
+        r#"[[], {"0_2": [[[1,0,0,1,["a"],[0.1,0.2]], [["foo", ["Q0"], ["Q0"]]]]]}]"#,
+        "class 0 ('0_2'): circuit 0: gate 0: unknown op 'foo'"
+    )]
+    fn invalid_ecc_file(#[case] json: &str, #[case] expected: &str) {
+        let err = parse_ecc_set(json).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(
+            err.to_string().contains(expected),
+            "'{err}' does not contain '{expected}'"
+        );
+    }
+
     #[test]
     #[cfg_attr(miri, ignore)] // Opening files is not supported in (isolated) miri
     fn test_read_complete() {
@@ -0,0 +1,165 @@
+//! Checkpoints of the search state of the Badger optimiser.
+//!
+//! A [`BadgerState`] captures everything needed to continue an interrupted
+//! optimisation run: the best circuit found so far, the frontier of
+//! candidate circuits still in the priority queue, the hashes of all the
+//! circuits already visited, and the number of circuits processed.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+use fxhash::FxHashSet;
+use hugr::hugr::hugrmut::HugrMut;
+use hugr::{Hugr, HugrView};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::circuit::{CircuitHash, HashError};
+use crate::Circuit;
+
+/// Metadata key marking the parent node of a circuit while it is serialised.
+///
+/// Serialising a HUGR renumbers its nodes, so the circuit parent cannot be
+/// stored as a node index.
+const METADATA_CHECKPOINT_PARENT: &str = "TKET2.badger_checkpoint_parent";
+
+/// The search state of a Badger optimisation run.
+///
+/// See [`BadgerOptimiser::optimise_resumable`] for running the optimiser
+/// with periodic checkpoints.
+///
+///   [`BadgerOptimiser::optimise_resumable`]: super::BadgerOptimiser::optimise_resumable
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BadgerState {
+    best: SavedCircuit,
+    frontier: Vec<SavedCircuit>,
+    seen_hashes: FxHashSet<u64>,
+    circ_cnt: usize,
+}
+
+impl BadgerState {
+    /// Create the initial state of an optimisation run on a circuit.
+    ///
+    /// Returns an error if the circuit cannot be hashed.
+    pub fn new(circ: &Circuit<impl HugrView>) -> Result<Self, BadgerStateError> {
+        let circ = circ.to_owned();
+        let hash = circ.circuit_hash()?;
+        Ok(Self {
+            best: SavedCircuit::new(circ.clone()),
+            frontier: vec![SavedCircuit::new(circ)],
+            seen_hashes: [hash].into_iter().collect(),
+            circ_cnt: 0,
+        })
+    }
+
+    /// Capture the state of a running optimisation.
+    pub(super) fn from_parts<'c>(
+        best: &Circuit,
+        frontier: impl IntoIterator<Item = &'c Circuit>,
+        seen_hashes: &FxHashSet<u64>,
+        circ_cnt: usize,
+    ) -> Self {
+        Self {
+            best: SavedCircuit::new(best.clone()),
+            frontier: frontier
+                .into_iter()
+                .map(|c| SavedCircuit::new(c.clone()))
+                .collect(),
+            seen_hashes: seen_hashes.clone(),
+            circ_cnt,
+        }
+    }
+
+    /// Split the state into the best circuit, the frontier, the visited
+    /// hashes and the circuit count.
+    pub(super) fn into_parts(self) -> (Circuit, Vec<Circuit>, FxHashSet<u64>, usize) {
+        let frontier = self.frontier.into_iter().map(SavedCircuit::into_circuit);
+        (
+            self.best.into_circuit(),
+            frontier.collect(),
+            self.seen_hashes,
+            self.circ_cnt,
+        )
+    }
+
+    /// The best circuit found so far.
+    pub fn best_circuit(&self) -> Circuit {
+        self.best.clone().into_circuit()
+    }
+
+    /// The number of circuits in the frontier that remain to be processed.
+    pub fn frontier_size(&self) -> usize {
+        self.frontier.len()
+    }
+
+    /// The number of distinct circuits seen so far.
+    pub fn seen_count(&self) -> usize {
+        self.seen_hashes.len()
+    }
+
+    /// The number of circuits processed so far.
+    pub fn circuit_count(&self) -> usize {
+        self.circ_cnt
+    }
+
+    /// Save the state to a file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), BadgerStateError> {
+        let file = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(file, self)?;
+        Ok(())
+    }
+
+    /// Load a state previously saved with [`BadgerState::save`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, BadgerStateError> {
+        let file = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(file)?)
+    }
+}
+
+/// A circuit in a serialisable form, with its parent node marked by
+/// metadata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+struct SavedCircuit {
+    hugr: Hugr,
+}
+
+impl SavedCircuit {
+    fn new(circ: Circuit) -> Self {
+        let parent = circ.parent();
+        let mut hugr = circ.into_hugr();
+        hugr.set_metadata(parent, METADATA_CHECKPOINT_PARENT, true);
+        Self { hugr }
+    }
+
+    fn into_circuit(self) -> Circuit {
+        let mut hugr = self.hugr;
+        let parent = hugr
+            .nodes()
+            .find(|&n| hugr.get_metadata(n, METADATA_CHECKPOINT_PARENT).is_some())
+            .unwrap_or(hugr.root());
+        if let Some(mut metadata) = hugr.take_node_metadata(parent) {
+            metadata.remove(METADATA_CHECKPOINT_PARENT);
+            if !metadata.is_empty() {
+                hugr.overwrite_node_metadata(parent, Some(metadata));
+            }
+        }
+        Circuit::new(hugr, parent)
+    }
+}
+
+/// Error while saving or loading a [`BadgerState`].
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum BadgerStateError {
+    /// An IO error occurred while reading or writing the checkpoint file.
+    #[error("IO error: {0}")]
+    IoError(#[from] io::Error),
+    /// The state could not be (de)serialised.
+    #[error("Serialisation error: {0}")]
+    SerdeError(#[from] serde_json::Error),
+    /// The initial circuit could not be hashed.
+    #[error("Hash error: {0}")]
+    HashError(#[from] HashError),
+}
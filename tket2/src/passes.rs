@@ -3,9 +3,44 @@
 mod commutation;
 pub use commutation::{apply_greedy_commutation, PullForwardError};
 
+pub mod classical;
+pub use classical::dead_bit_elimination;
+
+pub mod cleanup;
+pub use cleanup::{cleanup, remove_approx_noops, CleanupOptions, UnitaryPassError};
+
+pub mod context;
+pub use context::{run_passes, Pass, PassContext, PassPipeline, PassWarning, WarningKind};
+
+pub mod decompose;
+pub use decompose::{
+    decompose_boxes, decompose_to_cx, rebase_with, substitute_op, Decomposition, RebaseError,
+};
+
+pub mod fuse_phasedx;
+pub use fuse_phasedx::fuse_phasedx;
+
+pub mod measurements;
+pub use measurements::{push_measurements_to_end, PushMeasurementsError};
+
+pub mod normalise_angles;
+pub use normalise_angles::normalise_angles;
+
+pub mod predicate;
+pub use predicate::GatePredicate;
+
+pub mod permutation;
+pub use permutation::push_permutations;
+
 pub mod chunks;
 pub use chunks::CircuitChunks;
 
+pub mod resynthesis;
+pub use resynthesis::resynthesise_2q;
+
+pub mod squash;
+pub use squash::squash_rz_pi_over_4;
+
 pub mod pytket;
 pub use pytket::lower_to_pytket;
 
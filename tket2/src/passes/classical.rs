@@ -0,0 +1,173 @@
+//! Passes acting on the classical data of a circuit.
+
+use hugr::hugr::hugrmut::HugrMut;
+use hugr::{HugrView, IncomingPort, Node, OutgoingPort};
+use itertools::Itertools;
+
+use super::GatePredicate;
+use crate::{Circuit, Tk2Op};
+
+/// Remove measurements whose result bit is never read.
+///
+/// A `Measure` is removed when its bit output has no consumers and the
+/// measured qubit is immediately freed or reset, so that the collapse of the
+/// qubit state cannot be observed. Measurements followed by other operations
+/// on the qubit are kept, as they are not equivalent to the identity.
+///
+/// Bits connected to the circuit outputs, to conditionals, or to any other
+/// operation (including result-reporting operations) count as read, and
+/// their measurements are always kept.
+///
+/// Returns the new circuit and the number of measurements removed.
+pub fn dead_bit_elimination(circ: &Circuit<impl HugrView>) -> (Circuit, usize) {
+    let mut circ = circ.to_owned();
+    let is_measure = GatePredicate::op(Tk2Op::Measure);
+    let dead = circ
+        .commands()
+        .filter(|cmd| is_measure.matches_command(cmd))
+        .filter_map(|cmd| Some((cmd.node(), dead_measurement(&circ, cmd.node())?)))
+        .collect_vec();
+
+    let removed = dead.len();
+    let hugr = circ.hugr_mut();
+    for (measure, (next, next_port)) in dead {
+        let (prev, prev_port) = hugr
+            .single_linked_output(measure, IncomingPort::from(0))
+            .expect("Measure input must be connected");
+        hugr.remove_node(measure);
+        hugr.connect(prev, prev_port, next, next_port);
+    }
+    (circ, removed)
+}
+
+/// If `measure` is a measurement with an unread bit whose qubit is
+/// immediately discarded, returns the port consuming the measured qubit.
+fn dead_measurement(circ: &Circuit, measure: Node) -> Option<(Node, IncomingPort)> {
+    let hugr = circ.hugr();
+    let bit_port = OutgoingPort::from(1);
+    if hugr.linked_inputs(measure, bit_port).next().is_some() {
+        return None;
+    }
+    let (next, next_port) = hugr.single_linked_input(measure, OutgoingPort::from(0))?;
+    let discards = matches!(
+        Tk2Op::try_from(hugr.get_optype(next)),
+        Ok(Tk2Op::QFree | Tk2Op::Reset)
+    );
+    discards.then_some((next, next_port))
+}
+
+#[cfg(test)]
+mod test {
+    use hugr::builder::{
+        Dataflow, DataflowHugr, DataflowSubContainer, FunctionBuilder, SubContainer,
+    };
+    use hugr::extension::prelude::{BOOL_T, QB_T};
+    use hugr::types::Signature;
+    use hugr::{type_row, Hugr};
+
+    use super::*;
+    use crate::extension::REGISTRY;
+
+    /// Two measured qubits that are then freed. The bit of the second
+    /// measurement controls an `X` gate before the qubit is freed.
+    fn measure_circ() -> Circuit {
+        let mut h = FunctionBuilder::new(
+            "measure",
+            Signature::new(type_row![QB_T, QB_T], type_row![]),
+        )
+        .unwrap();
+        let [q0, q1] = h.input_wires_arr();
+
+        // Measurement into an unused bit.
+        let q0 = h.add_dataflow_op(Tk2Op::H, [q0]).unwrap().out_wire(0);
+        let [q0, _] = h
+            .add_dataflow_op(Tk2Op::Measure, [q0])
+            .unwrap()
+            .outputs_arr();
+        h.add_dataflow_op(Tk2Op::QFree, [q0]).unwrap();
+
+        // Measurement feeding a conditional.
+        let [q1, bit] = h
+            .add_dataflow_op(Tk2Op::Measure, [q1])
+            .unwrap()
+            .outputs_arr();
+        let mut cond = h
+            .conditional_builder(
+                ([type_row![], type_row![]], bit),
+                [(QB_T, q1)],
+                type_row![QB_T],
+            )
+            .unwrap();
+        let case0 = cond.case_builder(0).unwrap();
+        let [q] = case0.input_wires_arr();
+        case0.finish_with_outputs([q]).unwrap();
+        let mut case1 = cond.case_builder(1).unwrap();
+        let [q] = case1.input_wires_arr();
+        let q = case1.add_dataflow_op(Tk2Op::X, [q]).unwrap().out_wire(0);
+        case1.finish_with_outputs([q]).unwrap();
+        let [q1] = cond.finish_sub_container().unwrap().outputs_arr();
+        h.add_dataflow_op(Tk2Op::QFree, [q1]).unwrap();
+
+        let hugr: Hugr = h.finish_hugr_with_outputs([], &REGISTRY).unwrap();
+        hugr.into()
+    }
+
+    fn count_measurements(circ: &Circuit) -> usize {
+        circ.commands()
+            .filter(|cmd| Tk2Op::try_from(cmd.optype()) == Ok(Tk2Op::Measure))
+            .count()
+    }
+
+    #[test]
+    fn eliminate_dead_bit() {
+        let circ = measure_circ();
+        assert_eq!(count_measurements(&circ), 2);
+
+        let (circ, removed) = dead_bit_elimination(&circ);
+        circ.hugr().validate(&REGISTRY).unwrap();
+        assert_eq!(removed, 1);
+        assert_eq!(count_measurements(&circ), 1);
+
+        // The remaining measurement feeds the conditional.
+        let measure = circ
+            .commands()
+            .find(|cmd| Tk2Op::try_from(cmd.optype()) == Ok(Tk2Op::Measure))
+            .unwrap()
+            .node();
+        let (reader, _) = circ
+            .hugr()
+            .single_linked_input(measure, OutgoingPort::from(1))
+            .unwrap();
+        assert!(circ.hugr().get_optype(reader).is_conditional());
+
+        // The qubit of the removed measurement is freed right after the `H`.
+        let h_gate = circ
+            .commands()
+            .find(|cmd| Tk2Op::try_from(cmd.optype()) == Ok(Tk2Op::H))
+            .unwrap()
+            .node();
+        let (next, _) = circ
+            .hugr()
+            .single_linked_input(h_gate, OutgoingPort::from(0))
+            .unwrap();
+        assert_eq!(
+            Tk2Op::try_from(circ.hugr().get_optype(next)),
+            Ok(Tk2Op::QFree)
+        );
+
+        // Bits connected to the circuit outputs are kept.
+        let mut h = FunctionBuilder::new(
+            "measure",
+            Signature::new(type_row![QB_T], type_row![BOOL_T]),
+        )
+        .unwrap();
+        let [q] = h.input_wires_arr();
+        let [q, bit] = h
+            .add_dataflow_op(Tk2Op::Measure, [q])
+            .unwrap()
+            .outputs_arr();
+        h.add_dataflow_op(Tk2Op::QFree, [q]).unwrap();
+        let circ: Circuit = h.finish_hugr_with_outputs([bit], &REGISTRY).unwrap().into();
+        assert_eq!(dead_bit_elimination(&circ).1, 0);
+    }
+}
@@ -0,0 +1,566 @@
+//! A combined peephole optimisation pass.
+
+use std::collections::{HashMap, HashSet};
+
+use hugr::builder::{DFGBuilder, Dataflow, DataflowHugr};
+use hugr::extension::prelude::QB_T;
+use hugr::hugr::hugrmut::HugrMut;
+use hugr::ops::NamedOp;
+use hugr::std_extensions::arithmetic::float_types::ConstF64;
+use hugr::types::TypeRow;
+use hugr::{Hugr, HugrView, Node, PortIndex, Wire};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::apply_greedy_commutation;
+use crate::circuit::params::{remove_dead_constants, NumericParams};
+use crate::circuit::{is_unitary_command, BoundedParam};
+use crate::extension::REGISTRY;
+use crate::rewrite::{transfer_error_budget, CircuitRewrite, Subcircuit};
+use crate::serialize::pytket::METADATA_PHASE;
+use crate::{Circuit, Tk2Op};
+
+/// Tolerance used when comparing angles.
+const EPSILON: f64 = 1e-9;
+
+/// Configuration options for [`cleanup`].
+///
+/// Most fields enable one of the interleaved sub-passes, and are all enabled
+/// by default. The `strict` field controls how non-unitary operations are
+/// handled. Missing fields take their default value when deserialising.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CleanupOptions {
+    /// Commute operations towards the start of the circuit, see
+    /// [`apply_greedy_commutation`].
+    pub commute: bool,
+    /// Merge adjacent `Rz`, `Rx` and `ZZPhase` rotations with numeric angles
+    /// acting on the same qubits.
+    pub merge_rotations: bool,
+    /// Cancel adjacent pairs of mutually inverse gates, such as `H·H` or
+    /// `S·Sdg`.
+    pub cancel_inverses: bool,
+    /// Remove rotations by a multiple of 2π, adding their sign to the global
+    /// phase.
+    pub remove_noops: bool,
+    /// Fail with [`UnitaryPassError::NonUnitaryOp`] if the circuit contains a
+    /// non-unitary operation such as `Measure`, instead of treating it as a
+    /// barrier. Disabled by default.
+    pub strict: bool,
+}
+
+impl CleanupOptions {
+    /// Options with every sub-pass disabled, to be combined with the
+    /// sub-passes to run.
+    pub fn none() -> Self {
+        Self {
+            commute: false,
+            merge_rotations: false,
+            cancel_inverses: false,
+            remove_noops: false,
+            strict: false,
+        }
+    }
+}
+
+impl Default for CleanupOptions {
+    fn default() -> Self {
+        Self {
+            commute: true,
+            merge_rotations: true,
+            cancel_inverses: true,
+            remove_noops: true,
+            strict: false,
+        }
+    }
+}
+
+/// Simplify a circuit by interleaving local reductions until none applies.
+///
+/// Each round runs the sub-passes enabled in `options`: noop removal,
+/// rotation merging, inverse-pair cancellation and commutation. Since each
+/// sub-pass can expose reductions for the others, iterating them jointly
+/// finds more simplifications than running each one to a fixpoint on its
+/// own.
+///
+/// Returns the simplified circuit, and whether it was modified. The unitary
+/// of the circuit, including its global phase, is preserved.
+///
+/// # Errors
+///
+/// In [strict mode](CleanupOptions::strict), returns an error if the circuit
+/// contains a non-unitary operation.
+pub fn cleanup(
+    circ: &Circuit<impl HugrView>,
+    options: CleanupOptions,
+) -> Result<(Circuit, bool), UnitaryPassError> {
+    if options.strict {
+        check_unitary(circ)?;
+    }
+    let mut circ = circ.to_owned();
+    let mut changed = false;
+    loop {
+        let mut round_changed = false;
+        if options.remove_noops {
+            round_changed |= apply_all(&mut circ, |circ| find_noops(circ, EPSILON));
+        }
+        if options.merge_rotations {
+            round_changed |= apply_all(&mut circ, find_rotation_merges);
+        }
+        if options.cancel_inverses {
+            round_changed |= apply_all(&mut circ, find_inverse_pairs);
+        }
+        if options.commute {
+            let commuted = apply_greedy_commutation(&mut circ)
+                .expect("Greedy commutation should succeed on a valid circuit.");
+            round_changed |= commuted > 0;
+        }
+        if !round_changed {
+            break;
+        }
+        changed = true;
+    }
+    remove_dead_constants(&mut circ);
+    Ok((circ, changed))
+}
+
+/// Remove rotations whose angle is within `tol` radians of a multiple of 2π,
+/// adding their sign to the global phase.
+///
+/// This is an approximate version of the [noop
+/// removal](CleanupOptions::remove_noops) done by [`cleanup`], which only
+/// removes rotations that are the identity up to floating point error. The
+/// removed rotations are judged with [`BoundedParam::identity_up_to_phase`].
+///
+/// Returns the new circuit, and whether it was modified.
+pub fn remove_approx_noops(circ: &Circuit<impl HugrView>, tol: f64) -> (Circuit, bool) {
+    let mut circ = circ.to_owned();
+    let mut changed = false;
+    while apply_all(&mut circ, |circ| find_noops(circ, tol)) {
+        changed = true;
+    }
+    remove_dead_constants(&mut circ);
+    (circ, changed)
+}
+
+/// Error returned by the unitary passes when running in strict mode.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[non_exhaustive]
+pub enum UnitaryPassError {
+    /// The circuit contains a non-unitary operation.
+    #[error("Operation {0} is not unitary.")]
+    NonUnitaryOp(String),
+}
+
+/// Check that a circuit does not contain non-unitary operations, such as
+/// measurements or qubit allocations. See [`Circuit::is_unitary`].
+pub(super) fn check_unitary(circ: &Circuit<impl HugrView>) -> Result<(), UnitaryPassError> {
+    match circ.commands().find(|cmd| !is_unitary_command(circ, cmd)) {
+        Some(cmd) => Err(UnitaryPassError::NonUnitaryOp(
+            cmd.optype().name().to_string(),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Apply the non-overlapping rewrites returned by `find`. Returns `true` if
+/// any rewrite was applied.
+fn apply_all(circ: &mut Circuit, find: impl Fn(&Circuit) -> Vec<CircuitRewrite>) -> bool {
+    let rewrites = find(circ);
+    let changed = !rewrites.is_empty();
+    for rewrite in rewrites {
+        rewrite
+            .apply(circ)
+            .expect("Cleanup rewrites should be valid replacements.");
+    }
+    changed
+}
+
+/// The numeric angles of the operations in a circuit.
+type NodeAngles = HashMap<Node, Vec<f64>>;
+
+/// The [`Tk2Op`]s in the circuit in a topological order, along with their
+/// numeric angles.
+fn tk2_ops(circ: &Circuit) -> (Vec<(Node, Tk2Op)>, NodeAngles) {
+    let mut params = NumericParams::default();
+    let mut ops = Vec::new();
+    let mut angles = HashMap::new();
+    for cmd in circ.commands() {
+        if params.process(&cmd).unwrap_or(true) {
+            continue;
+        }
+        let Ok(op) = Tk2Op::try_from(cmd.optype()) else {
+            continue;
+        };
+        if let Ok(values) = params.inputs(&cmd) {
+            angles.insert(cmd.node(), values);
+        }
+        ops.push((cmd.node(), op));
+    }
+    (ops, angles)
+}
+
+/// Returns the node following `node` on all of its qubits, if it is the
+/// same for every qubit and each qubit stays on the same port.
+fn qubit_successor(circ: &Circuit, node: Node) -> Option<Node> {
+    let hugr = circ.hugr();
+    let n_qubits = hugr
+        .out_value_types(node)
+        .filter(|(_, ty)| ty == &QB_T)
+        .count();
+    let successors: Vec<_> = (0..n_qubits)
+        .map(|port| {
+            let (next, next_port) = hugr.single_linked_input(node, port)?;
+            (next_port.index() == port).then_some(next)
+        })
+        .collect::<Option<_>>()?;
+    let next = successors.into_iter().all_equal_value().ok()?;
+    let next_qubits = hugr
+        .in_value_types(next)
+        .filter(|(_, ty)| ty == &QB_T)
+        .count();
+    (next_qubits == n_qubits).then_some(next)
+}
+
+/// Rewrites removing rotations that implement `±I`, up to an angle
+/// tolerance `tol` in radians.
+fn find_noops(circ: &Circuit, tol: f64) -> Vec<CircuitRewrite> {
+    let (ops, angles) = tk2_ops(circ);
+    ops.into_iter()
+        .filter_map(|(node, op)| {
+            // `PhasedX(θ, φ)` is the identity for any `φ` when `θ = 0`.
+            let angle = match op {
+                Tk2Op::RzF64 | Tk2Op::RxF64 | Tk2Op::ZZPhase | Tk2Op::PhasedX => {
+                    angles.get(&node)?[0]
+                }
+                _ => return None,
+            };
+            let phase = BoundedParam::new(angle, tol).identity_up_to_phase()?;
+            Some(replace_nodes(circ, &[node], phase, |_, qubits| qubits))
+        })
+        .collect()
+}
+
+/// Rewrites merging pairs of adjacent rotations around the same axis.
+fn find_rotation_merges(circ: &Circuit) -> Vec<CircuitRewrite> {
+    let (ops, angles) = tk2_ops(circ);
+    let op_of: HashMap<Node, Tk2Op> = ops.iter().copied().collect();
+    let mut merged = HashSet::new();
+    let mut rewrites = Vec::new();
+    for (first, op) in ops {
+        if !matches!(op, Tk2Op::RzF64 | Tk2Op::RxF64 | Tk2Op::ZZPhase)
+            || merged.contains(&first)
+            || !angles.contains_key(&first)
+        {
+            continue;
+        }
+        let Some(second) = qubit_successor(circ, first) else {
+            continue;
+        };
+        if op_of.get(&second) != Some(&op)
+            || merged.contains(&second)
+            || !angles.contains_key(&second)
+        {
+            continue;
+        }
+        merged.extend([first, second]);
+
+        let angle = angles[&first][0] + angles[&second][0];
+        rewrites.push(replace_nodes(circ, &[first, second], 0., |dfg, qubits| {
+            let angle = dfg.add_load_value(ConstF64::new(angle));
+            dfg.add_dataflow_op(op, qubits.into_iter().chain([angle]))
+                .unwrap()
+                .outputs()
+                .collect()
+        }));
+    }
+    rewrites
+}
+
+/// The rotations that could be merged with their successor by
+/// [`find_rotation_merges`] if both angles were numeric.
+pub(super) fn unmerged_symbolic_rotations(circ: &Circuit) -> Vec<(Node, Tk2Op)> {
+    let (ops, angles) = tk2_ops(circ);
+    let op_of: HashMap<Node, Tk2Op> = ops.iter().copied().collect();
+    ops.into_iter()
+        .filter(|&(first, op)| {
+            matches!(op, Tk2Op::RzF64 | Tk2Op::RxF64 | Tk2Op::ZZPhase)
+                && qubit_successor(circ, first).is_some_and(|second| {
+                    op_of.get(&second) == Some(&op)
+                        && !(angles.contains_key(&first) && angles.contains_key(&second))
+                })
+        })
+        .collect()
+}
+
+/// Rewrites cancelling pairs of adjacent mutually inverse gates.
+fn find_inverse_pairs(circ: &Circuit) -> Vec<CircuitRewrite> {
+    let (ops, _) = tk2_ops(circ);
+    let op_of: HashMap<Node, Tk2Op> = ops.iter().copied().collect();
+    let mut cancelled = HashSet::new();
+    let mut rewrites = Vec::new();
+    for (first, op) in ops {
+        let Some(inverse) = op.inverse() else {
+            continue;
+        };
+        if cancelled.contains(&first) {
+            continue;
+        }
+        let Some(second) = qubit_successor(circ, first) else {
+            continue;
+        };
+        if op_of.get(&second) != Some(&inverse) || cancelled.contains(&second) {
+            continue;
+        }
+        cancelled.extend([first, second]);
+        rewrites.push(replace_nodes(circ, &[first, second], 0., |_, qubits| {
+            qubits
+        }));
+    }
+    rewrites
+}
+
+/// Build a rewrite replacing some nodes with a new circuit on the same
+/// qubits, adding `phase` half-turns to the global phase.
+///
+/// `build` receives the qubit inputs of the replacement in order, and returns
+/// its qubit outputs. Any other inputs are left unused.
+pub(super) fn replace_nodes(
+    circ: &Circuit,
+    nodes: &[Node],
+    phase: f64,
+    build: impl FnOnce(&mut DFGBuilder<Hugr>, Vec<Wire>) -> Vec<Wire>,
+) -> CircuitRewrite {
+    let subcirc = Subcircuit::try_from_nodes(nodes.to_vec(), circ).unwrap();
+    let signature = subcirc.signature(circ);
+    let input_types: TypeRow = signature.input().clone();
+    let mut dfg = DFGBuilder::new(signature).unwrap();
+    let qubits = dfg
+        .input_wires()
+        .zip(input_types.iter())
+        .filter(|(_, ty)| *ty == &QB_T)
+        .map(|(wire, _)| wire)
+        .collect_vec();
+    let outputs = build(&mut dfg, qubits);
+    let mut replacement: Circuit = dfg
+        .finish_hugr_with_outputs(outputs, &REGISTRY)
+        .unwrap()
+        .into();
+    if phase != 0. {
+        let root = replacement.parent();
+        replacement
+            .hugr_mut()
+            .set_metadata(root, METADATA_PHASE, phase.to_string());
+    }
+    transfer_error_budget(circ, nodes, &mut replacement);
+    subcirc.create_rewrite(circ, replacement).unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::passes::Pass;
+    use crate::utils::test::{assert_idempotent, assert_same_statevector};
+    use cool_asserts::assert_matches;
+    use hugr::types::Signature;
+    use rstest::rstest;
+    use std::f64::consts::PI;
+
+    /// A gate in a test circuit, with its qubits and numeric angle.
+    type Gate = (Tk2Op, &'static [usize], Option<f64>);
+
+    fn circuit(n_qubits: usize, gates: &[Gate]) -> Circuit {
+        let qbs = TypeRow::from(vec![QB_T; n_qubits]);
+        let mut dfg = DFGBuilder::new(Signature::new_endo(qbs)).unwrap();
+        let mut wires = dfg.input_wires().collect_vec();
+        for &(op, qubits, angle) in gates {
+            let angle = angle.map(|a| dfg.add_load_value(ConstF64::new(a)));
+            let inputs = qubits.iter().map(|&q| wires[q]).chain(angle);
+            let outputs = dfg.add_dataflow_op(op, inputs).unwrap().outputs();
+            for (&q, wire) in qubits.iter().zip(outputs) {
+                wires[q] = wire;
+            }
+        }
+        dfg.finish_hugr_with_outputs(wires, &REGISTRY)
+            .unwrap()
+            .into()
+    }
+
+    #[rstest]
+    #[case::h_rz_rz_h(1, &[
+        (Tk2Op::H, &[0][..], None),
+        (Tk2Op::RzF64, &[0][..], Some(0.3)),
+        (Tk2Op::RzF64, &[0][..], Some(-0.3)),
+        (Tk2Op::H, &[0][..], None),
+    ], 0)]
+    #[case::full_turn(1, &[
+        (Tk2Op::H, &[0][..], None),
+        (Tk2Op::RxF64, &[0][..], Some(PI)),
+        (Tk2Op::RxF64, &[0][..], Some(PI)),
+    ], 1)]
+    #[case::cx_pair(2, &[
+        (Tk2Op::X, &[0][..], None),
+        (Tk2Op::CX, &[0, 1][..], None),
+        (Tk2Op::CX, &[0, 1][..], None),
+        (Tk2Op::H, &[1][..], None),
+    ], 2)]
+    #[case::commute_then_cancel(2, &[
+        (Tk2Op::X, &[1][..], None),
+        (Tk2Op::H, &[0][..], None),
+        (Tk2Op::T, &[0][..], None),
+        (Tk2Op::CX, &[0, 1][..], None),
+        (Tk2Op::X, &[1][..], None),
+    ], 3)]
+    #[case::s_sdg_t_tdg(1, &[
+        (Tk2Op::H, &[0][..], None),
+        (Tk2Op::T, &[0][..], None),
+        (Tk2Op::S, &[0][..], None),
+        (Tk2Op::Sdg, &[0][..], None),
+        (Tk2Op::Tdg, &[0][..], None),
+    ], 1)]
+    fn cleanup_reduces(
+        #[case] n_qubits: usize,
+        #[case] gates: &[Gate],
+        #[case] expected_ops: usize,
+    ) {
+        let circ = circuit(n_qubits, gates);
+        let (cleaned, changed) = cleanup(&circ, CleanupOptions::default()).unwrap();
+        cleaned.hugr().validate(&REGISTRY).unwrap();
+        assert!(changed);
+        assert_eq!(cleaned.operations().count(), expected_ops);
+        // Only the angles of remaining operations are kept.
+        assert!(cleaned.commands().count() <= 2 * expected_ops);
+
+        assert_same_statevector(&cleaned, &circ);
+    }
+
+    #[rstest]
+    #[case::within_tolerance(1e-8, 1)]
+    #[case::outside_tolerance(1e-12, 2)]
+    fn approx_noops(#[case] tol: f64, #[case] expected_ops: usize) {
+        let circ = circuit(
+            1,
+            &[(Tk2Op::H, &[0], None), (Tk2Op::RzF64, &[0], Some(1e-10))],
+        );
+        let (cleaned, changed) = remove_approx_noops(&circ, tol);
+        cleaned.hugr().validate(&REGISTRY).unwrap();
+        assert_eq!(changed, expected_ops == 1);
+        assert_eq!(cleaned.operations().count(), expected_ops);
+    }
+
+    #[test]
+    fn cleanup_options() {
+        let circ = circuit(
+            1,
+            &[
+                (Tk2Op::H, &[0], None),
+                (Tk2Op::RzF64, &[0], Some(0.3)),
+                (Tk2Op::RzF64, &[0], Some(-0.3)),
+                (Tk2Op::H, &[0], None),
+            ],
+        );
+        let no_merge = CleanupOptions {
+            merge_rotations: false,
+            ..Default::default()
+        };
+        let (cleaned, changed) = cleanup(&circ, no_merge).unwrap();
+        assert!(!changed);
+        assert_eq!(cleaned.operations().count(), 4);
+
+        let (cleaned, changed) = cleanup(&cleaned, CleanupOptions::default()).unwrap();
+        assert!(changed);
+        assert_eq!(cleaned.operations().count(), 0);
+    }
+
+    #[test]
+    fn strict_mode() {
+        let circ = circuit(
+            1,
+            &[
+                (Tk2Op::H, &[0], None),
+                (Tk2Op::H, &[0], None),
+                (Tk2Op::Measure, &[0], None),
+            ],
+        );
+        let (cleaned, changed) = cleanup(&circ, CleanupOptions::default()).unwrap();
+        assert!(changed);
+        assert_eq!(cleaned.operations().count(), 1);
+
+        let strict = CleanupOptions {
+            strict: true,
+            ..Default::default()
+        };
+        assert_matches!(
+            cleanup(&circ, strict),
+            Err(UnitaryPassError::NonUnitaryOp(name)) if name.ends_with("Measure")
+        );
+    }
+
+    #[test]
+    fn merge_error_budgets() {
+        let mut circ = circuit(
+            1,
+            &[
+                (Tk2Op::RzF64, &[0], Some(0.3)),
+                (Tk2Op::RzF64, &[0], Some(0.2)),
+                (Tk2Op::H, &[0], None),
+                (Tk2Op::H, &[0], None),
+            ],
+        );
+        let ops = circ.operations().map(|cmd| cmd.node()).collect_vec();
+        for (node, budget) in ops.into_iter().zip([1e-3, 2e-3, 1e-4, 1e-4]) {
+            circ.set_error_budget(node, budget);
+        }
+        assert!((circ.total_error_budget() - 3.2e-3).abs() < EPSILON);
+
+        let options = CleanupOptions {
+            cancel_inverses: false,
+            ..Default::default()
+        };
+        let (merged, _) = cleanup(&circ, options).unwrap();
+        let rz = merged
+            .operations()
+            .find(|cmd| cmd.optype() == &Tk2Op::RzF64.into())
+            .unwrap();
+        assert!((merged.error_budget(rz.node()) - 3e-3).abs() < EPSILON);
+        assert!((merged.total_error_budget() - 3.2e-3).abs() < EPSILON);
+
+        // Budgets of removed gates are kept on the circuit.
+        let (cleaned, _) = cleanup(&merged, CleanupOptions::default()).unwrap();
+        assert_eq!(cleaned.num_operations(), 1);
+        assert!((cleaned.error_budget(cleaned.parent()) - 2e-4).abs() < EPSILON);
+        assert!((cleaned.total_error_budget() - 3.2e-3).abs() < EPSILON);
+    }
+
+    #[rstest]
+    #[case::merge_chain(Pass::MergeRotations, 2, &[
+        (Tk2Op::RzF64, &[0][..], Some(0.1)),
+        (Tk2Op::RzF64, &[0][..], Some(0.2)),
+        (Tk2Op::RzF64, &[0][..], Some(0.3)),
+        (Tk2Op::ZZPhase, &[0, 1][..], Some(PI)),
+        (Tk2Op::ZZPhase, &[0, 1][..], Some(PI)),
+        (Tk2Op::RxF64, &[1][..], Some(-0.4)),
+        (Tk2Op::RxF64, &[1][..], Some(0.4)),
+    ])]
+    #[case::cancel_nested(Pass::CancelInverses, 2, &[
+        (Tk2Op::H, &[0][..], None),
+        (Tk2Op::S, &[0][..], None),
+        (Tk2Op::CX, &[0, 1][..], None),
+        (Tk2Op::CX, &[0, 1][..], None),
+        (Tk2Op::Sdg, &[0][..], None),
+        (Tk2Op::H, &[0][..], None),
+        (Tk2Op::H, &[0][..], None),
+    ])]
+    #[case::noops(Pass::RemoveNoops, 1, &[
+        (Tk2Op::RzF64, &[0][..], Some(2. * PI)),
+        (Tk2Op::RxF64, &[0][..], Some(-4. * PI)),
+        (Tk2Op::RzF64, &[0][..], Some(2. * PI + 1e-12)),
+        (Tk2Op::H, &[0][..], None),
+    ])]
+    fn idempotent(#[case] pass: Pass, #[case] n_qubits: usize, #[case] gates: &[Gate]) {
+        let circ = circuit(n_qubits, gates);
+        let output = assert_idempotent(pass, &circ);
+        assert!(output.num_operations() < circ.num_operations());
+    }
+}
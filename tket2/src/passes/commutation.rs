@@ -1,4 +1,7 @@
-use std::{collections::HashMap, rc::Rc};
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
 
 use hugr::hugr::{hugrmut::HugrMut, HugrError, Rewrite};
 use hugr::{CircuitUnit, Direction, HugrView, Node, Port, PortIndex};
@@ -73,10 +76,7 @@ fn load_slices(circ: &Circuit<impl HugrView>) -> SliceVec {
     let n_qbs = circ.qubit_count();
     let mut qubit_free_slice = vec![0; n_qbs];
 
-    for command in circ
-        .commands()
-        .filter(|c| is_slice_op(circ.hugr(), c.node()))
-    {
+    for command in circ.commands().filter(is_slice_op) {
         let command: ComCommand = command.into();
         let free_slice = command
             .qubits()
@@ -98,10 +98,37 @@ fn load_slices(circ: &Circuit<impl HugrView>) -> SliceVec {
     slices
 }
 
-/// check if node is one we want to put in to a slice.
-fn is_slice_op(h: &impl HugrView, node: Node) -> bool {
-    let op: Result<Tk2Op, _> = h.get_optype(node).try_into();
-    op.is_ok()
+/// check if command is one we want to put in to a slice.
+///
+/// Non-Tk2Op operations acting on qubits are included so that they act as
+/// barriers, as nothing can commute through them.
+fn is_slice_op<T: HugrView>(command: &Command<'_, T>) -> bool {
+    let op: Result<Tk2Op, _> = command.optype().try_into();
+    op.is_ok() || command.linear_inputs().next().is_some()
+}
+
+/// The nodes a command depends on through its non-linear inputs.
+///
+/// These include the operations producing classical values fed forward into
+/// the command, such as measurements whose results are branched on to compute
+/// a rotation angle. Commuting the command past any of them would create a
+/// cycle in the circuit.
+fn classical_dependencies(circ: &Circuit, command: &ComCommand) -> HashSet<Node> {
+    let mut stack = command
+        .inputs
+        .iter()
+        .filter_map(|unit| match unit {
+            CircuitUnit::Wire(wire) => Some(wire.node()),
+            CircuitUnit::Linear(_) => None,
+        })
+        .collect_vec();
+    let mut dependencies = HashSet::new();
+    while let Some(node) = stack.pop() {
+        if dependencies.insert(node) {
+            stack.extend(circ.hugr().input_neighbours(node));
+        }
+    }
+    dependencies
 }
 
 /// Starting from starting_index, work back along slices to check for the
@@ -114,6 +141,7 @@ fn available_slice(
 ) -> Option<(usize, HashMap<Qb, Rc<ComCommand>>)> {
     let mut available = None;
     let mut prev_nodes: HashMap<Qb, Rc<ComCommand>> = HashMap::new();
+    let dependencies = classical_dependencies(circ, command);
     for slice_index in (0..=starting_index).rev() {
         // if all qubit slots are empty here the command can be moved here
         if command
@@ -123,6 +151,13 @@ fn available_slice(
             available = Some((slice_index, prev_nodes.clone()));
         } else if slice_index == 0 {
             break;
+        } else if command.qubits().any(|q| {
+            slice_vec[slice_index][q.index()]
+                .as_ref()
+                .is_some_and(|other| dependencies.contains(&other.node()))
+        }) {
+            // the command cannot be moved before the operations it depends on
+            break;
         } else {
             // if command commutes with all ports here it can be moved past,
             // otherwise stop
@@ -328,9 +363,9 @@ mod test {
 
     use crate::{extension::REGISTRY, ops::test::t2_bell_circuit, utils::build_simple_circuit};
     use hugr::{
-        builder::{DFGBuilder, Dataflow, DataflowHugr},
+        builder::{DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer, SubContainer},
         extension::prelude::{BOOL_T, QB_T},
-        std_extensions::arithmetic::float_types::FLOAT64_TYPE,
+        std_extensions::arithmetic::float_types::{ConstF64, FLOAT64_TYPE},
         type_row,
         types::Signature,
     };
@@ -476,6 +511,80 @@ mod test {
         build().unwrap().into()
     }
 
+    #[fixture]
+    // A non-Tk2Op operation blocks the commutation of `Z` through the `CX`
+    fn opaque_barrier() -> Circuit {
+        let build = || {
+            let qb_row = type_row![QB_T, QB_T];
+            let mut dfg = DFGBuilder::new(Signature::new_endo(qb_row))?;
+            let [q0, q1] = dfg.input_wires_arr();
+
+            let [q1] = dfg.add_dataflow_op(Tk2Op::H, [q1])?.outputs_arr();
+            let [q0, q1] = dfg.add_dataflow_op(Tk2Op::CX, [q0, q1])?.outputs_arr();
+            let barrier = dfg.dfg_builder(Signature::new_endo(type_row![QB_T]), [q0])?;
+            let [q0] = barrier.input_wires_arr();
+            let [q0] = barrier.finish_with_outputs([q0])?.outputs_arr();
+            let [q0] = dfg.add_dataflow_op(Tk2Op::Z, [q0])?.outputs_arr();
+
+            dfg.finish_hugr_with_outputs([q0, q1], &REGISTRY)
+        };
+        build().unwrap().into()
+    }
+
+    #[fixture]
+    // The angle of the `Rz` is fed forward from a measurement after the `CX`,
+    // so it cannot commute through it
+    fn classical_feedforward() -> Circuit {
+        let build = || {
+            let qb_row = type_row![QB_T, QB_T];
+            let mut dfg = DFGBuilder::new(Signature::new_endo(qb_row))?;
+            let [q0, q1] = dfg.input_wires_arr();
+
+            let [q1] = dfg.add_dataflow_op(Tk2Op::H, [q1])?.outputs_arr();
+            let [q0, q1] = dfg.add_dataflow_op(Tk2Op::CX, [q0, q1])?.outputs_arr();
+            let [q1, bit] = dfg.add_dataflow_op(Tk2Op::Measure, [q1])?.outputs_arr();
+            let mut cond = dfg.conditional_builder(
+                ([type_row![], type_row![]], bit),
+                [],
+                type_row![FLOAT64_TYPE],
+            )?;
+            for (i, angle) in [0., 0.5].into_iter().enumerate() {
+                let mut case = cond.case_builder(i)?;
+                let angle = case.add_load_value(ConstF64::new(angle));
+                case.finish_with_outputs([angle])?;
+            }
+            let [angle] = cond.finish_sub_container()?.outputs_arr();
+            let [q0] = dfg
+                .add_dataflow_op(Tk2Op::RzF64, [q0, angle])?
+                .outputs_arr();
+
+            dfg.finish_hugr_with_outputs([q0, q1], &REGISTRY)
+        };
+        build().unwrap().into()
+    }
+
+    #[fixture]
+    // As `classical_feedforward`, but with a constant angle the `Rz` commutes
+    // through the control of the `CX`
+    fn classical_constant_angle() -> Circuit {
+        let build = || {
+            let qb_row = type_row![QB_T, QB_T];
+            let mut dfg = DFGBuilder::new(Signature::new_endo(qb_row))?;
+            let [q0, q1] = dfg.input_wires_arr();
+
+            let [q1] = dfg.add_dataflow_op(Tk2Op::H, [q1])?.outputs_arr();
+            let [q0, q1] = dfg.add_dataflow_op(Tk2Op::CX, [q0, q1])?.outputs_arr();
+            let [q1, _] = dfg.add_dataflow_op(Tk2Op::Measure, [q1])?.outputs_arr();
+            let angle = dfg.add_load_value(ConstF64::new(0.5));
+            let [q0] = dfg
+                .add_dataflow_op(Tk2Op::RzF64, [q0, angle])?
+                .outputs_arr();
+
+            dfg.finish_hugr_with_outputs([q0, q1], &REGISTRY)
+        };
+        build().unwrap().into()
+    }
+
     // bug https://github.com/CQCL/tket2/issues/253
     fn cx_commute_bug() -> Circuit {
         build_simple_circuit(3, |circ| {
@@ -592,6 +701,9 @@ mod test {
     #[case(non_linear_inputs(), true, 1)]
     #[case(non_linear_outputs(), true, 1)]
     #[case(cx_commute_bug(), true, 1)]
+    #[case(opaque_barrier(), false, 0)]
+    #[case(classical_feedforward(), false, 0)]
+    #[case(classical_constant_angle(), false, 1)]
     fn commutation_example(
         #[case] mut case: Circuit,
         #[case] should_reduce: bool,
@@ -623,4 +735,26 @@ mod test {
             "depth optimisation should not change the number of nodes."
         )
     }
+
+    #[rstest]
+    #[case(classical_feedforward(), false)]
+    #[case(classical_constant_angle(), true)]
+    fn feedforward_blocks_commutation(#[case] mut circ: Circuit, #[case] commutes: bool) {
+        apply_greedy_commutation(&mut circ).unwrap();
+        circ.hugr_mut().update_validate(&REGISTRY).unwrap();
+
+        // The `Rz` is only moved before the `CX` when its angle does not
+        // depend on the measurement after it.
+        let rz = circ
+            .commands()
+            .find(|cmd| Tk2Op::try_from(cmd.optype()) == Ok(Tk2Op::RzF64))
+            .unwrap()
+            .node();
+        let (prev, _) = circ.hugr().single_linked_output(rz, 0).unwrap();
+        assert_eq!(prev == circ.input_node(), commutes);
+        if !commutes {
+            let prev_op = Tk2Op::try_from(circ.hugr().get_optype(prev));
+            assert_eq!(prev_op, Ok(Tk2Op::CX));
+        }
+    }
 }
@@ -0,0 +1,517 @@
+//! Running sequences of passes while collecting non-fatal diagnostics.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use hugr::{HugrView, Node};
+use serde::{Deserialize, Serialize};
+
+use super::cleanup::{check_unitary, cleanup, unmerged_symbolic_rotations};
+use super::{
+    decompose_to_cx, fuse_phasedx, normalise_angles, resynthesise_2q, squash_rz_pi_over_4,
+    CleanupOptions, GatePredicate, UnitaryPassError,
+};
+use crate::circuit::params::NumericParams;
+use crate::{Circuit, Tk2Op};
+
+/// A built-in pass that can be run by [`run_passes`].
+///
+/// Passes are serialised as objects with a `"pass"` field holding the
+/// [name](Pass::name) of the pass, alongside its parameters. For example,
+/// `{"pass": "resynthesise_2q", "strict": true}`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "pass", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum Pass {
+    /// [`cleanup`] with the given options.
+    Cleanup(CleanupOptions),
+    /// [`cleanup`] with only rotation merging enabled.
+    MergeRotations,
+    /// [`cleanup`] with only inverse-pair cancellation enabled.
+    CancelInverses,
+    /// [`cleanup`] with only noop removal enabled.
+    RemoveNoops,
+    /// [`fuse_phasedx`].
+    #[serde(rename = "fuse_phasedx")]
+    FusePhasedX,
+    /// [`normalise_angles`].
+    NormaliseAngles,
+    /// [`resynthesise_2q`], with `strict` set as given.
+    #[serde(rename = "resynthesise_2q")]
+    Resynthesise2q {
+        /// Fail on non-unitary operations instead of treating them as
+        /// barriers.
+        #[serde(default)]
+        strict: bool,
+    },
+    /// [`squash_rz_pi_over_4`].
+    #[serde(rename = "squash_rz_pi_over_4")]
+    SquashRzPiOver4,
+    /// [`decompose_to_cx`].
+    DecomposeToCx,
+}
+
+impl Pass {
+    /// The name of the pass, as reported in its warnings.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Pass::Cleanup(_) => "cleanup",
+            Pass::MergeRotations => "merge_rotations",
+            Pass::CancelInverses => "cancel_inverses",
+            Pass::RemoveNoops => "remove_noops",
+            Pass::FusePhasedX => "fuse_phasedx",
+            Pass::NormaliseAngles => "normalise_angles",
+            Pass::Resynthesise2q { .. } => "resynthesise_2q",
+            Pass::SquashRzPiOver4 => "squash_rz_pi_over_4",
+            Pass::DecomposeToCx => "decompose_to_cx",
+        }
+    }
+
+    /// Run the pass on a circuit, recording its warnings in `ctx`.
+    ///
+    /// If timing is enabled in `ctx`, the duration of the pass is recorded
+    /// too.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pass runs in strict mode and the circuit
+    /// contains a non-unitary operation.
+    pub fn run(
+        &self,
+        circ: &Circuit<impl HugrView>,
+        ctx: &mut PassContext,
+    ) -> Result<Circuit, UnitaryPassError> {
+        ctx.pass = self.name();
+        let start = ctx.timings.is_some().then(Instant::now);
+        let circ = self.apply(circ, ctx)?;
+        if let (Some(start), Some(timings)) = (start, &mut ctx.timings) {
+            timings.push((self.name(), start.elapsed()));
+        }
+        Ok(circ)
+    }
+
+    /// Run the pass on a circuit, without timing it.
+    fn apply(
+        &self,
+        circ: &Circuit<impl HugrView>,
+        ctx: &mut PassContext,
+    ) -> Result<Circuit, UnitaryPassError> {
+        let circ = match *self {
+            Pass::Cleanup(options) => {
+                let (circ, _) = cleanup(circ, options)?;
+                if options.merge_rotations {
+                    warn_unmerged_rotations(&circ, ctx);
+                }
+                circ
+            }
+            Pass::MergeRotations => {
+                let options = CleanupOptions {
+                    merge_rotations: true,
+                    ..CleanupOptions::none()
+                };
+                let (circ, _) = cleanup(circ, options)?;
+                warn_unmerged_rotations(&circ, ctx);
+                circ
+            }
+            Pass::CancelInverses => {
+                let options = CleanupOptions {
+                    cancel_inverses: true,
+                    ..CleanupOptions::none()
+                };
+                cleanup(circ, options)?.0
+            }
+            Pass::RemoveNoops => {
+                let options = CleanupOptions {
+                    remove_noops: true,
+                    ..CleanupOptions::none()
+                };
+                cleanup(circ, options)?.0
+            }
+            Pass::FusePhasedX => {
+                let circ = fuse_phasedx(circ);
+                warn_symbolic(&circ, ctx, &GatePredicate::op(Tk2Op::PhasedX));
+                circ
+            }
+            Pass::NormaliseAngles => {
+                let circ = normalise_angles(circ);
+                warn_symbolic(&circ, ctx, &rotations());
+                circ
+            }
+            Pass::Resynthesise2q { strict } => {
+                if strict {
+                    check_unitary(circ)?;
+                }
+                let (circ, _) = resynthesise_2q(circ, strict)?;
+                warn_symbolic(&circ, ctx, &rotations());
+                circ
+            }
+            Pass::SquashRzPiOver4 => {
+                let (circ, _) = squash_rz_pi_over_4(circ);
+                warn_symbolic(&circ, ctx, &GatePredicate::op(Tk2Op::RzF64));
+                circ
+            }
+            Pass::DecomposeToCx => decompose_to_cx(circ),
+        };
+        Ok(circ)
+    }
+}
+
+/// Run a sequence of passes on a circuit, in order.
+///
+/// Non-fatal diagnostics, such as operations skipped because of symbolic
+/// angles, are collected in `ctx`.
+///
+/// # Errors
+///
+/// Returns the error of the first failing pass. The circuit is left as
+/// produced by the preceding passes.
+pub fn run_passes(
+    circ: &mut Circuit,
+    passes: &[Pass],
+    ctx: &mut PassContext,
+) -> Result<(), UnitaryPassError> {
+    for pass in passes {
+        *circ = pass.run(circ, ctx)?;
+    }
+    Ok(())
+}
+
+/// A sequence of built-in passes, declared as data.
+///
+/// Pipelines are serialised as a list of [`Pass`]es, so that they can be
+/// shared and loaded from JSON:
+///
+/// ```
+/// # use tket2::passes::PassPipeline;
+/// let pipeline: PassPipeline = serde_json::from_str(
+///     r#"[{"pass": "cancel_inverses"}, {"pass": "merge_rotations"}]"#,
+/// ).unwrap();
+/// assert_eq!(pipeline.passes.len(), 2);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PassPipeline {
+    /// The passes to run, in order.
+    pub passes: Vec<Pass>,
+}
+
+impl PassPipeline {
+    /// Create a pipeline running the given passes in order.
+    pub fn new(passes: impl IntoIterator<Item = Pass>) -> Self {
+        Self {
+            passes: passes.into_iter().collect(),
+        }
+    }
+}
+
+impl Circuit {
+    /// Run a pipeline of passes on the circuit, in order.
+    ///
+    /// See [`run_passes`] for more details.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the first failing pass. The circuit is left as
+    /// produced by the preceding passes.
+    pub fn run_pipeline(
+        &mut self,
+        pipeline: &PassPipeline,
+        ctx: &mut PassContext,
+    ) -> Result<(), UnitaryPassError> {
+        run_passes(self, &pipeline.passes, ctx)
+    }
+}
+
+/// Collects the warnings emitted by passes, and optionally their running
+/// times.
+#[derive(Clone, Debug, Default)]
+pub struct PassContext {
+    /// The name of the pass currently running.
+    pass: &'static str,
+    warnings: Vec<PassWarning>,
+    /// The wall-clock duration of each pass run, if timing is enabled.
+    timings: Option<Vec<(&'static str, Duration)>>,
+}
+
+impl PassContext {
+    /// Create a context with no warnings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a context recording the duration of each pass run with it.
+    ///
+    /// Passes are not timed in contexts created with [`PassContext::new`].
+    pub fn with_timing() -> Self {
+        Self {
+            timings: Some(Vec::new()),
+            ..Self::default()
+        }
+    }
+
+    /// The name and wall-clock duration of each pass run so far, in order.
+    ///
+    /// Empty if timing is not enabled.
+    pub fn timings(&self) -> &[(&'static str, Duration)] {
+        self.timings.as_deref().unwrap_or_default()
+    }
+
+    /// Record a warning for the pass currently running.
+    ///
+    /// The node must be a node of the circuit produced by the pass.
+    pub fn warn(&mut self, node: Node, kind: WarningKind) {
+        self.warnings.push(PassWarning {
+            pass: self.pass,
+            node,
+            kind,
+        });
+    }
+
+    /// The warnings recorded so far.
+    pub fn warnings(&self) -> &[PassWarning] {
+        &self.warnings
+    }
+
+    /// Returns the warnings recorded so far, clearing them from the context.
+    pub fn take_warnings(&mut self) -> Vec<PassWarning> {
+        std::mem::take(&mut self.warnings)
+    }
+}
+
+/// A non-fatal diagnostic emitted by a pass.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PassWarning {
+    /// The name of the pass that emitted the warning.
+    pub pass: &'static str,
+    /// The node the warning refers to, in the circuit produced by the pass.
+    ///
+    /// Passes may renumber the nodes of their input circuit, so the node is
+    /// not necessarily valid in the input circuit nor in the circuits produced
+    /// by later passes.
+    pub node: Node,
+    /// The cause of the warning.
+    pub kind: WarningKind,
+}
+
+/// The cause of a [`PassWarning`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WarningKind {
+    /// The operation was skipped because some of its angles are not numeric
+    /// constants.
+    SymbolicAngle {
+        /// The skipped operation.
+        op: Tk2Op,
+    },
+}
+
+impl fmt::Display for PassWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            WarningKind::SymbolicAngle { op } => write!(
+                f,
+                "{}: skipped {op:?} in {} with a symbolic angle.",
+                self.pass, self.node
+            ),
+        }
+    }
+}
+
+/// The rotations with angle inputs.
+fn rotations() -> GatePredicate {
+    GatePredicate::ops([
+        Tk2Op::RzF64,
+        Tk2Op::RxF64,
+        Tk2Op::ZZPhase,
+        Tk2Op::PhasedX,
+        Tk2Op::TK1,
+    ])
+}
+
+/// Warn about the operations matching `pred` whose angles are not numeric
+/// constants, in the circuit produced by a pass.
+fn warn_symbolic(circ: &Circuit, ctx: &mut PassContext, pred: &GatePredicate) {
+    let mut params = NumericParams::default();
+    for cmd in circ.commands() {
+        if params.process(&cmd).unwrap_or(true) {
+            continue;
+        }
+        let Ok(op) = Tk2Op::try_from(cmd.optype()) else {
+            continue;
+        };
+        if pred.matches_command(&cmd) && params.inputs(&cmd).is_err() {
+            ctx.warn(cmd.node(), WarningKind::SymbolicAngle { op });
+        }
+    }
+}
+
+/// Warn about the adjacent rotations left unmerged by [`cleanup`] because of
+/// symbolic angles.
+fn warn_unmerged_rotations(circ: &Circuit, ctx: &mut PassContext) {
+    for (node, op) in unmerged_symbolic_rotations(circ) {
+        ctx.warn(node, WarningKind::SymbolicAngle { op });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::extension::REGISTRY;
+    use crate::ops::symbolic_constant_op;
+    use hugr::builder::{DFGBuilder, Dataflow, DataflowHugr};
+    use hugr::extension::prelude::QB_T;
+    use hugr::ops::handle::NodeHandle;
+    use hugr::std_extensions::arithmetic::float_types::ConstF64;
+    use hugr::type_row;
+    use hugr::types::Signature;
+    use itertools::Itertools;
+
+    /// A single-qubit circuit applying `Rz(a)`, `Rz(0.5)` and `Rz(0.25)`,
+    /// where `a` is symbolic.
+    fn symbolic_rotations() -> (Circuit, Node) {
+        let mut dfg = DFGBuilder::new(Signature::new_endo(type_row![QB_T])).unwrap();
+        let [mut q] = dfg.input_wires_arr();
+        let [alpha] = dfg
+            .add_dataflow_op(symbolic_constant_op("a".to_string()), [])
+            .unwrap()
+            .outputs_arr();
+        let symbolic = dfg.add_dataflow_op(Tk2Op::RzF64, [q, alpha]).unwrap();
+        [q] = symbolic.outputs_arr();
+        for angle in [0.5, 0.25] {
+            let angle = dfg.add_load_value(ConstF64::new(angle));
+            [q] = dfg
+                .add_dataflow_op(Tk2Op::RzF64, [q, angle])
+                .unwrap()
+                .outputs_arr();
+        }
+        let circ = dfg.finish_hugr_with_outputs([q], &REGISTRY).unwrap().into();
+        (circ, symbolic.node())
+    }
+
+    #[test]
+    fn merge_rotations_warns_on_symbolic() {
+        let (mut circ, symbolic) = symbolic_rotations();
+        let mut ctx = PassContext::new();
+        run_passes(&mut circ, &[Pass::MergeRotations], &mut ctx).unwrap();
+
+        // The numeric rotations are merged, the symbolic one is reported.
+        let rz_count = circ
+            .commands()
+            .filter(|cmd| cmd.optype() == &Tk2Op::RzF64.into())
+            .count();
+        assert_eq!(rz_count, 2);
+        assert_eq!(
+            ctx.warnings(),
+            [PassWarning {
+                pass: "merge_rotations",
+                node: symbolic,
+                kind: WarningKind::SymbolicAngle { op: Tk2Op::RzF64 },
+            }]
+        );
+        assert_eq!(
+            ctx.warnings()[0].to_string(),
+            format!("merge_rotations: skipped RzF64 in {symbolic} with a symbolic angle.")
+        );
+
+        // Later passes append their own warnings.
+        run_passes(&mut circ, &[Pass::NormaliseAngles], &mut ctx).unwrap();
+        let warnings = ctx.take_warnings();
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[1].pass, "normalise_angles");
+        assert!(ctx.warnings().is_empty());
+    }
+
+    #[test]
+    fn warnings_refer_to_output_nodes() {
+        let passes = [
+            Pass::NormaliseAngles,
+            Pass::Resynthesise2q { strict: false },
+            Pass::SquashRzPiOver4,
+        ];
+        for pass in passes {
+            let (circ, _) = symbolic_rotations();
+            let mut ctx = PassContext::new();
+            let circ = pass.run(&circ, &mut ctx).unwrap();
+            let [warning] = ctx.warnings() else {
+                panic!("{}: expected one warning", pass.name());
+            };
+            assert_eq!(warning.pass, pass.name());
+            let cmd = circ
+                .commands()
+                .find(|cmd| cmd.node() == warning.node)
+                .unwrap();
+            assert_eq!(cmd.optype(), &Tk2Op::RzF64.into());
+        }
+    }
+
+    #[test]
+    fn numeric_circuit_has_no_warnings() {
+        let mut circ = crate::utils::build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            Ok(())
+        })
+        .unwrap();
+        let mut ctx = PassContext::new();
+        let passes = [
+            Pass::Cleanup(CleanupOptions::default()),
+            Pass::Resynthesise2q { strict: true },
+        ];
+        run_passes(&mut circ, &passes, &mut ctx).unwrap();
+        assert_eq!(circ.num_operations(), 1);
+        assert!(ctx.warnings().is_empty());
+        assert!(ctx.timings().is_empty());
+    }
+
+    #[test]
+    fn pipeline_from_json() {
+        let pipeline: PassPipeline = serde_json::from_str(
+            r#"[
+                {"pass": "cleanup", "commute": false},
+                {"pass": "resynthesise_2q", "strict": true}
+            ]"#,
+        )
+        .unwrap();
+        let cleanup_options = CleanupOptions {
+            commute: false,
+            ..CleanupOptions::default()
+        };
+        assert_eq!(
+            pipeline,
+            PassPipeline::new([
+                Pass::Cleanup(cleanup_options),
+                Pass::Resynthesise2q { strict: true },
+            ])
+        );
+        let json = serde_json::to_string(&pipeline).unwrap();
+        assert_eq!(
+            serde_json::from_str::<PassPipeline>(&json).unwrap(),
+            pipeline
+        );
+
+        let mut circ = crate::utils::build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            Ok(())
+        })
+        .unwrap();
+        let mut ctx = PassContext::with_timing();
+        circ.run_pipeline(&pipeline, &mut ctx).unwrap();
+        assert_eq!(circ.num_operations(), 1);
+        let names = ctx.timings().iter().map(|&(name, _)| name).collect_vec();
+        assert_eq!(names, ["cleanup", "resynthesise_2q"]);
+    }
+
+    #[test]
+    fn pass_timings() {
+        let (mut circ, _) = symbolic_rotations();
+        let mut ctx = PassContext::with_timing();
+        let passes = [Pass::MergeRotations, Pass::NormaliseAngles];
+        run_passes(&mut circ, &passes, &mut ctx).unwrap();
+
+        let names = ctx.timings().iter().map(|&(name, _)| name).collect_vec();
+        assert_eq!(names, ["merge_rotations", "normalise_angles"]);
+    }
+}
@@ -0,0 +1,710 @@
+//! Decomposition of two-qubit gates into `CX` and single-qubit gates, of
+//! circuit boxes into their contents, of gates into user-provided
+//! subcircuits, and substitution of gates equal up to a global phase.
+
+use std::collections::{HashMap, HashSet};
+use std::f64::consts::FRAC_PI_2;
+
+use hugr::builder::{BuildError, DFGBuilder, Dataflow, DataflowHugr};
+use hugr::hugr::hugrmut::HugrMut;
+use hugr::hugr::rewrite::inline_dfg::InlineDFG;
+use hugr::ops::{NamedOp, OpTrait, OpType};
+use hugr::std_extensions::arithmetic::float_types::ConstF64;
+use hugr::{Hugr, HugrView, Node};
+use itertools::Itertools;
+use thiserror::Error;
+
+use crate::extension::REGISTRY;
+use crate::ops::match_custom_gate_op;
+use crate::rewrite::{add_circuit_phase, add_circuit_phase_expr, Subcircuit};
+use crate::serialize::pytket::METADATA_PHASE;
+use crate::{Circuit, Tk2Op};
+
+/// A user-provided decomposition of a gate into a subcircuit, see
+/// [`rebase_with`].
+///
+/// The subcircuit must have the same signature as the gate, including its
+/// angle inputs.
+pub type Decomposition = fn(&OpType) -> Circuit;
+
+/// Decompose every two-qubit gate in the circuit into `CX` gates and
+/// single-qubit rotations.
+///
+/// After the pass, `CX` is the only two-qubit gate in the circuit. The
+/// decompositions are exact, so both the unitary and the global phase of the
+/// circuit are preserved.
+///
+/// Only the top-level operations of the circuit are decomposed, see
+/// [`Circuit::operations`].
+pub fn decompose_to_cx(circ: &Circuit<impl HugrView>) -> Circuit {
+    let mut circ = circ.to_owned();
+    let rewrites = circ
+        .commands()
+        .filter_map(|cmd| {
+            let op: Tk2Op = cmd.optype().try_into().ok()?;
+            let replacement = cx_decomposition(op)?;
+            let subcirc = Subcircuit::try_from_nodes([cmd.node()], &circ).unwrap();
+            Some(subcirc.create_rewrite(&circ, replacement).unwrap())
+        })
+        .collect_vec();
+    for rewrite in rewrites {
+        rewrite
+            .apply(&mut circ)
+            .expect("Two-qubit gate decompositions should be valid replacements.");
+    }
+    circ
+}
+
+/// Replace every `from` gate in the circuit with a `to` gate with the given
+/// angles, in radians.
+///
+/// The two gates must act on the same qubits and be equal up to a global
+/// phase when expressed in their canonical forms, such as `ZZMax` and
+/// `ZZPhase(π/2)`, or `S` and `Rz(π/2)`. The difference of their
+/// [global phases](Tk2Op::global_phase) is added to the circuit for each
+/// substitution, so the unitary of the circuit is preserved.
+///
+/// Only the top-level operations of the circuit are substituted, see
+/// [`Circuit::operations`].
+///
+/// Returns the new circuit and the number of gates substituted.
+///
+/// # Panics
+///
+/// If `from` takes angle parameters, or if `angles` does not match the
+/// parameters of `to`.
+pub fn substitute_op(
+    circ: &Circuit<impl HugrView>,
+    from: Tk2Op,
+    to: Tk2Op,
+    angles: &[f64],
+) -> (Circuit, usize) {
+    let signature = OpType::from(from).dataflow_signature().unwrap();
+    let to_signature = OpType::from(to).dataflow_signature().unwrap();
+    assert_eq!(
+        signature.input_count(),
+        signature.output_count(),
+        "Cannot substitute a gate with angle parameters."
+    );
+    assert_eq!(
+        to_signature.input_count(),
+        signature.input_count() + angles.len(),
+        "The angles must match the parameters of the substituted gate."
+    );
+
+    let mut dfg = DFGBuilder::new(signature).unwrap();
+    let angles = angles
+        .iter()
+        .map(|&angle| dfg.add_load_value(ConstF64::new(angle)))
+        .collect_vec();
+    let inputs = dfg.input_wires().chain(angles).collect_vec();
+    let outputs = dfg.add_dataflow_op(to, inputs).unwrap().outputs();
+    let replacement: Circuit = dfg
+        .finish_hugr_with_outputs(outputs, &REGISTRY)
+        .unwrap()
+        .into();
+
+    let mut circ = circ.to_owned();
+    let rewrites = circ
+        .commands()
+        .filter(|cmd| cmd.optype() == &from.into())
+        .map(|cmd| {
+            let subcirc = Subcircuit::try_from_nodes([cmd.node()], &circ).unwrap();
+            subcirc.create_rewrite(&circ, replacement.clone()).unwrap()
+        })
+        .collect_vec();
+    let substituted = rewrites.len();
+    for rewrite in rewrites {
+        rewrite
+            .apply(&mut circ)
+            .expect("Gate substitutions should be valid replacements.");
+    }
+    add_circuit_phase(
+        &mut circ,
+        substituted as f64 * (from.global_phase() - to.global_phase()),
+    );
+    (circ, substituted)
+}
+
+/// Inline every box added with [`Circuit::append_box`].
+///
+/// Boxes nested inside other boxes are inlined too, so the resulting circuit
+/// contains no boxes. The global phase of each box, numeric or symbolic, is
+/// added to the phase of the circuit. Other nested DFGs are left unchanged.
+///
+/// Returns the flattened circuit and the number of boxes inlined.
+pub fn decompose_boxes(circ: &Circuit<impl HugrView>) -> (Circuit, usize) {
+    let mut circ = circ.to_owned();
+    let mut inlined = 0;
+    loop {
+        let parent = circ.parent();
+        let boxes = circ
+            .hugr()
+            .children(parent)
+            .filter(|&node| circ.box_name(node).is_some())
+            .collect_vec();
+        if boxes.is_empty() {
+            break;
+        }
+        for node in boxes {
+            let phase = circ
+                .hugr()
+                .get_metadata(node, METADATA_PHASE)
+                .and_then(|p| p.as_str())
+                .map(str::to_string);
+            circ.hugr_mut()
+                .apply_rewrite(InlineDFG(node.into()))
+                .expect("Circuit boxes can be inlined.");
+            if let Some(phase) = phase {
+                add_circuit_phase_expr(&mut circ, &phase);
+            }
+            inlined += 1;
+        }
+    }
+    (circ, inlined)
+}
+
+/// Replace every gate named in `table` by its decomposition, until no such
+/// gate remains.
+///
+/// Gates are named as in [`Tk2Op::name`], e.g. `"H"` or `"RzF64"`, and
+/// [`CustomGate`](crate::CustomGate)s by their name. Decompositions may
+/// contain other gates of the table, which are decomposed in turn. The global
+/// phase of each decomposition is added to the circuit.
+///
+/// Only the top-level operations of the circuit are decomposed, see
+/// [`Circuit::operations`].
+///
+/// Returns the new circuit and the number of gates decomposed.
+///
+/// # Errors
+///
+/// Returns an error if a gate eventually decomposes into itself, or if a
+/// decomposition does not have the signature of its gate.
+pub fn rebase_with(
+    circ: &Circuit<impl HugrView>,
+    table: &HashMap<String, Decomposition>,
+) -> Result<(Circuit, usize), RebaseError> {
+    let mut circ = circ.to_owned();
+    // The gates of the table produced by the decompositions of each gate.
+    let mut produces: HashMap<String, HashSet<String>> = HashMap::new();
+    let mut decomposed = 0;
+    loop {
+        let mut rewrites = Vec::new();
+        for cmd in circ.commands() {
+            let Some((gate, decompose)) =
+                gate_name(cmd.optype()).and_then(|gate| table.get_key_value(&gate))
+            else {
+                continue;
+            };
+            let replacement = decompose(cmd.optype());
+            let produced = replacement
+                .commands()
+                .filter_map(|cmd| gate_name(cmd.optype()))
+                .filter(|name| table.contains_key(name));
+            produces.entry(gate.clone()).or_default().extend(produced);
+            if produces_itself(&produces, gate) {
+                return Err(RebaseError::NonTerminating { gate: gate.clone() });
+            }
+
+            let node = cmd.node();
+            let subcirc = Subcircuit::try_from_nodes([node], &circ).unwrap();
+            let rewrite = subcirc.create_rewrite(&circ, replacement).map_err(|_| {
+                RebaseError::InvalidDecomposition {
+                    gate: gate.clone(),
+                    node,
+                }
+            })?;
+            rewrites.push(rewrite);
+        }
+        if rewrites.is_empty() {
+            break;
+        }
+        decomposed += rewrites.len();
+        for rewrite in rewrites {
+            rewrite
+                .apply(&mut circ)
+                .expect("Gate decompositions should be valid replacements.");
+        }
+    }
+    Ok((circ, decomposed))
+}
+
+impl Circuit<Hugr> {
+    /// Replace every gate named in `table` by its decomposition, until no
+    /// such gate remains.
+    ///
+    /// See [`rebase_with`] for more details. The circuit is not modified on
+    /// failure.
+    pub fn rebase_with(
+        &mut self,
+        table: &HashMap<String, Decomposition>,
+    ) -> Result<(), RebaseError> {
+        *self = rebase_with(self, table)?.0;
+        Ok(())
+    }
+}
+
+/// Error type for [`rebase_with`].
+#[derive(Debug, Clone, PartialEq, Error)]
+#[non_exhaustive]
+pub enum RebaseError {
+    /// The decomposition of a gate eventually produces the gate again.
+    #[error(
+        "The decomposition of {gate} does not terminate, as it eventually produces {gate} again."
+    )]
+    NonTerminating {
+        /// The gate name.
+        gate: String,
+    },
+    /// The decomposition of a gate does not have the signature of the gate.
+    #[error("The decomposition of {gate} in {node} does not match the signature of the gate.")]
+    InvalidDecomposition {
+        /// The gate name.
+        gate: String,
+        /// The node of the gate.
+        node: Node,
+    },
+}
+
+/// The name of a gate in a decomposition table, see [`rebase_with`].
+fn gate_name(op: &OpType) -> Option<String> {
+    if let Ok(op) = Tk2Op::try_from(op) {
+        return Some(op.name().to_string());
+    }
+    match_custom_gate_op(op).map(|gate| gate.name)
+}
+
+/// Whether the decompositions of `gate` eventually produce `gate` again.
+fn produces_itself(produces: &HashMap<String, HashSet<String>>, gate: &String) -> bool {
+    let mut seen = HashSet::new();
+    let mut stack = vec![gate];
+    while let Some(current) = stack.pop() {
+        for next in produces.get(current).into_iter().flatten() {
+            if next == gate {
+                return true;
+            }
+            if seen.insert(next) {
+                stack.push(next);
+            }
+        }
+    }
+    false
+}
+
+/// Returns the decomposition of a two-qubit gate into `CX` and single-qubit
+/// gates, or `None` if the operation does not need to be decomposed.
+fn cx_decomposition(op: Tk2Op) -> Option<Circuit> {
+    let build = || -> Result<Circuit, BuildError> {
+        let signature = OpType::from(op).dataflow_signature().unwrap();
+        let mut dfg = DFGBuilder::new(signature)?;
+        let mut inputs = dfg.input_wires();
+        let (a, b) = inputs.next_tuple().unwrap();
+        let [a, b] = match op {
+            // CZ = (I ⊗ H) CX (I ⊗ H)
+            Tk2Op::CZ => {
+                let [b] = dfg.add_dataflow_op(Tk2Op::H, [b])?.outputs_arr();
+                let [a, b] = dfg.add_dataflow_op(Tk2Op::CX, [a, b])?.outputs_arr();
+                let [b] = dfg.add_dataflow_op(Tk2Op::H, [b])?.outputs_arr();
+                [a, b]
+            }
+            // ZZPhase(θ) = CX (I ⊗ Rz(θ)) CX, and ZZMax = ZZPhase(π/2).
+            Tk2Op::ZZMax | Tk2Op::ZZPhase => {
+                let angle = match inputs.next() {
+                    Some(angle) => angle,
+                    None => dfg.add_load_value(ConstF64::new(FRAC_PI_2)),
+                };
+                let [a, b] = dfg.add_dataflow_op(Tk2Op::CX, [a, b])?.outputs_arr();
+                let [b] = dfg.add_dataflow_op(Tk2Op::RzF64, [b, angle])?.outputs_arr();
+                let [a, b] = dfg.add_dataflow_op(Tk2Op::CX, [a, b])?.outputs_arr();
+                [a, b]
+            }
+            _ => unreachable!(),
+        };
+        Ok(dfg.finish_hugr_with_outputs([a, b], &REGISTRY)?.into())
+    };
+
+    match op {
+        Tk2Op::CZ | Tk2Op::ZZMax | Tk2Op::ZZPhase => Some(build().unwrap()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::circuit::units::LinearUnit;
+    use crate::rewrite::circuit_phase;
+    use crate::utils::build_simple_circuit;
+    use cool_asserts::assert_matches;
+    use hugr::extension::prelude::QB_T;
+    use hugr::std_extensions::arithmetic::float_types::FLOAT64_TYPE;
+    use hugr::type_row;
+    use hugr::types::Signature;
+    use rstest::rstest;
+
+    /// A circuit applying a single two-qubit gate.
+    fn two_qubit_gate(op: Tk2Op) -> Circuit {
+        let signature = OpType::from(op).dataflow_signature().unwrap();
+        let mut dfg = DFGBuilder::new(signature).unwrap();
+        let outs = dfg
+            .add_dataflow_op(op, dfg.input_wires())
+            .unwrap()
+            .outputs();
+        dfg.finish_hugr_with_outputs(outs, &REGISTRY)
+            .unwrap()
+            .into()
+    }
+
+    /// A circuit applying a single two-qubit gate, with constant angles.
+    fn constant_two_qubit_gate(op: Tk2Op) -> Circuit {
+        let n_angles = OpType::from(op).dataflow_signature().unwrap().input_count() - 2;
+        let mut dfg = DFGBuilder::new(Signature::new_endo(type_row![QB_T, QB_T])).unwrap();
+        let angles = (0..n_angles)
+            .map(|_| dfg.add_load_value(ConstF64::new(0.3)))
+            .collect_vec();
+        let inputs = dfg.input_wires().chain(angles).collect_vec();
+        let outs = dfg.add_dataflow_op(op, inputs).unwrap().outputs();
+        dfg.finish_hugr_with_outputs(outs, &REGISTRY)
+            .unwrap()
+            .into()
+    }
+
+    /// Assert that two circuits implement the same unitary, including their
+    /// global phase.
+    fn assert_same_unitary(a: &Circuit, b: &Circuit) {
+        let (u, v) = (a.unitary().unwrap(), b.unitary().unwrap());
+        assert!(u.iter().zip(&v).all(|(x, y)| (x - y).norm() < 1e-9));
+    }
+
+    #[rstest]
+    #[case::cx(Tk2Op::CX, &[Tk2Op::CX])]
+    #[case::cz(Tk2Op::CZ, &[Tk2Op::H, Tk2Op::CX, Tk2Op::H])]
+    #[case::zzmax(Tk2Op::ZZMax, &[Tk2Op::CX, Tk2Op::RzF64, Tk2Op::CX])]
+    #[case::zzphase(Tk2Op::ZZPhase, &[Tk2Op::CX, Tk2Op::RzF64, Tk2Op::CX])]
+    fn decompose_gate(#[case] op: Tk2Op, #[case] expected: &[Tk2Op]) {
+        let circ = decompose_to_cx(&two_qubit_gate(op));
+        circ.hugr().validate(&REGISTRY).unwrap();
+
+        let ops = circ
+            .commands()
+            .filter_map(|cmd| Tk2Op::try_from(cmd.optype()).ok())
+            .collect_vec();
+        assert_eq!(ops, expected);
+        assert_eq!(
+            circ.circuit_signature(),
+            two_qubit_gate(op).circuit_signature()
+        );
+
+        let constant = constant_two_qubit_gate(op);
+        assert_same_unitary(&constant, &decompose_to_cx(&constant));
+    }
+
+    #[test]
+    fn only_cx_remains() {
+        let mut dfg = DFGBuilder::new(Signature::new(
+            type_row![QB_T, QB_T, FLOAT64_TYPE],
+            type_row![QB_T, QB_T],
+        ))
+        .unwrap();
+        let [a, b, angle] = dfg.input_wires_arr();
+        let [a, b] = dfg
+            .add_dataflow_op(Tk2Op::CZ, [a, b])
+            .unwrap()
+            .outputs_arr();
+        let [a] = dfg.add_dataflow_op(Tk2Op::H, [a]).unwrap().outputs_arr();
+        let [a, b] = dfg
+            .add_dataflow_op(Tk2Op::ZZPhase, [a, b, angle])
+            .unwrap()
+            .outputs_arr();
+        let [b, a] = dfg
+            .add_dataflow_op(Tk2Op::ZZMax, [b, a])
+            .unwrap()
+            .outputs_arr();
+        let circ: Circuit = dfg
+            .finish_hugr_with_outputs([a, b], &REGISTRY)
+            .unwrap()
+            .into();
+
+        let circ = decompose_to_cx(&circ);
+        circ.hugr().validate(&REGISTRY).unwrap();
+        let two_qubit_ops = circ
+            .commands()
+            .filter(|cmd| cmd.input_qubits().count() == 2)
+            .map(|cmd| cmd.optype().clone())
+            .collect_vec();
+        assert_eq!(two_qubit_ops.len(), 5);
+        assert!(two_qubit_ops.iter().all(|op| *op == Tk2Op::CX.into()));
+    }
+
+    #[rstest]
+    #[case::zzmax(Tk2Op::ZZMax, Tk2Op::ZZPhase, &[FRAC_PI_2], 0.)]
+    #[case::s(Tk2Op::S, Tk2Op::RzF64, &[FRAC_PI_2], 0.5)]
+    #[case::tdg(Tk2Op::Tdg, Tk2Op::RzF64, &[-FRAC_PI_2 / 2.], -0.25)]
+    #[case::x(Tk2Op::X, Tk2Op::RxF64, &[2. * FRAC_PI_2], 1.)]
+    fn substitute_gate(
+        #[case] from: Tk2Op,
+        #[case] to: Tk2Op,
+        #[case] angles: &[f64],
+        #[case] expected_phase: f64,
+    ) {
+        // Two `from` gates on the first qubits, between `H`s.
+        let circ = build_simple_circuit(2, |circ| {
+            let qubits: &[usize] = if from == Tk2Op::ZZMax { &[0, 1] } else { &[0] };
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(from, qubits.iter().copied())?;
+            circ.append(Tk2Op::H, [1])?;
+            circ.append(from, qubits.iter().copied())?;
+            Ok(())
+        })
+        .unwrap();
+
+        let (substituted, count) = substitute_op(&circ, from, to, angles);
+        substituted.hugr().validate(&REGISTRY).unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(
+            substituted
+                .operations()
+                .filter(|cmd| cmd.optype() == &to.into())
+                .count(),
+            2
+        );
+        assert_eq!(circuit_phase(&substituted), expected_phase);
+
+        // The unitaries agree, including the global phase.
+        assert_same_unitary(&circ, &substituted);
+    }
+
+    /// `H = i Rz(π/2) Rx(π/2) Rz(π/2)`.
+    fn h_decomposition(_: &OpType) -> Circuit {
+        let mut dfg = DFGBuilder::new(Signature::new_endo(type_row![QB_T])).unwrap();
+        let [mut q] = dfg.input_wires_arr();
+        for op in [Tk2Op::RzF64, Tk2Op::RxF64, Tk2Op::RzF64] {
+            let angle = dfg.add_load_value(ConstF64::new(FRAC_PI_2));
+            [q] = dfg.add_dataflow_op(op, [q, angle]).unwrap().outputs_arr();
+        }
+        let mut circ: Circuit = dfg.finish_hugr_with_outputs([q], &REGISTRY).unwrap().into();
+        add_circuit_phase(&mut circ, 0.5);
+        circ
+    }
+
+    #[test]
+    fn rebase_with_table() {
+        let circ = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(Tk2Op::H, [1])?;
+            Ok(())
+        })
+        .unwrap();
+        let table = HashMap::from([("H".to_string(), h_decomposition as Decomposition)]);
+
+        let mut rebased = circ.clone();
+        rebased.rebase_with(&table).unwrap();
+        rebased.hugr().validate(&REGISTRY).unwrap();
+        let ops = rebased
+            .operations()
+            .map(|cmd| Tk2Op::try_from(cmd.optype()).unwrap())
+            .collect_vec();
+        assert_eq!(ops.iter().filter(|&&op| op == Tk2Op::H).count(), 0);
+        assert_eq!(ops.iter().filter(|&&op| op == Tk2Op::CX).count(), 1);
+        assert_eq!(ops.len(), 7);
+
+        // The unitaries agree, including the global phase.
+        assert_same_unitary(&circ, &rebased);
+
+        // Decompositions are applied until no gate of the table remains.
+        let s_decomposition: Decomposition = |_| {
+            build_simple_circuit(1, |circ| {
+                circ.append(Tk2Op::T, [0])?;
+                circ.append(Tk2Op::T, [0])?;
+                Ok(())
+            })
+            .unwrap()
+        };
+        let t_decomposition: Decomposition = |_| {
+            build_simple_circuit(1, |circ| {
+                circ.append(Tk2Op::H, [0])?;
+                circ.append(Tk2Op::Tdg, [0])?;
+                circ.append(Tk2Op::H, [0])?;
+                Ok(())
+            })
+            .unwrap()
+        };
+        let table = HashMap::from([
+            ("S".to_string(), s_decomposition),
+            ("T".to_string(), t_decomposition),
+            ("H".to_string(), h_decomposition as Decomposition),
+        ]);
+        let circ = build_simple_circuit(1, |circ| {
+            circ.append(Tk2Op::S, [0])?;
+            Ok(())
+        })
+        .unwrap();
+        let (rebased, decomposed) = rebase_with(&circ, &table).unwrap();
+        assert_eq!(decomposed, 1 + 2 + 4);
+        assert!(rebased.operations().all(|cmd| {
+            matches!(
+                Tk2Op::try_from(cmd.optype()),
+                Ok(Tk2Op::RzF64 | Tk2Op::RxF64 | Tk2Op::Tdg)
+            )
+        }));
+    }
+
+    #[test]
+    fn rebase_non_terminating() {
+        let circ = build_simple_circuit(1, |circ| {
+            circ.append(Tk2Op::X, [0])?;
+            Ok(())
+        })
+        .unwrap();
+        let x_decomposition: Decomposition = |_| {
+            build_simple_circuit(1, |circ| {
+                circ.append(Tk2Op::H, [0])?;
+                circ.append(Tk2Op::Z, [0])?;
+                circ.append(Tk2Op::H, [0])?;
+                Ok(())
+            })
+            .unwrap()
+        };
+        let z_decomposition: Decomposition = |_| {
+            build_simple_circuit(1, |circ| {
+                circ.append(Tk2Op::H, [0])?;
+                circ.append(Tk2Op::X, [0])?;
+                circ.append(Tk2Op::H, [0])?;
+                Ok(())
+            })
+            .unwrap()
+        };
+
+        let self_loop: Decomposition = |_| {
+            build_simple_circuit(1, |circ| {
+                circ.append(Tk2Op::X, [0])?;
+                Ok(())
+            })
+            .unwrap()
+        };
+        let table = HashMap::from([("X".to_string(), self_loop)]);
+        assert_eq!(
+            rebase_with(&circ, &table).unwrap_err(),
+            RebaseError::NonTerminating {
+                gate: "X".to_string()
+            }
+        );
+
+        let table = HashMap::from([
+            ("X".to_string(), x_decomposition),
+            ("Z".to_string(), z_decomposition),
+        ]);
+        let mut cycle = circ.clone();
+        assert_matches!(
+            cycle.rebase_with(&table),
+            Err(RebaseError::NonTerminating { .. })
+        );
+        assert_eq!(cycle.num_operations(), 1);
+
+        // Decompositions must match the signature of the gate.
+        let cx: Decomposition = |_| two_qubit_gate(Tk2Op::CX);
+        let table = HashMap::from([("X".to_string(), cx)]);
+        assert_matches!(
+            rebase_with(&circ, &table),
+            Err(RebaseError::InvalidDecomposition { .. })
+        );
+    }
+
+    #[test]
+    fn decompose_boxes() {
+        let bell = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            Ok(())
+        })
+        .unwrap();
+        let mut nested = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::X, [1])?;
+            Ok(())
+        })
+        .unwrap();
+        nested
+            .append_box("bell", &bell, &[LinearUnit::new(1), LinearUnit::new(0)])
+            .unwrap();
+        let parent = nested.parent();
+        nested
+            .hugr_mut()
+            .set_metadata(parent, METADATA_PHASE, "0.5");
+
+        let mut circ = build_simple_circuit(3, |circ| {
+            circ.append(Tk2Op::T, [2])?;
+            Ok(())
+        })
+        .unwrap();
+        let bell_box = circ
+            .append_box("bell", &bell, &[LinearUnit::new(0), LinearUnit::new(1)])
+            .unwrap();
+        let nested_box = circ
+            .append_box("nested", &nested, &[LinearUnit::new(1), LinearUnit::new(2)])
+            .unwrap();
+        circ.hugr().validate(&REGISTRY).unwrap();
+        assert_eq!(circ.box_name(bell_box), Some("bell"));
+        assert_eq!(circ.box_name(nested_box), Some("nested"));
+        assert_eq!(circ.commands().count(), 3);
+        // Boxes are nested DFGs holding a copy of the subcircuit.
+        assert_matches!(circ.hugr().get_optype(bell_box), OpType::DFG(_));
+        assert_eq!(
+            circ.hugr().children(bell_box).count(),
+            bell.hugr().children(bell.parent()).count()
+        );
+
+        let (flat, inlined) = super::decompose_boxes(&circ);
+        flat.hugr().validate(&REGISTRY).unwrap();
+        assert_eq!(inlined, 3);
+        assert_eq!(flat.commands().count(), 6);
+        assert!(flat
+            .commands()
+            .all(|cmd| Tk2Op::try_from(cmd.optype()).is_ok()));
+        assert_eq!(circuit_phase(&flat), 0.5);
+    }
+
+    #[test]
+    fn decompose_daggered_box() {
+        let bell = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::T, [1])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            Ok(())
+        })
+        .unwrap();
+        let qubits = [LinearUnit::new(0), LinearUnit::new(1)];
+        let mut circ = bell.clone();
+        circ.append_box("bell_dg", &bell.dagger().unwrap(), &qubits)
+            .unwrap();
+
+        let (flat, inlined) = super::decompose_boxes(&circ);
+        flat.hugr().validate(&REGISTRY).unwrap();
+        assert_eq!(inlined, 1);
+        assert_eq!(flat.commands().count(), 6);
+        let identity = build_simple_circuit(2, |_| Ok(())).unwrap();
+        assert_same_unitary(&flat, &identity);
+    }
+
+    #[test]
+    fn decompose_box_symbolic_phase() {
+        let mut inner = build_simple_circuit(1, |circ| {
+            circ.append(Tk2Op::X, [0])?;
+            Ok(())
+        })
+        .unwrap();
+        let parent = inner.parent();
+        inner
+            .hugr_mut()
+            .set_metadata(parent, METADATA_PHASE, "alpha");
+        let mut circ = build_simple_circuit(1, |_| Ok(())).unwrap();
+        circ.append_box("x", &inner, &[LinearUnit::new(0)]).unwrap();
+        let parent = circ.parent();
+        circ.hugr_mut().set_metadata(parent, METADATA_PHASE, "0.5");
+
+        let (flat, _) = super::decompose_boxes(&circ);
+        let phase = flat.hugr().get_metadata(flat.parent(), METADATA_PHASE);
+        assert_eq!(phase.and_then(|p| p.as_str()), Some("0.5 + alpha"));
+    }
+}
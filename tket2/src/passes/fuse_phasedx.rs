@@ -0,0 +1,236 @@
+//! Fusion of adjacent `PhasedX` gates.
+
+use std::collections::{HashMap, HashSet};
+use std::f64::consts::TAU;
+
+use hugr::builder::{DFGBuilder, Dataflow, DataflowHugr};
+use hugr::extension::prelude::QB_T;
+use hugr::hugr::hugrmut::HugrMut;
+use hugr::std_extensions::arithmetic::float_types::ConstF64;
+use hugr::{HugrView, Node};
+use itertools::Itertools;
+use num_complex::Complex64;
+
+use crate::circuit::params::{remove_dead_constants, NumericParams};
+use crate::circuit::simulate::{gate_matrix, matmul};
+use crate::extension::REGISTRY;
+use crate::rewrite::{CircuitRewrite, Subcircuit};
+use crate::serialize::pytket::METADATA_PHASE;
+use crate::{Circuit, Tk2Op};
+
+/// Tolerance used when comparing angles and matrix entries.
+const EPSILON: f64 = 1e-9;
+
+/// Fuse sequences of adjacent `PhasedX` gates acting on the same qubit.
+///
+/// Each pair of consecutive `PhasedX` gates is multiplied into a single
+/// rotation. The result is a `PhasedX` gate when possible, and a `TK1` gate
+/// otherwise. Any sign introduced by the Euler decomposition is added to the
+/// global phase of the circuit, so the unitary is preserved exactly.
+///
+/// Only gates with numeric parameters are fused. Gates with symbolic angles
+/// are left unchanged.
+pub fn fuse_phasedx(circ: &Circuit<impl HugrView>) -> Circuit {
+    let mut circ = circ.to_owned();
+    loop {
+        let rewrites = find_fusions(&circ);
+        if rewrites.is_empty() {
+            break;
+        }
+        for rewrite in rewrites {
+            rewrite
+                .apply(&mut circ)
+                .expect("PhasedX fusions should be valid replacements.");
+        }
+    }
+    remove_dead_constants(&mut circ);
+    circ
+}
+
+/// Returns rewrites fusing non-overlapping pairs of adjacent numeric
+/// `PhasedX` gates.
+fn find_fusions(circ: &Circuit) -> Vec<CircuitRewrite> {
+    let mut params = NumericParams::default();
+    let mut angles: HashMap<Node, Vec<f64>> = HashMap::new();
+    let mut order = Vec::new();
+    for cmd in circ.commands() {
+        if params.process(&cmd).unwrap_or(true) {
+            continue;
+        }
+        if Tk2Op::try_from(cmd.optype()) != Ok(Tk2Op::PhasedX) {
+            continue;
+        }
+        if let Ok(values) = params.inputs(&cmd) {
+            angles.insert(cmd.node(), values);
+            order.push(cmd.node());
+        }
+    }
+
+    let hugr = circ.hugr();
+    let mut fused = HashSet::new();
+    let mut rewrites = Vec::new();
+    for first in order {
+        if fused.contains(&first) {
+            continue;
+        }
+        let Some((second, _)) = hugr.single_linked_input(first, 0) else {
+            continue;
+        };
+        if fused.contains(&second) || !angles.contains_key(&second) {
+            continue;
+        }
+        fused.extend([first, second]);
+
+        let matrix = |node| gate_matrix(Tk2Op::PhasedX, &angles[&node]).unwrap();
+        let unitary = matmul(&matrix(second), &matrix(first));
+        let subcirc = Subcircuit::try_from_nodes([first, second], circ).unwrap();
+        let replacement = fused_replacement(&subcirc, circ, &unitary);
+        rewrites.push(subcirc.create_rewrite(circ, replacement).unwrap());
+    }
+    rewrites
+}
+
+/// Build a single-gate replacement implementing a 2x2 special unitary.
+///
+/// The replacement takes the same inputs as the subcircuit, ignoring the
+/// original parameters in favour of new constants.
+fn fused_replacement(subcirc: &Subcircuit, circ: &Circuit, unitary: &[Complex64]) -> Circuit {
+    let (op, angles) = euler_angles(unitary);
+    let matrix = gate_matrix(op, &angles).unwrap();
+    let negated = unitary
+        .iter()
+        .zip(&matrix)
+        .all(|(u, m)| (u + m).norm() < EPSILON);
+
+    let signature = subcirc.signature(circ);
+    let qb_index = signature
+        .input_types()
+        .iter()
+        .position(|t| t == &QB_T)
+        .unwrap();
+    let mut dfg = DFGBuilder::new(signature).unwrap();
+    let qb = dfg.input_wires().nth(qb_index).unwrap();
+    let angles = angles
+        .iter()
+        .map(|&a| dfg.add_load_value(ConstF64::new(a)))
+        .collect_vec();
+    let [qb] = dfg
+        .add_dataflow_op(op, [qb].into_iter().chain(angles))
+        .unwrap()
+        .outputs_arr();
+    let mut replacement: Circuit = dfg
+        .finish_hugr_with_outputs([qb], &REGISTRY)
+        .unwrap()
+        .into();
+    if negated {
+        let root = replacement.parent();
+        replacement
+            .hugr_mut()
+            .set_metadata(root, METADATA_PHASE, "1");
+    }
+    replacement
+}
+
+/// Decompose a 2x2 special unitary `U = ±Rz(α) Rx(β) Rz(γ)`.
+///
+/// Returns a `PhasedX(β, α)` gate when `α + γ` is a multiple of 2π, and a
+/// `TK1(α, β, γ)` gate otherwise, along with its angles.
+fn euler_angles(u: &[Complex64]) -> (Tk2Op, Vec<f64>) {
+    let beta = 2. * u[2].norm().atan2(u[0].norm());
+    // α + γ and α - γ are only defined when the cosine and sine of β/2 are
+    // non-zero, respectively.
+    let sum = match u[0].norm() > EPSILON {
+        true => u[3].arg() - u[0].arg(),
+        false => 0.,
+    };
+    let diff = match u[2].norm() > EPSILON {
+        true => u[2].arg() - u[1].arg(),
+        false => 0.,
+    };
+    let (alpha, gamma) = ((sum + diff) / 2., (sum - diff) / 2.);
+
+    let turns = sum / TAU;
+    if (turns - turns.round()).abs() < EPSILON {
+        // Rz(α) Rx(β) Rz(γ) = ±Rz(α) Rx(β) Rz(-α) when α + γ = 2πk.
+        (Tk2Op::PhasedX, vec![beta, alpha.rem_euclid(TAU)])
+    } else {
+        (Tk2Op::TK1, vec![alpha, beta, gamma])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ops::symbolic_constant_op;
+    use crate::utils::test::assert_same_statevector;
+    use hugr::type_row;
+    use hugr::types::Signature;
+    use rstest::rstest;
+    use std::f64::consts::PI;
+
+    /// A single-qubit circuit with an `H` gate followed by `PhasedX` gates.
+    fn phasedx_circuit(angles: &[[f64; 2]]) -> Circuit {
+        let mut dfg = DFGBuilder::new(Signature::new_endo(type_row![QB_T])).unwrap();
+        let [mut q] = dfg.input_wires_arr();
+        [q] = dfg.add_dataflow_op(Tk2Op::H, [q]).unwrap().outputs_arr();
+        for &[theta, phi] in angles {
+            let theta = dfg.add_load_value(ConstF64::new(theta));
+            let phi = dfg.add_load_value(ConstF64::new(phi));
+            [q] = dfg
+                .add_dataflow_op(Tk2Op::PhasedX, [q, theta, phi])
+                .unwrap()
+                .outputs_arr();
+        }
+        dfg.finish_hugr_with_outputs([q], &REGISTRY).unwrap().into()
+    }
+
+    #[rstest]
+    #[case::same_axis(&[[0.3, 0.2], [0.5, 0.2]], Tk2Op::PhasedX)]
+    #[case::full_turn(&[[PI, 0.2], [PI, 0.2]], Tk2Op::PhasedX)]
+    #[case::different_axes(&[[0.3, 0.2], [0.5, 1.1]], Tk2Op::TK1)]
+    #[case::chain(&[[0.3, 0.2], [0.5, 0.2], [1.5, 0.2]], Tk2Op::PhasedX)]
+    fn fuse(#[case] angles: &[[f64; 2]], #[case] expected: Tk2Op) {
+        let circ = phasedx_circuit(angles);
+        let fused = fuse_phasedx(&circ);
+        fused.hugr().validate(&REGISTRY).unwrap();
+
+        let ops = fused
+            .operations()
+            .map(|cmd| Tk2Op::try_from(cmd.optype()).unwrap())
+            .collect_vec();
+        assert_eq!(ops, [Tk2Op::H, expected]);
+        // Only the constant angles of the fused gate remain.
+        let n_angles = match expected {
+            Tk2Op::PhasedX => 2,
+            _ => 3,
+        };
+        assert_eq!(fused.commands().count(), 2 + 2 * n_angles);
+
+        assert_same_statevector(&fused, &circ);
+    }
+
+    #[test]
+    fn symbolic_angles() {
+        let mut dfg = DFGBuilder::new(Signature::new_endo(type_row![QB_T])).unwrap();
+        let [mut q] = dfg.input_wires_arr();
+        let [theta] = dfg
+            .add_dataflow_op(symbolic_constant_op("alpha".to_string()), [])
+            .unwrap()
+            .outputs_arr();
+        let phi = dfg.add_load_value(ConstF64::new(0.2));
+        for _ in 0..2 {
+            [q] = dfg
+                .add_dataflow_op(Tk2Op::PhasedX, [q, theta, phi])
+                .unwrap()
+                .outputs_arr();
+        }
+        let circ: Circuit = dfg.finish_hugr_with_outputs([q], &REGISTRY).unwrap().into();
+
+        let fused = fuse_phasedx(&circ);
+        let phasedx_count = fused
+            .operations()
+            .filter(|cmd| Tk2Op::try_from(cmd.optype()) == Ok(Tk2Op::PhasedX))
+            .count();
+        assert_eq!(phasedx_count, 2);
+    }
+}
@@ -0,0 +1,256 @@
+//! Passes that move measurements within a circuit.
+
+use hugr::hugr::hugrmut::HugrMut;
+use hugr::ops::OpType;
+use hugr::{HugrView, IncomingPort, Node, OutgoingPort, PortIndex};
+use itertools::Itertools;
+use thiserror::Error;
+
+use super::GatePredicate;
+use crate::{Circuit, Tk2Op};
+
+/// Move all measurements to the end of the circuit.
+///
+/// A measurement is terminal when the measured qubit is only freed or
+/// returned by the circuit afterwards. Measurements followed only by
+/// [diagonal](Tk2Op::is_diagonal) gates are commuted past them. Using the
+/// deferred measurement principle, every other non-terminal measurement is
+/// replaced by a `CX` onto a freshly allocated ancilla, which is then
+/// measured and freed. The original qubit carries on to the following
+/// operations, and the measurement result is read from the ancilla instead.
+///
+/// Only top-level measurements are considered, and their results may only be
+/// returned by the circuit. A result read by any other operation (e.g. to
+/// classically control a gate) cannot be deferred.
+///
+/// Returns the new circuit and whether any measurement was moved.
+///
+/// # Errors
+///
+/// Returns an error if the result of a measurement is consumed by an
+/// operation other than the circuit's output.
+pub fn push_measurements_to_end(
+    circ: &Circuit<impl HugrView>,
+) -> Result<(Circuit, bool), PushMeasurementsError> {
+    let mut circ = circ.to_owned();
+    let output = circ.output_node();
+    let is_measure = GatePredicate::op(Tk2Op::Measure);
+    let measures = circ
+        .commands()
+        .filter(|cmd| is_measure.matches_command(cmd))
+        .map(|cmd| cmd.node())
+        .collect_vec();
+
+    let mut changed = false;
+    for measure in measures {
+        let hugr = circ.hugr();
+        let bit_readers = hugr
+            .linked_inputs(measure, OutgoingPort::from(1))
+            .collect_vec();
+        if bit_readers.iter().any(|&(reader, _)| reader != output) {
+            return Err(PushMeasurementsError::ResultConsumed { node: measure });
+        }
+        let (next, next_port) = hugr
+            .single_linked_input(measure, OutgoingPort::from(0))
+            .expect("Measure output qubit must be connected");
+        let (prev, prev_port) = hugr
+            .single_linked_output(measure, IncomingPort::from(0))
+            .expect("Measure input qubit must be connected");
+
+        // Measurements commute with the diagonal gates following them.
+        let (end, end_port) = skip_diagonal_gates(&circ, next, next_port);
+        let is_terminal = |node| {
+            node == output || Tk2Op::try_from(circ.hugr().get_optype(node)) == Ok(Tk2Op::QFree)
+        };
+        if is_terminal(end) {
+            if end != next {
+                let (last, last_port) = circ.hugr().single_linked_output(end, end_port).unwrap();
+                let hugr = circ.hugr_mut();
+                hugr.disconnect(measure, IncomingPort::from(0));
+                hugr.disconnect(measure, OutgoingPort::from(0));
+                hugr.disconnect(end, end_port);
+                hugr.connect(prev, prev_port, next, next_port);
+                hugr.connect(last, last_port, measure, 0);
+                hugr.connect(measure, 0, end, end_port);
+                changed = true;
+            }
+            continue;
+        }
+
+        let parent = circ.parent();
+        let hugr = circ.hugr_mut();
+        hugr.remove_node(measure);
+        let alloc = hugr.add_node_with_parent(parent, OpType::from(Tk2Op::QAlloc));
+        let cx = hugr.add_node_with_parent(parent, OpType::from(Tk2Op::CX));
+        let ancilla_measure = hugr.add_node_with_parent(parent, OpType::from(Tk2Op::Measure));
+        let free = hugr.add_node_with_parent(parent, OpType::from(Tk2Op::QFree));
+        hugr.connect(prev, prev_port, cx, 0);
+        hugr.connect(alloc, 0, cx, 1);
+        hugr.connect(cx, 0, next, next_port);
+        hugr.connect(cx, 1, ancilla_measure, 0);
+        hugr.connect(ancilla_measure, 0, free, 0);
+        for (reader, port) in bit_readers {
+            hugr.connect(ancilla_measure, 1, reader, port);
+        }
+        changed = true;
+    }
+    Ok((circ, changed))
+}
+
+/// Follow a qubit wire through the diagonal gates starting at `node`, and
+/// return the first port on it that is not the input of a diagonal gate.
+fn skip_diagonal_gates(
+    circ: &Circuit,
+    mut node: Node,
+    mut port: IncomingPort,
+) -> (Node, IncomingPort) {
+    let hugr = circ.hugr();
+    while Tk2Op::try_from(hugr.get_optype(node)).is_ok_and(|op| op.is_diagonal()) {
+        // Diagonal gates return each qubit on the port it was given on.
+        let out_port = OutgoingPort::from(port.index());
+        (node, port) = hugr
+            .single_linked_input(node, out_port)
+            .expect("Qubit outputs must be connected");
+    }
+    (node, port)
+}
+
+/// Error type for [`push_measurements_to_end`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[non_exhaustive]
+pub enum PushMeasurementsError {
+    /// The result of a measurement is consumed before the end of the circuit.
+    #[error(
+        "The result of the measurement {node} is consumed mid-circuit and cannot be deferred."
+    )]
+    ResultConsumed {
+        /// The measurement node.
+        node: Node,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use hugr::builder::{
+        Dataflow, DataflowHugr, DataflowSubContainer, FunctionBuilder, SubContainer,
+    };
+    use hugr::extension::prelude::{BOOL_T, QB_T};
+    use hugr::types::Signature;
+    use hugr::{type_row, Hugr};
+
+    use super::*;
+    use crate::extension::REGISTRY;
+
+    /// Whether every measurement in the circuit is terminal.
+    fn measurements_at_end(circ: &Circuit) -> bool {
+        circ.commands()
+            .filter(|cmd| Tk2Op::try_from(cmd.optype()) == Ok(Tk2Op::Measure))
+            .all(|cmd| {
+                let (next, _) = circ
+                    .hugr()
+                    .single_linked_input(cmd.node(), OutgoingPort::from(0))
+                    .unwrap();
+                next == circ.output_node()
+                    || Tk2Op::try_from(circ.hugr().get_optype(next)) == Ok(Tk2Op::QFree)
+            })
+    }
+
+    #[test]
+    fn push_mid_circuit_measurement() {
+        let mut h = FunctionBuilder::new(
+            "measure",
+            Signature::new(type_row![QB_T, QB_T], type_row![QB_T, QB_T, BOOL_T]),
+        )
+        .unwrap();
+        let [q0, q1] = h.input_wires_arr();
+        let q0 = h.add_dataflow_op(Tk2Op::H, [q0]).unwrap().out_wire(0);
+        let [q0, bit] = h
+            .add_dataflow_op(Tk2Op::Measure, [q0])
+            .unwrap()
+            .outputs_arr();
+        let [q0, q1] = h
+            .add_dataflow_op(Tk2Op::CX, [q0, q1])
+            .unwrap()
+            .outputs_arr();
+        let hugr: Hugr = h
+            .finish_hugr_with_outputs([q0, q1, bit], &REGISTRY)
+            .unwrap();
+        let circ: Circuit = hugr.into();
+        assert!(!measurements_at_end(&circ));
+
+        let (circ, changed) = push_measurements_to_end(&circ).unwrap();
+        circ.hugr().validate(&REGISTRY).unwrap();
+        assert!(changed);
+        assert!(measurements_at_end(&circ));
+        assert_eq!(circ.n_measurements(), 1);
+
+        // Running the pass again does nothing.
+        let (_, changed) = push_measurements_to_end(&circ).unwrap();
+        assert!(!changed);
+    }
+
+    #[test]
+    fn commute_past_diagonal_gates() {
+        let mut h = FunctionBuilder::new(
+            "measure",
+            Signature::new(type_row![QB_T, QB_T], type_row![QB_T, QB_T, BOOL_T]),
+        )
+        .unwrap();
+        let [q0, q1] = h.input_wires_arr();
+        let [q0, bit] = h
+            .add_dataflow_op(Tk2Op::Measure, [q0])
+            .unwrap()
+            .outputs_arr();
+        let q0 = h.add_dataflow_op(Tk2Op::S, [q0]).unwrap().out_wire(0);
+        let [q1, q0] = h
+            .add_dataflow_op(Tk2Op::CZ, [q1, q0])
+            .unwrap()
+            .outputs_arr();
+        let hugr: Hugr = h
+            .finish_hugr_with_outputs([q0, q1, bit], &REGISTRY)
+            .unwrap();
+        let circ: Circuit = hugr.into();
+        assert!(!measurements_at_end(&circ));
+
+        let (circ, changed) = push_measurements_to_end(&circ).unwrap();
+        circ.hugr().validate(&REGISTRY).unwrap();
+        assert!(changed);
+        assert!(measurements_at_end(&circ));
+        // No ancilla is needed.
+        assert_eq!(circ.operations().count(), 3);
+    }
+
+    #[test]
+    fn classically_consumed_measurement() {
+        let mut h =
+            FunctionBuilder::new("measure", Signature::new(type_row![QB_T], type_row![QB_T]))
+                .unwrap();
+        let [q] = h.input_wires_arr();
+        let [q, bit] = h
+            .add_dataflow_op(Tk2Op::Measure, [q])
+            .unwrap()
+            .outputs_arr();
+        let mut cond = h
+            .conditional_builder(
+                ([type_row![], type_row![]], bit),
+                [(QB_T, q)],
+                type_row![QB_T],
+            )
+            .unwrap();
+        for i in 0..2 {
+            let case = cond.case_builder(i).unwrap();
+            let [q] = case.input_wires_arr();
+            case.finish_with_outputs([q]).unwrap();
+        }
+        let [q] = cond.finish_sub_container().unwrap().outputs_arr();
+        let hugr: Hugr = h.finish_hugr_with_outputs([q], &REGISTRY).unwrap();
+        let circ: Circuit = hugr.into();
+
+        assert_eq!(
+            push_measurements_to_end(&circ).unwrap_err(),
+            PushMeasurementsError::ResultConsumed {
+                node: circ.commands().next().unwrap().node()
+            }
+        );
+    }
+}
@@ -0,0 +1,226 @@
+//! Reduction of rotation angles to a canonical range.
+
+use std::f64::consts::TAU;
+
+use hugr::builder::{DFGBuilder, Dataflow, DataflowHugr};
+use hugr::hugr::hugrmut::HugrMut;
+use hugr::std_extensions::arithmetic::float_types::ConstF64;
+use hugr::HugrView;
+use itertools::Itertools;
+
+use crate::circuit::params::{remove_dead_constants, NumericParams};
+use crate::extension::REGISTRY;
+use crate::rewrite::Subcircuit;
+use crate::serialize::pytket::METADATA_PHASE;
+use crate::{Circuit, Tk2Op};
+
+/// Reduce every numeric rotation angle in the circuit to the range `[0, 2π)`.
+///
+/// Shifting the angle of a rotation gate by 2π negates its unitary, so each
+/// such shift adds a half-turn to the global phase of the circuit. The
+/// azimuthal angle of `PhasedX` is exactly 2π-periodic and is reduced without
+/// changing the phase.
+///
+/// Gates with symbolic angles, and angles already in range, are left
+/// unchanged.
+pub fn normalise_angles(circ: &Circuit<impl HugrView>) -> Circuit {
+    let mut circ = circ.to_owned();
+    let mut params = NumericParams::default();
+    let mut normalisations = Vec::new();
+    for cmd in circ.commands() {
+        if params.process(&cmd).unwrap_or(true) {
+            continue;
+        }
+        let Ok(op) = Tk2Op::try_from(cmd.optype()) else {
+            continue;
+        };
+        let Some(sign_flips) = angle_sign_flips(op) else {
+            continue;
+        };
+        let Ok(angles) = params.inputs(&cmd) else {
+            continue;
+        };
+        let mut phase = 0.;
+        let reduced = angles
+            .iter()
+            .zip(sign_flips)
+            .map(|(&angle, &flips)| {
+                let (turns, reduced) = reduce_angle(angle);
+                if flips {
+                    phase += turns.rem_euclid(2.);
+                }
+                (reduced != angle).then_some(reduced)
+            })
+            .collect_vec();
+        if reduced.iter().any(Option::is_some) {
+            normalisations.push((cmd.node(), op, reduced, phase));
+        }
+    }
+
+    let rewrites = normalisations
+        .into_iter()
+        .map(|(node, op, reduced, phase)| {
+            let subcirc = Subcircuit::try_from_nodes([node], &circ).unwrap();
+            let mut dfg = DFGBuilder::new(subcirc.signature(&circ)).unwrap();
+            // The inputs of a single-node subcircuit follow the node's ports:
+            // the qubits come first, followed by the angles.
+            let inputs = dfg.input_wires().collect_vec();
+            let n_qubits = inputs.len() - reduced.len();
+            let angles = inputs[n_qubits..]
+                .iter()
+                .zip(reduced)
+                .map(|(&wire, angle)| match angle {
+                    Some(angle) => dfg.add_load_value(ConstF64::new(angle)),
+                    None => wire,
+                })
+                .collect_vec();
+            let outputs = dfg
+                .add_dataflow_op(op, inputs[..n_qubits].iter().copied().chain(angles))
+                .unwrap()
+                .outputs();
+            let mut replacement: Circuit = dfg
+                .finish_hugr_with_outputs(outputs, &REGISTRY)
+                .unwrap()
+                .into();
+            if phase != 0. {
+                let root = replacement.parent();
+                replacement
+                    .hugr_mut()
+                    .set_metadata(root, METADATA_PHASE, phase.to_string());
+            }
+            subcirc.create_rewrite(&circ, replacement).unwrap()
+        })
+        .collect_vec();
+    for rewrite in rewrites {
+        rewrite
+            .apply(&mut circ)
+            .expect("Angle normalisations should be valid replacements.");
+    }
+    remove_dead_constants(&mut circ);
+    circ
+}
+
+/// Split an angle into a whole number of turns and a remainder in `[0, 2π)`.
+fn reduce_angle(angle: f64) -> (f64, f64) {
+    let mut turns = (angle / TAU).floor();
+    let mut reduced = angle - turns * TAU;
+    // Rounding errors can leave the remainder just outside the range, which
+    // would be reduced again by a second run of the pass.
+    if reduced < 0. {
+        reduced += TAU;
+        turns -= 1.;
+    }
+    if reduced >= TAU {
+        reduced -= TAU;
+        turns += 1.;
+    }
+    (turns, reduced)
+}
+
+/// For each angle parameter of a rotation gate, whether shifting it by 2π
+/// negates the unitary. Returns `None` for operations without angles.
+fn angle_sign_flips(op: Tk2Op) -> Option<&'static [bool]> {
+    match op {
+        Tk2Op::RzF64 | Tk2Op::RxF64 | Tk2Op::ZZPhase => Some(&[true]),
+        // PhasedX(θ, φ) = Rz(φ) Rx(θ) Rz(-φ)
+        Tk2Op::PhasedX => Some(&[true, false]),
+        Tk2Op::TK1 => Some(&[true, true, true]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::passes::Pass;
+    use crate::rewrite::circuit_phase;
+    use crate::utils::test::{assert_idempotent, assert_same_statevector};
+    use hugr::extension::prelude::QB_T;
+    use hugr::type_row;
+    use hugr::types::Signature;
+    use rstest::rstest;
+    use std::f64::consts::PI;
+
+    /// A single-qubit circuit applying `H` and then `op` with the given
+    /// angles.
+    fn rotation_circuit(op: Tk2Op, angles: &[f64]) -> Circuit {
+        let mut dfg = DFGBuilder::new(Signature::new_endo(type_row![QB_T])).unwrap();
+        let [q] = dfg.input_wires_arr();
+        let [q] = dfg.add_dataflow_op(Tk2Op::H, [q]).unwrap().outputs_arr();
+        let angles = angles
+            .iter()
+            .map(|&a| dfg.add_load_value(ConstF64::new(a)))
+            .collect_vec();
+        let [q] = dfg
+            .add_dataflow_op(op, [q].into_iter().chain(angles))
+            .unwrap()
+            .outputs_arr();
+        dfg.finish_hugr_with_outputs([q], &REGISTRY).unwrap().into()
+    }
+
+    /// The angles of the last operation in a circuit.
+    fn last_angles(circ: &Circuit) -> Vec<f64> {
+        let mut params = NumericParams::default();
+        let mut angles = Vec::new();
+        for cmd in circ.commands() {
+            if !params.process(&cmd).unwrap() {
+                angles = params.inputs(&cmd).unwrap();
+            }
+        }
+        angles
+    }
+
+    #[rstest]
+    #[case::rz_two_turns(Tk2Op::RzF64, &[5.5 * PI], &[1.5 * PI], 0.)]
+    #[case::rz_one_turn(Tk2Op::RzF64, &[3. * PI], &[PI], 1.)]
+    #[case::rx_negative(Tk2Op::RxF64, &[-0.5 * PI], &[1.5 * PI], 1.)]
+    #[case::phasedx_azimuth(Tk2Op::PhasedX, &[0.5, 0.3 + TAU], &[0.5, 0.3], 0.)]
+    #[case::in_range(Tk2Op::RzF64, &[0.5], &[0.5], 0.)]
+    fn normalise(
+        #[case] op: Tk2Op,
+        #[case] angles: &[f64],
+        #[case] expected_angles: &[f64],
+        #[case] expected_phase: f64,
+    ) {
+        let circ = rotation_circuit(op, angles);
+        let normalised = normalise_angles(&circ);
+        normalised.hugr().validate(&REGISTRY).unwrap();
+
+        let reduced = last_angles(&normalised);
+        assert_eq!(reduced.len(), expected_angles.len());
+        for (a, b) in reduced.iter().zip(expected_angles) {
+            assert!((a - b).abs() < 1e-9, "{reduced:?} != {expected_angles:?}");
+        }
+        let phase = circuit_phase(&normalised);
+        assert_eq!(phase, expected_phase);
+        assert_eq!(normalised.commands().count(), circ.commands().count());
+
+        assert_same_statevector(&normalised, &circ);
+    }
+
+    #[rstest]
+    #[case::in_range(Tk2Op::RzF64, &[0.5])]
+    #[case::negative(Tk2Op::RxF64, &[-0.5 * PI])]
+    #[case::tiny_negative(Tk2Op::RzF64, &[-1e-17])]
+    #[case::below_full_turn(Tk2Op::ZZPhase, &[TAU - 1e-16])]
+    #[case::tk1(Tk2Op::TK1, &[-1e-17, 3. * TAU, TAU - 1e-16])]
+    fn idempotent(#[case] op: Tk2Op, #[case] angles: &[f64]) {
+        let circ = match op {
+            Tk2Op::ZZPhase => {
+                let qbs = type_row![QB_T, QB_T];
+                let mut dfg = DFGBuilder::new(Signature::new_endo(qbs)).unwrap();
+                let [q0, q1] = dfg.input_wires_arr();
+                let angle = dfg.add_load_value(ConstF64::new(angles[0]));
+                let outputs = dfg.add_dataflow_op(op, [q0, q1, angle]).unwrap().outputs();
+                dfg.finish_hugr_with_outputs(outputs, &REGISTRY)
+                    .unwrap()
+                    .into()
+            }
+            _ => rotation_circuit(op, angles),
+        };
+        let normalised = assert_idempotent(Pass::NormaliseAngles, &circ);
+        for angle in last_angles(&normalised) {
+            assert!((0. ..TAU).contains(&angle), "{angle} is out of range");
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! Removal of explicit qubit permutations.
+
+use hugr::hugr::hugrmut::HugrMut;
+use hugr::{IncomingPort, OutgoingPort};
+use itertools::Itertools;
+
+use crate::ops::match_permutation_op;
+use crate::Circuit;
+
+/// Remove every qubit permutation operation from the circuit, absorbing it
+/// into the wiring of the circuit.
+///
+/// Permutations carry no quantum operation, so each qubit wire entering a
+/// permutation is connected directly to the consumers of the corresponding
+/// output. A permutation that reaches the circuit boundary is absorbed into the
+/// mapping between input and output qubits, reported by
+/// [`Circuit::implicit_permutation`] and exported to pytket as its
+/// `implicit_permutation`.
+///
+/// Only the top-level operations of the circuit are considered, see
+/// [`Circuit::operations`]. Returns the number of permutations removed.
+pub fn push_permutations(circ: &mut Circuit<impl HugrMut>) -> usize {
+    let permutations = circ
+        .commands()
+        .filter_map(|cmd| Some((cmd.node(), match_permutation_op(cmd.optype())?)))
+        .collect_vec();
+
+    let hugr = circ.hugr_mut();
+    for (node, perm) in &permutations {
+        let links = perm
+            .iter()
+            .enumerate()
+            .map(|(i, &p)| {
+                let (src, src_port) = hugr
+                    .single_linked_output(*node, IncomingPort::from(i))
+                    .expect("Permutation inputs must be connected.");
+                let targets = hugr
+                    .linked_inputs(*node, OutgoingPort::from(p))
+                    .collect_vec();
+                (src, src_port, targets)
+            })
+            .collect_vec();
+        hugr.remove_node(*node);
+        for (src, src_port, targets) in links {
+            for (tgt, tgt_port) in targets {
+                hugr.connect(src, src_port, tgt, tgt_port);
+            }
+        }
+    }
+    permutations.len()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::extension::REGISTRY;
+    use crate::utils::build_simple_circuit;
+    use crate::{permutation_op, Tk2Op};
+    use hugr::{HugrView, Node, PortIndex};
+
+    #[test]
+    fn absorb_boundary_permutation() {
+        let mut circ = build_simple_circuit(3, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(permutation_op([2, 0, 1]), [0, 1, 2])?;
+            Ok(())
+        })
+        .unwrap();
+        circ.hugr().validate(&REGISTRY).unwrap();
+        let h = circ.operations().next().unwrap().node();
+
+        assert_eq!(push_permutations(&mut circ), 1);
+        circ.hugr().validate(&REGISTRY).unwrap();
+        assert_eq!(circ.operations().count(), 1);
+
+        // The `H` qubit now ends at output 2, and inputs 1 and 2 are connected
+        // to outputs 0 and 1.
+        let output_source = |port: usize| -> (Node, usize) {
+            let (node, port) = circ
+                .hugr()
+                .single_linked_output(circ.output_node(), port)
+                .unwrap();
+            (node, port.index())
+        };
+        let input = circ.input_node();
+        assert_eq!(output_source(0), (input, 1));
+        assert_eq!(output_source(1), (input, 2));
+        assert_eq!(output_source(2), (h, 0));
+        assert_eq!(circ.implicit_permutation(), [1, 2, 0]);
+    }
+}
@@ -0,0 +1,181 @@
+//! Composable predicates for selecting the operations a pass acts on.
+
+use std::fmt;
+use std::ops::Not;
+use std::sync::Arc;
+
+use hugr::ops::OpType;
+use hugr::HugrView;
+use itertools::Itertools;
+
+use crate::circuit::units::LinearUnit;
+use crate::circuit::Command;
+use crate::{Circuit, Tk2Op};
+
+/// The function evaluated by a [`GatePredicate`].
+type PredicateFn = dyn Fn(&OpType, &[LinearUnit]) -> bool + Send + Sync;
+
+/// A condition on an operation and the qubits it acts on.
+///
+/// Predicates are built from the provided constructors, or from an arbitrary
+/// function with [`GatePredicate::new`], and combined with
+/// [`GatePredicate::and`], [`GatePredicate::or`] and negation (`!`).
+///
+/// # Example
+///
+/// ```
+/// use tket2::passes::GatePredicate;
+///
+/// // Two-qubit gates that are not Clifford.
+/// let pred = GatePredicate::two_qubit().and(!GatePredicate::clifford());
+/// ```
+#[derive(Clone)]
+pub struct GatePredicate {
+    pred: Arc<PredicateFn>,
+}
+
+impl GatePredicate {
+    /// Create a predicate from a function of the operation and the qubits it
+    /// acts on.
+    pub fn new(pred: impl Fn(&OpType, &[LinearUnit]) -> bool + Send + Sync + 'static) -> Self {
+        Self {
+            pred: Arc::new(pred),
+        }
+    }
+
+    /// A predicate matching every operation.
+    pub fn any() -> Self {
+        Self::new(|_, _| true)
+    }
+
+    /// Operations acting on exactly `n` qubits.
+    pub fn n_qubits(n: usize) -> Self {
+        Self::new(move |_, qubits| qubits.len() == n)
+    }
+
+    /// Operations acting on a single qubit.
+    pub fn single_qubit() -> Self {
+        Self::n_qubits(1)
+    }
+
+    /// Operations acting on exactly two qubits.
+    pub fn two_qubit() -> Self {
+        Self::n_qubits(2)
+    }
+
+    /// Operations acting on the given qubit.
+    pub fn on_qubit(qubit: LinearUnit) -> Self {
+        Self::new(move |_, qubits| qubits.contains(&qubit))
+    }
+
+    /// A specific [`Tk2Op`].
+    pub fn op(op: Tk2Op) -> Self {
+        Self::new(move |optype, _| Tk2Op::try_from(optype) == Ok(op))
+    }
+
+    /// Any of the given [`Tk2Op`]s.
+    pub fn ops(ops: impl IntoIterator<Item = Tk2Op>) -> Self {
+        let ops = ops.into_iter().collect_vec();
+        Self::new(move |optype, _| Tk2Op::try_from(optype).is_ok_and(|op| ops.contains(&op)))
+    }
+
+    /// Clifford [`Tk2Op`]s, see [`Tk2Op::is_clifford`].
+    pub fn clifford() -> Self {
+        Self::new(|optype, _| Tk2Op::try_from(optype).is_ok_and(|op| op.is_clifford()))
+    }
+
+    /// Quantum [`Tk2Op`]s, see [`Tk2Op::is_quantum`].
+    pub fn quantum() -> Self {
+        Self::new(|optype, _| Tk2Op::try_from(optype).is_ok_and(|op| op.is_quantum()))
+    }
+
+    /// Operations matching both predicates.
+    pub fn and(self, other: Self) -> Self {
+        Self::new(move |op, qubits| (self.pred)(op, qubits) && (other.pred)(op, qubits))
+    }
+
+    /// Operations matching either predicate.
+    pub fn or(self, other: Self) -> Self {
+        Self::new(move |op, qubits| (self.pred)(op, qubits) || (other.pred)(op, qubits))
+    }
+
+    /// Evaluate the predicate on an operation acting on some qubits.
+    pub fn matches(&self, op: &OpType, qubits: &[LinearUnit]) -> bool {
+        (self.pred)(op, qubits)
+    }
+
+    /// Evaluate the predicate on a command of a circuit.
+    ///
+    /// The qubits of the command are its qubit inputs, followed by any qubits
+    /// it allocates.
+    pub fn matches_command(&self, cmd: &Command<'_, impl HugrView>) -> bool {
+        let qubits = cmd
+            .input_qubits()
+            .map(|(qb, _, _)| qb)
+            .chain(cmd.output_qubits().map(|(qb, _, _)| qb))
+            .unique()
+            .collect_vec();
+        self.matches(cmd.optype(), &qubits)
+    }
+}
+
+impl Not for GatePredicate {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Self::new(move |op, qubits| !(self.pred)(op, qubits))
+    }
+}
+
+impl fmt::Debug for GatePredicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GatePredicate").finish_non_exhaustive()
+    }
+}
+
+impl<T: HugrView> Circuit<T> {
+    /// Count the top-level operations of the circuit matching a predicate.
+    ///
+    /// See [`Circuit::operations`].
+    pub fn count_where(&self, pred: &GatePredicate) -> usize {
+        self.operations()
+            .filter(|cmd| pred.matches_command(cmd))
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::build_simple_circuit;
+    use rstest::rstest;
+
+    fn circ() -> Circuit {
+        build_simple_circuit(3, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(Tk2Op::T, [1])?;
+            circ.append(Tk2Op::ZZMax, [1, 2])?;
+            circ.append(Tk2Op::CX, [2, 0])?;
+            Ok(())
+        })
+        .unwrap()
+    }
+
+    #[rstest]
+    #[case::any(GatePredicate::any(), 5)]
+    #[case::two_qubit(GatePredicate::two_qubit(), 3)]
+    #[case::not_clifford(!GatePredicate::clifford(), 1)]
+    #[case::and(GatePredicate::two_qubit().and(GatePredicate::op(Tk2Op::CX)), 2)]
+    #[case::or(GatePredicate::single_qubit().or(GatePredicate::op(Tk2Op::ZZMax)), 3)]
+    #[case::ops(GatePredicate::ops([Tk2Op::H, Tk2Op::T]), 2)]
+    #[case::on_qubit(GatePredicate::on_qubit(LinearUnit::new(2)), 2)]
+    #[case::nested(
+        GatePredicate::on_qubit(LinearUnit::new(0))
+            .and(!GatePredicate::single_qubit().or(GatePredicate::op(Tk2Op::ZZMax))),
+        2
+    )]
+    fn count_where(#[case] pred: GatePredicate, #[case] expected: usize) {
+        assert_eq!(circ().count_where(&pred), expected);
+    }
+}
@@ -0,0 +1,290 @@
+//! Peephole resynthesis of two-qubit subcircuits.
+
+use std::collections::HashMap;
+
+use hugr::builder::{DFGBuilder, Dataflow, DataflowHugr};
+use hugr::extension::prelude::QB_T;
+use hugr::hugr::hugrmut::HugrMut;
+use hugr::std_extensions::arithmetic::float_types::ConstF64;
+use hugr::{HugrView, Node, PortIndex};
+use itertools::Itertools;
+use num_complex::Complex64;
+
+use super::cleanup::{check_unitary, UnitaryPassError};
+use crate::circuit::params::{remove_dead_constants, NumericParams};
+use crate::circuit::simulate::{apply_gate, gate_matrix};
+use crate::circuit::synth::kak_gates;
+use crate::extension::REGISTRY;
+use crate::rewrite::{transfer_error_budget, CircuitRewrite, Subcircuit};
+use crate::serialize::pytket::METADATA_PHASE;
+use crate::{Circuit, Tk2Op};
+
+/// Resynthesise the two-qubit blocks of a circuit with as few `CX` gates as
+/// possible.
+///
+/// A block is a maximal sequence of unitary gates acting on a fixed pair of
+/// qubits, delimited by gates entangling either qubit with a third one, or
+/// by non-unitary operations. Each block is replaced by its KAK
+/// decomposition (see [`kak`]) when that uses fewer two-qubit gates than the
+/// original. Blocks containing gates with symbolic angles, or whose
+/// decomposition fails numerically, are left untouched.
+///
+/// Non-unitary operations such as `Measure` are treated as barriers between
+/// blocks, unless `strict` is set.
+///
+/// Returns the resynthesised circuit, and whether it was modified. The
+/// unitary of the circuit, including its global phase, is preserved.
+///
+/// # Errors
+///
+/// If `strict` is set, returns an error if the circuit contains a non-unitary
+/// operation.
+///
+/// [`kak`]: crate::circuit::synth::kak
+pub fn resynthesise_2q(
+    circ: &Circuit<impl HugrView>,
+    strict: bool,
+) -> Result<(Circuit, bool), UnitaryPassError> {
+    if strict {
+        check_unitary(circ)?;
+    }
+    let mut circ = circ.to_owned();
+    let rewrites = two_qubit_blocks(&circ)
+        .into_iter()
+        .filter_map(|block| block.resynthesise(&circ))
+        .collect_vec();
+    let changed = !rewrites.is_empty();
+    for rewrite in rewrites {
+        rewrite
+            .apply(&mut circ)
+            .expect("Resynthesis rewrites should be valid replacements.");
+    }
+    remove_dead_constants(&mut circ);
+    Ok((circ, changed))
+}
+
+/// A sequence of gates acting on a pair of qubits.
+#[derive(Debug, Clone, Default)]
+struct Block {
+    /// The gates in the block, in order, with the block qubit (`0` or `1`) on
+    /// each of their qubit ports and their unitary.
+    gates: Vec<(Node, Vec<usize>, Vec<Complex64>)>,
+    /// The number of two-qubit gates in the block.
+    two_qubit_count: usize,
+}
+
+impl Block {
+    /// The 4×4 unitary of the block.
+    fn unitary(&self) -> Vec<Complex64> {
+        let columns = (0..4)
+            .map(|col| {
+                let mut state = vec![Complex64::new(0., 0.); 4];
+                state[col] = Complex64::new(1., 0.);
+                for (_, qubits, matrix) in &self.gates {
+                    apply_gate(&mut state, 2, qubits, matrix);
+                }
+                state
+            })
+            .collect_vec();
+        (0..16).map(|k| columns[k % 4][k / 4]).collect()
+    }
+
+    /// A rewrite replacing the block with a circuit using fewer two-qubit
+    /// gates, if there is one.
+    fn resynthesise(&self, circ: &Circuit) -> Option<CircuitRewrite> {
+        if self.two_qubit_count < 2 {
+            return None;
+        }
+        // A failed decomposition leaves the block unchanged.
+        let (gates, phase) = kak_gates(&self.unitary()).ok()?;
+        let cx_count = gates.iter().filter(|(op, _, _)| *op == Tk2Op::CX).count();
+        if cx_count >= self.two_qubit_count {
+            return None;
+        }
+
+        // The block qubit on each qubit port of the gates. The qubits of a
+        // `Tk2Op` are on the same ports at its input and output.
+        let block_qubit: HashMap<(Node, usize), usize> = self
+            .gates
+            .iter()
+            .flat_map(|(node, qubits, _)| {
+                qubits
+                    .iter()
+                    .enumerate()
+                    .map(|(port, &q)| ((*node, port), q))
+            })
+            .collect();
+
+        let nodes = self.gates.iter().map(|(node, _, _)| *node).collect_vec();
+        let subcirc = Subcircuit::try_from_nodes(nodes, circ).ok()?;
+        let signature = subcirc.signature(circ);
+        let mut dfg = DFGBuilder::new(signature.clone()).unwrap();
+        let mut qbs = [None; 2];
+        for ((wire, ty), ports) in dfg
+            .input_wires()
+            .zip(signature.input().iter())
+            .zip(subcirc.subgraph.incoming_ports())
+        {
+            if ty == &QB_T {
+                let (node, port) = ports[0];
+                qbs[block_qubit[&(node, port.index())]] = Some(wire);
+            }
+        }
+        let mut qbs = qbs.map(|wire| wire.expect("The block acts on two qubits."));
+        for &(op, qubits, angle) in &gates {
+            let mut inputs = qubits.iter().map(|&q| qbs[q]).collect_vec();
+            if let Some(angle) = angle {
+                inputs.push(dfg.add_load_value(ConstF64::new(angle)));
+            }
+            let outputs = dfg.add_dataflow_op(op, inputs).unwrap().outputs();
+            for (&q, wire) in qubits.iter().zip(outputs) {
+                qbs[q] = wire;
+            }
+        }
+        let outputs = subcirc
+            .subgraph
+            .outgoing_ports()
+            .iter()
+            .map(|&(node, port)| qbs[block_qubit[&(node, port.index())]])
+            .collect_vec();
+        let mut replacement: Circuit = dfg
+            .finish_hugr_with_outputs(outputs, &REGISTRY)
+            .unwrap()
+            .into();
+        let root = replacement.parent();
+        replacement
+            .hugr_mut()
+            .set_metadata(root, METADATA_PHASE, phase.to_string());
+        transfer_error_budget(circ, subcirc.nodes(), &mut replacement);
+        subcirc.create_rewrite(circ, replacement).ok()
+    }
+}
+
+/// The maximal two-qubit blocks of a circuit.
+fn two_qubit_blocks(circ: &Circuit) -> Vec<Block> {
+    let mut params = NumericParams::default();
+    let mut blocks: Vec<(Block, [usize; 2])> = Vec::new();
+    // The block currently open on each qubit.
+    let mut open: HashMap<usize, usize> = HashMap::new();
+    for cmd in circ.commands() {
+        if params.process(&cmd).unwrap_or(true) {
+            continue;
+        }
+        let qubits = cmd
+            .input_qubits()
+            .map(|(unit, _, _)| unit.index())
+            .collect_vec();
+        let matrix = Tk2Op::try_from(cmd.optype())
+            .ok()
+            .zip(params.inputs(&cmd).ok())
+            .and_then(|(op, angles)| gate_matrix(op, &angles));
+        let Some(matrix) = matrix else {
+            for q in &qubits {
+                open.remove(q);
+            }
+            continue;
+        };
+        match *qubits.as_slice() {
+            [q] => {
+                if let Some(&b) = open.get(&q) {
+                    let (block, block_qubits) = &mut blocks[b];
+                    let label = block_qubits.iter().position(|&bq| bq == q).unwrap();
+                    block.gates.push((cmd.node(), vec![label], matrix));
+                }
+            }
+            [q0, q1] => {
+                let b = match (open.get(&q0), open.get(&q1)) {
+                    (Some(&b0), Some(&b1)) if b0 == b1 => b0,
+                    _ => {
+                        blocks.push((Block::default(), [q0, q1]));
+                        open.insert(q0, blocks.len() - 1);
+                        open.insert(q1, blocks.len() - 1);
+                        blocks.len() - 1
+                    }
+                };
+                let (block, block_qubits) = &mut blocks[b];
+                let labels = [q0, q1]
+                    .iter()
+                    .map(|q| block_qubits.iter().position(|bq| bq == q).unwrap())
+                    .collect();
+                block.gates.push((cmd.node(), labels, matrix));
+                block.two_qubit_count += 1;
+            }
+            _ => {
+                for q in &qubits {
+                    open.remove(q);
+                }
+            }
+        }
+    }
+    blocks.into_iter().map(|(block, _)| block).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::build_simple_circuit;
+    use crate::utils::test::cx_count;
+
+    #[test]
+    fn reduce_cx_block() {
+        // A block of four `CX`s on qubits 0 and 1, where the last pair only
+        // conjugates an `X` on the target. The first pair needs two `CX`s.
+        let circ = build_simple_circuit(3, |circ| {
+            circ.append(Tk2Op::H, [2])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::T, [1])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(Tk2Op::X, [1])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(Tk2Op::CX, [1, 2])?;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(cx_count(&circ), 5);
+
+        let (resynth, changed) = resynthesise_2q(&circ, false).unwrap();
+        assert!(changed);
+        resynth.hugr().validate(&REGISTRY).unwrap();
+        assert_eq!(cx_count(&resynth), 3);
+
+        let (u, v) = (circ.unitary().unwrap(), resynth.unitary().unwrap());
+        assert!(u.iter().zip(&v).all(|(a, b)| (a - b).norm() < 1e-6));
+    }
+
+    #[test]
+    fn keep_error_budget() {
+        let mut circ = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            Ok(())
+        })
+        .unwrap();
+        let cxs = circ.operations().map(|cmd| cmd.node()).collect_vec();
+        for cx in cxs {
+            circ.set_error_budget(cx, 0.25);
+        }
+
+        let (resynth, changed) = resynthesise_2q(&circ, false).unwrap();
+        assert!(changed);
+        assert_eq!(cx_count(&resynth), 1);
+        assert_eq!(resynth.total_error_budget(), 0.75);
+    }
+
+    #[test]
+    fn keep_optimal_block() {
+        let circ = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(Tk2Op::CX, [1, 0])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            Ok(())
+        })
+        .unwrap();
+        let (resynth, changed) = resynthesise_2q(&circ, false).unwrap();
+        assert!(!changed);
+        assert_eq!(cx_count(&resynth), 3);
+    }
+}
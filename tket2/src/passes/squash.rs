@@ -0,0 +1,266 @@
+//! Merging of `Rz` rotations across `CX` networks, reducing the number of `T`
+//! gates.
+
+use std::collections::{BTreeSet, HashMap};
+use std::f64::consts::{FRAC_PI_4, TAU};
+
+use hugr::builder::Dataflow;
+use hugr::std_extensions::arithmetic::float_types::ConstF64;
+use hugr::{HugrView, Node};
+use itertools::Itertools;
+
+use super::cleanup::replace_nodes;
+use crate::circuit::params::{quarter_turns, remove_dead_constants, NumericParams};
+use crate::rewrite::CircuitRewrite;
+use crate::{Circuit, Tk2Op};
+
+/// The number of `T` gates of a circuit before and after
+/// [`squash_rz_pi_over_4`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TCountReduction {
+    /// The `T`-count of the original circuit.
+    pub before: usize,
+    /// The `T`-count of the optimised circuit.
+    pub after: usize,
+}
+
+/// Merge the `Rz` rotations of a circuit that act on the same parity of
+/// qubits.
+///
+/// Within a network of `CX` gates and diagonal single-qubit gates, each qubit
+/// holds a parity of the values entering the network. Diagonal gates acting on
+/// the same parity commute with the rest of the network, so they are merged
+/// into a single rotation placed at the first of them. Any other gate starts a
+/// new parity on its qubits. The diagonal gates considered are `T`, `S`, `Z`,
+/// their inverses, and `Rz` with a numeric angle.
+///
+/// Merged rotations by a multiple of `π/4` are emitted as `T`, `S` and `Z`
+/// gates, so two `T` gates on the same parity become an `S`. A group of
+/// rotations is only replaced if that reduces its number of gates, so the
+/// `T`-count never increases. See [`Circuit::t_count`] for the gates counted.
+///
+/// Returns the optimised circuit and its `T`-count before and after the pass.
+/// The unitary of the circuit, including its global phase, is preserved.
+pub fn squash_rz_pi_over_4(circ: &Circuit<impl HugrView>) -> (Circuit, TCountReduction) {
+    let mut circ = circ.to_owned();
+    let before = circ.t_count();
+    let rewrites = parity_groups(&circ)
+        .into_iter()
+        .filter_map(|group| group.rewrites(&circ))
+        .flatten()
+        .collect_vec();
+    for rewrite in rewrites {
+        rewrite
+            .apply(&mut circ)
+            .expect("Rotation merges should be valid replacements.");
+    }
+    remove_dead_constants(&mut circ);
+    let after = circ.t_count();
+    (circ, TCountReduction { before, after })
+}
+
+/// The diagonal gates acting on a parity of qubits.
+#[derive(Debug, Clone)]
+struct ParityGroup {
+    /// The gates in the group, in order.
+    nodes: Vec<Node>,
+    /// The sum of the `Rz` angles of the gates, in radians.
+    angle: f64,
+    /// The sum of the global phases of the gates relative to their `Rz`
+    /// rotations, in half-turns.
+    phase: f64,
+}
+
+impl ParityGroup {
+    /// Rewrites replacing the first gate of the group with the merged
+    /// rotation and removing the other ones, if that reduces the number of
+    /// gates.
+    fn rewrites(&self, circ: &Circuit) -> Option<Vec<CircuitRewrite>> {
+        let gates = merged_gates(self.angle);
+        if gates.len() >= self.nodes.len() {
+            return None;
+        }
+        // Rotations by angles differing by 2π are opposite.
+        let (merged_angle, merged_phase) = gates
+            .iter()
+            .map(|&(op, angle)| (angle, op.global_phase()))
+            .fold((0., 0.), |(a, p), (angle, phase)| (a + angle, p + phase));
+        let turns = ((self.angle - merged_angle) / TAU).round();
+        let phase = self.phase - merged_phase + turns;
+
+        let (first, rest) = self.nodes.split_first()?;
+        let mut rewrites = vec![replace_nodes(circ, &[*first], phase, |dfg, qubits| {
+            gates.iter().fold(qubits, |qubits, &(op, angle)| {
+                let angle = (op == Tk2Op::RzF64).then(|| dfg.add_load_value(ConstF64::new(angle)));
+                dfg.add_dataflow_op(op, qubits.into_iter().chain(angle))
+                    .unwrap()
+                    .outputs()
+                    .collect()
+            })
+        })];
+        rewrites.extend(
+            rest.iter()
+                .map(|&node| replace_nodes(circ, &[node], 0., |_, qubits| qubits)),
+        );
+        Some(rewrites)
+    }
+}
+
+/// The gates implementing an `Rz` rotation by `angle`, up to a global phase,
+/// with their `Rz` angles.
+fn merged_gates(angle: f64) -> Vec<(Tk2Op, f64)> {
+    let Some(k) = quarter_turns(angle) else {
+        return vec![(Tk2Op::RzF64, angle)];
+    };
+    // Reduce to a multiple of π/4 in (-π, π].
+    let k = (k + 3).rem_euclid(8) - 3;
+    let ops: &[Tk2Op] = match k {
+        0 => &[],
+        1 => &[Tk2Op::T],
+        2 => &[Tk2Op::S],
+        3 => &[Tk2Op::S, Tk2Op::T],
+        4 => &[Tk2Op::Z],
+        -1 => &[Tk2Op::Tdg],
+        -2 => &[Tk2Op::Sdg],
+        -3 => &[Tk2Op::Sdg, Tk2Op::Tdg],
+        _ => unreachable!(),
+    };
+    ops.iter()
+        .map(|&op| (op, z_rotation(op).unwrap()))
+        .collect()
+}
+
+/// The `Rz` angle of a fixed diagonal gate, in radians.
+fn z_rotation(op: Tk2Op) -> Option<f64> {
+    let quarters = match op {
+        Tk2Op::T => 1.,
+        Tk2Op::Tdg => -1.,
+        Tk2Op::S => 2.,
+        Tk2Op::Sdg => -2.,
+        Tk2Op::Z => 4.,
+        _ => return None,
+    };
+    Some(quarters * FRAC_PI_4)
+}
+
+/// Group the diagonal gates of a circuit by the parity they act on.
+fn parity_groups(circ: &Circuit) -> Vec<ParityGroup> {
+    let mut params = NumericParams::default();
+    let mut groups: Vec<ParityGroup> = Vec::new();
+    let mut group_of: HashMap<BTreeSet<usize>, usize> = HashMap::new();
+    // The parity on each qubit, as a set of variables.
+    let mut parities: HashMap<usize, BTreeSet<usize>> = HashMap::new();
+    let mut next_var = 0;
+    let mut fresh = || {
+        next_var += 1;
+        BTreeSet::from([next_var - 1])
+    };
+
+    for cmd in circ.commands() {
+        if params.process(&cmd).unwrap_or(true) {
+            continue;
+        }
+        let qubits = cmd
+            .input_qubits()
+            .map(|(unit, _, _)| unit.index())
+            .collect_vec();
+        for &q in &qubits {
+            parities.entry(q).or_insert_with(&mut fresh);
+        }
+        let op = Tk2Op::try_from(cmd.optype()).ok();
+        let rotation = match op {
+            Some(Tk2Op::RzF64) => params.inputs(&cmd).ok().map(|angles| angles[0]),
+            Some(op) => z_rotation(op),
+            None => None,
+        };
+        match (op, qubits.as_slice()) {
+            (Some(Tk2Op::CX), &[c, t]) => {
+                let control = parities[&c].clone();
+                let target = parities.get_mut(&t).unwrap();
+                *target = target.symmetric_difference(&control).copied().collect();
+            }
+            (Some(op), &[q]) if rotation.is_some() => {
+                let parity = parities[&q].clone();
+                let g = *group_of.entry(parity).or_insert_with(|| {
+                    groups.push(ParityGroup {
+                        nodes: Vec::new(),
+                        angle: 0.,
+                        phase: 0.,
+                    });
+                    groups.len() - 1
+                });
+                groups[g].nodes.push(cmd.node());
+                groups[g].angle += rotation.unwrap();
+                groups[g].phase += op.global_phase();
+            }
+            // `Rz` rotations with symbolic angles are diagonal, but cannot be
+            // merged.
+            (Some(Tk2Op::RzF64), _) => {}
+            _ => {
+                for q in qubits {
+                    parities.insert(q, fresh());
+                }
+            }
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::extension::REGISTRY;
+    use crate::utils::build_simple_circuit;
+    use rstest::rstest;
+
+    #[rstest]
+    // The first and last `T` act on `q0`, separated by a `CX` pair.
+    #[case::merge_across_cx(&[
+        (Tk2Op::T, &[0][..]),
+        (Tk2Op::CX, &[0, 1][..]),
+        (Tk2Op::T, &[1][..]),
+        (Tk2Op::CX, &[0, 1][..]),
+        (Tk2Op::T, &[0][..]),
+    ], 3, 1, &[Tk2Op::S, Tk2Op::CX, Tk2Op::T, Tk2Op::CX])]
+    // The `T` on `q1` acts on `q0 ⊕ q1` after the first `CX`, and on `q1`
+    // again after the second one.
+    #[case::merge_parity(&[
+        (Tk2Op::CX, &[0, 1][..]),
+        (Tk2Op::T, &[1][..]),
+        (Tk2Op::CX, &[0, 1][..]),
+        (Tk2Op::Tdg, &[1][..]),
+        (Tk2Op::CX, &[0, 1][..]),
+        (Tk2Op::Tdg, &[1][..]),
+    ], 3, 1, &[Tk2Op::CX, Tk2Op::CX, Tk2Op::Tdg, Tk2Op::CX])]
+    #[case::blocked_by_h(&[
+        (Tk2Op::T, &[0][..]),
+        (Tk2Op::H, &[0][..]),
+        (Tk2Op::T, &[0][..]),
+    ], 2, 2, &[Tk2Op::T, Tk2Op::H, Tk2Op::T])]
+    fn squash_t_gates(
+        #[case] gates: &[(Tk2Op, &[usize])],
+        #[case] before: usize,
+        #[case] after: usize,
+        #[case] expected_ops: &[Tk2Op],
+    ) {
+        let circ = build_simple_circuit(2, |circ| {
+            for &(op, qubits) in gates {
+                circ.append(op, qubits.iter().copied())?;
+            }
+            Ok(())
+        })
+        .unwrap();
+        let (squashed, reduction) = squash_rz_pi_over_4(&circ);
+        squashed.hugr().validate(&REGISTRY).unwrap();
+        assert_eq!(reduction, TCountReduction { before, after });
+
+        let ops = squashed
+            .operations()
+            .map(|cmd| Tk2Op::try_from(cmd.optype()).unwrap())
+            .collect_vec();
+        assert_eq!(ops, expected_ops);
+
+        let (u, v) = (circ.unitary().unwrap(), squashed.unitary().unwrap());
+        assert!(u.iter().zip(&v).all(|(a, b)| (a - b).norm() < 1e-9));
+    }
+}
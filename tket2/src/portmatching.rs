@@ -59,7 +59,9 @@ pub mod pattern;
 use hugr::types::EdgeKind;
 use hugr::{HugrView, OutgoingPort};
 use itertools::Itertools;
-pub use matcher::{PatternMatch, PatternMatcher};
+pub use matcher::{
+    non_overlapping_subset, non_overlapping_subset_by, PatternMatch, PatternMatcher,
+};
 pub use pattern::CircuitPattern;
 
 use hugr::{
@@ -1,6 +1,7 @@
 //! Pattern and matcher objects for circuit matching
 
 use std::{
+    collections::HashSet,
     fmt::Debug,
     fs::File,
     io,
@@ -12,7 +13,8 @@ use hugr::hugr::views::sibling_subgraph::{
     InvalidReplacement, InvalidSubgraph, InvalidSubgraphBoundary, TopoConvexChecker,
 };
 use hugr::hugr::views::SiblingSubgraph;
-use hugr::ops::{CustomOp, NamedOp, OpType};
+use hugr::ops::{CustomOp, NamedOp, OpTrait, OpType};
+use hugr::types::{Type, TypeRow};
 use hugr::{HugrView, IncomingPort, Node, OutgoingPort, Port, PortIndex};
 use itertools::Itertools;
 use portgraph::algorithms::ConvexChecker;
@@ -26,6 +28,7 @@ use thiserror::Error;
 use crate::{
     circuit::Circuit,
     rewrite::{CircuitRewrite, Subcircuit},
+    utils::type_is_linear,
 };
 
 /// Matchable operations in a circuit.
@@ -50,6 +53,40 @@ impl From<OpType> for MatchOp {
     }
 }
 
+impl MatchOp {
+    /// A wildcard matching any custom operation with the same linear port
+    /// types as `op`.
+    pub(crate) fn wildcard(op: &OpType) -> Self {
+        let encoded = rmp_serde::encode::to_vec(&linear_signature(op)).ok();
+        Self {
+            op_name: WILDCARD_OP_NAME.into(),
+            encoded,
+        }
+    }
+
+    /// The name of the matched operation.
+    pub(crate) fn name(&self) -> &str {
+        &self.op_name
+    }
+
+    /// Whether this is a wildcard node, see [`MatchOp::wildcard`].
+    pub(crate) fn is_wildcard(&self) -> bool {
+        self.op_name == WILDCARD_OP_NAME
+    }
+}
+
+/// The operation name used to identify wildcard pattern nodes.
+const WILDCARD_OP_NAME: &str = "tket2.wildcard";
+
+/// The linear input and output types of an operation.
+fn linear_signature(op: &OpType) -> (Vec<Type>, Vec<Type>) {
+    let Some(sig) = op.dataflow_signature() else {
+        return Default::default();
+    };
+    let linear = |row: &TypeRow| row.iter().filter(|t| type_is_linear(t)).cloned().collect();
+    (linear(sig.input()), linear(sig.output()))
+}
+
 /// Encode a unique identifier for an operation.
 ///
 /// Avoids encoding some data if we know the operation can be uniquely
@@ -109,6 +146,19 @@ impl PatternMatch {
         self.position.nodes()
     }
 
+    /// Returns the set of nodes covered by the match.
+    ///
+    /// Matches with disjoint covered nodes can be rewritten independently.
+    pub fn covered_nodes(&self) -> HashSet<Node> {
+        self.nodes().iter().copied().collect()
+    }
+
+    /// Whether the match covers some node of another match.
+    pub fn overlaps(&self, other: &PatternMatch) -> bool {
+        let covered = self.covered_nodes();
+        other.nodes().iter().any(|n| covered.contains(n))
+    }
+
     /// Create a pattern match from the image of a pattern root.
     ///
     /// This checks at construction time that the match is convex. This will
@@ -151,7 +201,7 @@ impl PatternMatch {
         let map = pattern_ref
             .get_match_map(root, circ)
             .ok_or(InvalidPatternMatch::MatchNotFound)?;
-        let inputs = pattern_ref
+        let mut inputs = pattern_ref
             .inputs
             .iter()
             .map(|ps| {
@@ -160,11 +210,34 @@ impl PatternMatch {
                     .collect_vec()
             })
             .collect_vec();
-        let outputs = pattern_ref
+        let mut outputs = pattern_ref
             .outputs
             .iter()
             .map(|(n, p)| (map[n], p.as_outgoing().unwrap()))
             .collect_vec();
+
+        // Operations matched by wildcards may have additional non-linear
+        // ports, which are appended to the boundary.
+        let hugr = circ.hugr();
+        let matched_nodes: HashSet<Node> = map.values().copied().collect();
+        for node in pattern_ref.wildcards.iter().map(|n| map[n]) {
+            for (port, _) in hugr.in_value_types(node) {
+                let is_boundary = hugr
+                    .linked_outputs(node, port)
+                    .any(|(n, _)| !matched_nodes.contains(&n));
+                if is_boundary && !inputs.iter().flatten().contains(&(node, port)) {
+                    inputs.push(vec![(node, port)]);
+                }
+            }
+            for (port, _) in hugr.out_value_types(node) {
+                let is_boundary = hugr
+                    .linked_inputs(node, port)
+                    .any(|(n, _)| !matched_nodes.contains(&n));
+                if is_boundary && !outputs.contains(&(node, port)) {
+                    outputs.push((node, port));
+                }
+            }
+        }
         Self::try_from_io_with_checker(root, pattern, circ, inputs, outputs, checker)
     }
 
@@ -238,6 +311,11 @@ impl Debug for PatternMatch {
 ///
 /// This uses a state automaton internally to match against a set of patterns
 /// simultaneously.
+///
+/// The matcher is immutable once built and holds no interior mutability, so
+/// it is [`Send`] and [`Sync`]: a single matcher can be shared between threads
+/// (e.g. behind an [`Arc`](std::sync::Arc)) to match against many circuits
+/// concurrently.
 #[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct PatternMatcher {
     automaton: ScopeAutomaton<PNode, PEdge, Port>,
@@ -279,11 +357,25 @@ impl PatternMatcher {
         circuit: &'c Circuit<impl HugrView>,
     ) -> impl Iterator<Item = PatternMatch> + 'a {
         let checker = TopoConvexChecker::new(circuit.hugr());
+        let anchors = self.anchors();
         circuit
             .commands()
+            .filter(move |cmd| {
+                anchors
+                    .as_ref()
+                    .map_or(true, |anchors| anchors.contains(&cmd.optype().name()))
+            })
             .flat_map(move |cmd| self.find_rooted_matches(circuit, cmd.node(), &checker))
     }
 
+    /// The operations that pattern roots are anchored on, or `None` if some
+    /// pattern is not anchored.
+    ///
+    /// See [`CircuitPattern::with_anchor`].
+    fn anchors(&self) -> Option<HashSet<SmolStr>> {
+        self.patterns.iter().map(|p| p.anchor.clone()).collect()
+    }
+
     /// Find all convex pattern matches in a circuit.and collect in to a vector
     pub fn find_matches(&self, circuit: &Circuit<impl HugrView>) -> Vec<PatternMatch> {
         self.find_matches_iter(circuit).collect()
@@ -373,6 +465,44 @@ impl PatternMatcher {
     }
 }
 
+/// Select a subset of pairwise non-overlapping matches.
+///
+/// Matches are considered greedily in order, keeping each one that does not
+/// overlap with those already kept. The result is maximal: every discarded
+/// match overlaps with a kept one.
+pub fn non_overlapping_subset(
+    matches: impl IntoIterator<Item = PatternMatch>,
+) -> Vec<PatternMatch> {
+    let mut covered = HashSet::new();
+    matches
+        .into_iter()
+        .filter(|m| {
+            if m.nodes().iter().any(|n| covered.contains(n)) {
+                return false;
+            }
+            covered.extend(m.nodes().iter().copied());
+            true
+        })
+        .collect()
+}
+
+/// Select a subset of pairwise non-overlapping matches, preferring the ones
+/// with the highest weight.
+///
+/// As [`non_overlapping_subset`], after sorting the matches by decreasing
+/// weight. Matches of equal weight are kept in order.
+pub fn non_overlapping_subset_by<W: PartialOrd>(
+    matches: impl IntoIterator<Item = PatternMatch>,
+    weight: impl Fn(&PatternMatch) -> W,
+) -> Vec<PatternMatch> {
+    let weighted = matches
+        .into_iter()
+        .map(|m| (weight(&m), m))
+        .sorted_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(_, m)| m);
+    non_overlapping_subset(weighted)
+}
+
 /// Errors that can occur when constructing matches.
 #[derive(Debug, Clone, PartialEq, Eq, Error)]
 pub enum InvalidPatternMatch {
@@ -430,7 +560,7 @@ impl From<InvalidSubgraph> for InvalidPatternMatch {
     }
 }
 
-fn compatible_offsets(e1: &PEdge, e2: &PEdge) -> bool {
+pub(super) fn compatible_offsets(e1: &PEdge, e2: &PEdge) -> bool {
     let PEdge::InternalEdge { dst: dst1, .. } = e1 else {
         return false;
     };
@@ -472,7 +602,12 @@ pub(crate) fn validate_circuit_node(
         let NodeID::HugrNode(node) = node else {
             return false;
         };
-        &MatchOp::from(circ.hugr().get_optype(node).clone()) == prop
+        let op = circ.hugr().get_optype(node);
+        if prop.is_wildcard() {
+            op.is_custom_op() && &MatchOp::wildcard(op) == prop
+        } else {
+            &MatchOp::from(op.clone()) == prop
+        }
     }
 }
 
@@ -494,10 +629,21 @@ mod tests {
     use itertools::Itertools;
     use rstest::{fixture, rstest};
 
+    use hugr::builder::{DFGBuilder, Dataflow, DataflowHugr};
+    use hugr::extension::prelude::QB_T;
+    use hugr::ops::{NamedOp, OpType};
+    use hugr::std_extensions::arithmetic::float_types::FLOAT64_TYPE;
+    use hugr::types::Signature;
+    use hugr::{type_row, HugrView};
+
+    use crate::extension::REGISTRY;
     use crate::utils::build_simple_circuit;
     use crate::{Circuit, Tk2Op};
 
-    use super::{CircuitPattern, PatternMatcher};
+    use super::{
+        non_overlapping_subset, non_overlapping_subset_by, CircuitPattern, PatternMatcher,
+    };
+    use crate::portmatching::pattern::InvalidPattern;
 
     fn h_cx() -> Circuit {
         build_simple_circuit(2, |circ| {
@@ -508,6 +654,15 @@ mod tests {
         .unwrap()
     }
 
+    fn h_cx_control() -> Circuit {
+        build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::H, [0]).unwrap();
+            circ.append(Tk2Op::CX, [0, 1]).unwrap();
+            Ok(())
+        })
+        .unwrap()
+    }
+
     fn cx_xc() -> Circuit {
         build_simple_circuit(2, |circ| {
             circ.append(Tk2Op::CX, [0, 1]).unwrap();
@@ -568,6 +723,58 @@ mod tests {
         assert_eq!(buf, buf2);
     }
 
+    #[rstest]
+    #[case::h(Tk2Op::H)]
+    #[case::rz(Tk2Op::RzF64)]
+    fn wildcard_match(#[case] op: Tk2Op) {
+        // Any single-qubit gate followed by a CX.
+        let pattern_circ = h_cx_control();
+        let wildcard = pattern_circ.commands().next().unwrap().node();
+        let p = CircuitPattern::try_from_circuit_with_wildcards(&pattern_circ, [wildcard]).unwrap();
+        let m = PatternMatcher::from_patterns(vec![p]);
+
+        let circ = {
+            let mut dfg = DFGBuilder::new(Signature::new(
+                type_row![QB_T, QB_T, FLOAT64_TYPE],
+                type_row![QB_T, QB_T],
+            ))
+            .unwrap();
+            let [q0, q1, f] = dfg.input_wires_arr();
+            let q0 = match op {
+                Tk2Op::RzF64 => dfg.add_dataflow_op(op, [q0, f]),
+                _ => dfg.add_dataflow_op(op, [q0]),
+            }
+            .unwrap()
+            .out_wire(0);
+            let [q0, q1] = dfg
+                .add_dataflow_op(Tk2Op::CX, [q0, q1])
+                .unwrap()
+                .outputs_arr();
+            let hugr = dfg.finish_hugr_with_outputs([q0, q1], &REGISTRY).unwrap();
+            Circuit::from(hugr)
+        };
+        let op_node = circ.commands().next().unwrap().node();
+
+        let matches = m.find_matches(&circ);
+        assert_eq!(matches.len(), 1);
+        assert!(matches[0].nodes().contains(&op_node));
+
+        // The match map reports the concrete operation for the wildcard.
+        let map = m
+            .get_pattern(matches[0].pattern_id())
+            .unwrap()
+            .get_match_map(matches[0].root(), &circ)
+            .unwrap();
+        assert_eq!(map[&wildcard], op_node);
+        assert_eq!(
+            Tk2Op::try_from(circ.hugr().get_optype(map[&wildcard])),
+            Ok(op)
+        );
+
+        // Two-qubit gates are not matched by the single-qubit wildcard.
+        assert!(m.find_matches(&cx_xc()).is_empty());
+    }
+
     #[rstest]
     fn cx_cx_replace_to_id(cx_cx: Circuit, cx_cx_3: Circuit) {
         let p = CircuitPattern::try_from_circuit(&cx_cx_3).unwrap();
@@ -576,4 +783,81 @@ mod tests {
         let matches = m.find_matches(&cx_cx);
         assert_eq!(matches.len(), 0);
     }
+
+    #[test]
+    fn anchored_match() {
+        let pattern = CircuitPattern::try_from_circuit(&h_cx_control()).unwrap();
+        let cx_name = OpType::from(Tk2Op::CX).name();
+        let anchored = pattern.clone().with_anchor(cx_name.clone()).unwrap();
+        assert_eq!(anchored.anchor(), Some(cx_name.as_str()));
+
+        let circ = build_simple_circuit(3, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::H, [1])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(Tk2Op::H, [1])?;
+            circ.append(Tk2Op::H, [2])?;
+            circ.append(Tk2Op::CX, [1, 2])?;
+            circ.append(Tk2Op::CX, [2, 0])?;
+            circ.append(Tk2Op::H, [0])?;
+            Ok(())
+        })
+        .unwrap();
+
+        let match_nodes = |matcher: PatternMatcher| {
+            matcher
+                .find_matches(&circ)
+                .into_iter()
+                .map(|m| m.nodes().iter().copied().sorted().collect_vec())
+                .sorted()
+                .collect_vec()
+        };
+        let unanchored = match_nodes(PatternMatcher::from_patterns(vec![pattern.clone()]));
+        let anchored_matcher = PatternMatcher::from_patterns(vec![anchored]);
+        for m in anchored_matcher.find_matches(&circ) {
+            assert_eq!(circ.hugr().get_optype(m.root()).name(), cx_name);
+        }
+        assert_eq!(unanchored.len(), 2);
+        assert_eq!(match_nodes(anchored_matcher), unanchored);
+
+        // The anchor must be an operation in the pattern.
+        assert_eq!(
+            pattern
+                .with_anchor(OpType::from(Tk2Op::T).name())
+                .unwrap_err(),
+            InvalidPattern::InvalidAnchor(OpType::from(Tk2Op::T).name())
+        );
+    }
+
+    #[test]
+    fn overlapping_matches() {
+        // Three consecutive CX gates contain two overlapping CX·CX matches.
+        let pattern = CircuitPattern::try_from_circuit(&cx_cx()).unwrap();
+        let matcher = PatternMatcher::from_patterns(vec![pattern]);
+        let circ = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            Ok(())
+        })
+        .unwrap();
+        let matches = matcher.find_matches(&circ);
+        assert_eq!(matches.len(), 2);
+        assert!(matches[0].overlaps(&matches[1]));
+        let shared = matches[0]
+            .covered_nodes()
+            .intersection(&matches[1].covered_nodes())
+            .count();
+        assert_eq!(shared, 1);
+
+        let subset = non_overlapping_subset(matches.clone());
+        assert_eq!(subset.len(), 1);
+        assert_eq!(subset[0].nodes(), matches[0].nodes());
+
+        // Prefer the match rooted at the last node.
+        let last = matches.iter().map(|m| m.root()).max().unwrap();
+        let subset = non_overlapping_subset_by(matches, |m| m.root() == last);
+        assert_eq!(subset.len(), 1);
+        assert_eq!(subset[0].root(), last);
+    }
 }
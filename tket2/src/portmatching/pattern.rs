@@ -4,11 +4,12 @@ use hugr::{HugrView, IncomingPort};
 use hugr::{Node, Port};
 use itertools::Itertools;
 use portmatching::{patterns::NoRootFound, HashMap, Pattern, SinglePatternMatcher};
+use smol_str::SmolStr;
 use std::fmt::Debug;
 use thiserror::Error;
 
 use super::{
-    matcher::{validate_circuit_edge, validate_circuit_node},
+    matcher::{compatible_offsets, validate_circuit_edge, validate_circuit_node, MatchOp},
     PEdge, PNode,
 };
 use crate::{circuit::Circuit, portmatching::NodeID};
@@ -21,6 +22,14 @@ pub struct CircuitPattern {
     pub(super) inputs: Vec<Vec<(Node, Port)>>,
     /// The output ports
     pub(super) outputs: Vec<(Node, Port)>,
+    /// The pattern nodes that match any operation with the same linear
+    /// signature.
+    #[serde(default)]
+    pub(super) wildcards: Vec<Node>,
+    /// The name of the operation at the root of the pattern, if the pattern
+    /// has been anchored with [`CircuitPattern::with_anchor`].
+    #[serde(default)]
+    pub(super) anchor: Option<SmolStr>,
 }
 
 impl CircuitPattern {
@@ -31,14 +40,44 @@ impl CircuitPattern {
 
     /// Construct a pattern from a circuit.
     pub fn try_from_circuit(circuit: &Circuit) -> Result<Self, InvalidPattern> {
+        Self::try_from_circuit_with_wildcards(circuit, [])
+    }
+
+    /// Construct a pattern from a circuit, where some of the operations are
+    /// wildcards.
+    ///
+    /// A wildcard node matches any custom operation with the same linear
+    /// input and output types at the same port offsets, regardless of the
+    /// operation itself. E.g. an `H` wildcard matches any single-qubit gate,
+    /// including parametric rotations.
+    ///
+    /// Any additional non-linear ports of the matched operation (such as
+    /// rotation angles) are appended to the boundary of the
+    /// [`PatternMatch`][super::PatternMatch] subcircuit, after the pattern's
+    /// own inputs and outputs. The concrete operation can be recovered from
+    /// the node that [`CircuitPattern::get_match_map`] assigns to the
+    /// wildcard node.
+    pub fn try_from_circuit_with_wildcards(
+        circuit: &Circuit,
+        wildcards: impl IntoIterator<Item = Node>,
+    ) -> Result<Self, InvalidPattern> {
         let hugr = circuit.hugr();
         if circuit.num_operations() == 0 {
             return Err(InvalidPattern::EmptyCircuit);
         }
+        let wildcards = wildcards.into_iter().collect_vec();
+        if let Some(&node) = wildcards.iter().find(|&&n| {
+            hugr.get_parent(n) != Some(circuit.parent()) || !hugr.get_optype(n).is_custom_op()
+        }) {
+            return Err(InvalidPattern::InvalidWildcard(node));
+        }
         let mut pattern = Pattern::new();
         for cmd in circuit.commands() {
-            let op = cmd.optype().clone();
-            pattern.require(cmd.node().into(), op.into());
+            let op = match wildcards.contains(&cmd.node()) {
+                true => MatchOp::wildcard(cmd.optype()),
+                false => cmd.optype().clone().into(),
+            };
+            pattern.require(cmd.node().into(), op);
             for in_offset in 0..cmd.input_count() {
                 let in_offset: IncomingPort = in_offset.into();
                 let edge_prop = PEdge::try_from_port(cmd.node(), in_offset.into(), circuit)
@@ -95,9 +134,59 @@ impl CircuitPattern {
             pattern,
             inputs,
             outputs,
+            wildcards,
+            anchor: None,
         })
     }
 
+    /// Anchor the pattern on an operation.
+    ///
+    /// The pattern is rooted at a node with the operation named `op_name`
+    /// (see [`NamedOp::name`](hugr::ops::NamedOp::name)), so that a
+    /// [`PatternMatcher`] only attempts to match it at circuit nodes of that
+    /// operation. This does not change the set of matches found, but can
+    /// greatly reduce the matching time when the anchor operation is rare in
+    /// the circuits.
+    ///
+    /// The anchor cannot be a wildcard node.
+    ///
+    ///   [`PatternMatcher`]: super::PatternMatcher
+    pub fn with_anchor(mut self, op_name: impl Into<SmolStr>) -> Result<Self, InvalidPattern> {
+        let op_name = op_name.into();
+        let edges = self.pattern.edges().unwrap_or_default();
+        let candidates = edges
+            .iter()
+            .flat_map(|e| [e.source, e.target])
+            .chain([self.pattern.root()])
+            .flatten()
+            .filter(|&node| {
+                self.pattern
+                    .node_property(node)
+                    .is_some_and(|op| !op.is_wildcard() && op.name() == op_name)
+            })
+            .sorted()
+            .dedup()
+            .collect_vec();
+        let root = candidates
+            .into_iter()
+            .find(|&root| {
+                let mut pattern = self.pattern.clone();
+                pattern.set_root(root);
+                pattern.try_into_line_pattern(compatible_offsets).is_some()
+            })
+            .ok_or_else(|| InvalidPattern::InvalidAnchor(op_name.clone()))?;
+        self.pattern.set_root(root);
+        self.anchor = Some(op_name);
+        Ok(self)
+    }
+
+    /// The name of the operation the pattern is anchored on, if any.
+    ///
+    /// See [`CircuitPattern::with_anchor`].
+    pub fn anchor(&self) -> Option<&str> {
+        self.anchor.as_deref()
+    }
+
     /// Compute the map from pattern nodes to circuit nodes in `circ`.
     pub fn get_match_map(
         &self,
@@ -142,6 +231,12 @@ pub enum InvalidPattern {
     /// Patterns must be connected circuits.
     #[error("The pattern is not connected")]
     NotConnected,
+    /// Wildcard nodes must be operations in the pattern circuit.
+    #[error("Wildcard node {0} is not an operation in the pattern circuit")]
+    InvalidWildcard(Node),
+    /// The anchor operation does not appear in the pattern.
+    #[error("The pattern cannot be anchored on operation {0}")]
+    InvalidAnchor(SmolStr),
     /// Patterns cannot include empty wires.
     #[error("The pattern contains an empty wire between {from_node}:{from_port} and {to_node}:{to_port}")]
     #[allow(missing_docs)]
@@ -4,23 +4,28 @@
 pub mod ecc_rewriter;
 pub mod strategy;
 pub mod trace;
+pub mod undo;
 
 use bytemuck::TransparentWrapper;
 #[cfg(feature = "portmatching")]
-pub use ecc_rewriter::ECCRewriter;
+pub use ecc_rewriter::{ECCRewriter, TargetID};
+pub use undo::UndoableCircuit;
 
 use derive_more::{From, Into};
 use hugr::hugr::hugrmut::HugrMut;
 use hugr::hugr::views::sibling_subgraph::{InvalidReplacement, InvalidSubgraph};
 use hugr::hugr::views::ExtractHugr;
+use hugr::ops::OpType;
 use hugr::types::Signature;
 use hugr::{
     hugr::{views::SiblingSubgraph, Rewrite, SimpleReplacementError},
     SimpleReplacement,
 };
 use hugr::{Hugr, HugrView, Node};
+use itertools::Itertools;
 
 use crate::circuit::Circuit;
+use crate::serialize::pytket::METADATA_PHASE;
 
 /// A subcircuit of a circuit.
 #[derive(Debug, Clone, From, Into)]
@@ -129,11 +134,19 @@ impl CircuitRewrite {
         self.0.invalidation_set()
     }
 
+    /// The global phase introduced by the rewrite, in half-turns.
+    ///
+    /// This is the numeric phase stored in the replacement circuit's metadata,
+    /// and is added to the phase of the circuit when the rewrite is applied.
+    pub fn phase(&self) -> f64 {
+        circuit_phase(&self.replacement())
+    }
+
     /// Apply the rewrite rule to a circuit.
     #[inline]
     pub fn apply(self, circ: &mut Circuit<impl HugrMut>) -> Result<(), SimpleReplacementError> {
         circ.add_rewrite_trace(&self);
-        self.0.apply(circ.hugr_mut())
+        self.apply_notrace(circ)
     }
 
     /// Apply the rewrite rule to a circuit, without registering it in the rewrite trace.
@@ -142,12 +155,135 @@ impl CircuitRewrite {
         self,
         circ: &mut Circuit<impl HugrMut>,
     ) -> Result<(), SimpleReplacementError> {
-        self.0.apply(circ.hugr_mut())
+        let phase = self.phase();
+        let replacement = self.replacement();
+        let budget = replacement.error_budget(replacement.parent());
+        self.0.apply(circ.hugr_mut())?;
+        add_circuit_phase(circ, phase);
+        if budget != 0. {
+            let parent = circ.parent();
+            let budget = circ.error_budget(parent) + budget;
+            circ.set_error_budget(parent, budget);
+        }
+        Ok(())
+    }
+}
+
+/// Carry the error budgets of the nodes replaced by a rewrite over to its
+/// replacement.
+///
+/// The combined budget is added to the first operation of the replacement.
+/// If the replacement has no operations, it is added to its root instead, and
+/// transferred to the circuit's parent when the rewrite is applied.
+pub(crate) fn transfer_error_budget(
+    circ: &Circuit<impl HugrView>,
+    nodes: &[Node],
+    replacement: &mut Circuit,
+) {
+    let budget: f64 = nodes.iter().map(|&node| circ.error_budget(node)).sum();
+    if budget == 0. {
+        return;
     }
+    let target = replacement
+        .operations()
+        .next()
+        .map_or(replacement.parent(), |cmd| cmd.node());
+    let budget = replacement.error_budget(target) + budget;
+    replacement.set_error_budget(target, budget);
+}
+
+/// The numeric global phase of a circuit, in half-turns.
+///
+/// Missing or symbolic phases are read as zero.
+pub(crate) fn circuit_phase(circ: &Circuit<impl HugrView>) -> f64 {
+    circ.hugr()
+        .get_metadata(circ.parent(), METADATA_PHASE)
+        .and_then(|p| p.as_str())
+        .and_then(|p| p.parse().ok())
+        .unwrap_or(0.0)
+}
+
+/// Add `phase` half-turns to the global phase of a circuit.
+pub(crate) fn add_circuit_phase(circ: &mut Circuit<impl HugrMut>, phase: f64) {
+    if phase == 0.0 {
+        return;
+    }
+    let parent = circ.parent();
+    let old_phase = circ
+        .hugr()
+        .get_metadata(parent, METADATA_PHASE)
+        .and_then(|p| p.as_str());
+    let new_phase = match old_phase.map(|p| (p, p.parse::<f64>())) {
+        None => phase.to_string(),
+        Some((_, Ok(old))) => (old + phase).to_string(),
+        Some((expr, Err(_))) => format!("{expr} + {phase}"),
+    };
+    circ.hugr_mut()
+        .set_metadata(parent, METADATA_PHASE, new_phase);
+}
+
+/// Add a phase of `expr` half-turns to the global phase of a circuit, where
+/// `expr` is either a number or a symbolic expression.
+pub(crate) fn add_circuit_phase_expr(circ: &mut Circuit<impl HugrMut>, expr: &str) {
+    if let Ok(phase) = expr.parse::<f64>() {
+        add_circuit_phase(circ, phase);
+        return;
+    }
+    let parent = circ.parent();
+    let old_phase = circ
+        .hugr()
+        .get_metadata(parent, METADATA_PHASE)
+        .and_then(|p| p.as_str());
+    let new_phase = match old_phase {
+        None => expr.to_string(),
+        Some(old) => format!("{old} + {expr}"),
+    };
+    circ.hugr_mut()
+        .set_metadata(parent, METADATA_PHASE, new_phase);
 }
 
 /// Generate rewrite rules for circuits.
 pub trait Rewriter {
     /// Get the rewrite rules for a circuit.
     fn get_rewrites(&self, circ: &Circuit<impl HugrView>) -> Vec<CircuitRewrite>;
+
+    /// Get at most `k` rewrite rules for a circuit.
+    ///
+    /// Returns the first `k` rewrites of [`Rewriter::get_rewrites`].
+    /// Implementations should override this to stop looking for rewrites once
+    /// enough have been found.
+    fn get_rewrites_limited(&self, circ: &Circuit<impl HugrView>, k: usize) -> Vec<CircuitRewrite> {
+        let mut rewrites = self.get_rewrites(circ);
+        rewrites.truncate(k);
+        rewrites
+    }
+
+    /// Get the rewrite rules for a circuit, ranked by the change in cost they
+    /// would cause.
+    ///
+    /// The cost delta of a rewrite is the cost of its replacement minus the
+    /// cost of the matched subcircuit, summing `op_cost` over their
+    /// operations. It is computed without applying the rewrite.
+    ///
+    /// The rewrites are sorted in ascending order of cost delta, so that the
+    /// first one reduces the cost the most. Rewrites with equal deltas keep
+    /// the order of [`Rewriter::get_rewrites`].
+    fn get_rewrites_ranked(
+        &self,
+        circ: &Circuit<impl HugrView>,
+        op_cost: impl Fn(&OpType) -> i64,
+    ) -> Vec<(CircuitRewrite, i64)> {
+        let mut rewrites = self
+            .get_rewrites(circ)
+            .into_iter()
+            .map(|rw| {
+                let old_cost: i64 =
+                    circ.nodes_cost(rw.subcircuit().nodes().iter().copied(), &op_cost);
+                let new_cost: i64 = rw.replacement().circuit_cost(&op_cost);
+                (rw, new_cost - old_cost)
+            })
+            .collect_vec();
+        rewrites.sort_by_key(|&(_, delta)| delta);
+        rewrites
+    }
 }
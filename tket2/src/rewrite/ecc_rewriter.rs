@@ -13,27 +13,37 @@
 //! of the Quartz repository.
 
 use derive_more::{From, Into};
+use hugr::hugr::hugrmut::HugrMut;
+use hugr::ops::OpType;
 use hugr::{Hugr, HugrView, PortIndex};
 use itertools::Itertools;
 use portmatching::PatternID;
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     fs::File,
     io,
+    iter::Sum,
     path::{Path, PathBuf},
 };
 use thiserror::Error;
 
 use crate::{
-    circuit::{remove_empty_wire, Circuit},
+    circuit::{remove_empty_wire, Circuit, CircuitHash},
     optimiser::badger::{load_eccs_json_file, EqCircClass},
     portmatching::{CircuitPattern, PatternMatcher},
+    serialize::pytket::METADATA_PHASE,
 };
 
-use super::{CircuitRewrite, Rewriter};
+use super::{circuit_phase, CircuitRewrite, Rewriter};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, From, Into, serde::Serialize, serde::Deserialize)]
-struct TargetID(usize);
+/// Identifier of a target circuit of an [`ECCRewriter`].
+///
+/// Each rewrite produced by the rewriter replaces a match of a pattern, given
+/// by its [`PatternID`], with one of the target circuits of that pattern.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, From, Into, serde::Serialize, serde::Deserialize,
+)]
+pub struct TargetID(usize);
 
 /// A rewriter based on circuit equivalence classes.
 ///
@@ -41,6 +51,13 @@ struct TargetID(usize);
 /// Valid rewrites turn a non-representative circuit into its representative,
 /// or a representative circuit into any of the equivalent non-representative
 /// circuits.
+///
+/// # Thread safety
+///
+/// The rewriter is [`Send`] and [`Sync`], and [`Rewriter::get_rewrites`] only
+/// reads from it. A rewriter can therefore be loaded once and shared between
+/// threads behind an [`Arc`](std::sync::Arc), with each thread computing
+/// rewrites for its own circuits. See the `concurrent_rewriter` example.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ECCRewriter {
     /// Matcher for finding patterns.
@@ -54,6 +71,26 @@ pub struct ECCRewriter {
     /// Wires that have been removed in the pattern circuit -- to be removed
     /// in the target circuit as well when generating a rewrite.
     empty_wires: Vec<Vec<usize>>,
+    /// Map from the structural hash of every circuit in the equivalence
+    /// classes to the index of its class.
+    ///
+    /// Rewriters serialised before this index was introduced load without
+    /// it, and the index is rebuilt from the rewrite rules on load.
+    #[serde(default)]
+    class_index: HashMap<u64, usize>,
+    /// Global phase of each pattern circuit, in half-turns. Rewrites introduce
+    /// the phase difference between the target and the pattern.
+    ///
+    /// Rewriters serialised before the phases were introduced load with
+    /// zero phases.
+    #[serde(default)]
+    pattern_phases: Vec<f64>,
+    /// The target circuit each pattern was built from.
+    ///
+    /// Rewriters serialised before the sources were introduced load without
+    /// them, and the sources are recomputed from the targets on load.
+    #[serde(default)]
+    pattern_sources: Vec<TargetID>,
 }
 
 impl ECCRewriter {
@@ -74,45 +111,262 @@ impl ECCRewriter {
     /// Equivalence classes are represented as [`EqCircClass`]s, lists of
     /// HUGRs where one of the elements is chosen as the representative.
     pub fn from_eccs(eccs: impl Into<Vec<EqCircClass>>) -> Self {
+        Self::from_eccs_with_max_pattern_size(eccs, usize::MAX).0
+    }
+
+    /// Create a new rewriter from a list of equivalent circuit classes,
+    /// skipping the patterns with more than `max_gates` operations.
+    ///
+    /// Large patterns can make the pattern matcher prohibitively expensive to
+    /// build and run. The skipped circuits cannot be matched, but are still
+    /// used as rewrite targets for the other circuits in their class.
+    ///
+    /// Returns the rewriter and the number of skipped patterns.
+    pub fn from_eccs_with_max_pattern_size(
+        eccs: impl Into<Vec<EqCircClass>>,
+        max_gates: usize,
+    ) -> (Self, usize) {
+        Self::build(eccs.into(), max_gates, None)
+    }
+
+    /// Create a new rewriter from a list of equivalent circuit classes, only
+    /// keeping the rewrite rules that strictly decrease the cost of the
+    /// circuit.
+    ///
+    /// The cost of each circuit in a class is computed by summing `cost_fn`
+    /// over its operations. Rewrite rules to a circuit of equal or higher cost
+    /// are dropped, along with the patterns left without any rule. Applying
+    /// the resulting rewrites greedily is therefore guaranteed to terminate.
+    pub fn from_eccs_reducing_only<F, C>(eccs: impl Into<Vec<EqCircClass>>, cost_fn: F) -> Self
+    where
+        F: Fn(&OpType) -> C,
+        C: Sum + PartialOrd,
+    {
         let eccs: Vec<EqCircClass> = eccs.into();
+        let costs = eccs
+            .iter()
+            .flat_map(|rs| rs.circuits())
+            .map(|hugr| Circuit::from(hugr).circuit_cost(&cost_fn))
+            .collect_vec();
+        let is_reducing = |source: usize, target: usize| costs[target] < costs[source];
+        Self::build(eccs, usize::MAX, Some(&is_reducing)).0
+    }
+
+    /// Build the rewriter, skipping patterns with more than `max_gates`
+    /// operations.
+    ///
+    /// If `rule_filter` is given, only the rewrite rules between the circuits
+    /// with the given indices for which it returns `true` are kept, and
+    /// patterns without any rule are dropped.
+    fn build(
+        eccs: Vec<EqCircClass>,
+        max_gates: usize,
+        rule_filter: Option<&dyn Fn(usize, usize) -> bool>,
+    ) -> (Self, usize) {
         let rewrite_rules = get_rewrite_rules(&eccs);
-        let patterns = get_patterns(&eccs);
+        let class_index = get_class_index(
+            eccs.iter()
+                .enumerate()
+                .flat_map(|(class, rs)| rs.circuits().map(move |hugr| (class, hugr))),
+        );
+        let (patterns, n_skipped) = get_patterns(&eccs, max_gates);
+        let phases = eccs
+            .iter()
+            .flat_map(|rs| rs.circuits())
+            .map(|hugr| circuit_phase(&Circuit::from(hugr)))
+            .collect_vec();
         let targets = into_targets(eccs);
         // Remove failed patterns
-        let (patterns, empty_wires, rewrite_rules): (Vec<_>, Vec<_>, Vec<_>) = patterns
+        let (patterns, empty_wires, rewrite_rules, pattern_phases, pattern_sources): (
+            Vec<_>,
+            Vec<_>,
+            Vec<_>,
+            Vec<_>,
+            Vec<_>,
+        ) = patterns
             .into_iter()
             .zip(rewrite_rules)
-            .filter_map(|(p, r)| {
+            .zip(phases)
+            .enumerate()
+            .filter_map(|(source, ((p, r), phase))| {
                 // Filter out target IDs where empty wires are not empty
                 let (pattern, pattern_empty_wires) = p?;
                 let targets = r
                     .into_iter()
-                    .filter(|&id| {
-                        let circ = (&targets[id.0]).into();
-                        let target_empty_wires: HashSet<_> =
-                            empty_wires(&circ).into_iter().collect();
-                        pattern_empty_wires
-                            .iter()
-                            .all(|&w| target_empty_wires.contains(&w))
-                    })
-                    .collect();
-                Some((pattern, pattern_empty_wires, targets))
+                    .filter(|&id| rule_filter.map_or(true, |keep| keep(source, id.0)))
+                    .filter(|&id| has_empty_wires(&targets[id.0], &pattern_empty_wires))
+                    .collect_vec();
+                if rule_filter.is_some() && targets.is_empty() {
+                    return None;
+                }
+                Some((
+                    pattern,
+                    pattern_empty_wires,
+                    targets,
+                    phase,
+                    TargetID(source),
+                ))
             })
             .multiunzip();
         let matcher = PatternMatcher::from_patterns(patterns);
-        Self {
+        let rewriter = Self {
             matcher,
             targets,
             rewrite_rules,
             empty_wires,
+            class_index,
+            pattern_phases,
+            pattern_sources,
+        };
+        (rewriter, n_skipped)
+    }
+
+    /// Extend the rewriter with the inverse of each of its rewrite rules.
+    ///
+    /// For every rule rewriting a pattern into a target circuit, the target
+    /// circuit is registered as a pattern rewriting back into the source of
+    /// the original rule. Rules that already exist are not duplicated, and
+    /// targets that are not valid patterns are skipped. The pattern matcher
+    /// is rebuilt if new patterns were added.
+    ///
+    /// This is useful for equivalence classes that only list one direction
+    /// of some equivalences.
+    ///
+    /// The inverse of a size-reducing rule increases the circuit size, so the
+    /// extended rewriter offers rewrites that undo each other. In particular,
+    /// extending a rewriter built with [`ECCRewriter::from_eccs_reducing_only`]
+    /// reintroduces the rules it left out. Applying rewrites greedily until
+    /// none are left may then never terminate; callers should filter rewrites
+    /// by a cost function, as the [`BadgerOptimiser`] does.
+    ///
+    /// [`BadgerOptimiser`]: crate::optimiser::BadgerOptimiser
+    pub fn with_inverse_rules(mut self) -> Self {
+        if self.pattern_sources.len() != self.rewrite_rules.len() {
+            tracing::warn!(
+                "Cannot invert the rules of a rewriter with {} patterns but {} pattern sources.",
+                self.rewrite_rules.len(),
+                self.pattern_sources.len()
+            );
+            return self;
+        }
+        let mut pattern_of: HashMap<TargetID, usize> = self
+            .pattern_sources
+            .iter()
+            .enumerate()
+            .map(|(pattern, &source)| (source, pattern))
+            .collect();
+        let inverse_rules = self
+            .pattern_sources
+            .iter()
+            .zip(&self.rewrite_rules)
+            .flat_map(|(&source, targets)| targets.iter().map(move |&target| (target, source)))
+            .collect_vec();
+
+        let mut new_patterns = Vec::new();
+        for (source, target) in inverse_rules {
+            let pattern = match pattern_of.get(&source) {
+                Some(&pattern) => pattern,
+                None => {
+                    let Some((pattern, empty_qbs)) = to_pattern(&self.targets[source.0]) else {
+                        continue;
+                    };
+                    let index = self.rewrite_rules.len();
+                    let phase = circuit_phase(&Circuit::from(&self.targets[source.0]));
+                    new_patterns.push(pattern);
+                    self.rewrite_rules.push(Vec::new());
+                    self.empty_wires.push(empty_qbs);
+                    self.pattern_phases.push(phase);
+                    self.pattern_sources.push(source);
+                    pattern_of.insert(source, index);
+                    index
+                }
+            };
+            if self.rewrite_rules[pattern].contains(&target)
+                || !has_empty_wires(&self.targets[target.0], &self.empty_wires[pattern])
+            {
+                continue;
+            }
+            self.rewrite_rules[pattern].push(target);
         }
+
+        if !new_patterns.is_empty() {
+            let patterns = (0..self.matcher.n_patterns())
+                .map(|id| self.matcher.get_pattern(PatternID(id)).unwrap().clone())
+                .chain(new_patterns)
+                .collect_vec();
+            self.matcher = PatternMatcher::from_patterns(patterns);
+        }
+        self
+    }
+
+    /// The number of patterns matched by the rewriter.
+    pub fn n_patterns(&self) -> usize {
+        self.matcher.n_patterns()
+    }
+
+    /// Returns the index of the equivalence class containing `circ`, if any.
+    ///
+    /// The circuit must be structurally equal to one of the circuits of the
+    /// equivalence classes the rewriter was built from, either a
+    /// representative or a non-representative circuit. Returns `None` for
+    /// unknown circuits.
+    pub fn class_of(&self, circ: &Circuit<impl HugrView>) -> Option<usize> {
+        let hash = circ.circuit_hash().ok()?;
+        self.class_index.get(&hash).copied()
     }
 
     /// Get all targets of rewrite rules given a source pattern.
-    fn get_targets(&self, pattern: PatternID) -> impl Iterator<Item = Circuit<&Hugr>> {
+    fn get_targets(&self, pattern: PatternID) -> impl Iterator<Item = (TargetID, Circuit<&Hugr>)> {
         self.rewrite_rules[pattern.0]
             .iter()
-            .map(|id| (&self.targets[id.0]).into())
+            .map(|&id| (id, (&self.targets[id.0]).into()))
+    }
+
+    /// Returns the pattern with the given id, if it exists.
+    pub fn get_pattern(&self, id: PatternID) -> Option<&CircuitPattern> {
+        self.matcher.get_pattern(id)
+    }
+
+    /// Returns the target circuit with the given id, if it exists.
+    pub fn get_target(&self, id: TargetID) -> Option<Circuit<&Hugr>> {
+        self.targets.get(id.0).map(Circuit::from)
+    }
+
+    /// Get the rewrites for a circuit, along with the ids of the pattern they
+    /// match and of the target circuit they insert.
+    ///
+    /// This returns the same rewrites as [`Rewriter::get_rewrites`], in the
+    /// same order.
+    pub fn get_rewrites_with_ids(
+        &self,
+        circ: &Circuit<impl HugrView>,
+    ) -> Vec<(CircuitRewrite, PatternID, TargetID)> {
+        self.rewrites_with_ids_iter(circ).collect()
+    }
+
+    /// Lazily compute the rewrites for a circuit, along with the ids of the
+    /// pattern they match and of the target circuit they insert.
+    fn rewrites_with_ids_iter<'a>(
+        &'a self,
+        circ: &'a Circuit<impl HugrView>,
+    ) -> impl Iterator<Item = (CircuitRewrite, PatternID, TargetID)> + 'a {
+        self.matcher.find_matches_iter(circ).flat_map(move |m| {
+            let pattern_id = m.pattern_id();
+            let pattern_phase = self.pattern_phases[pattern_id.0];
+            self.get_targets(pattern_id).map(move |(target_id, repl)| {
+                let mut repl = repl.to_owned();
+                for &empty_qb in self.empty_wires[pattern_id.0].iter().rev() {
+                    remove_empty_wire(&mut repl, empty_qb).unwrap();
+                }
+                // Replace the target phase by the relative phase of the rewrite.
+                let phase = circuit_phase(&repl) - pattern_phase;
+                let root = repl.parent();
+                repl.hugr_mut()
+                    .set_metadata(root, METADATA_PHASE, phase.to_string());
+                let rewrite = m.to_rewrite(circ, repl).expect("invalid replacement");
+                (rewrite, pattern_id, target_id)
+            })
+        })
     }
 
     /// Serialise a rewriter to an IO stream.
@@ -136,7 +390,74 @@ impl ECCRewriter {
     #[cfg(feature = "binary-eccs")]
     pub fn load_binary_io<R: io::Read>(reader: R) -> Result<Self, RewriterSerialisationError> {
         let data = zstd::decode_all(reader)?;
-        Ok(rmp_serde::decode::from_slice(&data)?)
+        let mut rewriter: Self = rmp_serde::decode::from_slice(&data)?;
+        rewriter.restore_missing_fields();
+        Ok(rewriter)
+    }
+
+    /// Recompute the fields missing from rewriters serialised before they
+    /// were introduced.
+    #[cfg(feature = "binary-eccs")]
+    fn restore_missing_fields(&mut self) {
+        if self.pattern_phases.is_empty() {
+            self.pattern_phases = vec![0.; self.rewrite_rules.len()];
+        }
+        if self.pattern_sources.is_empty() {
+            self.pattern_sources = self.recompute_pattern_sources();
+        }
+        if self.class_index.is_empty() {
+            self.class_index = self.rebuild_class_index();
+        }
+    }
+
+    /// Rebuild the class index from the rewrite rules.
+    ///
+    /// The source and targets of a rewrite rule are in the same class.
+    /// Classes are numbered by their first target circuit, as when the
+    /// rewriter was built from its equivalence classes.
+    #[cfg(feature = "binary-eccs")]
+    fn rebuild_class_index(&self) -> HashMap<u64, usize> {
+        use petgraph::unionfind::UnionFind;
+
+        let mut classes = UnionFind::<usize>::new(self.targets.len());
+        for (pattern, targets) in self.rewrite_rules.iter().enumerate() {
+            let source = self.pattern_sources.get(pattern).copied();
+            for (a, b) in source
+                .into_iter()
+                .chain(targets.iter().copied())
+                .tuple_windows()
+            {
+                classes.union(a.0, b.0);
+            }
+        }
+        let mut class_of_root = HashMap::new();
+        let mut circuits = Vec::with_capacity(self.targets.len());
+        for (i, hugr) in self.targets.iter().enumerate() {
+            let n_classes = class_of_root.len();
+            let class = *class_of_root.entry(classes.find(i)).or_insert(n_classes);
+            circuits.push((class, hugr));
+        }
+        get_class_index(circuits)
+    }
+
+    /// Recompute the target circuit each pattern was built from.
+    ///
+    /// Patterns are built in order from every target that is a valid
+    /// pattern. Returns an empty list if the number of such targets does not
+    /// match the number of patterns.
+    #[cfg(feature = "binary-eccs")]
+    fn recompute_pattern_sources(&self) -> Vec<TargetID> {
+        let sources = self
+            .targets
+            .iter()
+            .enumerate()
+            .filter(|(_, hugr)| to_pattern(hugr).is_some())
+            .map(|(i, _)| TargetID(i))
+            .collect_vec();
+        if sources.len() != self.rewrite_rules.len() {
+            return Vec::new();
+        }
+        sources
     }
 
     /// Save a rewriter as a binary file.
@@ -171,23 +492,52 @@ impl ECCRewriter {
         // `zstd::decode_all`.
         Self::load_binary_io(&mut file)
     }
+
+    /// Replace the rules of the rewriter with the ones loaded from `path`.
+    ///
+    /// Files with the `.rwr` extension are loaded as binary rewriters (see
+    /// [`ECCRewriter::load_binary`]), any other file as equivalence classes
+    /// in the Quartz JSON format (see [`ECCRewriter::try_from_eccs_json_file`]).
+    ///
+    /// The new rewriter is built in full before replacing the current one, so
+    /// if loading fails the rewriter is left unchanged.
+    ///
+    /// # Locking
+    ///
+    /// Reloading requires exclusive access to the rewriter. To swap the rules
+    /// of a rewriter shared between threads, keep it behind a
+    /// [`RwLock`](std::sync::RwLock): readers holding the read lock while
+    /// calling [`Rewriter::get_rewrites`] then see either the old or the new
+    /// rules, never a mix of both. Readers are blocked while the new rules
+    /// are loaded; to avoid this, load a separate rewriter first and only
+    /// assign it under the write lock.
+    pub fn reload_from(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<(), RewriterSerialisationError> {
+        let path = path.as_ref();
+        let rewriter = match path.extension() {
+            #[cfg(feature = "binary-eccs")]
+            Some(ext) if ext == "rwr" => Self::load_binary(path)?,
+            _ => Self::try_from_eccs_json_file(path)?,
+        };
+        *self = rewriter;
+        Ok(())
+    }
 }
 
 impl Rewriter for ECCRewriter {
     fn get_rewrites(&self, circ: &Circuit<impl HugrView>) -> Vec<CircuitRewrite> {
-        let matches = self.matcher.find_matches(circ);
-        matches
+        self.get_rewrites_with_ids(circ)
             .into_iter()
-            .flat_map(|m| {
-                let pattern_id = m.pattern_id();
-                self.get_targets(pattern_id).map(move |repl| {
-                    let mut repl = repl.to_owned();
-                    for &empty_qb in self.empty_wires[pattern_id.0].iter().rev() {
-                        remove_empty_wire(&mut repl, empty_qb).unwrap();
-                    }
-                    m.to_rewrite(circ, repl).expect("invalid replacement")
-                })
-            })
+            .map(|(rewrite, _, _)| rewrite)
+            .collect()
+    }
+
+    fn get_rewrites_limited(&self, circ: &Circuit<impl HugrView>, k: usize) -> Vec<CircuitRewrite> {
+        self.rewrites_with_ids_iter(circ)
+            .take(k)
+            .map(|(rewrite, _, _)| rewrite)
             .collect()
     }
 }
@@ -213,6 +563,21 @@ fn into_targets(rep_sets: Vec<EqCircClass>) -> Vec<Hugr> {
         .collect()
 }
 
+/// Map the structural hash of each circuit to the index of its class.
+///
+/// If several classes contain the same circuit, the first one is kept.
+fn get_class_index<'a>(
+    circuits: impl IntoIterator<Item = (usize, &'a Hugr)>,
+) -> HashMap<u64, usize> {
+    let mut class_index = HashMap::new();
+    for (class, hugr) in circuits {
+        if let Ok(hash) = hugr.circuit_hash() {
+            class_index.entry(hash).or_insert(class);
+        }
+    }
+    class_index
+}
+
 fn get_rewrite_rules(rep_sets: &[EqCircClass]) -> Vec<Vec<TargetID>> {
     let n_circs = rep_sets.iter().map(|rs| rs.n_circuits()).sum::<usize>();
     let mut rewrite_rules = vec![Default::default(); n_circs];
@@ -233,21 +598,51 @@ fn get_rewrite_rules(rep_sets: &[EqCircClass]) -> Vec<Vec<TargetID>> {
 
 /// For an equivalence class, return all valid patterns together with the
 /// indices of the wires that have been removed in the pattern circuit.
-fn get_patterns(rep_sets: &[EqCircClass]) -> Vec<Option<(CircuitPattern, Vec<usize>)>> {
-    rep_sets
+///
+/// Circuits with more than `max_gates` operations are not turned into
+/// patterns. Their number is returned along with the patterns.
+#[allow(clippy::type_complexity)]
+fn get_patterns(
+    rep_sets: &[EqCircClass],
+    max_gates: usize,
+) -> (Vec<Option<(CircuitPattern, Vec<usize>)>>, usize) {
+    let mut n_skipped = 0;
+    let patterns = rep_sets
         .iter()
         .flat_map(|rs| rs.circuits())
         .map(|hugr| {
-            let mut circ: Circuit = hugr.clone().into();
-            let empty_qbs = empty_wires(&circ);
-            for &qb in empty_qbs.iter().rev() {
-                remove_empty_wire(&mut circ, qb).unwrap();
+            if Circuit::from(hugr).num_operations() > max_gates {
+                n_skipped += 1;
+                return None;
             }
-            CircuitPattern::try_from_circuit(&circ)
-                .ok()
-                .map(|circ| (circ, empty_qbs))
+            to_pattern(hugr)
         })
-        .collect()
+        .collect();
+    (patterns, n_skipped)
+}
+
+/// Convert a circuit into a pattern, along with the indices of the wires
+/// that have been removed from it.
+///
+/// Returns `None` if the circuit is not a valid pattern.
+fn to_pattern(hugr: &Hugr) -> Option<(CircuitPattern, Vec<usize>)> {
+    let mut circ: Circuit = hugr.clone().into();
+    let empty_qbs = empty_wires(&circ);
+    for &qb in empty_qbs.iter().rev() {
+        remove_empty_wire(&mut circ, qb).unwrap();
+    }
+    CircuitPattern::try_from_circuit(&circ)
+        .ok()
+        .map(|circ| (circ, empty_qbs))
+}
+
+/// Whether the wires removed from a pattern are also empty in the target
+/// circuit, so that they can be removed from it when rewriting.
+fn has_empty_wires(target: &Hugr, pattern_empty_wires: &[usize]) -> bool {
+    let target_empty_wires: HashSet<_> = empty_wires(&target.into()).into_iter().collect();
+    pattern_empty_wires
+        .iter()
+        .all(|w| target_empty_wires.contains(w))
 }
 
 /// The port offsets of wires that are empty.
@@ -275,6 +670,7 @@ fn empty_wires(circ: &Circuit<impl HugrView>) -> Vec<usize> {
 
 #[cfg(test)]
 mod tests {
+    use crate::circuit::cost::is_quantum;
     use crate::{utils::build_simple_circuit, Tk2Op};
 
     use super::*;
@@ -338,12 +734,110 @@ mod tests {
         assert_eq!(
             rewriter
                 .get_targets(PatternID(1))
-                .map(|c| c.to_owned())
+                .map(|(_, c)| c.to_owned())
                 .collect_vec(),
             [h_h()]
         );
     }
 
+    #[test]
+    fn max_pattern_size() {
+        let test_file = "../test_files/eccs/small_eccs.json";
+        let eccs = load_eccs_json_file(test_file).unwrap();
+        let full = ECCRewriter::from_eccs(eccs.clone());
+        let max_gates = eccs
+            .iter()
+            .flat_map(|ecc| ecc.circuits())
+            .map(|hugr| Circuit::from(hugr).num_operations())
+            .max()
+            .unwrap();
+
+        let (capped, n_skipped) = ECCRewriter::from_eccs_with_max_pattern_size(eccs, max_gates - 1);
+        assert!(n_skipped > 0);
+        assert_eq!(capped.n_patterns(), full.n_patterns() - n_skipped);
+        assert_eq!(capped.targets, full.targets);
+        assert_eq!(capped.rewrite_rules.len(), capped.n_patterns());
+    }
+
+    #[test]
+    fn reducing_only() {
+        let test_file = "../test_files/eccs/small_eccs.json";
+        let eccs = load_eccs_json_file(test_file).unwrap();
+        let cost = |op: &OpType| is_quantum(op) as usize;
+        let full = ECCRewriter::from_eccs(eccs.clone());
+        let reducing = ECCRewriter::from_eccs_reducing_only(eccs.clone(), cost);
+        assert!(reducing.n_patterns() < full.n_patterns());
+
+        let mut n_rewrites = 0;
+        for hugr in eccs.iter().flat_map(|ecc| ecc.circuits()) {
+            let circ = Circuit::from(hugr.clone());
+            let before = circ.circuit_cost(cost);
+            for rewrite in reducing.get_rewrites(&circ) {
+                let mut rewritten = circ.clone();
+                rewrite.apply(&mut rewritten).unwrap();
+                assert!(rewritten.circuit_cost(cost) < before);
+                n_rewrites += 1;
+            }
+        }
+        assert!(n_rewrites > 0);
+    }
+
+    #[test]
+    fn rewrites_ranked() {
+        let ecc = EqCircClass::new(h_h(), vec![empty(), cx_cx()]);
+        let rewriter = ECCRewriter::from_eccs([ecc]);
+        let cost = |op: &OpType| is_quantum(op) as i64;
+        let circ = h_h();
+
+        let ranked = rewriter.get_rewrites_ranked(&circ, cost);
+        assert_eq!(ranked.len(), rewriter.get_rewrites(&circ).len());
+        assert!(ranked.len() > 1);
+        let min_delta = ranked.iter().map(|&(_, delta)| delta).min().unwrap();
+        assert_eq!(ranked[0].1, min_delta);
+        assert!(min_delta < ranked.last().unwrap().1);
+        assert!(ranked.iter().tuple_windows().all(|(a, b)| a.1 <= b.1));
+
+        // The deltas match the cost change when applying the rewrites.
+        let before = circ.circuit_cost(cost);
+        for (rewrite, delta) in ranked {
+            let mut rewritten = circ.clone();
+            rewrite.apply(&mut rewritten).unwrap();
+            assert_eq!(rewritten.circuit_cost(cost) - before, delta);
+        }
+    }
+
+    #[test]
+    fn rewrites_limited() {
+        let test_file = "../test_files/eccs/small_eccs.json";
+        let rewriter = ECCRewriter::try_from_eccs_json_file(test_file).unwrap();
+        let circ = build_simple_circuit(2, |circ| {
+            for _ in 0..50 {
+                circ.append(Tk2Op::T, [0])?;
+                circ.append(Tk2Op::X, [0])?;
+                circ.append(Tk2Op::Tdg, [1])?;
+                circ.append(Tk2Op::X, [1])?;
+            }
+            Ok(())
+        })
+        .unwrap();
+
+        let all = rewriter.get_rewrites(&circ);
+        let limited = rewriter.get_rewrites_limited(&circ, 5);
+
+        assert!(all.len() > 5);
+        assert_eq!(limited.len(), 5);
+        let nodes = |rewrites: &[CircuitRewrite]| {
+            rewrites
+                .iter()
+                .map(|r| r.subcircuit().nodes().to_vec())
+                .collect_vec()
+        };
+        assert_eq!(nodes(&limited), nodes(&all[..5]));
+        assert!(rewriter.get_rewrites_limited(&circ, 0).is_empty());
+        let unbounded = rewriter.get_rewrites_limited(&circ, all.len() + 1);
+        assert_eq!(unbounded.len(), all.len());
+    }
+
     #[test]
     fn ecc_rewriter_from_file() {
         // In this example, all circuits are valid patterns, thus
@@ -379,6 +873,220 @@ mod tests {
         assert_eq!(n_eccs_of_len, exp_n_eccs_of_len);
     }
 
+    #[test]
+    fn ecc_class_of() {
+        let test_file = "../test_files/eccs/small_eccs.json";
+        let eccs = load_eccs_json_file(test_file).unwrap();
+        let rewriter = ECCRewriter::from_eccs(eccs.clone());
+
+        for (class, ecc) in eccs.iter().enumerate() {
+            let target: Circuit = ecc.others()[0].clone().into();
+            assert_eq!(rewriter.class_of(&target), Some(class));
+        }
+
+        let unknown = build_simple_circuit(5, |circ| {
+            circ.append(Tk2Op::H, [4])?;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(rewriter.class_of(&unknown), None);
+
+        // The bundled binary rewriter carries the same index, and it is
+        // rebuilt for rewriters serialised before it was introduced.
+        #[cfg(feature = "binary-eccs")]
+        for file in ["small_eccs.rwr", "small_eccs_legacy.rwr"] {
+            // Class indices depend on the compilation, so only the classes
+            // themselves are compared.
+            let binary = ECCRewriter::load_binary(format!("../test_files/eccs/{file}")).unwrap();
+            for ecc in &eccs {
+                let classes = [ecc.rep_circ()]
+                    .into_iter()
+                    .chain(ecc.others())
+                    .map(|circ| binary.class_of(&Circuit::from(circ.clone())))
+                    .collect_vec();
+                assert!(classes[0].is_some(), "{file}");
+                assert!(classes.iter().all_equal(), "{file}");
+            }
+        }
+    }
+
+    #[test]
+    fn rewrite_ids() {
+        let test_file = "../test_files/eccs/small_eccs.json";
+        let eccs = load_eccs_json_file(test_file).unwrap();
+        let rewriter = ECCRewriter::from_eccs(eccs.clone());
+
+        // The representative of the first class is also the first pattern.
+        let circ: Circuit = eccs[0].rep_circ().clone().into();
+        let rewrites = rewriter.get_rewrites_with_ids(&circ);
+        assert_eq!(rewrites.len(), rewriter.get_rewrites(&circ).len());
+
+        let full_matches = rewrites
+            .iter()
+            .filter(|(_, pattern, _)| *pattern == PatternID(0))
+            .collect_vec();
+        assert_eq!(full_matches.len(), eccs[0].others().len());
+        for (rewrite, pattern, target) in full_matches {
+            assert!(rewriter.get_pattern(*pattern).is_some());
+            assert_eq!(rewrite.subcircuit().node_count(), circ.num_operations());
+
+            let target = rewriter.get_target(*target).unwrap();
+            assert_eq!(rewriter.class_of(&target), Some(0));
+            assert_eq!(
+                rewrite.replacement().circuit_hash().unwrap(),
+                target.circuit_hash().unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn ecc_rewriter_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<ECCRewriter>();
+        assert_send_sync::<PatternMatcher>();
+    }
+
+    #[test]
+    fn ecc_rewriter_concurrent() {
+        let test_file = "../test_files/eccs/small_eccs.json";
+        let rewriter = ECCRewriter::try_from_eccs_json_file(test_file).unwrap();
+        let circs = (0..8)
+            .map(|seed| Circuit::random_clifford(3, 6, seed))
+            .collect_vec();
+        let expected = circs
+            .iter()
+            .map(|circ| rewriter.get_rewrites(circ).len())
+            .collect_vec();
+
+        let counts = std::thread::scope(|s| {
+            let handles = circs
+                .iter()
+                .map(|circ| s.spawn(|| rewriter.get_rewrites(circ).len()))
+                .collect_vec();
+            handles.into_iter().map(|h| h.join().unwrap()).collect_vec()
+        });
+        assert_eq!(counts, expected);
+    }
+
+    #[test]
+    fn reload_from() {
+        let mut rewriter =
+            ECCRewriter::try_from_eccs_json_file("../test_files/eccs/small_eccs.json").unwrap();
+        let n_patterns = rewriter.n_patterns();
+        let rules = rewriter.rewrite_rules.clone();
+        let cx_cx = cx_cx();
+        let n_rewrites = rewriter.get_rewrites(&cx_cx).len();
+
+        // A failed reload keeps the previous rules.
+        assert!(rewriter
+            .reload_from("../test_files/eccs/does_not_exist.json")
+            .is_err());
+        assert_eq!(rewriter.n_patterns(), n_patterns);
+        assert_eq!(rewriter.rewrite_rules, rules);
+        assert_eq!(rewriter.get_rewrites(&cx_cx).len(), n_rewrites);
+
+        rewriter
+            .reload_from("../test_files/eccs/phase_eccs.json")
+            .unwrap();
+        let expected =
+            ECCRewriter::try_from_eccs_json_file("../test_files/eccs/phase_eccs.json").unwrap();
+        assert_eq!(rewriter.n_patterns(), expected.n_patterns());
+        assert_eq!(rewriter.rewrite_rules, expected.rewrite_rules);
+    }
+
+    #[test]
+    fn ecc_rewriter_phase() {
+        let test_file = "../test_files/eccs/phase_eccs.json";
+        let rewriter = ECCRewriter::try_from_eccs_json_file(test_file).unwrap();
+
+        // X;Z = -Z;X
+        let mut circ = build_simple_circuit(1, |circ| {
+            circ.append(Tk2Op::X, [0])?;
+            circ.append(Tk2Op::Z, [0])?;
+            Ok(())
+        })
+        .unwrap();
+        let rewrite = rewriter
+            .get_rewrites(&circ)
+            .into_iter()
+            .exactly_one()
+            .unwrap();
+        assert_eq!(rewrite.phase(), 1.0);
+
+        rewrite.apply(&mut circ).unwrap();
+        assert_eq!(circuit_phase(&circ), 1.0);
+        let rewrite = rewriter
+            .get_rewrites(&circ)
+            .into_iter()
+            .exactly_one()
+            .unwrap();
+        assert_eq!(rewrite.phase(), -1.0);
+        rewrite.apply(&mut circ).unwrap();
+        assert_eq!(circuit_phase(&circ), 0.0);
+    }
+
+    #[test]
+    fn inverse_rules() {
+        let ecc = EqCircClass::new(h_h(), vec![cx_cx()]);
+        let cost = |op: &OpType| is_quantum(op) as usize;
+        let reducing = ECCRewriter::from_eccs_reducing_only([ecc.clone()], cost);
+        assert_eq!(reducing.n_patterns(), 1);
+        assert!(reducing.get_rewrites(&cx_cx()).is_empty());
+
+        let extended = reducing.with_inverse_rules();
+        assert_eq!(extended.n_patterns(), 2);
+        let forward = extended
+            .get_rewrites(&h_h())
+            .into_iter()
+            .exactly_one()
+            .unwrap();
+        let inverse = extended
+            .get_rewrites(&cx_cx())
+            .into_iter()
+            .exactly_one()
+            .unwrap();
+        let mut circ = h_h();
+        forward.apply(&mut circ).unwrap();
+        assert_eq!(circ.circuit_hash(), cx_cx().circuit_hash());
+        let mut circ = cx_cx();
+        inverse.apply(&mut circ).unwrap();
+        assert_eq!(circ.circuit_hash(), h_h().circuit_hash());
+
+        // Existing rules are not duplicated.
+        let full = ECCRewriter::from_eccs([ecc]);
+        let extended = full.clone().with_inverse_rules();
+        assert_eq!(extended.n_patterns(), full.n_patterns());
+        assert_eq!(extended.rewrite_rules, full.rewrite_rules);
+    }
+
+    #[test]
+    #[cfg(feature = "binary-eccs")]
+    fn legacy_binary() {
+        // A rewriter serialised before the phases, pattern sources and class
+        // index were introduced.
+        let legacy = ECCRewriter::load_binary("../test_files/eccs/small_eccs_legacy.rwr").unwrap();
+        let current = ECCRewriter::load_binary("../test_files/eccs/small_eccs.rwr").unwrap();
+        assert_eq!(legacy.n_patterns(), current.n_patterns());
+        assert_eq!(legacy.pattern_phases, vec![0.; legacy.n_patterns()]);
+        assert_eq!(legacy.pattern_sources, current.pattern_sources);
+        assert_eq!(legacy.class_index.len(), current.class_index.len());
+
+        let extended = legacy.clone().with_inverse_rules();
+        assert_eq!(extended.n_patterns(), legacy.n_patterns());
+    }
+
+    #[test]
+    #[cfg(feature = "binary-eccs")]
+    fn bundled_binary() {
+        let rewriter = ECCRewriter::load_binary("../test_files/eccs/small_eccs.rwr").unwrap();
+        assert_eq!(rewriter.pattern_phases.len(), rewriter.n_patterns());
+        assert_eq!(rewriter.pattern_sources.len(), rewriter.n_patterns());
+
+        // The rules of the complete equivalence classes are already symmetric.
+        let extended = rewriter.clone().with_inverse_rules();
+        assert_eq!(extended.n_patterns(), rewriter.n_patterns());
+    }
+
     /// Some inputs are left untouched: these parameters should be removed to
     /// obtain convex patterns
     #[test]
@@ -0,0 +1,322 @@
+//! Undo and redo of circuit rewrites.
+
+use std::collections::HashSet;
+use std::mem;
+
+use hugr::hugr::hugrmut::HugrMut;
+use hugr::hugr::views::SiblingSubgraph;
+use hugr::hugr::SimpleReplacementError;
+use hugr::{HugrView, IncomingPort, Node, OutgoingPort};
+use itertools::Itertools;
+
+use super::{CircuitRewrite, Subcircuit};
+use crate::circuit::Circuit;
+use crate::serialize::pytket::METADATA_PHASE;
+
+/// A circuit recording the rewrites applied to it, so that they can be undone
+/// and redone.
+///
+/// Each applied rewrite is recorded as its inverse: a rewrite replacing the
+/// inserted nodes with a copy of the removed subcircuit. Some rewrites cannot
+/// be inverted this way, for instance when the replacement leaves a wire
+/// empty. A copy of the circuit before the rewrite is recorded instead.
+#[derive(Debug, Clone)]
+pub struct UndoableCircuit {
+    circ: Circuit,
+    undo_stack: Vec<Edit>,
+    redo_stack: Vec<Edit>,
+}
+
+/// A reversible modification of an [`UndoableCircuit`].
+#[derive(Debug, Clone)]
+enum Edit {
+    /// Apply a rewrite to the circuit.
+    Rewrite(CircuitRewrite),
+    /// Replace the circuit with a previous version.
+    Snapshot(Circuit),
+}
+
+impl UndoableCircuit {
+    /// Wrap a circuit, with an empty edit history.
+    pub fn new(circ: Circuit) -> Self {
+        Self {
+            circ,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+        }
+    }
+
+    /// The current circuit.
+    pub fn circuit(&self) -> &Circuit {
+        &self.circ
+    }
+
+    /// Returns the current circuit, discarding the edit history.
+    pub fn into_circuit(self) -> Circuit {
+        self.circ
+    }
+
+    /// Whether there is a rewrite to undo.
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    /// Whether there is an undone rewrite to redo.
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// Apply a rewrite to the circuit, recording it in the edit history.
+    ///
+    /// This discards any undone rewrites.
+    pub fn apply(&mut self, rewrite: CircuitRewrite) -> Result<(), SimpleReplacementError> {
+        self.circ.add_rewrite_trace(&rewrite);
+        let inverse = self.apply_edit(Edit::Rewrite(rewrite))?;
+        self.undo_stack.push(inverse);
+        self.redo_stack.clear();
+        Ok(())
+    }
+
+    /// Undo the last applied rewrite.
+    ///
+    /// Returns `false` if there was nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let Some(edit) = self.undo_stack.pop() else {
+            return false;
+        };
+        let inverse = self
+            .apply_edit(edit)
+            .expect("Recorded edits are always valid.");
+        self.redo_stack.push(inverse);
+        true
+    }
+
+    /// Redo the last undone rewrite.
+    ///
+    /// Returns `false` if there was nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        let Some(edit) = self.redo_stack.pop() else {
+            return false;
+        };
+        let inverse = self
+            .apply_edit(edit)
+            .expect("Recorded edits are always valid.");
+        self.undo_stack.push(inverse);
+        true
+    }
+
+    /// Apply an edit to the circuit, returning the edit reverting it.
+    fn apply_edit(&mut self, edit: Edit) -> Result<Edit, SimpleReplacementError> {
+        let rewrite = match edit {
+            Edit::Snapshot(circ) => return Ok(Edit::Snapshot(mem::replace(&mut self.circ, circ))),
+            Edit::Rewrite(rewrite) => rewrite,
+        };
+        let Some(boundary) = InverseBoundary::new(&self.circ, &rewrite) else {
+            let snapshot = self.circ.clone();
+            rewrite.apply_notrace(&mut self.circ)?;
+            return Ok(Edit::Snapshot(snapshot));
+        };
+        rewrite.apply_notrace(&mut self.circ)?;
+        Ok(Edit::Rewrite(boundary.into_rewrite(&self.circ)))
+    }
+}
+
+impl From<Circuit> for UndoableCircuit {
+    fn from(circ: Circuit) -> Self {
+        Self::new(circ)
+    }
+}
+
+/// The information needed to invert a rewrite, captured before applying it.
+struct InverseBoundary {
+    /// The subcircuit removed by the rewrite.
+    removed: Circuit,
+    /// The nodes of the circuit before the rewrite.
+    nodes: HashSet<Node>,
+    /// For each input of the replaced subcircuit, the port feeding it.
+    inputs: Vec<(Node, OutgoingPort)>,
+    /// For each output of the replaced subcircuit, a port it feeds.
+    outputs: Vec<(Node, IncomingPort)>,
+}
+
+impl InverseBoundary {
+    /// Capture the boundary of a rewrite.
+    ///
+    /// Returns `None` if the nodes inserted by the rewrite cannot be
+    /// identified as a subcircuit with the same boundary as the replaced one.
+    fn new(circ: &Circuit, rewrite: &CircuitRewrite) -> Option<Self> {
+        let hugr = circ.hugr();
+        let subgraph = &rewrite.subcircuit().subgraph;
+
+        // Every input of the replacement must feed some operation, rather than
+        // being discarded or passed directly to the output.
+        let replacement = rewrite.replacement();
+        let [rep_inp, rep_out] = replacement.io_nodes();
+        let rep = replacement.hugr();
+        let feeds_op = |port| {
+            let mut targets = rep.linked_inputs(rep_inp, port).peekable();
+            targets.peek().is_some() && targets.all(|(n, _)| n != rep_out)
+        };
+        if !rep.node_outputs(rep_inp).all(feeds_op) {
+            return None;
+        }
+
+        let inputs = subgraph
+            .incoming_ports()
+            .iter()
+            .map(|group| hugr.single_linked_output(group[0].0, group[0].1))
+            .collect::<Option<Vec<_>>>()?;
+        if !inputs.iter().all_unique() {
+            return None;
+        }
+        let outputs = subgraph
+            .outgoing_ports()
+            .iter()
+            .map(|&(n, p)| hugr.linked_inputs(n, p).next())
+            .collect::<Option<Vec<_>>>()?;
+
+        let mut removed = Circuit::from(subgraph.extract_subgraph(hugr, ""));
+        let phase = rewrite.phase();
+        if phase != 0.0 {
+            let root = removed.parent();
+            removed
+                .hugr_mut()
+                .set_metadata(root, METADATA_PHASE, (-phase).to_string());
+        }
+
+        Some(Self {
+            removed,
+            nodes: hugr.children(circ.parent()).collect(),
+            inputs,
+            outputs,
+        })
+    }
+
+    /// Build the rewrite replacing the nodes inserted by the rewrite with the
+    /// removed subcircuit.
+    fn into_rewrite(self, circ: &Circuit) -> CircuitRewrite {
+        let hugr = circ.hugr();
+        let is_inserted = |n: &Node| !self.nodes.contains(n);
+        let incoming = self
+            .inputs
+            .iter()
+            .map(|&(n, p)| {
+                hugr.linked_inputs(n, p)
+                    .filter(|(n, _)| is_inserted(n))
+                    .collect_vec()
+            })
+            .collect_vec();
+        let outgoing = self
+            .outputs
+            .iter()
+            .map(|&(n, p)| hugr.single_linked_output(n, p).unwrap())
+            .collect_vec();
+        let subgraph = SiblingSubgraph::try_new(incoming, outgoing, hugr)
+            .expect("The inserted nodes form a convex subcircuit.");
+        Subcircuit::from(subgraph)
+            .create_rewrite(circ, self.removed)
+            .expect("The removed subcircuit has the signature of the inserted one.")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::build_simple_circuit;
+    use crate::Tk2Op;
+
+    fn find(circ: &Circuit, op: Tk2Op) -> Node {
+        circ.commands()
+            .find(|cmd| cmd.optype() == &op.into())
+            .unwrap()
+            .node()
+    }
+
+    #[test]
+    fn undo_redo() {
+        let original = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::CX, [0, 1])?;
+            circ.append(Tk2Op::T, [1])?;
+            Ok(())
+        })
+        .unwrap();
+        let mut circ = UndoableCircuit::new(original.clone());
+        assert!(!circ.undo());
+
+        // Replace H by Z;X.
+        let z_x = build_simple_circuit(1, |circ| {
+            circ.append(Tk2Op::Z, [0])?;
+            circ.append(Tk2Op::X, [0])?;
+            Ok(())
+        })
+        .unwrap();
+        let h = find(circ.circuit(), Tk2Op::H);
+        let rewrite = Subcircuit::try_from_nodes([h], circ.circuit())
+            .unwrap()
+            .create_rewrite(circ.circuit(), z_x)
+            .unwrap();
+        circ.apply(rewrite).unwrap();
+
+        // Replace CX;T by CZ.
+        let cz = build_simple_circuit(2, |circ| {
+            circ.append(Tk2Op::CZ, [0, 1])?;
+            Ok(())
+        })
+        .unwrap();
+        let nodes = [
+            find(circ.circuit(), Tk2Op::CX),
+            find(circ.circuit(), Tk2Op::T),
+        ];
+        let rewrite = Subcircuit::try_from_nodes(nodes, circ.circuit())
+            .unwrap()
+            .create_rewrite(circ.circuit(), cz)
+            .unwrap();
+        circ.apply(rewrite).unwrap();
+        let rewritten = circ.circuit().clone();
+        assert_eq!(rewritten.num_operations(), 3);
+
+        assert!(circ.undo());
+        assert!(circ.undo());
+        assert!(!circ.can_undo());
+        assert!(circ.circuit().structurally_eq(&original, false));
+        circ.circuit()
+            .hugr()
+            .validate(&crate::extension::REGISTRY)
+            .unwrap();
+
+        assert!(circ.redo());
+        assert!(circ.redo());
+        assert!(!circ.redo());
+        assert!(circ.circuit().structurally_eq(&rewritten, false));
+    }
+
+    #[test]
+    fn undo_empty_replacement() {
+        let original = build_simple_circuit(1, |circ| {
+            circ.append(Tk2Op::H, [0])?;
+            circ.append(Tk2Op::H, [0])?;
+            Ok(())
+        })
+        .unwrap();
+        let mut circ = UndoableCircuit::from(original.clone());
+
+        let empty = build_simple_circuit(1, |_| Ok(())).unwrap();
+        let nodes = circ
+            .circuit()
+            .commands()
+            .map(|cmd| cmd.node())
+            .collect_vec();
+        let rewrite = Subcircuit::try_from_nodes(nodes, circ.circuit())
+            .unwrap()
+            .create_rewrite(circ.circuit(), empty)
+            .unwrap();
+        circ.apply(rewrite).unwrap();
+        assert_eq!(circ.circuit().num_operations(), 0);
+
+        assert!(circ.undo());
+        assert!(circ.circuit().structurally_eq(&original, false));
+        assert!(circ.redo());
+        assert_eq!(circ.circuit().num_operations(), 0);
+    }
+}
@@ -4,9 +4,11 @@ mod decoder;
 mod encoder;
 mod op;
 
+use hugr::extension::prelude::{BOOL_T, QB_T};
 use hugr::types::Type;
 
-use hugr::Node;
+use hugr::hugr::hugrmut::HugrMut;
+use hugr::{HugrView, Node};
 use itertools::Itertools;
 // Required for serialising ops in the tket1 hugr extension.
 pub(crate) use op::serialised::OpaqueTk1Op;
@@ -15,6 +17,7 @@ pub(crate) use op::serialised::OpaqueTk1Op;
 mod tests;
 
 use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
 use std::hash::{Hash, Hasher};
 use std::path::Path;
 use std::{fs, io};
@@ -26,17 +29,17 @@ use thiserror::Error;
 use tket_json_rs::circuit_json::{self, SerialCircuit};
 use tket_json_rs::optype::OpType as SerialOpType;
 
-use crate::circuit::Circuit;
+use crate::circuit::{Circuit, CircuitMutError};
 
 use self::decoder::Tk1Decoder;
-use self::encoder::Tk1Encoder;
+use self::encoder::{RegisterUnitGenerator, Tk1Encoder};
 
 pub use crate::passes::pytket::lower_to_pytket;
 
 /// Prefix used for storing metadata in the hugr nodes.
 pub const METADATA_PREFIX: &str = "TKET1";
 /// The global phase specified as metadata.
-const METADATA_PHASE: &str = "TKET1.phase";
+pub(crate) const METADATA_PHASE: &str = "TKET1.phase";
 /// Explicit names for the input qubit registers.
 const METADATA_Q_REGISTERS: &str = "TKET1.qubit_registers";
 /// The reordered qubit registers in the output, if an implicit permutation was applied.
@@ -47,6 +50,8 @@ const METADATA_B_REGISTERS: &str = "TKET1.bit_registers";
 const METADATA_B_OUTPUT_REGISTERS: &str = "TKET1.bit_output_registers";
 /// A tket1 operation "opgroup" field.
 const METADATA_OPGROUP: &str = "TKET1.opgroup";
+/// The classical register unit written by an operation's bit output.
+const METADATA_BIT_TARGET: &str = "TKET1.bit_target";
 
 /// A serialized representation of a [`Circuit`].
 ///
@@ -67,14 +72,10 @@ impl TKETDecode for SerialCircuit {
     type EncodeError = TK1ConvertError;
 
     fn decode(self) -> Result<Circuit, Self::DecodeError> {
+        // The global phase is stored in the circuit metadata by the decoder,
+        // along with the phase of any rotation dropped while decoding.
         let mut decoder = Tk1Decoder::try_new(&self)?;
 
-        if !self.phase.is_empty() {
-            // TODO - add a phase gate
-            // let phase = Param::new(serialcirc.phase);
-            // decoder.add_phase(phase);
-        }
-
         for com in self.commands {
             decoder.add_command(com)?;
         }
@@ -111,6 +112,347 @@ pub fn load_tk1_json_str(json: &str) -> Result<Circuit, TK1ConvertError> {
     load_tk1_json_reader(reader)
 }
 
+impl Circuit {
+    /// Returns the shape of each pytket register used by the circuit.
+    ///
+    /// Each register name is mapped to its size along every dimension of its
+    /// unit indices, i.e. one plus the maximum index used in that dimension.
+    /// A circuit using `q[0]`, `q[1]` and `q[2]` has shape `{"q": [3]}`.
+    ///
+    /// Registers are assigned as when encoding the circuit as a
+    /// [`SerialCircuit`], including the default `q` and `c` registers for
+    /// unnamed units.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the circuit cannot be encoded as a pytket circuit.
+    pub fn register_shapes(&self) -> Result<HashMap<String, Vec<u32>>, TK1ConvertError> {
+        let serial_circ = SerialCircuit::encode(self)?;
+        let mut shapes: HashMap<String, Vec<u32>> = HashMap::new();
+        for circuit_json::Register(name, index) in
+            serial_circ.qubits.iter().chain(&serial_circ.bits)
+        {
+            let shape = shapes.entry(name.clone()).or_default();
+            if shape.len() < index.len() {
+                shape.resize(index.len(), 0);
+            }
+            for (size, &i) in shape.iter_mut().zip(index) {
+                *size = (*size).max(i as u32 + 1);
+            }
+        }
+        Ok(shapes)
+    }
+}
+
+impl<T: HugrMut> Circuit<T> {
+    /// Make a measurement write its result into the classical bit
+    /// `register[index]` when the circuit is encoded as a pytket circuit.
+    ///
+    /// By default, the encoder assigns a fresh bit to each measurement
+    /// result. The target is stored as metadata on the command, and applies
+    /// to any operation with a single bit output.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `node` is not a command of the circuit, or if it
+    /// does not have exactly one bit output.
+    pub fn set_measure_target(
+        &mut self,
+        node: Node,
+        register: impl Into<String>,
+        index: i64,
+    ) -> Result<(), CircuitMutError> {
+        if self.hugr().get_parent(node) != Some(self.parent()) || self.io_nodes().contains(&node) {
+            return Err(CircuitMutError::NotACommand(node));
+        }
+        let bit_outputs = self
+            .hugr()
+            .out_value_types(node)
+            .filter(|(_, ty)| ty == &BOOL_T)
+            .count();
+        if bit_outputs != 1 {
+            return Err(CircuitMutError::NoBitOutput(node));
+        }
+        let target = circuit_json::Register(register.into(), vec![index]);
+        self.hugr_mut()
+            .set_metadata(node, METADATA_BIT_TARGET, serde_json::json!(target));
+        Ok(())
+    }
+
+    /// Rename a pytket register of the circuit, keeping the indices of its
+    /// units.
+    ///
+    /// Renames the register in the circuit boundary and in the measurement
+    /// targets set with [`Circuit::set_measure_target`]. The wiring of the
+    /// circuit is not modified. Circuits without register metadata use the
+    /// default `q` and `c` registers, as when encoding.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `new` is already the name of a register of a
+    /// different type, or if a renamed unit would collide with an existing
+    /// unit. The circuit is not modified on failure.
+    pub fn rename_register(&mut self, old: &str, new: &str) -> Result<(), CircuitMutError> {
+        if old == new {
+            return Ok(());
+        }
+        let conflict = || CircuitMutError::RegisterConflict {
+            old: old.to_string(),
+            new: new.to_string(),
+        };
+        let rename = |reg: &mut circuit_json::Register| {
+            if reg.0 == old {
+                reg.0 = new.to_string();
+            }
+        };
+
+        let mut metadata = Vec::new();
+        let mut names_by_type = Vec::new();
+        for (inputs_key, outputs_key, default_register, typ) in REGISTER_METADATA {
+            let mut inputs = input_registers(self, inputs_key, default_register, &typ);
+            let mut outputs = read_registers(self, outputs_key);
+            let units = inputs.iter().chain(outputs.iter().flatten());
+            names_by_type.push(
+                units
+                    .clone()
+                    .map(|reg| reg.0.clone())
+                    .collect::<HashSet<_>>(),
+            );
+
+            // Renamed units must not collide with the existing units.
+            let indices = |name: &str| {
+                units
+                    .clone()
+                    .filter(|reg| reg.0 == name)
+                    .map(|reg| &reg.1)
+                    .collect::<HashSet<_>>()
+            };
+            if !indices(old).is_disjoint(&indices(new)) {
+                return Err(conflict());
+            }
+
+            inputs.iter_mut().for_each(rename);
+            metadata.push((inputs_key, serde_json::json!(inputs)));
+            if let Some(outputs) = &mut outputs {
+                outputs.iter_mut().for_each(rename);
+                metadata.push((outputs_key, serde_json::json!(outputs)));
+            }
+        }
+        let [qubit_names, bit_names] = &names_by_type[..] else {
+            unreachable!("There are two register types.")
+        };
+        if (qubit_names.contains(old) && bit_names.contains(new))
+            || (bit_names.contains(old) && qubit_names.contains(new))
+        {
+            return Err(conflict());
+        }
+
+        let parent = self.parent();
+        for (key, value) in metadata {
+            self.hugr_mut().set_metadata(parent, key, value);
+        }
+        let targets = self
+            .hugr()
+            .children(parent)
+            .filter_map(|node| Some((node, self.measure_target(node)?)))
+            .filter(|(_, target)| target.0 == old)
+            .collect_vec();
+        for (node, mut target) in targets {
+            rename(&mut target);
+            self.hugr_mut()
+                .set_metadata(node, METADATA_BIT_TARGET, serde_json::json!(target));
+        }
+        Ok(())
+    }
+
+    /// Relabel individual pytket bits of the circuit.
+    ///
+    /// Each bit in the keys of `map` is renamed to the corresponding value, in
+    /// the circuit boundary and in the measurement targets set with
+    /// [`Circuit::set_measure_target`]. Other bits keep their names. The
+    /// wiring of the circuit is not modified. Circuits without register
+    /// metadata use the default `c` register, as when encoding.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a unit in `map` belongs to a qubit register, or if
+    /// two bits of the boundary would end up with the same name. The circuit
+    /// is not modified on failure.
+    pub fn relabel_bits(
+        &mut self,
+        map: &HashMap<circuit_json::Register, circuit_json::Register>,
+    ) -> Result<(), CircuitMutError> {
+        let [qubit_metadata, bit_metadata] = REGISTER_METADATA;
+        let (q_inputs_key, q_outputs_key, q_default, q_type) = qubit_metadata;
+        let (inputs_key, outputs_key, default_register, typ) = bit_metadata;
+        let qubit_registers: HashSet<String> =
+            input_registers(self, q_inputs_key, q_default, &q_type)
+                .into_iter()
+                .chain(read_registers(self, q_outputs_key).into_iter().flatten())
+                .map(|reg| reg.0)
+                .collect();
+        if let Some(unit) = map
+            .iter()
+            .flat_map(|(old, new)| [old, new])
+            .find(|unit| qubit_registers.contains(&unit.0))
+        {
+            return Err(CircuitMutError::NotABit(fmt_unit(unit)));
+        }
+        let relabel = |reg: &mut circuit_json::Register| {
+            if let Some(new) = map.get(reg) {
+                *reg = new.clone();
+            }
+        };
+        let check_unique = |regs: &[circuit_json::Register]| match regs.iter().duplicates().next() {
+            Some(reg) => Err(CircuitMutError::DuplicateBit(fmt_unit(reg))),
+            None => Ok(()),
+        };
+
+        let mut inputs = input_registers(self, inputs_key, default_register, &typ);
+        inputs.iter_mut().for_each(relabel);
+        check_unique(&inputs)?;
+        let mut outputs = read_registers(self, outputs_key);
+        if let Some(outputs) = &mut outputs {
+            outputs.iter_mut().for_each(relabel);
+            check_unique(outputs)?;
+        }
+
+        let parent = self.parent();
+        self.hugr_mut()
+            .set_metadata(parent, inputs_key, serde_json::json!(inputs));
+        if let Some(outputs) = outputs {
+            self.hugr_mut()
+                .set_metadata(parent, outputs_key, serde_json::json!(outputs));
+        }
+        let targets = self
+            .hugr()
+            .children(parent)
+            .filter_map(|node| Some((node, self.measure_target(node)?)))
+            .filter(|(_, target)| map.contains_key(target))
+            .collect_vec();
+        for (node, mut target) in targets {
+            relabel(&mut target);
+            self.hugr_mut()
+                .set_metadata(node, METADATA_BIT_TARGET, serde_json::json!(target));
+        }
+        Ok(())
+    }
+}
+
+impl<T: HugrView> Circuit<T> {
+    /// Returns the classical bit a measurement writes into, if it was set
+    /// with [`Circuit::set_measure_target`].
+    pub fn measure_target(&self, node: Node) -> Option<circuit_json::Register> {
+        let target = self.hugr().get_metadata(node, METADATA_BIT_TARGET)?;
+        serde_json::from_value(target.clone()).ok()
+    }
+}
+
+/// Format a register unit as `name[i, j, ...]`.
+fn fmt_unit(reg: &circuit_json::Register) -> String {
+    format!("{}[{}]", reg.0, reg.1.iter().join(", "))
+}
+
+/// The metadata keys for the input and output registers of each unit type,
+/// with the default register name used by the encoder.
+const REGISTER_METADATA: [(&str, &str, &str, Type); 2] = [
+    (METADATA_Q_REGISTERS, METADATA_Q_OUTPUT_REGISTERS, "q", QB_T),
+    (
+        METADATA_B_REGISTERS,
+        METADATA_B_OUTPUT_REGISTERS,
+        "c",
+        BOOL_T,
+    ),
+];
+
+/// Read a list of registers from the metadata of the circuit parent.
+fn read_registers(circ: &Circuit<impl HugrView>, key: &str) -> Option<Vec<circuit_json::Register>> {
+    let regs = circ.hugr().get_metadata(circ.parent(), key)?;
+    serde_json::from_value(regs.clone()).ok()
+}
+
+/// The registers of the circuit inputs of type `typ`.
+///
+/// If the circuit has no register metadata, the inputs are assigned to the
+/// default register as when encoding.
+fn input_registers(
+    circ: &Circuit<impl HugrView>,
+    key: &str,
+    default_register: &str,
+    typ: &Type,
+) -> Vec<circuit_json::Register> {
+    read_registers(circ, key).unwrap_or_else(|| {
+        let count = circ.units().filter(|(_, _, ty)| ty == typ).count();
+        let mut gen = RegisterUnitGenerator::new(default_register, []);
+        (0..count).map(|_| gen.next()).collect()
+    })
+}
+
+/// The pytket register metadata for the parallel composition of two
+/// circuits, with the units of `left` followed by those of `right`.
+///
+/// Units of `right` whose names are already used by `left` are renamed to
+/// fresh indices of the same register. Circuits without register metadata
+/// use the default `q` and `c` registers, as when encoding. Returns an empty
+/// list if neither circuit has register metadata.
+pub(crate) fn tensor_register_metadata(
+    left: &Circuit,
+    right: &Circuit,
+) -> Vec<(&'static str, serde_json::Value)> {
+    let mut metadata = Vec::new();
+    for (inputs_key, outputs_key, default_register, typ) in REGISTER_METADATA {
+        let registers = |circ: &Circuit| {
+            let inputs = input_registers(circ, inputs_key, default_register, &typ);
+            let outputs = read_registers(circ, outputs_key);
+            (inputs, outputs)
+        };
+        let (mut inputs, left_outputs) = registers(left);
+        let (right_inputs, right_outputs) = registers(right);
+        let has_inputs = read_registers(left, inputs_key).is_some()
+            || read_registers(right, inputs_key).is_some();
+        let has_outputs = left_outputs.is_some() || right_outputs.is_some();
+        if !has_inputs && !has_outputs {
+            continue;
+        }
+        let mut outputs = left_outputs.unwrap_or_else(|| inputs.clone());
+        let right_outputs = right_outputs.unwrap_or_else(|| right_inputs.clone());
+
+        // Rename the units of `right` that collide with those of `left`.
+        let existing = inputs
+            .iter()
+            .chain(&outputs)
+            .chain(&right_inputs)
+            .chain(&right_outputs)
+            .cloned()
+            .collect_vec();
+        let left_units: HashSet<_> = inputs.iter().chain(&outputs).cloned().collect();
+        let mut renames = HashMap::new();
+        let mut generators: HashMap<String, RegisterUnitGenerator> = HashMap::new();
+        let mut rename = |reg: circuit_json::Register| {
+            if !left_units.contains(&reg) {
+                return reg;
+            }
+            renames
+                .entry(reg.clone())
+                .or_insert_with(|| {
+                    generators
+                        .entry(reg.0.clone())
+                        .or_insert_with(|| RegisterUnitGenerator::new(&reg.0, &existing))
+                        .next()
+                })
+                .clone()
+        };
+        inputs.extend(right_inputs.into_iter().map(&mut rename));
+        outputs.extend(right_outputs.into_iter().map(&mut rename));
+
+        metadata.push((inputs_key, serde_json::json!(inputs)));
+        if has_outputs {
+            metadata.push((outputs_key, serde_json::json!(outputs)));
+        }
+    }
+    metadata
+}
+
 /// Save a circuit to file in TK1 JSON format.
 ///
 /// You may need to normalize the circuit using [`lower_to_pytket`] before saving.
@@ -164,6 +506,13 @@ pub enum OpConvertError {
     /// The serialized operation is not supported.
     #[error("Cannot serialize tket2 operation: {0:?}")]
     UnsupportedOpSerialization(OpType),
+    /// A user-defined gate cannot be serialized, as pytket requires a circuit
+    /// definition for its custom gates.
+    #[error("Cannot serialize the user-defined gate {name}: pytket custom gates require a gate definition.")]
+    UndefinedCustomGate {
+        /// The name of the gate.
+        name: String,
+    },
     /// The operation has non-serializable inputs.
     #[error("Operation {} in {node} has an unsupported input of type {typ}.", optype.name())]
     UnsupportedInputType {
@@ -7,6 +7,7 @@ use hugr::extension::prelude::{BOOL_T, QB_T};
 
 use hugr::ops::handle::NodeHandle;
 use hugr::ops::OpType;
+use hugr::std_extensions::arithmetic::float_types::ConstF64;
 use hugr::types::Signature;
 use hugr::{Hugr, Wire};
 
@@ -21,8 +22,10 @@ use super::{
     METADATA_B_OUTPUT_REGISTERS, METADATA_B_REGISTERS, METADATA_OPGROUP, METADATA_PHASE,
     METADATA_Q_OUTPUT_REGISTERS, METADATA_Q_REGISTERS,
 };
+use crate::circuit::BoundedParam;
 use crate::extension::{REGISTRY, TKET1_EXTENSION_ID};
-use crate::symbolic_constant_op;
+use crate::rewrite::add_circuit_phase;
+use crate::{symbolic_constant_op, Circuit, Tk2Op};
 
 /// The state of an in-progress [`FunctionBuilder`] being built from a [`SerialCircuit`].
 ///
@@ -37,6 +40,9 @@ pub(super) struct Tk1Decoder {
     ordered_registers: Vec<RegisterHash>,
     /// A set of registers that encode qubits.
     qubit_registers: HashSet<RegisterHash>,
+    /// The global phase of the rotations dropped from the circuit for being
+    /// the identity up to a phase, in half-turns.
+    phase: f64,
 }
 
 impl Tk1Decoder {
@@ -112,6 +118,7 @@ impl Tk1Decoder {
             register_wires,
             ordered_registers,
             qubit_registers,
+            phase: 0.,
         })
     }
 
@@ -128,9 +135,13 @@ impl Tk1Decoder {
             "Some output wires were not associated with a register."
         );
 
-        self.hugr
+        let hugr = self
+            .hugr
             .finish_hugr_with_outputs(outputs, &REGISTRY)
-            .unwrap()
+            .unwrap();
+        let mut circ = Circuit::from(hugr);
+        add_circuit_phase(&mut circ, self.phase);
+        circ.into_hugr()
     }
 
     /// Add a tket1 [`circuit_json::Command`] from the serial circuit to the
@@ -149,6 +160,12 @@ impl Tk1Decoder {
         let num_input_bits = args.len() - num_qubits;
         let tk1op = Tk1Op::from_serialised_op(op, num_qubits, num_input_bits);
 
+        // Rotations by a multiple of 2π only contribute to the global phase.
+        if let Some(phase) = identity_rotation_phase(&tk1op, &op_params) {
+            self.phase += phase;
+            return Ok(());
+        }
+
         let (input_wires, output_registers) = self.get_op_wires(&tk1op, &args, op_params)?;
         let op: OpType = (&tk1op).into();
 
@@ -295,3 +312,21 @@ fn check_register(register: &circuit_json::Register) -> Result<(), TK1ConvertErr
         Ok(())
     }
 }
+
+/// If `tk1op` is a rotation whose angle is a constant multiple of 2π, returns
+/// the global phase it introduces, in half-turns.
+///
+/// `Rz`, `Rx` and `ZZPhase` rotations by `2πk`, and `PhasedX` gates with such
+/// a first angle, are equal to `(-1)^k` times the identity.
+fn identity_rotation_phase(tk1op: &Tk1Op, params: &[String]) -> Option<f64> {
+    let Tk1Op::Native(native) = tk1op else {
+        return None;
+    };
+    match Tk2Op::try_from(native.optype()).ok()? {
+        Tk2Op::RzF64 | Tk2Op::RxF64 | Tk2Op::ZZPhase | Tk2Op::PhasedX => {}
+        _ => return None,
+    }
+    let angle = try_param_to_constant(params.first()?)?;
+    let radians = **angle.get_custom_value::<ConstF64>()?;
+    BoundedParam::new(radians, 0.).identity_up_to_phase()
+}
@@ -19,9 +19,9 @@ use crate::Tk2Op;
 
 use super::op::Tk1Op;
 use super::{
-    try_constant_to_param, OpConvertError, TK1ConvertError, METADATA_B_OUTPUT_REGISTERS,
-    METADATA_B_REGISTERS, METADATA_OPGROUP, METADATA_PHASE, METADATA_Q_OUTPUT_REGISTERS,
-    METADATA_Q_REGISTERS,
+    try_constant_to_param, OpConvertError, TK1ConvertError, METADATA_BIT_TARGET,
+    METADATA_B_OUTPUT_REGISTERS, METADATA_B_REGISTERS, METADATA_OPGROUP, METADATA_PHASE,
+    METADATA_Q_OUTPUT_REGISTERS, METADATA_Q_REGISTERS,
 };
 
 /// The state of an in-progress [`SerialCircuit`] being built from a [`Circuit`].
@@ -160,10 +160,19 @@ impl Tk1Encoder {
                 //
                 // This may cause register duplication for opaque operations
                 // with input bits.
+                //
+                // Measurements may instead target a specific register set
+                // with `Circuit::set_measure_target`.
                 let CircuitUnit::Wire(wire) = unit else {
                     panic!("Bool types are not linear.")
                 };
-                let reg = self.bits.add_bit_register(wire);
+                let target = command
+                    .metadata(METADATA_BIT_TARGET)
+                    .and_then(|target| serde_json::from_value(target.clone()).ok());
+                let reg = match target {
+                    Some(target) => self.bits.set_bit_register(wire, target),
+                    None => self.bits.add_bit_register(wire),
+                };
                 bit_args.push(reg.clone());
             } else {
                 return Err(OpConvertError::UnsupportedOutputType {
@@ -315,9 +324,14 @@ impl QubitTracker {
 
     /// Consumes the tracker and returns the final list of qubit registers, along
     /// with the final permutation of the outputs.
+    ///
+    /// The permutation combines the output registers with the
+    /// [implicit permutation](Circuit::implicit_permutation) of the circuit's
+    /// wiring. Circuits allocating or discarding qubits only use the output
+    /// registers.
     pub fn finish(
         mut self,
-        _circ: &Circuit<impl HugrView>,
+        circ: &Circuit<impl HugrView>,
     ) -> (Vec<RegisterUnit>, Vec<circuit_json::Permutation>) {
         // Ensure the input and output lists have the same registers.
         let mut outputs = self.outputs.unwrap_or_default();
@@ -345,14 +359,22 @@ impl QubitTracker {
             }
         }
 
-        // TODO: Look at the circuit outputs to determine the final permutation.
-        //
-        // We don't have the `CircuitUnit::Linear` assignments for the outputs
-        // here, so that requires some extra piping.
+        // The qubit ending at the `k`-th output starts at the input
+        // `wiring[k]`, which is the identity unless the wiring permutes the
+        // qubits.
+        let qubit_count = circ.units().filter(|(_, _, ty)| ty == &QB_T).count();
+        let wiring = Some(circ.implicit_permutation())
+            .filter(|wiring| wiring.len() == qubit_count)
+            .filter(|wiring| wiring.iter().all(|&i| i < qubit_count))
+            .unwrap_or_default();
         let permutation = outputs
             .into_iter()
             .zip(&self.inputs)
-            .map(|(out, inp)| circuit_json::Permutation(inp.clone(), out))
+            .enumerate()
+            .map(|(k, (out, inp))| {
+                let inp = wiring.get(k).map_or(inp, |&i| &self.inputs[i]);
+                circuit_json::Permutation(inp.clone(), out)
+            })
             .collect_vec();
 
         (self.inputs, permutation)
@@ -458,6 +480,13 @@ impl BitTracker {
         self.bit_to_reg.get(&wire).unwrap()
     }
 
+    /// Assign a given register unit to a bit wire.
+    pub fn set_bit_register(&mut self, wire: Wire, reg: RegisterUnit) -> &RegisterUnit {
+        self.unused_registers.retain(|r| r != &reg);
+        self.bit_to_reg.insert(wire, reg);
+        self.bit_to_reg.get(&wire).unwrap()
+    }
+
     /// Returns the register unit for a bit wire, if it exists.
     pub fn get(&self, wire: &Wire) -> Option<&RegisterUnit> {
         self.bit_to_reg.get(wire)
@@ -473,7 +502,10 @@ impl BitTracker {
         for (node, port) in circ.hugr().all_linked_outputs(circ.output_node()) {
             let wire = Wire::new(node, port);
             if let Some(reg) = self.bit_to_reg.get(&wire) {
-                circuit_output_order.push(reg.clone());
+                // Wires assigned to the same register are only output once.
+                if !circuit_output_order.contains(reg) {
+                    circuit_output_order.push(reg.clone());
+                }
             }
         }
 
@@ -497,7 +529,7 @@ impl BitTracker {
 
         // Add registers defined mid-circuit to both ends.
         for reg in self.bit_to_reg.into_values() {
-            if !input_regs.contains(&(&reg).into()) {
+            if input_regs.insert((&reg).into()) {
                 self.inputs.push(reg.clone());
                 outputs.push(reg);
             }
@@ -651,7 +683,7 @@ impl ParameterTracker {
 
 /// A utility class for finding new unused qubit/bit names.
 #[derive(Debug, Clone, Default)]
-struct RegisterUnitGenerator {
+pub(super) struct RegisterUnitGenerator {
     /// The next index to use for a new register.
     next_unit: u16,
     /// The register name to use.
@@ -13,6 +13,7 @@ use hugr::ops::OpType;
 use hugr::IncomingPort;
 use tket_json_rs::circuit_json;
 
+use crate::ops::match_custom_gate_op;
 use crate::Tk2Op;
 
 use self::native::NativeOp;
@@ -38,7 +39,9 @@ impl Tk1Op {
     ///
     /// # Errors
     ///
-    /// Returns an error if the operation is not supported by the TKET1 serialization.
+    /// Returns an error if the operation is not supported by the TKET1
+    /// serialization, including [`CustomGate`](crate::CustomGate)s, which have
+    /// no gate definition to export.
     pub fn try_from_optype(op: OpType) -> Result<Option<Self>, OpConvertError> {
         if let Ok(tk2op) = Tk2Op::try_from(&op) {
             let native = NativeOp::try_from_tk2op(tk2op)
@@ -48,6 +51,9 @@ impl Tk1Op {
                 return Ok(None);
             }
             Ok(Some(Tk1Op::Native(native)))
+        } else if let Some(gate) = match_custom_gate_op(&op) {
+            // pytket requires a circuit definition for its custom gates.
+            Err(OpConvertError::UndefinedCustomGate { name: gate.name })
         } else {
             let opaque = OpaqueTk1Op::try_from_tket2(&op)?;
             Ok(opaque.map(Tk1Op::Opaque))
@@ -3,21 +3,24 @@
 use std::collections::{HashMap, HashSet};
 use std::io::BufReader;
 
+use cool_asserts::assert_matches;
 use hugr::builder::{DFGBuilder, Dataflow, DataflowHugr};
 use hugr::extension::prelude::{BOOL_T, QB_T};
 
 use hugr::hugr::hugrmut::HugrMut;
 use hugr::std_extensions::arithmetic::float_types::{ConstF64, FLOAT64_TYPE};
 use hugr::types::Signature;
-use hugr::HugrView;
+use hugr::{Hugr, HugrView};
+use itertools::Itertools;
 use rstest::{fixture, rstest};
 use tket_json_rs::circuit_json::{self, SerialCircuit};
 use tket_json_rs::optype;
 
-use super::{TKETDecode, METADATA_Q_OUTPUT_REGISTERS};
-use crate::circuit::Circuit;
+use super::{OpConvertError, TK1ConvertError, TKETDecode, METADATA_Q_OUTPUT_REGISTERS};
+use crate::circuit::{Circuit, CircuitMutError};
 use crate::extension::REGISTRY;
-use crate::Tk2Op;
+use crate::ops::match_custom_gate_op;
+use crate::{CustomGate, Tk2Op};
 
 const SIMPLE_JSON: &str = r#"{
         "phase": "0",
@@ -69,6 +72,18 @@ const PARAMETERIZED: &str = r#"{
         "implicit_permutation": [[["q", [0]], ["q", [0]]], [["q", [1]], ["q", [1]]]]
     }"#;
 
+const IDENTITY_UP_TO_PHASE: &str = r#"{
+        "phase": "0",
+        "bits": [],
+        "qubits": [["q", [0]], ["q", [1]]],
+        "commands": [
+            {"args": [["q", [0]]], "op": {"params": ["2.0"], "type": "Rx"}},
+            {"args": [["q", [0]], ["q", [1]]], "op": {"type": "CX"}},
+            {"args": [["q", [1]]], "op": {"params": ["4"], "type": "Rz"}}
+        ],
+        "implicit_permutation": [[["q", [0]], ["q", [0]]], [["q", [1]], ["q", [1]]]]
+    }"#;
+
 /// Check some properties of the serial circuit.
 fn validate_serial_circ(circ: &SerialCircuit) {
     // Check that all commands have valid arguments.
@@ -283,6 +298,39 @@ fn circuit_roundtrip(#[case] circ: Circuit, #[case] decoded_sig: Signature) {
     compare_serial_circs(&ser, &reser);
 }
 
+/// A circuit with a parametric user-defined gate.
+#[fixture]
+fn circ_custom_gate() -> Circuit {
+    let gate = CustomGate::new("MS", 2, 1).with_dagger("MSdg");
+    let mut h = DFGBuilder::new(Signature::new_endo(vec![QB_T, QB_T])).unwrap();
+    let [q0, q1] = h.input_wires_arr();
+    let [q0] = h.add_dataflow_op(Tk2Op::H, [q0]).unwrap().outputs_arr();
+    let angle = h.add_load_value(ConstF64::new(0.5));
+    let qbs = h.add_dataflow_op(gate, [q0, q1, angle]).unwrap().outputs();
+    h.finish_hugr_with_outputs(qbs, &REGISTRY).unwrap().into()
+}
+
+#[rstest]
+fn custom_gate_serialisation(circ_custom_gate: Circuit) {
+    // pytket cannot represent a custom gate without its definition.
+    assert_matches!(
+        SerialCircuit::encode(&circ_custom_gate),
+        Err(TK1ConvertError::OpConversionError(OpConvertError::UndefinedCustomGate { name })) => {
+            assert_eq!(name, "MS");
+        }
+    );
+
+    // The gate survives a HUGR JSON roundtrip.
+    let json = serde_json::to_string(circ_custom_gate.hugr()).unwrap();
+    let hugr: Hugr = serde_json::from_str(&json).unwrap();
+    hugr.validate(&REGISTRY).unwrap();
+    let gates = hugr
+        .nodes()
+        .filter_map(|node| match_custom_gate_op(hugr.get_optype(node)))
+        .collect_vec();
+    assert_eq!(gates, [CustomGate::new("MS", 2, 1).with_dagger("MSdg")]);
+}
+
 /// Test serialisation of circuits with a symbolic expression.
 ///
 /// Note: this is not a proper roundtrip as the symbols f0 and f1 are not
@@ -302,3 +350,191 @@ fn test_add_angle_serialise(#[case] circ_add_angles: Circuit, #[case] param_str:
     validate_serial_circ(&reser);
     compare_serial_circs(&ser, &reser);
 }
+
+/// Rotations by a multiple of 2π are dropped when decoding, and their phase
+/// is added to the global phase of the circuit.
+#[test]
+fn identity_rotation_phase() {
+    let ser: SerialCircuit = serde_json::from_str(IDENTITY_UP_TO_PHASE).unwrap();
+    let circ: Circuit = ser.decode().unwrap();
+    circ.hugr().validate(&REGISTRY).unwrap();
+    assert_eq!(circ.num_operations(), 1);
+
+    let reser = SerialCircuit::encode(&circ).unwrap();
+    validate_serial_circ(&reser);
+    assert_eq!(reser.commands.len(), 1);
+    assert_eq!(reser.phase.parse::<f64>(), Ok(1.0));
+
+    let deser: Circuit = reser.decode().unwrap();
+    let phase = SerialCircuit::encode(&deser).unwrap().phase;
+    assert_eq!(phase.parse::<f64>(), Ok(1.0));
+}
+
+#[rstest]
+#[case::simple(SIMPLE_JSON, &[("q", vec![2])])]
+#[case::multi_register(MULTI_REGISTER, &[("q", vec![3]), ("my_qubits", vec![3])])]
+#[case::bits(
+    r#"{
+        "phase": "0",
+        "bits": [["c", [0]], ["c", [1]]],
+        "qubits": [["q", [0]], ["q", [1]], ["q", [2]]],
+        "commands": [
+            {"args": [["q", [0]], ["q", [2]]], "op": {"type": "CX"}},
+            {"args": [["q", [2]], ["c", [1]]], "op": {"type": "Measure"}}
+        ],
+        "implicit_permutation": []
+    }"#,
+    &[("q", vec![3]), ("c", vec![2])]
+)]
+fn register_shapes(#[case] circ_s: &str, #[case] expected: &[(&str, Vec<u32>)]) {
+    let ser: SerialCircuit = serde_json::from_str(circ_s).unwrap();
+    let circ: Circuit = ser.decode().unwrap();
+
+    let expected: HashMap<String, Vec<u32>> = expected
+        .iter()
+        .map(|(name, shape)| (name.to_string(), shape.clone()))
+        .collect();
+    assert_eq!(circ.register_shapes().unwrap(), expected);
+}
+
+#[rstest]
+fn measure_target(circ_measure_ancilla: Circuit) {
+    let mut circ = circ_measure_ancilla;
+    let node_with_op = |circ: &Circuit, op: Tk2Op| {
+        circ.commands()
+            .find(|cmd| cmd.optype() == &op.into())
+            .unwrap()
+            .node()
+    };
+    let measure = node_with_op(&circ, Tk2Op::Measure);
+    circ.set_measure_target(measure, "results", 3).unwrap();
+    let target = circuit_json::Register("results".to_string(), vec![3]);
+    assert_eq!(circ.measure_target(measure), Some(target.clone()));
+
+    let ser = SerialCircuit::encode(&circ).unwrap();
+    validate_serial_circ(&ser);
+    let measured_bits = ser
+        .commands
+        .iter()
+        .filter(|cmd| cmd.op.op_type == optype::OpType::Measure)
+        .map(|cmd| cmd.args.last().unwrap().clone())
+        .collect_vec();
+    assert_eq!(measured_bits.len(), 2);
+    assert_eq!(measured_bits[0], target);
+    assert_ne!(measured_bits[1], target);
+    assert!(ser.bits.contains(&target));
+
+    let qfree = node_with_op(&circ, Tk2Op::QFree);
+    assert_matches!(
+        circ.set_measure_target(qfree, "results", 0),
+        Err(CircuitMutError::NoBitOutput(n)) => {
+            assert_eq!(n, qfree);
+        }
+    );
+    let input = circ.input_node();
+    assert_matches!(
+        circ.set_measure_target(input, "results", 0),
+        Err(CircuitMutError::NotACommand(_))
+    );
+}
+
+#[test]
+fn rename_register() {
+    let ser: SerialCircuit = serde_json::from_str(MULTI_REGISTER).unwrap();
+    let mut circ: Circuit = ser.decode().unwrap();
+
+    circ.rename_register("q", "data").unwrap();
+    let ser = SerialCircuit::encode(&circ).unwrap();
+    validate_serial_circ(&ser);
+    let data = |i| circuit_json::Register("data".to_string(), vec![i]);
+    let my_qubits = circuit_json::Register("my_qubits".to_string(), vec![2]);
+    assert_eq!(ser.qubits, vec![data(2), data(1), my_qubits.clone()]);
+    assert_eq!(ser.commands[0].args, vec![my_qubits]);
+    assert_eq!(ser.commands[1].args, vec![data(2), data(1)]);
+
+    // Units with the same index cannot be merged into one register.
+    assert_matches!(
+        circ.rename_register("my_qubits", "data"),
+        Err(CircuitMutError::RegisterConflict { .. })
+    );
+
+    // Registers cannot be renamed to the name of a bit register.
+    let ser: SerialCircuit = serde_json::from_str(UNKNOWN_OP).unwrap();
+    let mut circ: Circuit = ser.decode().unwrap();
+    assert_matches!(
+        circ.rename_register("q", "c"),
+        Err(CircuitMutError::RegisterConflict { .. })
+    );
+    circ.rename_register("c", "results").unwrap();
+    let ser = SerialCircuit::encode(&circ).unwrap();
+    assert!(ser.bits.iter().all(|reg| reg.0 == "results"));
+}
+
+#[rstest]
+fn relabel_bits(circ_measure_ancilla: Circuit) {
+    let bit = |name: &str, i| circuit_json::Register(name.to_string(), vec![i]);
+
+    // Swap the two bits of a decoded circuit, moving them to a new register.
+    let ser: SerialCircuit = serde_json::from_str(UNKNOWN_OP).unwrap();
+    let mut circ: Circuit = ser.decode().unwrap();
+    let map = HashMap::from([(bit("c", 0), bit("out", 1)), (bit("c", 1), bit("out", 0))]);
+    circ.relabel_bits(&map).unwrap();
+    let ser = SerialCircuit::encode(&circ).unwrap();
+    validate_serial_circ(&ser);
+    assert_eq!(ser.bits, vec![bit("out", 1), bit("out", 0)]);
+    assert_eq!(ser.commands[1].args, vec![bit("q", 1), bit("out", 0)]);
+
+    // Measurement targets follow the relabelling.
+    let mut circ = circ_measure_ancilla;
+    let measure = circ
+        .commands()
+        .find(|cmd| cmd.optype() == &Tk2Op::Measure.into())
+        .unwrap()
+        .node();
+    circ.set_measure_target(measure, "c", 0).unwrap();
+    circ.relabel_bits(&HashMap::from([(bit("c", 0), bit("results", 5))]))
+        .unwrap();
+    assert_eq!(circ.measure_target(measure), Some(bit("results", 5)));
+
+    // Qubits cannot be relabelled as bits, and bits cannot be merged.
+    assert_matches!(
+        circ.relabel_bits(&HashMap::from([(bit("q", 0), bit("c", 3))])),
+        Err(CircuitMutError::NotABit(unit)) => {
+            assert_eq!(unit, "q[0]");
+        }
+    );
+    let ser: SerialCircuit = serde_json::from_str(UNKNOWN_OP).unwrap();
+    let mut circ: Circuit = ser.decode().unwrap();
+    assert_matches!(
+        circ.relabel_bits(&HashMap::from([(bit("c", 0), bit("c", 1))])),
+        Err(CircuitMutError::DuplicateBit(unit)) => {
+            assert_eq!(unit, "c[1]");
+        }
+    );
+}
+
+#[test]
+fn wiring_permutation() {
+    let mut circ = crate::utils::build_simple_circuit(3, |circ| {
+        circ.append(Tk2Op::H, [0])?;
+        circ.append(crate::permutation_op([2, 0, 1]), [0, 1, 2])?;
+        Ok(())
+    })
+    .unwrap();
+    crate::passes::push_permutations(&mut circ);
+    assert_eq!(circ.implicit_permutation(), [1, 2, 0]);
+
+    // The qubit starting at `q[1]` ends at `q[0]`, and so on.
+    let ser = SerialCircuit::encode(&circ).unwrap();
+    validate_serial_circ(&ser);
+    let q = |i| circuit_json::Register("q".to_string(), vec![i]);
+    let perm: HashSet<_> = ser
+        .implicit_permutation
+        .iter()
+        .map(|p| (p.0.clone(), p.1.clone()))
+        .collect();
+    assert_eq!(
+        perm,
+        HashSet::from([(q(1), q(0)), (q(2), q(1)), (q(0), q(2))])
+    );
+}
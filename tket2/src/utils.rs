@@ -68,9 +68,46 @@ where
 #[allow(unused_imports)]
 #[cfg(test)]
 pub(crate) mod test {
-    use crate::Circuit;
+    use crate::passes::{Pass, PassContext};
+    use crate::{Circuit, Tk2Op};
     use hugr::HugrView;
 
+    /// Assert that a pass is idempotent on a circuit: running it again on its
+    /// own output must leave the circuit unchanged.
+    ///
+    /// Returns the output of the first run.
+    pub(crate) fn assert_idempotent(pass: Pass, circ: &Circuit) -> Circuit {
+        let mut ctx = PassContext::new();
+        let once = pass.run(circ, &mut ctx).unwrap();
+        once.hugr().validate(&crate::extension::REGISTRY).unwrap();
+        let twice = pass.run(&once, &mut ctx).unwrap();
+        assert!(
+            once.structurally_eq(&twice, true),
+            "{} is not idempotent:\n{}\nbecame\n{}",
+            pass.name(),
+            once.mermaid_string(),
+            twice.mermaid_string()
+        );
+        once
+    }
+
+    /// Assert that two circuits produce the same statevector from the
+    /// all-zero state, including their global phase.
+    pub(crate) fn assert_same_statevector(a: &Circuit, b: &Circuit) {
+        let state = a.statevector().unwrap();
+        let expected_state = b.statevector().unwrap();
+        for (x, y) in state.iter().zip(&expected_state) {
+            assert!((x - y).norm() < 1e-9, "{state:?} != {expected_state:?}");
+        }
+    }
+
+    /// The number of `CX` gates in a circuit.
+    pub(crate) fn cx_count(circ: &Circuit) -> usize {
+        circ.commands()
+            .filter(|cmd| cmd.optype() == &Tk2Op::CX.into())
+            .count()
+    }
+
     /// Open a browser page to render a dot string graph.
     ///
     /// This can be used directly on the output of `Hugr::dot_string`